@@ -480,6 +480,8 @@ impl AgentBackend for AgentSdkBackend {
             signal_json: None,
             ran_in_docker: false,
             container_test_results: Vec::new(),
+            failovers: Vec::new(),
+            resources: None,
         })
     }
 
@@ -561,6 +563,7 @@ impl AgentBackend for AgentSdkBackend {
             cost_usd: result.cost_usd,
             tool_calls: result.tool_calls,
             raw_stream: result.raw_stream,
+            failovers: Vec::new(),
         })
     }
 
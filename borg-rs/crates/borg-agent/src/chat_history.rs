@@ -0,0 +1,89 @@
+//! Per-session chat transcript for backends without native session resume
+//! (Gemini CLI, Ollama). Stored next to the chat's other state in
+//! `ChatContext.session_dir`, so continuity follows the session directory.
+
+use std::path::Path;
+
+use anyhow::Result;
+use borg_core::traits::{ChatContext, ChatMessage, ChatRequest};
+
+const HISTORY_FILE: &str = ".borg-chat-history.json";
+/// Oldest turns are dropped beyond this many messages.
+const MAX_HISTORY_MESSAGES: usize = 40;
+
+pub fn load_history(session_dir: &str) -> Vec<ChatMessage> {
+    std::fs::read_to_string(Path::new(session_dir).join(HISTORY_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// History for this turn: the caller-supplied history when present,
+/// otherwise the transcript saved in the session directory.
+pub fn history_for(request: &ChatRequest, ctx: &ChatContext) -> Vec<ChatMessage> {
+    if request.conversation_history.is_empty() {
+        load_history(&ctx.session_dir)
+    } else {
+        request.conversation_history.clone()
+    }
+}
+
+pub fn append_history(session_dir: &str, user: &str, assistant: &str) -> Result<()> {
+    if session_dir.is_empty() {
+        return Ok(());
+    }
+    let mut history = load_history(session_dir);
+    history.push(ChatMessage {
+        role: "user".into(),
+        content: user.into(),
+    });
+    history.push(ChatMessage {
+        role: "assistant".into(),
+        content: assistant.into(),
+    });
+    let excess = history.len().saturating_sub(MAX_HISTORY_MESSAGES);
+    history.drain(..excess);
+    std::fs::create_dir_all(session_dir)?;
+    std::fs::write(
+        Path::new(session_dir).join(HISTORY_FILE),
+        serde_json::to_string(&history)?,
+    )?;
+    Ok(())
+}
+
+/// Fold prior turns into a single prompt for CLIs that take one message.
+pub fn render_transcript(history: &[ChatMessage], message: &str) -> String {
+    if history.is_empty() {
+        return message.to_string();
+    }
+    let mut out = String::from("Conversation so far:\n\n");
+    for m in history {
+        out.push_str(&format!("{}: {}\n\n", m.role, m.content));
+    }
+    out.push_str("Current message:\n");
+    out.push_str(message);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_round_trips_and_is_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = dir.path().to_str().unwrap();
+        assert!(load_history(session).is_empty());
+        for i in 0..25 {
+            append_history(session, &format!("q{i}"), &format!("a{i}")).unwrap();
+        }
+        let history = load_history(session);
+        assert_eq!(history.len(), MAX_HISTORY_MESSAGES);
+        assert_eq!(history[0].content, "q5");
+        assert_eq!(history.last().unwrap().content, "a24");
+        let prompt = render_transcript(&history[38..], "next");
+        assert!(prompt.starts_with("Conversation so far:"));
+        assert!(prompt.contains("user: q24"));
+        assert!(prompt.ends_with("Current message:\nnext"));
+    }
+}
@@ -8,8 +8,10 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 use borg_core::{
     agent::AgentBackend,
+    resources::ResourceMonitor,
     sandbox::{Sandbox, SandboxMode},
     types::{ContainerTestResult, PhaseConfig, PhaseContext, PhaseOutput, Task},
+    warm_pool::{worktree_repo, WarmKey, WarmLease},
 };
 use serde_json::json;
 use tokio::{
@@ -77,22 +79,21 @@ fn isolated_proxy_base_url(base_url: &str) -> String {
     }
 }
 
-fn container_host_ip(isolated: bool) -> &'static str {
-    if isolated {
-        "172.31.0.1"
-    } else {
-        "172.30.0.1"
-    }
+fn container_host_ip(isolated: bool) -> Option<&'static str> {
+    Sandbox::host_gateway(isolated)
 }
 
-fn container_reachable_url(base_url: &str, host_ip: &str) -> String {
-    if let Some(port) = base_url.strip_prefix("http://127.0.0.1:") {
-        return format!("http://{host_ip}:{port}");
-    }
-    if let Some(port) = base_url.strip_prefix("http://localhost:") {
-        return format!("http://{host_ip}:{port}");
+/// `base_url` as seen from inside an agent container. Loopback URLs become
+/// empty when the container has no route to the host.
+fn container_reachable_url(base_url: &str, host_ip: Option<&str>) -> String {
+    let port = base_url
+        .strip_prefix("http://127.0.0.1:")
+        .or_else(|| base_url.strip_prefix("http://localhost:"));
+    match (port, host_ip) {
+        (Some(port), Some(host_ip)) => format!("http://{host_ip}:{port}"),
+        (Some(_), None) => String::new(),
+        (None, _) => base_url.to_string(),
     }
-    base_url.to_string()
 }
 
 fn latest_jsonl_file(root: &Path) -> Option<PathBuf> {
@@ -259,8 +260,9 @@ impl AgentBackend for ClaudeBackend {
         } else {
             SandboxMode::Direct
         };
-        let is_docker = matches!(effective_mode, SandboxMode::Docker);
-        let host_ip = container_host_ip(ctx.isolated);
+        let is_docker = effective_mode.is_container();
+        // Egress-restricted runs also sit on the internal network.
+        let host_ip = container_host_ip(ctx.isolated || ctx.egress_proxy.is_some());
         let reachable_borg_api_url = if is_docker {
             container_reachable_url(&ctx.borg_api_url, host_ip)
         } else {
@@ -446,6 +448,7 @@ impl AgentBackend for ClaudeBackend {
             String::new()
         };
 
+        let mut warm_lease: Option<WarmLease> = None;
         let mut child: tokio::process::Child = match effective_mode {
             SandboxMode::Bwrap => {
                 let git_dir = Path::new(&task.repo_path).join(".git");
@@ -484,7 +487,7 @@ impl AgentBackend for ClaudeBackend {
                     .spawn()
                     .context("failed to spawn bwrap")?
             },
-            SandboxMode::Docker => {
+            SandboxMode::Docker | SandboxMode::Podman => {
                 let workspace_host = if !task.repo_path.is_empty()
                     && Path::new(&task.repo_path).join(".git").exists()
                 {
@@ -492,10 +495,14 @@ impl AgentBackend for ClaudeBackend {
                 } else {
                     ctx.work_dir.clone()
                 };
-                let binds = [(workspace_host, "/workspace".to_string(), false),
-                    (ctx.session_dir.clone(), "/home/bun".to_string(), false)];
-                let volumes_owned = [("rustup-cache".to_string(), "/home/bun/.rustup".to_string()),
-                    ("cargo-cache".to_string(), "/home/bun/.cargo".to_string())];
+                let binds = [
+                    (workspace_host, "/workspace".to_string(), false),
+                    (ctx.session_dir.clone(), "/home/bun".to_string(), false),
+                ];
+                let volumes_owned = [
+                    ("rustup-cache".to_string(), "/home/bun/.rustup".to_string()),
+                    ("cargo-cache".to_string(), "/home/bun/.cargo".to_string()),
+                ];
                 let mut env_kv = vec![
                     ("HOME".to_string(), "/home/bun".to_string()),
                     ("RUSTUP_HOME".to_string(), "/home/bun/.rustup".to_string()),
@@ -517,7 +524,12 @@ impl AgentBackend for ClaudeBackend {
                     env_kv.push(("API_TOKEN".to_string(), ctx.borg_api_token.clone()));
                 }
 
-                env_kv.push(("BORG_HOST_IP".to_string(), host_ip.to_string()));
+                if let Some(host_ip) = host_ip {
+                    env_kv.push(("BORG_HOST_IP".to_string(), host_ip.to_string()));
+                    if let Some(proxy) = &ctx.egress_proxy {
+                        env_kv.extend(borg_core::egress::proxy_env(proxy, host_ip));
+                    }
+                }
 
                 let binds_ref: Vec<(&str, &str, bool)> = binds
                     .iter()
@@ -532,30 +544,51 @@ impl AgentBackend for ClaudeBackend {
                     .map(|(k, v)| (k.as_str(), v.as_str()))
                     .collect();
 
-                let mut docker_cmd = Sandbox::docker_command(
-                    &self.docker_image,
-                    &binds_ref,
-                    &volumes_ref,
-                    "",
-                    &[],
-                    &env_ref,
-                    self.container_memory_mb,
-                    self.container_cpus,
-                    ctx.agent_network.as_deref(),
-                );
-                if let Some(ref cid_path) = cidfile_path {
-                    let existing_args: Vec<_> = docker_cmd
-                        .as_std()
-                        .get_args()
-                        .map(|a| a.to_os_string())
-                        .collect();
-                    let mut new_cmd = Command::new("docker");
-                    new_cmd.arg("run").arg("--cidfile").arg(cid_path);
-                    for arg in existing_args.into_iter().skip(1) {
-                        new_cmd.arg(arg);
-                    }
-                    docker_cmd = new_cmd;
-                }
+                let image = ctx.container_image.as_deref().unwrap_or(&self.docker_image);
+                // Warm containers are shared per repo; the worktree is staged
+                // in on checkout.
+                let warm_key = worktree_repo(&binds[0].0).map(|repo| {
+                    WarmKey::new(
+                        image,
+                        repo,
+                        &volumes_ref,
+                        ctx.agent_network.as_deref(),
+                        self.container_memory_mb,
+                        self.container_cpus,
+                    )
+                });
+                warm_lease = match (Sandbox::warm_pool(), &warm_key) {
+                    (Some(pool), Some(key)) => {
+                        pool.acquire(key, &ctx.session_dir, &binds[0].0).await
+                    },
+                    _ => None,
+                };
+                let mut docker_cmd = match &warm_lease {
+                    Some(lease) => {
+                        info!(task_id = task.id, container = %lease.container_id(), "using warm container");
+                        if let Some(ref cid_path) = cidfile_path {
+                            let _ = lease.write_cidfile(cid_path);
+                        }
+                        lease.exec_command(&env_ref)
+                    },
+                    None => {
+                        let mut cmd = Sandbox::docker_command(
+                            image,
+                            &binds_ref,
+                            &volumes_ref,
+                            "",
+                            &[],
+                            &env_ref,
+                            self.container_memory_mb,
+                            self.container_cpus,
+                            ctx.agent_network.as_deref(),
+                        );
+                        if let Some(ref cid_path) = cidfile_path {
+                            cmd = Sandbox::with_cidfile(&cmd, cid_path);
+                        }
+                        cmd
+                    },
+                };
 
                 docker_cmd
                     .kill_on_drop(true)
@@ -563,7 +596,7 @@ impl AgentBackend for ClaudeBackend {
                     .stderr(Stdio::piped())
                     .stdin(Stdio::piped())
                     .spawn()
-                    .context("failed to spawn docker")?
+                    .with_context(|| format!("failed to spawn {}", Sandbox::cli()))?
             },
             SandboxMode::Direct => {
                 let augmented_path = format!(
@@ -601,6 +634,13 @@ impl AgentBackend for ClaudeBackend {
             },
         };
 
+        let monitor = match &cidfile_path {
+            Some(cid_path) => Some(ResourceMonitor::container(Sandbox::cli(), cid_path.clone())),
+            None => child
+                .id()
+                .map(|pid| ResourceMonitor::process(pid, &format!("task-{}-{pid}", task.id))),
+        };
+
         if is_docker {
             if let Some(mut stdin) = child.stdin.take() {
                 let repo_test_cmd = ctx.repo_config.test_cmd.clone();
@@ -670,7 +710,7 @@ impl AgentBackend for ClaudeBackend {
                                 if !l.is_empty() {
                                     let test_line = l.strip_prefix("---BORG_TEST_RESULT---").unwrap_or(&l);
                                     if let Ok(res) = serde_json::from_str::<ContainerTestResult>(test_line) {
-                                        container_test_results.push(res);
+                                        container_test_results.push(res.with_parsed_failures());
                                     } else {
                                         if let Some(tx) = &stream_tx {
                                             let evt = serde_json::json!({
@@ -715,6 +755,16 @@ impl AgentBackend for ClaudeBackend {
             io_future.await
         };
 
+        let resources = match monitor {
+            Some(m) => Some(m.finish().await),
+            None => None,
+        };
+        // Restores the session dir, so it must come before reading the transcript.
+        let warm_used = warm_lease.is_some();
+        if let Some(lease) = warm_lease {
+            lease.release(success).await;
+        }
+
         let raw_stream = load_latest_session_transcript(&ctx.session_dir).unwrap_or_else(|| {
             warn!(
                 task_id = task.id,
@@ -726,16 +776,20 @@ impl AgentBackend for ClaudeBackend {
         });
 
         if let Some(cid_path) = cidfile_path {
-            if let Ok(cid) = std::fs::read_to_string(&cid_path) {
-                let cid = cid.trim();
-                if !cid.is_empty() {
-                    info!("cleaning up container {cid}");
-                    let _ = std::process::Command::new("docker")
-                        .args(["rm", "-f", cid])
-                        .stdout(Stdio::null())
-                        .stderr(Stdio::null())
-                        .status();
-                }
+            // Warm containers belong to the pool; only cold ones are removed here.
+            let cid = if warm_used {
+                String::new()
+            } else {
+                std::fs::read_to_string(&cid_path).unwrap_or_default()
+            };
+            let cid = cid.trim();
+            if !cid.is_empty() {
+                info!("cleaning up container {cid}");
+                let _ = std::process::Command::new(Sandbox::cli())
+                    .args(["rm", "-f", cid])
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status();
             }
             let _ = std::fs::remove_file(cid_path);
         }
@@ -748,9 +802,15 @@ impl AgentBackend for ClaudeBackend {
             signal_json,
             ran_in_docker: is_docker,
             container_test_results,
+            failovers: Vec::new(),
+            resources,
         })
     }
 
+    fn enforces_network_policy(&self, phase: &PhaseConfig) -> bool {
+        phase.use_docker && self.sandbox_mode.is_container()
+    }
+
     fn name(&self) -> &str {
         "claude"
     }
@@ -826,13 +886,18 @@ mod tests {
     #[test]
     fn container_reachable_url_rewrites_local_api_host() {
         assert_eq!(
-            container_reachable_url("http://127.0.0.1:3231", "172.31.0.1"),
+            container_reachable_url("http://127.0.0.1:3231", Some("172.31.0.1")),
             "http://172.31.0.1:3231"
         );
         assert_eq!(
-            container_reachable_url("http://localhost:4231", "172.30.0.1"),
+            container_reachable_url("http://localhost:4231", Some("172.30.0.1")),
             "http://172.30.0.1:4231"
         );
+        assert_eq!(container_reachable_url("http://127.0.0.1:3231", None), "");
+        assert_eq!(
+            container_reachable_url("https://api.example.com", None),
+            "https://api.example.com"
+        );
     }
 
     #[test]
@@ -4,12 +4,16 @@ use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use borg_core::{
     agent::AgentBackend,
+    traits::{ChatContext, ChatRequest, ChatResponse, ToolCallRecord},
     types::{PhaseConfig, PhaseContext, PhaseOutput, Task},
 };
 use serde_json::json;
 use tracing::{info, warn};
 
-use crate::drain::{drain_child, DrainConfig};
+use crate::{
+    drain::{drain_child, DrainConfig},
+    event,
+};
 
 /// Runs Codex (openai/codex) as the agent backend.
 ///
@@ -146,6 +150,47 @@ impl CodexBackend {
         args.push(format!("{key}={value}"));
     }
 
+    /// Translate an `mcpServers` map (as built for Claude) into `-c` overrides.
+    fn push_mcp_servers(
+        args: &mut Vec<String>,
+        servers: &serde_json::Map<String, serde_json::Value>,
+    ) {
+        for (name, spec) in servers {
+            Self::push_config_arg(
+                args,
+                &format!("mcp_servers.{name}.command"),
+                spec["command"].clone(),
+            );
+            Self::push_config_arg(
+                args,
+                &format!("mcp_servers.{name}.args"),
+                spec["args"].clone(),
+            );
+            if let Some(env) = spec["env"].as_object() {
+                for (key, value) in env {
+                    Self::push_config_arg(
+                        args,
+                        &format!("mcp_servers.{name}.env.{key}"),
+                        value.clone(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Prepare `$CODEX_HOME` inside a session directory; returns its path and
+    /// whether linked (file-based) credentials are available there.
+    fn prepare_codex_home(&self, session_dir: &str) -> Result<(String, bool)> {
+        let codex_home = format!("{session_dir}/.codex");
+        std::fs::create_dir_all(&codex_home)
+            .with_context(|| format!("failed to create Codex home: {codex_home}"))?;
+        if self.api_key.is_empty() {
+            Self::ensure_session_auth(&self.credentials_path, &codex_home);
+        }
+        let has_linked_auth = Path::new(&codex_home).join("auth.json").exists();
+        Ok((codex_home, has_linked_auth))
+    }
+
     fn append_mcp_config(
         &self,
         args: &mut Vec<String>,
@@ -215,6 +260,109 @@ impl CodexBackend {
     }
 }
 
+/// Summary of a `codex exec --json` run.
+#[derive(Debug, Default)]
+struct CodexChatResult {
+    text: String,
+    thread_id: Option<String>,
+    input_tokens: u64,
+    output_tokens: u64,
+    tool_calls: Vec<ToolCallRecord>,
+    error: Option<String>,
+    /// The events re-encoded as stream-json lines.
+    stream_lines: Vec<String>,
+}
+
+/// Translate one `codex exec --json` event into stream-json lines and fold it
+/// into `result`.
+fn apply_codex_event(result: &mut CodexChatResult, line: &str) -> Vec<String> {
+    let Ok(ev) = serde_json::from_str::<serde_json::Value>(line) else {
+        return Vec::new();
+    };
+    let item = &ev["item"];
+    let item_id = item["id"].as_str().unwrap_or("");
+    let mut out = Vec::new();
+    match (ev["type"].as_str().unwrap_or(""), item["type"].as_str()) {
+        ("thread.started", _) => {
+            result.thread_id = ev["thread_id"].as_str().map(str::to_string);
+        },
+        ("item.started", Some("command_execution")) => {
+            out.push(event::tool_use_line(
+                item_id,
+                "Bash",
+                &json!({"command": item["command"]}),
+            ));
+        },
+        ("item.started", Some("mcp_tool_call")) => {
+            let name = format!(
+                "mcp__{}__{}",
+                item["server"].as_str().unwrap_or(""),
+                item["tool"].as_str().unwrap_or("")
+            );
+            out.push(event::tool_use_line(item_id, &name, &item["arguments"]));
+        },
+        ("item.completed", Some("agent_message")) => {
+            let text = item["text"].as_str().unwrap_or("");
+            if !text.is_empty() {
+                out.push(event::assistant_text_line(text));
+                result.text = text.to_string();
+            }
+        },
+        ("item.completed", Some("command_execution")) => {
+            let output = item["aggregated_output"].as_str().unwrap_or("");
+            let success = item["exit_code"].as_i64() == Some(0);
+            out.push(event::tool_result_line(item_id, output, !success));
+            result.tool_calls.push(ToolCallRecord {
+                tool_name: "Bash".into(),
+                input_summary: item["command"]
+                    .as_str()
+                    .unwrap_or("")
+                    .chars()
+                    .take(500)
+                    .collect(),
+                output_summary: output.chars().take(500).collect(),
+                duration_ms: 0,
+                success,
+                error: (!success).then(|| format!("exit code {}", item["exit_code"])),
+            });
+        },
+        ("item.completed", Some("mcp_tool_call")) => {
+            let failed = item["status"].as_str() == Some("failed");
+            let content = if failed {
+                item["error"]["message"].as_str().unwrap_or("").to_string()
+            } else {
+                item["result"].to_string()
+            };
+            out.push(event::tool_result_line(item_id, &content, failed));
+            result.tool_calls.push(ToolCallRecord {
+                tool_name: format!(
+                    "mcp__{}__{}",
+                    item["server"].as_str().unwrap_or(""),
+                    item["tool"].as_str().unwrap_or("")
+                ),
+                input_summary: item["arguments"].to_string().chars().take(500).collect(),
+                output_summary: content.chars().take(500).collect(),
+                duration_ms: 0,
+                success: !failed,
+                error: failed.then(|| content.clone()),
+            });
+        },
+        ("turn.completed", _) => {
+            result.input_tokens += ev["usage"]["input_tokens"].as_u64().unwrap_or(0);
+            result.output_tokens += ev["usage"]["output_tokens"].as_u64().unwrap_or(0);
+        },
+        ("turn.failed", _) => {
+            result.error = ev["error"]["message"].as_str().map(str::to_string);
+        },
+        ("error", _) => {
+            result.error = ev["message"].as_str().map(str::to_string);
+        },
+        _ => {},
+    }
+    result.stream_lines.extend(out.iter().cloned());
+    out
+}
+
 #[async_trait]
 impl AgentBackend for CodexBackend {
     async fn run_phase(
@@ -248,13 +396,7 @@ impl AgentBackend for CodexBackend {
         codex_args.push(instruction.clone());
 
         let mut cmd = tokio::process::Command::new(&self.codex_bin);
-        let codex_home = format!("{}/.codex", ctx.session_dir);
-        std::fs::create_dir_all(&codex_home)
-            .with_context(|| format!("failed to create Codex home: {codex_home}"))?;
-        if self.api_key.is_empty() {
-            Self::ensure_session_auth(&self.credentials_path, &codex_home);
-        }
-        let has_linked_auth = Path::new(&codex_home).join("auth.json").exists();
+        let (codex_home, has_linked_auth) = self.prepare_codex_home(&ctx.session_dir)?;
         cmd.args(&codex_args)
             .current_dir(&ctx.work_dir)
             .env_remove("CLAUDECODE")
@@ -308,6 +450,134 @@ impl AgentBackend for CodexBackend {
             signal_json: None,
             ran_in_docker: false,
             container_test_results: Vec::new(),
+            failovers: Vec::new(),
+            resources: None,
+        })
+    }
+
+    /// Chat turns run `codex exec --json` in the chat's session directory.
+    /// The system prompt goes into `AGENTS.md`, and later turns resume the
+    /// Codex thread recorded in `ctx.session_id`.
+    async fn run_chat(&self, request: &ChatRequest, ctx: &ChatContext) -> Result<ChatResponse> {
+        if !self.is_available().await {
+            bail!("codex binary not found: {}", self.codex_bin);
+        }
+        let (codex_home, has_linked_auth) = self.prepare_codex_home(&ctx.session_dir)?;
+        if !request.system_prompt.is_empty() {
+            std::fs::write(
+                Path::new(&ctx.session_dir).join("AGENTS.md"),
+                &request.system_prompt,
+            )
+            .context("failed to write AGENTS.md")?;
+        }
+
+        let model = if request.model.is_empty() {
+            self.model.clone()
+        } else {
+            request.model.clone()
+        };
+        let mut args = vec![
+            "exec".to_string(),
+            "--json".to_string(),
+            "--skip-git-repo-check".to_string(),
+            "--full-auto".to_string(),
+            "--model".to_string(),
+            model,
+            "-c".to_string(),
+            format!("model_reasoning_effort=\"{}\"", self.reasoning_effort),
+        ];
+        if let Some(servers) = request.mcp_servers_json.as_object() {
+            Self::push_mcp_servers(&mut args, servers);
+        }
+        if let Some(sid) = ctx.session_id.as_deref().filter(|s| !s.is_empty()) {
+            args.push("resume".into());
+            args.push(sid.to_string());
+        }
+        args.push(request.message.clone());
+
+        info!(session_dir = %ctx.session_dir, "spawning codex chat");
+        let mut cmd = tokio::process::Command::new(&self.codex_bin);
+        cmd.args(&args)
+            .current_dir(&ctx.session_dir)
+            .env_remove("CLAUDECODE")
+            .env("HOME", &ctx.session_dir)
+            .env("CODEX_HOME", &codex_home)
+            .envs(&ctx.provider_env)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if !has_linked_auth && !self.api_key.is_empty() {
+            cmd.env("OPENAI_API_KEY", &self.api_key);
+        }
+        let mut child = cmd
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("failed to spawn codex binary: {}", self.codex_bin))?;
+
+        let (line_tx, mut line_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let stream_tx = ctx.stream_tx.clone();
+        let translator = tokio::spawn(async move {
+            let mut result = CodexChatResult::default();
+            while let Some(line) = line_rx.recv().await {
+                for out in apply_codex_event(&mut result, &line) {
+                    if let Some(tx) = &stream_tx {
+                        let _ = tx.send(out);
+                    }
+                }
+            }
+            result
+        });
+        let drain = drain_child(
+            &mut child,
+            DrainConfig {
+                backend: "codex",
+                task_id: 0,
+                phase_name: "chat",
+                timeout_s: self.timeout_s,
+                stream_tx: Some(line_tx),
+                is_warning_stderr: Self::is_warning_stderr,
+            },
+        )
+        .await?;
+        let exit_status = child
+            .wait()
+            .await
+            .context("failed to wait for codex process")?;
+        let mut result = translator
+            .await
+            .context("codex event translator panicked")?;
+
+        if drain.timed_out {
+            bail!("codex chat timed out after {}s", self.timeout_s);
+        }
+        if let Some(err) = result.error.take() {
+            bail!("codex error: {err}");
+        }
+        if !exit_status.success() && result.text.is_empty() {
+            bail!("codex exited with {exit_status}");
+        }
+
+        let final_line = event::result_line(
+            &result.text,
+            result.thread_id.as_deref(),
+            false,
+            1,
+            result.input_tokens,
+            result.output_tokens,
+        );
+        if let Some(tx) = &ctx.stream_tx {
+            let _ = tx.send(final_line.clone());
+        }
+        result.stream_lines.push(final_line);
+
+        Ok(ChatResponse {
+            text: result.text,
+            session_id: result.thread_id.or_else(|| ctx.session_id.clone()),
+            input_tokens: result.input_tokens,
+            output_tokens: result.output_tokens,
+            cost_usd: 0.0,
+            tool_calls: result.tool_calls,
+            raw_stream: result.stream_lines.join("\n"),
+            failovers: Vec::new(),
         })
     }
 
@@ -412,4 +682,27 @@ mod tests {
         assert!(!copied);
         assert!(!codex_home.join("auth.json").exists());
     }
+
+    #[test]
+    fn codex_json_events_become_stream_json() {
+        let lines = [
+            r#"{"type":"thread.started","thread_id":"th_1"}"#,
+            r#"{"type":"item.started","item":{"id":"i0","type":"command_execution","command":"ls","status":"in_progress"}}"#,
+            r#"{"type":"item.completed","item":{"id":"i0","type":"command_execution","command":"ls","aggregated_output":"a.txt\n","exit_code":0,"status":"completed"}}"#,
+            r#"{"type":"item.completed","item":{"id":"i1","type":"agent_message","text":"Found a.txt"}}"#,
+            r#"{"type":"turn.completed","usage":{"input_tokens":120,"cached_input_tokens":0,"output_tokens":8}}"#,
+        ];
+        let mut result = CodexChatResult::default();
+        for line in lines {
+            apply_codex_event(&mut result, line);
+        }
+        assert_eq!(result.thread_id.as_deref(), Some("th_1"));
+        assert_eq!(result.text, "Found a.txt");
+        assert_eq!((result.input_tokens, result.output_tokens), (120, 8));
+        assert_eq!(result.tool_calls.len(), 1);
+        assert!(result.tool_calls[0].success);
+        assert_eq!(result.stream_lines.len(), 3);
+        let (text, _) = crate::event::parse_stream(&result.stream_lines.join("\n"));
+        assert_eq!(text, "Found a.txt");
+    }
 }
@@ -4,9 +4,11 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 use borg_core::{
     agent::AgentBackend,
+    resources::ResourceMonitor,
     sandbox::Sandbox,
     traits::BackendCapabilities,
     types::{ContainerTestResult, PhaseConfig, PhaseContext, PhaseOutput, Task},
+    warm_pool::{worktree_repo, WarmKey},
 };
 use serde_json::json;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
@@ -45,22 +47,21 @@ impl ContainerBackend {
     }
 }
 
-fn container_host_ip(isolated: bool) -> &'static str {
-    if isolated {
-        "172.31.0.1"
-    } else {
-        "172.30.0.1"
-    }
+fn container_host_ip(isolated: bool) -> Option<&'static str> {
+    Sandbox::host_gateway(isolated)
 }
 
-fn container_reachable_url(base_url: &str, host_ip: &str) -> String {
-    if let Some(port) = base_url.strip_prefix("http://127.0.0.1:") {
-        return format!("http://{host_ip}:{port}");
-    }
-    if let Some(port) = base_url.strip_prefix("http://localhost:") {
-        return format!("http://{host_ip}:{port}");
+/// `base_url` as seen from inside an agent container. Loopback URLs become
+/// empty when the container has no route to the host.
+fn container_reachable_url(base_url: &str, host_ip: Option<&str>) -> String {
+    let port = base_url
+        .strip_prefix("http://127.0.0.1:")
+        .or_else(|| base_url.strip_prefix("http://localhost:"));
+    match (port, host_ip) {
+        (Some(port), Some(host_ip)) => format!("http://{host_ip}:{port}"),
+        (Some(_), None) => String::new(),
+        (None, _) => base_url.to_string(),
     }
-    base_url.to_string()
 }
 
 #[async_trait]
@@ -72,7 +73,8 @@ impl AgentBackend for ContainerBackend {
         ctx: PhaseContext,
     ) -> Result<PhaseOutput> {
         let instruction = crate::instruction::build_instruction(task, phase, &ctx, None);
-        let host_ip = container_host_ip(ctx.isolated);
+        // Egress-restricted runs also sit on the internal network.
+        let host_ip = container_host_ip(ctx.isolated || ctx.egress_proxy.is_some());
         let reachable_borg_api_url = container_reachable_url(&ctx.borg_api_url, host_ip);
 
         let workspace_host = if !task.repo_path.is_empty()
@@ -108,7 +110,12 @@ impl AgentBackend for ContainerBackend {
         if !ctx.borg_api_token.is_empty() {
             env_kv.push(("API_TOKEN".to_string(), ctx.borg_api_token.clone()));
         }
-        env_kv.push(("BORG_HOST_IP".to_string(), host_ip.to_string()));
+        if let Some(host_ip) = host_ip {
+            env_kv.push(("BORG_HOST_IP".to_string(), host_ip.to_string()));
+            if let Some(proxy) = &ctx.egress_proxy {
+                env_kv.extend(borg_core::egress::proxy_env(proxy, host_ip));
+            }
+        }
 
         let binds_ref: Vec<(&str, &str, bool)> = binds
             .iter()
@@ -123,18 +130,50 @@ impl AgentBackend for ContainerBackend {
             .map(|(k, v)| (k.as_str(), v.as_str()))
             .collect();
 
-        let mut docker_cmd = Sandbox::docker_command(
-            &self.docker_image,
-            &binds_ref,
-            &volumes_ref,
-            "",
-            &[],
-            &env_ref,
-            self.container_memory_mb,
-            self.container_cpus,
-            ctx.agent_network.as_deref(),
-        );
+        let ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let cid_path = format!("/tmp/borg-cid-{}-{}.txt", task.id, ms);
+        let _ = std::fs::remove_file(&cid_path);
 
+        let image = ctx.container_image.as_deref().unwrap_or(&self.docker_image);
+        // Warm containers are shared per repo; the worktree is staged in on
+        // checkout.
+        let warm_key = worktree_repo(&binds[0].0).map(|repo| {
+            WarmKey::new(
+                image,
+                repo,
+                &volumes_ref,
+                ctx.agent_network.as_deref(),
+                self.container_memory_mb,
+                self.container_cpus,
+            )
+        });
+        let warm_lease = match (Sandbox::warm_pool(), &warm_key) {
+            (Some(pool), Some(key)) => pool.acquire(key, &ctx.session_dir, &binds[0].0).await,
+            _ => None,
+        };
+        let mut docker_cmd = match &warm_lease {
+            Some(lease) => {
+                let _ = lease.write_cidfile(&cid_path);
+                lease.exec_command(&env_ref)
+            },
+            None => {
+                let cmd = Sandbox::docker_command(
+                    image,
+                    &binds_ref,
+                    &volumes_ref,
+                    "",
+                    &[],
+                    &env_ref,
+                    self.container_memory_mb,
+                    self.container_cpus,
+                    ctx.agent_network.as_deref(),
+                );
+                Sandbox::with_cidfile(&cmd, &cid_path)
+            },
+        };
         docker_cmd
             .kill_on_drop(true)
             .stdout(Stdio::piped())
@@ -144,7 +183,8 @@ impl AgentBackend for ContainerBackend {
         info!(
             task_id = task.id,
             phase = %phase.name,
-            image = %self.docker_image,
+            image = %image,
+            warm = warm_lease.is_some(),
             "spawning container agent"
         );
 
@@ -162,6 +202,7 @@ impl AgentBackend for ContainerBackend {
         };
 
         let mut child = docker_cmd.spawn().context("failed to spawn docker")?;
+        let monitor = ResourceMonitor::container(Sandbox::cli(), cid_path.clone());
 
         if let Some(mut stdin) = child.stdin.take() {
             let input = json!({
@@ -220,7 +261,7 @@ impl AgentBackend for ContainerBackend {
                                 if !l.is_empty() {
                                     let test_line = l.strip_prefix("---BORG_TEST_RESULT---").unwrap_or(&l);
                                     if let Ok(res) = serde_json::from_str::<ContainerTestResult>(test_line) {
-                                        container_test_results.push(res);
+                                        container_test_results.push(res.with_parsed_failures());
                                     } else {
                                         debug!("container stderr: {l}");
                                     }
@@ -254,6 +295,11 @@ impl AgentBackend for ContainerBackend {
         } else {
             io_future.await
         };
+        let resources = monitor.finish().await;
+        let _ = std::fs::remove_file(&cid_path);
+        if let Some(lease) = warm_lease {
+            lease.release(success).await;
+        }
 
         Ok(PhaseOutput {
             output: stdout_text,
@@ -263,9 +309,15 @@ impl AgentBackend for ContainerBackend {
             signal_json,
             ran_in_docker: true,
             container_test_results,
+            failovers: Vec::new(),
+            resources: Some(resources),
         })
     }
 
+    fn enforces_network_policy(&self, _phase: &PhaseConfig) -> bool {
+        true
+    }
+
     fn name(&self) -> &str {
         "container"
     }
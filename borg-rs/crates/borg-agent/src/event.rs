@@ -153,3 +153,56 @@ pub fn parse_stream(data: &str) -> (String, Option<String>) {
 
     (output, session_id)
 }
+
+// ── Stream builders ──────────────────────────────────────────────────────
+// Backends that do not speak stream-json natively (Codex, Gemini, Ollama)
+// translate their output into these lines so the UI and `parse_stream` treat
+// every backend alike.
+
+pub fn assistant_text_line(text: &str) -> String {
+    serde_json::json!({
+        "type": "assistant",
+        "message": {"role": "assistant", "content": [{"type": "text", "text": text}]},
+    })
+    .to_string()
+}
+
+pub fn tool_use_line(id: &str, name: &str, input: &Value) -> String {
+    serde_json::json!({
+        "type": "assistant",
+        "message": {"role": "assistant", "content": [
+            {"type": "tool_use", "id": id, "name": name, "input": input}
+        ]},
+    })
+    .to_string()
+}
+
+pub fn tool_result_line(tool_use_id: &str, content: &str, is_error: bool) -> String {
+    serde_json::json!({
+        "type": "user",
+        "message": {"role": "user", "content": [
+            {"type": "tool_result", "tool_use_id": tool_use_id, "content": content, "is_error": is_error}
+        ]},
+    })
+    .to_string()
+}
+
+pub fn result_line(
+    text: &str,
+    session_id: Option<&str>,
+    is_error: bool,
+    num_turns: u64,
+    input_tokens: u64,
+    output_tokens: u64,
+) -> String {
+    serde_json::json!({
+        "type": "result",
+        "subtype": if is_error { "error" } else { "success" },
+        "result": text,
+        "session_id": session_id,
+        "is_error": is_error,
+        "num_turns": num_turns,
+        "usage": {"input_tokens": input_tokens, "output_tokens": output_tokens},
+    })
+    .to_string()
+}
@@ -1,14 +1,20 @@
-use std::process::Stdio;
+use std::{path::Path, process::Stdio};
 
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use borg_core::{
     agent::AgentBackend,
+    traits::{ChatContext, ChatRequest, ChatResponse},
     types::{PhaseConfig, PhaseContext, PhaseOutput, Task},
 };
-use tracing::info;
+use serde_json::{json, Value};
+use tracing::{info, warn};
 
-use crate::drain::{drain_child, DrainConfig};
+use crate::{
+    chat_history,
+    drain::{drain_child, DrainConfig},
+    event,
+};
 
 /// Runs Gemini CLI (@google/gemini-cli) as the agent backend.
 ///
@@ -128,6 +134,119 @@ impl AgentBackend for GeminiBackend {
             signal_json: None,
             ran_in_docker: false,
             container_test_results: Vec::new(),
+            failovers: Vec::new(),
+            resources: None,
+        })
+    }
+
+    /// Chat turns run Gemini CLI in the chat's session directory with JSON
+    /// output. The CLI has no resumable session id, so prior turns are replayed
+    /// from the transcript kept in the session directory.
+    async fn run_chat(&self, request: &ChatRequest, ctx: &ChatContext) -> Result<ChatResponse> {
+        if !self.is_available().await {
+            bail!("gemini binary not found: {}", self.gemini_bin);
+        }
+        let session_dir = Path::new(&ctx.session_dir);
+        if !request.system_prompt.is_empty() {
+            std::fs::write(session_dir.join("GEMINI.md"), &request.system_prompt)
+                .context("failed to write GEMINI.md")?;
+        }
+        if let Some(servers) = request
+            .mcp_servers_json
+            .as_object()
+            .filter(|s| !s.is_empty())
+        {
+            let settings_dir = session_dir.join(".gemini");
+            std::fs::create_dir_all(&settings_dir)?;
+            std::fs::write(
+                settings_dir.join("settings.json"),
+                json!({ "mcpServers": servers }).to_string(),
+            )
+            .context("failed to write gemini settings")?;
+        }
+
+        let history = chat_history::history_for(request, ctx);
+        let prompt = chat_history::render_transcript(&history, &request.message);
+        let mut cmd = tokio::process::Command::new(&self.gemini_bin);
+        cmd.arg("--approval-mode=yolo")
+            .arg("--output-format")
+            .arg("json");
+        if !request.model.is_empty() {
+            cmd.arg("--model").arg(&request.model);
+        }
+        cmd.arg(&prompt)
+            .current_dir(&ctx.session_dir)
+            .env("HOME", &ctx.session_dir)
+            .envs(&ctx.provider_env)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if !self.api_key.is_empty() {
+            cmd.env("GEMINI_API_KEY", &self.api_key);
+        }
+        info!(session_dir = %ctx.session_dir, "spawning gemini chat");
+        let mut child = cmd
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("failed to spawn gemini binary: {}", self.gemini_bin))?;
+        let drain = drain_child(
+            &mut child,
+            DrainConfig {
+                backend: "gemini",
+                task_id: 0,
+                phase_name: "chat",
+                timeout_s: self.timeout_s,
+                stream_tx: None,
+                is_warning_stderr: Self::is_warning_stderr,
+            },
+        )
+        .await?;
+        let exit_status = child
+            .wait()
+            .await
+            .context("failed to wait for gemini process")?;
+        if drain.timed_out {
+            bail!("gemini chat timed out after {}s", self.timeout_s);
+        }
+
+        let parsed = parse_json_output(&drain.output);
+        if let Some(err) = parsed.error {
+            bail!("gemini error: {err}");
+        }
+        if !exit_status.success() && parsed.text.is_empty() {
+            bail!("gemini exited with {exit_status}");
+        }
+
+        let lines = [
+            event::assistant_text_line(&parsed.text),
+            event::result_line(
+                &parsed.text,
+                None,
+                false,
+                1,
+                parsed.input_tokens,
+                parsed.output_tokens,
+            ),
+        ];
+        if let Some(tx) = &ctx.stream_tx {
+            for line in &lines {
+                let _ = tx.send(line.clone());
+            }
+        }
+        if let Err(e) =
+            chat_history::append_history(&ctx.session_dir, &request.message, &parsed.text)
+        {
+            warn!("failed to save gemini chat history: {e}");
+        }
+
+        Ok(ChatResponse {
+            text: parsed.text,
+            session_id: None,
+            input_tokens: parsed.input_tokens,
+            output_tokens: parsed.output_tokens,
+            cost_usd: 0.0,
+            tool_calls: Vec::new(),
+            raw_stream: lines.join("\n"),
+            failovers: Vec::new(),
         })
     }
 
@@ -137,7 +256,7 @@ impl AgentBackend for GeminiBackend {
 
     fn capabilities(&self) -> borg_core::BackendCapabilities {
         borg_core::BackendCapabilities {
-            supports_mcp: false,
+            supports_mcp: true,
             supports_sessions: false,
             supports_tools: true,
             supports_streaming: true,
@@ -146,3 +265,58 @@ impl AgentBackend for GeminiBackend {
         }
     }
 }
+
+#[derive(Debug, Default, PartialEq)]
+struct GeminiOutput {
+    text: String,
+    input_tokens: u64,
+    output_tokens: u64,
+    error: Option<String>,
+}
+
+/// Parse `gemini --output-format json`. Older CLIs ignore the flag and print
+/// plain text, which is taken as the response as-is.
+fn parse_json_output(stdout: &str) -> GeminiOutput {
+    let start = stdout.find('{').unwrap_or(0);
+    let Ok(v) = serde_json::from_str::<Value>(&stdout[start..]) else {
+        return GeminiOutput {
+            text: stdout.trim().to_string(),
+            ..Default::default()
+        };
+    };
+    let mut out = GeminiOutput {
+        text: v["response"].as_str().unwrap_or("").trim().to_string(),
+        error: v["error"]["message"].as_str().map(str::to_string),
+        ..Default::default()
+    };
+    if let Some(models) = v["stats"]["models"].as_object() {
+        for model in models.values() {
+            out.input_tokens += model["tokens"]["prompt"].as_u64().unwrap_or(0);
+            out.output_tokens += model["tokens"]["candidates"].as_u64().unwrap_or(0);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_json_output_with_usage() {
+        let stdout = r#"Loaded cached credentials.
+{"response": "Hello there.", "stats": {"models": {"gemini-2.5-pro": {"tokens": {"prompt": 40, "candidates": 3, "total": 43}}}}}"#;
+        let out = parse_json_output(stdout);
+        assert_eq!(out.text, "Hello there.");
+        assert_eq!((out.input_tokens, out.output_tokens), (40, 3));
+        assert_eq!(out.error, None);
+    }
+
+    #[test]
+    fn falls_back_to_plain_text_and_reports_errors() {
+        assert_eq!(parse_json_output("just text\n").text, "just text");
+        let out =
+            parse_json_output(r#"{"error": {"type": "ApiError", "message": "quota exceeded"}}"#);
+        assert_eq!(out.error.as_deref(), Some("quota exceeded"));
+    }
+}
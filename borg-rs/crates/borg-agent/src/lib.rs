@@ -1,4 +1,5 @@
 pub mod bridge;
+pub mod chat_history;
 pub mod claude;
 pub mod container;
 pub use claude::extract_phase_result;
@@ -8,10 +9,13 @@ pub mod event;
 pub mod gemini;
 pub mod instruction;
 pub mod mcp;
+pub mod native_tools;
 pub mod ollama;
 pub mod reliable;
+pub mod replay;
 
 pub use bridge::AgentSdkBackend;
 pub use gemini::GeminiBackend;
 pub use ollama::OllamaBackend;
-pub use reliable::ReliableBackend;
+pub use reliable::{FailoverPolicy, ReliableBackend};
+pub use replay::{RecordingBackend, ReplayBackend};
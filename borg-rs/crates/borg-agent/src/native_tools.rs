@@ -0,0 +1,788 @@
+//! Rust-native implementations of the core agent tools (Read, Glob, Grep,
+//! Write, Edit, Bash) for backends that speak a tool-calling chat API but have
+//! no agent harness of their own (Ollama, OpenAI-compatible servers).
+//!
+//! Every path is resolved inside the task worktree; anything that would escape
+//! it is rejected. Bash runs in the worktree under bwrap, or in a container
+//! from the task image in Docker/Podman mode.
+
+use std::{
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use borg_core::sandbox::{Sandbox, SandboxMode};
+use serde_json::{json, Value};
+
+/// Tools implemented natively, in the order they are offered to the model.
+pub const NATIVE_TOOLS: &[&str] = &["Read", "Glob", "Grep", "Write", "Edit", "Bash"];
+
+const MAX_READ_LINES: usize = 2000;
+const MAX_OUTPUT_CHARS: usize = 30_000;
+const MAX_GLOB_RESULTS: usize = 500;
+const MAX_GREP_MATCHES: usize = 200;
+const DEFAULT_BASH_TIMEOUT_MS: u64 = 120_000;
+const MAX_BASH_TIMEOUT_MS: u64 = 600_000;
+const SKIP_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// Result of one tool invocation, fed back to the model verbatim.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolOutcome {
+    pub content: String,
+    pub is_error: bool,
+}
+
+impl ToolOutcome {
+    fn ok(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            is_error: false,
+        }
+    }
+
+    fn error(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            is_error: true,
+        }
+    }
+}
+
+/// Image and network that Bash commands run in under Docker/Podman.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolContainer {
+    pub image: String,
+    pub network: Option<String>,
+}
+
+/// The tool set available to one phase run.
+#[derive(Clone)]
+pub struct NativeTools {
+    root: PathBuf,
+    session_dir: String,
+    sandbox: SandboxMode,
+    container: Option<ToolContainer>,
+    /// Memory (MB) and CPU limits for the Bash container; 0 means unlimited.
+    container_memory_mb: u64,
+    container_cpus: f64,
+    enabled: Vec<&'static str>,
+}
+
+impl NativeTools {
+    /// `allowed` and `disallowed` are comma-separated tool lists in the same
+    /// format as `PhaseConfig::allowed_tools`; an empty allow list enables
+    /// every native tool. Entries such as `Bash(git:*)` match on the tool name.
+    pub fn new(
+        work_dir: &str,
+        session_dir: &str,
+        sandbox: SandboxMode,
+        allowed: &str,
+        disallowed: &str,
+    ) -> Self {
+        let allowed = tool_names(allowed);
+        let disallowed = tool_names(disallowed);
+        let enabled = NATIVE_TOOLS
+            .iter()
+            .copied()
+            .filter(|t| allowed.is_empty() || allowed.iter().any(|a| a == t))
+            .filter(|t| !disallowed.iter().any(|d| d == t))
+            .collect();
+        Self {
+            root: PathBuf::from(work_dir),
+            session_dir: session_dir.to_string(),
+            sandbox,
+            container: None,
+            container_memory_mb: 0,
+            container_cpus: 0.0,
+            enabled,
+        }
+    }
+
+    /// Run Bash in a container from `image` when the sandbox is Docker or
+    /// Podman. Without one, those modes refuse shell commands rather than
+    /// running them on the host.
+    pub fn in_container(mut self, image: &str, network: Option<&str>) -> Self {
+        if !image.is_empty() {
+            self.container = Some(ToolContainer {
+                image: image.to_string(),
+                network: network.map(str::to_string),
+            });
+        }
+        self
+    }
+
+    /// Limits for the Bash container, as the phase containers get them.
+    pub fn with_resource_limits(mut self, memory_mb: u64, cpus: f64) -> Self {
+        self.container_memory_mb = memory_mb;
+        self.container_cpus = cpus;
+        self
+    }
+
+    pub fn enabled(&self) -> &[&'static str] {
+        &self.enabled
+    }
+
+    /// Tool definitions in the `tools` format shared by Ollama and the
+    /// OpenAI chat completions API.
+    pub fn definitions(&self) -> Vec<Value> {
+        self.enabled
+            .iter()
+            .map(|name| {
+                let (description, parameters) = tool_schema(name);
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": name,
+                        "description": description,
+                        "parameters": parameters,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    pub async fn execute(&self, name: &str, input: &Value) -> ToolOutcome {
+        let Some(&name) = self.enabled.iter().find(|t| **t == name) else {
+            return ToolOutcome::error(format!("tool '{name}' is not available in this phase"));
+        };
+        let result = match name {
+            "Bash" => self.bash(input).await,
+            // File tools walk and read the worktree synchronously, so they run
+            // on the blocking pool instead of a runtime worker.
+            _ => {
+                let tools = self.clone();
+                let input = input.clone();
+                tokio::task::spawn_blocking(move || match name {
+                    "Read" => tools.read(&input),
+                    "Glob" => tools.glob(&input),
+                    "Grep" => tools.grep(&input),
+                    "Write" => tools.write(&input),
+                    "Edit" => tools.edit(&input),
+                    _ => Err(anyhow!("unknown tool '{name}'")),
+                })
+                .await
+                .unwrap_or_else(|e| Err(anyhow!("tool '{name}' panicked: {e}")))
+            },
+        };
+        match result {
+            Ok(outcome) => outcome,
+            Err(e) => ToolOutcome::error(format!("{e:#}")),
+        }
+    }
+
+    /// Resolve a model-supplied path inside the worktree. Relative paths are
+    /// taken from the worktree root; absolute paths must already point into it.
+    fn resolve(&self, raw: &str) -> Result<PathBuf> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            bail!("path is empty");
+        }
+        let candidate = Path::new(raw);
+        let joined = if candidate.is_absolute() {
+            candidate.to_path_buf()
+        } else {
+            self.root.join(candidate)
+        };
+        let normalized = normalize(&joined);
+        if !normalized.starts_with(&self.root) {
+            bail!("path '{raw}' is outside the worktree");
+        }
+        // The file itself must not be a link: reading or writing through it
+        // would follow it wherever it points.
+        if normalized
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            bail!("path '{raw}' is a symlink");
+        }
+        // Canonicalize the deepest existing ancestor (the parent directory for
+        // a new file) so a linked directory cannot reach outside the worktree,
+        // and hand back the real path so later calls do not re-follow links.
+        let root = self
+            .root
+            .canonicalize()
+            .context("worktree is not accessible")?;
+        let existing = normalized
+            .ancestors()
+            .find(|p| p.symlink_metadata().is_ok())
+            .unwrap_or(&self.root);
+        let real = existing
+            .canonicalize()
+            .with_context(|| format!("path '{raw}' does not resolve"))?;
+        if !real.starts_with(&root) {
+            bail!("path '{raw}' resolves outside the worktree");
+        }
+        match normalized.strip_prefix(existing) {
+            Ok(rest) if !rest.as_os_str().is_empty() => Ok(real.join(rest)),
+            _ => Ok(real),
+        }
+    }
+
+    fn display(&self, path: &Path) -> String {
+        let real_root = self
+            .root
+            .canonicalize()
+            .unwrap_or_else(|_| self.root.clone());
+        path.strip_prefix(&real_root)
+            .or_else(|_| path.strip_prefix(&self.root))
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn read(&self, input: &Value) -> Result<ToolOutcome> {
+        let path = self.resolve(str_arg(input, "file_path")?)?;
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", self.display(&path)))?;
+        let offset = usize_arg(input, "offset").unwrap_or(1).max(1);
+        let limit = usize_arg(input, "limit")
+            .unwrap_or(MAX_READ_LINES)
+            .min(MAX_READ_LINES);
+        let mut out = String::new();
+        for (i, line) in text.lines().enumerate().skip(offset - 1).take(limit) {
+            out.push_str(&format!("{:>6}\t{line}\n", i + 1));
+        }
+        if out.is_empty() {
+            out = "(no lines in range)".into();
+        }
+        Ok(ToolOutcome::ok(truncate(out)))
+    }
+
+    fn glob(&self, input: &Value) -> Result<ToolOutcome> {
+        let pattern = str_arg(input, "pattern")?;
+        let base = match input.get("path").and_then(Value::as_str) {
+            Some(p) if !p.is_empty() => self.resolve(p)?,
+            _ => self.root.clone(),
+        };
+        let matcher = glob_to_regex(pattern)?;
+        let mut hits = Vec::new();
+        walk(&base, &mut |path| {
+            let rel = path.strip_prefix(&base).unwrap_or(path).to_string_lossy();
+            if matcher.is_match(&rel) {
+                hits.push(self.display(path));
+            }
+            hits.len() < MAX_GLOB_RESULTS
+        });
+        hits.sort();
+        if hits.is_empty() {
+            return Ok(ToolOutcome::ok("No files found"));
+        }
+        Ok(ToolOutcome::ok(truncate(hits.join("\n"))))
+    }
+
+    fn grep(&self, input: &Value) -> Result<ToolOutcome> {
+        let pattern = str_arg(input, "pattern")?;
+        let re = regex::Regex::new(pattern).context("invalid regex")?;
+        let base = match input.get("path").and_then(Value::as_str) {
+            Some(p) if !p.is_empty() => self.resolve(p)?,
+            _ => self.root.clone(),
+        };
+        let file_filter = match input.get("glob").and_then(Value::as_str) {
+            Some(g) if !g.is_empty() => Some(glob_to_regex(g)?),
+            _ => None,
+        };
+        let mut matches = Vec::new();
+        let mut search = |path: &Path| {
+            if let Some(filter) = &file_filter {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let rel = path.strip_prefix(&base).unwrap_or(path).to_string_lossy();
+                if !filter.is_match(&name) && !filter.is_match(&rel) {
+                    return true;
+                }
+            }
+            let Ok(text) = std::fs::read_to_string(path) else {
+                return true;
+            };
+            for (i, line) in text.lines().enumerate() {
+                if re.is_match(line) {
+                    matches.push(format!("{}:{}:{line}", self.display(path), i + 1));
+                    if matches.len() >= MAX_GREP_MATCHES {
+                        return false;
+                    }
+                }
+            }
+            true
+        };
+        if base.is_file() {
+            search(&base);
+        } else {
+            walk(&base, &mut search);
+        }
+        if matches.is_empty() {
+            return Ok(ToolOutcome::ok("No matches found"));
+        }
+        Ok(ToolOutcome::ok(truncate(matches.join("\n"))))
+    }
+
+    fn write(&self, input: &Value) -> Result<ToolOutcome> {
+        let path = self.resolve(str_arg(input, "file_path")?)?;
+        let content = str_arg(input, "content")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("failed to write {}", self.display(&path)))?;
+        Ok(ToolOutcome::ok(format!(
+            "Wrote {} bytes to {}",
+            content.len(),
+            self.display(&path)
+        )))
+    }
+
+    fn edit(&self, input: &Value) -> Result<ToolOutcome> {
+        let path = self.resolve(str_arg(input, "file_path")?)?;
+        let old = str_arg(input, "old_string")?;
+        let new = str_arg(input, "new_string")?;
+        let replace_all = input
+            .get("replace_all")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        if old.is_empty() {
+            bail!("old_string must not be empty");
+        }
+        if old == new {
+            bail!("old_string and new_string are identical");
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", self.display(&path)))?;
+        let count = text.matches(old).count();
+        if count == 0 {
+            bail!("old_string not found in {}", self.display(&path));
+        }
+        if count > 1 && !replace_all {
+            bail!(
+                "old_string occurs {count} times in {}; add context to make it unique or set replace_all",
+                self.display(&path)
+            );
+        }
+        let updated = if replace_all {
+            text.replace(old, new)
+        } else {
+            text.replacen(old, new, 1)
+        };
+        std::fs::write(&path, updated)?;
+        Ok(ToolOutcome::ok(format!(
+            "Replaced {} occurrence(s) in {}",
+            if replace_all { count } else { 1 },
+            self.display(&path)
+        )))
+    }
+
+    async fn bash(&self, input: &Value) -> Result<ToolOutcome> {
+        let command = str_arg(input, "command")?;
+        let timeout_ms = input
+            .get("timeout")
+            .and_then(Value::as_u64)
+            .unwrap_or(DEFAULT_BASH_TIMEOUT_MS)
+            .min(MAX_BASH_TIMEOUT_MS);
+        let work_dir = self.root.to_string_lossy().to_string();
+        let argv = vec!["bash".to_string(), "-c".to_string(), command.to_string()];
+
+        let mut cmd = match self.sandbox {
+            // The loop itself runs on the host, so container-mode phases run
+            // each shell command in a throwaway container from the task image.
+            SandboxMode::Docker | SandboxMode::Podman => {
+                let Some(container) = &self.container else {
+                    bail!("no container image for the {:?} sandbox; refusing to run shell commands on the host", self.sandbox);
+                };
+                let mut binds = vec![(work_dir.as_str(), "/workspace", false)];
+                if !self.session_dir.is_empty() {
+                    binds.push((self.session_dir.as_str(), "/home/bun", false));
+                }
+                Sandbox::docker_command(
+                    &container.image,
+                    &binds,
+                    &[],
+                    "/workspace",
+                    &argv,
+                    &[("HOME", "/home/bun")],
+                    self.container_memory_mb,
+                    self.container_cpus,
+                    container.network.as_deref(),
+                )
+            },
+            SandboxMode::Bwrap => {
+                let home = std::env::var("HOME").unwrap_or_default();
+                let mut writable = vec![work_dir.as_str()];
+                if !self.session_dir.is_empty() {
+                    writable.push(self.session_dir.as_str());
+                }
+                let mut hide: Vec<&str> = vec!["/root"];
+                if !home.is_empty() && home != "/root" {
+                    hide.push(home.as_str());
+                }
+                let mut cmd = Sandbox::bwrap_command(&writable, &hide, &[], &work_dir, &argv);
+                if !self.session_dir.is_empty() {
+                    cmd.env("HOME", &self.session_dir);
+                }
+                cmd
+            },
+            SandboxMode::Direct => {
+                let mut cmd = tokio::process::Command::new(&argv[0]);
+                cmd.args(&argv[1..]).current_dir(&self.root);
+                if !self.session_dir.is_empty() {
+                    cmd.env("HOME", &self.session_dir);
+                }
+                cmd
+            },
+        };
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let child = cmd.spawn().context("failed to spawn bash")?;
+        let output =
+            match tokio::time::timeout(Duration::from_millis(timeout_ms), child.wait_with_output())
+                .await
+            {
+                Ok(out) => out?,
+                Err(_) => {
+                    return Ok(ToolOutcome::error(format!(
+                        "command timed out after {timeout_ms}ms"
+                    )))
+                },
+            };
+        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(&stderr);
+        }
+        let code = output.status.code().unwrap_or(-1);
+        if code != 0 {
+            text.push_str(&format!("\n[exit code {code}]"));
+        }
+        let text = truncate(text);
+        Ok(if output.status.success() {
+            ToolOutcome::ok(text)
+        } else {
+            ToolOutcome::error(text)
+        })
+    }
+}
+
+/// Split a comma-separated tool list into bare tool names.
+fn tool_names(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|t| t.split('(').next().unwrap_or("").trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn tool_schema(name: &str) -> (&'static str, Value) {
+    match name {
+        "Read" => (
+            "Read a text file from the worktree. Returns numbered lines.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "file_path": {"type": "string", "description": "Path relative to the worktree root"},
+                    "offset": {"type": "integer", "description": "1-based line to start from"},
+                    "limit": {"type": "integer", "description": "Maximum number of lines"}
+                },
+                "required": ["file_path"]
+            }),
+        ),
+        "Glob" => (
+            "List files matching a glob pattern such as **/*.rs.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {"type": "string"},
+                    "path": {"type": "string", "description": "Directory to search, defaults to the worktree root"}
+                },
+                "required": ["pattern"]
+            }),
+        ),
+        "Grep" => (
+            "Search file contents with a regular expression. Returns path:line:text.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {"type": "string"},
+                    "path": {"type": "string", "description": "File or directory to search"},
+                    "glob": {"type": "string", "description": "Only search files matching this glob"}
+                },
+                "required": ["pattern"]
+            }),
+        ),
+        "Write" => (
+            "Create or overwrite a file in the worktree.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "file_path": {"type": "string"},
+                    "content": {"type": "string"}
+                },
+                "required": ["file_path", "content"]
+            }),
+        ),
+        "Edit" => (
+            "Replace an exact string in a file. old_string must be unique unless replace_all is true.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "file_path": {"type": "string"},
+                    "old_string": {"type": "string"},
+                    "new_string": {"type": "string"},
+                    "replace_all": {"type": "boolean"}
+                },
+                "required": ["file_path", "old_string", "new_string"]
+            }),
+        ),
+        _ => (
+            "Run a shell command in the worktree and return its output.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string"},
+                    "timeout": {"type": "integer", "description": "Timeout in milliseconds (max 600000)"}
+                },
+                "required": ["command"]
+            }),
+        ),
+    }
+}
+
+fn str_arg<'a>(input: &'a Value, key: &str) -> Result<&'a str> {
+    input
+        .get(key)
+        .and_then(Value::as_str)
+        .with_context(|| format!("missing string argument '{key}'"))
+}
+
+fn usize_arg(input: &Value, key: &str) -> Option<usize> {
+    input.get(key).and_then(Value::as_u64).map(|v| v as usize)
+}
+
+/// Lexically resolve `.` and `..` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                out.pop();
+            },
+            Component::CurDir => {},
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn truncate(mut s: String) -> String {
+    if s.len() > MAX_OUTPUT_CHARS {
+        let mut cut = MAX_OUTPUT_CHARS;
+        while !s.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        s.truncate(cut);
+        s.push_str("\n[output truncated]");
+    }
+    s
+}
+
+/// Translate a glob (`*`, `**`, `?`, `{a,b}`) into an anchored regex over
+/// `/`-separated relative paths.
+fn glob_to_regex(glob: &str) -> Result<regex::Regex> {
+    let mut re = String::from("^");
+    let chars: Vec<char> = glob.trim_start_matches("./").chars().collect();
+    let mut i = 0;
+    let mut in_group = false;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                if chars.get(i + 2) == Some(&'/') {
+                    re.push_str("(?:.*/)?");
+                    i += 2;
+                } else {
+                    re.push_str(".*");
+                    i += 1;
+                }
+            },
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '{' => {
+                in_group = true;
+                re.push_str("(?:");
+            },
+            '}' if in_group => {
+                in_group = false;
+                re.push(')');
+            },
+            ',' if in_group => re.push('|'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    re.push('$');
+    regex::Regex::new(&re).with_context(|| format!("invalid glob '{glob}'"))
+}
+
+/// Depth-first walk over regular files, skipping VCS and build directories.
+/// `visit` returns false to stop the walk.
+fn walk(dir: &Path, visit: &mut dyn FnMut(&Path) -> bool) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return true;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            if SKIP_DIRS.iter().any(|s| name == *s) {
+                continue;
+            }
+            if !walk(&path, visit) {
+                return false;
+            }
+        } else if file_type.is_file() && !visit(&path) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tools(dir: &Path, allowed: &str, disallowed: &str) -> NativeTools {
+        NativeTools::new(
+            dir.to_str().unwrap(),
+            "",
+            SandboxMode::Direct,
+            allowed,
+            disallowed,
+        )
+    }
+
+    #[test]
+    fn allow_and_deny_lists_filter_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(tools(dir.path(), "", "").enabled(), NATIVE_TOOLS);
+        let t = tools(dir.path(), "Read,Grep,Bash(git:*),WebFetch", "Grep");
+        assert_eq!(t.enabled(), &["Read", "Bash"]);
+        assert_eq!(t.definitions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn paths_cannot_escape_the_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        let t = tools(dir.path(), "", "");
+        let out = t
+            .execute(
+                "Write",
+                &json!({"file_path": "../escape.txt", "content": "x"}),
+            )
+            .await;
+        assert!(out.is_error, "{}", out.content);
+        let out = t
+            .execute("Read", &json!({"file_path": "/etc/passwd"}))
+            .await;
+        assert!(out.is_error);
+        let out = t
+            .execute(
+                "Write",
+                &json!({"file_path": ".borg/signal.json", "content": "{\"status\":\"done\"}"}),
+            )
+            .await;
+        assert!(!out.is_error, "{}", out.content);
+        assert!(dir.path().join(".borg/signal.json").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_cannot_be_followed_out_of_the_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "s3cret").unwrap();
+        std::fs::write(dir.path().join("inside.txt"), "ok").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("out")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("inside.txt"), dir.path().join("link.txt"))
+            .unwrap();
+        let t = tools(dir.path(), "", "");
+
+        let out = t
+            .execute("Read", &json!({"file_path": "out/secret.txt"}))
+            .await;
+        assert!(out.is_error, "{}", out.content);
+        let out = t
+            .execute(
+                "Write",
+                &json!({"file_path": "out/new.txt", "content": "x"}),
+            )
+            .await;
+        assert!(out.is_error, "{}", out.content);
+        assert!(!outside.path().join("new.txt").exists());
+        let out = t
+            .execute("Write", &json!({"file_path": "link.txt", "content": "x"}))
+            .await;
+        assert!(out.content.contains("symlink"), "{}", out.content);
+        let out = t.execute("Read", &json!({"file_path": "inside.txt"})).await;
+        assert!(!out.is_error, "{}", out.content);
+    }
+
+    #[tokio::test]
+    async fn container_modes_never_run_bash_on_the_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let t = NativeTools::new(
+            dir.path().to_str().unwrap(),
+            "",
+            SandboxMode::Docker,
+            "",
+            "",
+        );
+        let out = t
+            .execute("Bash", &json!({"command": "touch ran-on-host"}))
+            .await;
+        assert!(out.is_error);
+        assert!(!dir.path().join("ran-on-host").exists());
+    }
+
+    #[tokio::test]
+    async fn edit_requires_unique_match_unless_replace_all() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "foo bar foo").unwrap();
+        let t = tools(dir.path(), "", "");
+        let args = json!({"file_path": "a.txt", "old_string": "foo", "new_string": "baz"});
+        assert!(t.execute("Edit", &args).await.is_error);
+        let args = json!({"file_path": "a.txt", "old_string": "foo", "new_string": "baz", "replace_all": true});
+        assert!(!t.execute("Edit", &args).await.is_error);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "baz bar baz"
+        );
+    }
+
+    #[tokio::test]
+    async fn glob_grep_and_bash_run_in_the_worktree() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "fn alpha() {}\n").unwrap();
+        std::fs::write(dir.path().join("src/nested/mod.rs"), "fn beta() {}\n").unwrap();
+        std::fs::write(dir.path().join("README.md"), "alpha\n").unwrap();
+        let t = tools(dir.path(), "", "");
+
+        let out = t.execute("Glob", &json!({"pattern": "**/*.rs"})).await;
+        assert_eq!(out.content, "src/lib.rs\nsrc/nested/mod.rs");
+        let out = t
+            .execute("Grep", &json!({"pattern": "alpha", "glob": "*.rs"}))
+            .await;
+        assert_eq!(out.content, "src/lib.rs:1:fn alpha() {}");
+        let out = t.execute("Bash", &json!({"command": "ls src"})).await;
+        assert!(!out.is_error);
+        assert!(out.content.contains("lib.rs"));
+        let out = t.execute("Bash", &json!({"command": "exit 3"})).await;
+        assert!(out.is_error);
+        assert!(out.content.contains("exit code 3"));
+    }
+}
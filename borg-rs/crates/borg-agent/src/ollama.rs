@@ -1,24 +1,71 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
 use borg_core::{
     agent::AgentBackend,
+    sandbox::SandboxMode,
+    traits::{ChatContext, ChatRequest, ChatResponse, ToolCallRecord},
     types::{PhaseConfig, PhaseContext, PhaseOutput, Task},
 };
-use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use tracing::{info, warn};
 
-/// Calls a locally-hosted Ollama model via its native chat API.
+use crate::{
+    chat_history, event,
+    native_tools::{NativeTools, ToolOutcome},
+};
+
+const DEFAULT_MAX_TURNS: u32 = 50;
+
+/// Wire format of the chat endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatApi {
+    /// Ollama's native `/api/chat`.
+    Ollama,
+    /// Any OpenAI-compatible `/v1/chat/completions` server (vLLM, llama.cpp,
+    /// LM Studio, Ollama's own compatibility layer, ...).
+    OpenAi,
+}
+
+/// Calls a locally-hosted model through a tool-calling chat API and drives
+/// the agent loop itself.
 ///
 /// Intended for privacy-sensitive pipelines (legal, HR, medical) where
-/// task content must not leave the local machine. No tool-calling support;
-/// phases that require tool use will receive plain-text output only.
+/// task content must not leave the local machine. Read/Glob/Grep/Write/Edit/Bash
+/// are executed natively inside the task worktree (see [`NativeTools`]), so
+/// phases can read files, write deliverables and emit `.borg/signal.json`.
 pub struct OllamaBackend {
     pub base_url: String,
     pub model: String,
     pub timeout_secs: u64,
+    pub api: ChatApi,
+    pub api_key: String,
+    pub sandbox_mode: SandboxMode,
+    /// Image Bash tool calls run in under Docker/Podman, unless the task
+    /// brings its own.
+    pub docker_image: String,
+    pub container_memory_mb: u64,
+    pub container_cpus: f64,
+    pub max_turns: u32,
     http: reqwest::Client,
 }
 
+/// One parsed assistant turn.
+#[derive(Debug, Default, PartialEq)]
+struct AssistantTurn {
+    content: String,
+    tool_calls: Vec<ToolCall>,
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ToolCall {
+    id: String,
+    name: String,
+    /// Parsed arguments, or the parse error for malformed argument JSON.
+    arguments: std::result::Result<Value, String>,
+}
+
 impl OllamaBackend {
     pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Result<Self> {
         let timeout_secs = 300u64;
@@ -30,9 +77,29 @@ impl OllamaBackend {
             model: model.into(),
             http,
             timeout_secs,
+            api: ChatApi::Ollama,
+            api_key: String::new(),
+            sandbox_mode: SandboxMode::Direct,
+            docker_image: String::new(),
+            container_memory_mb: 0,
+            container_cpus: 0.0,
+            max_turns: DEFAULT_MAX_TURNS,
         })
     }
 
+    /// Backend for an OpenAI-compatible chat completions server. `base_url`
+    /// may be given with or without the trailing `/v1`.
+    pub fn openai_compatible(
+        base_url: impl Into<String>,
+        model: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Result<Self> {
+        let mut backend = Self::new(base_url, model)?;
+        backend.api = ChatApi::OpenAi;
+        backend.api_key = api_key.into();
+        Ok(backend)
+    }
+
     pub fn with_timeout(mut self, secs: u64) -> Result<Self> {
         self.timeout_secs = secs;
         self.http = reqwest::Client::builder()
@@ -40,29 +107,329 @@ impl OllamaBackend {
             .build()?;
         Ok(self)
     }
+
+    pub fn with_sandbox(mut self, mode: SandboxMode) -> Self {
+        self.sandbox_mode = mode;
+        self
+    }
+
+    pub fn with_docker_image(mut self, image: impl Into<String>) -> Self {
+        self.docker_image = image.into();
+        self
+    }
+
+    pub fn with_resource_limits(mut self, memory_mb: u64, cpus: f64) -> Self {
+        self.container_memory_mb = memory_mb;
+        self.container_cpus = cpus;
+        self
+    }
+
+    pub fn with_max_turns(mut self, turns: u32) -> Self {
+        self.max_turns = turns.max(1);
+        self
+    }
+
+    fn endpoint(&self) -> String {
+        let base = self.base_url.trim_end_matches('/');
+        match self.api {
+            ChatApi::Ollama => format!("{base}/api/chat"),
+            ChatApi::OpenAi if base.ends_with("/v1") => format!("{base}/chat/completions"),
+            ChatApi::OpenAi => format!("{base}/v1/chat/completions"),
+        }
+    }
+
+    fn request_body(&self, messages: &[Value], tools: &[Value]) -> Value {
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": false,
+        });
+        if !tools.is_empty() {
+            body["tools"] = json!(tools);
+        }
+        body
+    }
+
+    async fn chat(&self, messages: &[Value], tools: &[Value], turn: u32) -> Result<AssistantTurn> {
+        let mut req = self
+            .http
+            .post(self.endpoint())
+            .json(&self.request_body(messages, tools));
+        if !self.api_key.is_empty() {
+            req = req.bearer_auth(&self.api_key);
+        }
+        let response = match req.send().await {
+            Ok(r) => r,
+            Err(e) if e.is_timeout() => {
+                bail!(
+                    "{} request timed out after {}s",
+                    self.label(),
+                    self.timeout_secs
+                )
+            },
+            Err(e) => bail!("{} request failed: {}", self.label(), e),
+        };
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("{} error {}: {}", self.label(), status, body);
+        }
+        let body: Value = match response.json().await {
+            Ok(v) => v,
+            Err(e) => bail!("Failed to parse {} response: {}", self.label(), e),
+        };
+        parse_turn(self.api, &body, turn)
+    }
+
+    fn label(&self) -> &'static str {
+        match self.api {
+            ChatApi::Ollama => "Ollama",
+            ChatApi::OpenAi => "OpenAI-compatible",
+        }
+    }
 }
 
-#[derive(Serialize)]
-struct OllamaMessage {
-    role: String,
-    content: String,
+/// Parse a non-streaming chat response into text and tool calls.
+fn parse_turn(api: ChatApi, body: &Value, turn: u32) -> Result<AssistantTurn> {
+    let (message, input_tokens, output_tokens) = match api {
+        ChatApi::Ollama => (
+            body.get("message"),
+            body["prompt_eval_count"].as_u64().unwrap_or(0),
+            body["eval_count"].as_u64().unwrap_or(0),
+        ),
+        ChatApi::OpenAi => (
+            body.pointer("/choices/0/message"),
+            body.pointer("/usage/prompt_tokens")
+                .and_then(Value::as_u64)
+                .unwrap_or(0),
+            body.pointer("/usage/completion_tokens")
+                .and_then(Value::as_u64)
+                .unwrap_or(0),
+        ),
+    };
+    let Some(message) = message else {
+        bail!("response has no assistant message");
+    };
+    let tool_calls = message["tool_calls"]
+        .as_array()
+        .map(|calls| {
+            calls
+                .iter()
+                .enumerate()
+                .map(|(i, call)| {
+                    let id = call["id"]
+                        .as_str()
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("call_{turn}_{i}"));
+                    let name = call["function"]["name"].as_str().unwrap_or("").to_string();
+                    // OpenAI encodes arguments as a JSON string, Ollama as an object.
+                    let arguments = match &call["function"]["arguments"] {
+                        Value::String(s) if s.trim().is_empty() => Ok(json!({})),
+                        Value::String(s) => serde_json::from_str(s)
+                            .map_err(|e| format!("arguments are not valid JSON: {e}")),
+                        Value::Null => Ok(json!({})),
+                        other => Ok(other.clone()),
+                    };
+                    ToolCall {
+                        id,
+                        name,
+                        arguments,
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(AssistantTurn {
+        content: message["content"].as_str().unwrap_or("").to_string(),
+        tool_calls,
+        input_tokens,
+        output_tokens,
+    })
 }
 
-#[derive(Serialize)]
-struct OllamaChatRequest {
-    model: String,
-    messages: Vec<OllamaMessage>,
-    stream: bool,
+/// The assistant message to append to the conversation history.
+fn assistant_message(api: ChatApi, turn: &AssistantTurn) -> Value {
+    let mut msg = json!({"role": "assistant", "content": turn.content});
+    if turn.tool_calls.is_empty() {
+        return msg;
+    }
+    let calls: Vec<Value> = turn
+        .tool_calls
+        .iter()
+        .map(|c| {
+            let args = c.arguments.clone().unwrap_or_else(|_| json!({}));
+            match api {
+                ChatApi::Ollama => json!({"function": {"name": c.name, "arguments": args}}),
+                ChatApi::OpenAi => json!({
+                    "id": c.id,
+                    "type": "function",
+                    "function": {"name": c.name, "arguments": args.to_string()},
+                }),
+            }
+        })
+        .collect();
+    msg["tool_calls"] = json!(calls);
+    msg
 }
 
-#[derive(Deserialize)]
-struct OllamaChatResponse {
-    message: OllamaResponseMessage,
+fn tool_message(api: ChatApi, call: &ToolCall, outcome: &ToolOutcome) -> Value {
+    match api {
+        ChatApi::Ollama => json!({
+            "role": "tool",
+            "tool_name": call.name,
+            "content": outcome.content,
+        }),
+        ChatApi::OpenAi => json!({
+            "role": "tool",
+            "tool_call_id": call.id,
+            "content": outcome.content,
+        }),
+    }
 }
 
-#[derive(Deserialize)]
-struct OllamaResponseMessage {
-    content: String,
+/// Records stream-json events (the Claude Code NDJSON shape the UI already
+/// renders) and forwards them to the live stream when one is attached.
+struct EventLog {
+    tx: Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    raw: String,
+}
+
+impl EventLog {
+    fn new(tx: Option<tokio::sync::mpsc::UnboundedSender<String>>) -> Self {
+        Self {
+            tx,
+            raw: String::new(),
+        }
+    }
+
+    fn emit(&mut self, line: String) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(line.clone());
+        }
+        self.raw.push_str(&line);
+        self.raw.push('\n');
+    }
+}
+
+/// How a tool loop ended.
+struct LoopOutcome {
+    /// Final assistant text, or `None` if the turn limit was reached.
+    text: Option<String>,
+    turns: u32,
+    input_tokens: u64,
+    output_tokens: u64,
+    tool_calls: Vec<ToolCallRecord>,
+}
+
+impl OllamaBackend {
+    /// Alternate model turns and tool executions until the model answers
+    /// without calling a tool or `max_turns` is reached.
+    async fn drive(
+        &self,
+        messages: &mut Vec<Value>,
+        tools: &NativeTools,
+        max_turns: u32,
+        log: &mut EventLog,
+    ) -> Result<LoopOutcome> {
+        let tool_defs = tools.definitions();
+        let mut outcome = LoopOutcome {
+            text: None,
+            turns: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            tool_calls: Vec::new(),
+        };
+        log.emit(
+            json!({
+                "type": "system",
+                "subtype": "init",
+                "model": self.model,
+                "tools": tools.enabled(),
+            })
+            .to_string(),
+        );
+        while outcome.turns < max_turns {
+            outcome.turns += 1;
+            let reply = self.chat(messages, &tool_defs, outcome.turns).await?;
+            outcome.input_tokens += reply.input_tokens;
+            outcome.output_tokens += reply.output_tokens;
+
+            if !reply.content.is_empty() {
+                log.emit(event::assistant_text_line(&reply.content));
+            }
+            for call in &reply.tool_calls {
+                let input = call.arguments.clone().unwrap_or_else(|_| json!({}));
+                log.emit(event::tool_use_line(&call.id, &call.name, &input));
+            }
+            messages.push(assistant_message(self.api, &reply));
+
+            if reply.tool_calls.is_empty() {
+                outcome.text = Some(reply.content);
+                break;
+            }
+
+            for call in &reply.tool_calls {
+                let started = std::time::Instant::now();
+                let result = match &call.arguments {
+                    Ok(args) => tools.execute(&call.name, args).await,
+                    Err(e) => ToolOutcome {
+                        content: e.clone(),
+                        is_error: true,
+                    },
+                };
+                log.emit(event::tool_result_line(
+                    &call.id,
+                    &result.content,
+                    result.is_error,
+                ));
+                outcome.tool_calls.push(ToolCallRecord {
+                    tool_name: call.name.clone(),
+                    input_summary: summarize(
+                        &call
+                            .arguments
+                            .as_ref()
+                            .map(|v| v.to_string())
+                            .unwrap_or_default(),
+                    ),
+                    output_summary: summarize(&result.content),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    success: !result.is_error,
+                    error: result.is_error.then(|| summarize(&result.content)),
+                });
+                messages.push(tool_message(self.api, call, &result));
+            }
+        }
+        Ok(outcome)
+    }
+
+    fn max_turns_message(&self, max_turns: u32) -> String {
+        format!(
+            "{} agent stopped after {} turns without a final answer",
+            self.label(),
+            max_turns
+        )
+    }
+}
+
+fn summarize(s: &str) -> String {
+    const MAX: usize = 500;
+    if s.len() <= MAX {
+        return s.to_string();
+    }
+    let mut cut = MAX;
+    while !s.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}...", &s[..cut])
+}
+
+fn join_prompt(base: &str, suffix: &str) -> String {
+    match (base.is_empty(), suffix.is_empty()) {
+        (_, true) => base.to_string(),
+        (true, false) => suffix.to_string(),
+        (false, false) => format!("{base}\n\n{suffix}"),
+    }
 }
 
 #[async_trait]
@@ -73,114 +440,194 @@ impl AgentBackend for OllamaBackend {
         phase: &PhaseConfig,
         ctx: PhaseContext,
     ) -> Result<PhaseOutput> {
-        let user_content = crate::instruction::build_instruction(task, phase, &ctx, None);
+        let file_listing = if phase.include_file_listing {
+            let git = borg_core::git::Git::new(&ctx.work_dir);
+            git.ls_files_manifest(&ctx.work_dir, 200, 16_000).ok()
+        } else {
+            None
+        };
+        let user_content =
+            crate::instruction::build_instruction(task, phase, &ctx, file_listing.as_deref());
 
-        let mut messages = Vec::new();
-        if !phase.system_prompt.is_empty() {
-            messages.push(OllamaMessage {
-                role: "system".into(),
-                content: phase.system_prompt.clone(),
-            });
+        let mut disallowed = phase.disallowed_tools.clone();
+        if !ctx.disallowed_tools.is_empty() {
+            if !disallowed.is_empty() {
+                disallowed.push(',');
+            }
+            disallowed.push_str(&ctx.disallowed_tools);
         }
-        messages.push(OllamaMessage {
-            role: "user".into(),
-            content: user_content,
-        });
-
-        let request_body = OllamaChatRequest {
-            model: self.model.clone(),
-            messages,
-            stream: false,
+        let sandbox = if phase.use_docker {
+            self.sandbox_mode.clone()
+        } else {
+            SandboxMode::Direct
         };
+        // Without a worktree there is nothing for the tools to operate on.
+        let allowed = if ctx.work_dir.is_empty() {
+            "none"
+        } else {
+            phase.allowed_tools.as_str()
+        };
+        let tools = NativeTools::new(
+            &ctx.work_dir,
+            &ctx.session_dir,
+            sandbox,
+            allowed,
+            &disallowed,
+        )
+        .in_container(
+            ctx.container_image.as_deref().unwrap_or(&self.docker_image),
+            ctx.agent_network.as_deref(),
+        )
+        .with_resource_limits(self.container_memory_mb, self.container_cpus);
+
+        let mut messages = Vec::new();
+        let system_prompt = join_prompt(&phase.system_prompt, &ctx.system_prompt_suffix);
+        if !system_prompt.is_empty() {
+            messages.push(json!({"role": "system", "content": system_prompt}));
+        }
+        messages.push(json!({"role": "user", "content": user_content}));
 
         info!(
             task_id = task.id,
             phase = %phase.name,
             model = %self.model,
             base_url = %self.base_url,
-            "calling ollama chat API"
+            tools = ?tools.enabled(),
+            "starting native agent loop"
         );
 
-        let url = format!("{}/api/chat", self.base_url.trim_end_matches('/'));
-
-        let response = match self.http.post(&url).json(&request_body).send().await {
-            Ok(r) => r,
-            Err(e) if e.is_timeout() => {
-                warn!(
-                    task_id = task.id,
-                    phase = %phase.name,
-                    timeout_secs = self.timeout_secs,
-                    "ollama request timed out"
-                );
-                return Ok(PhaseOutput::failed(format!(
-                    "Ollama request timed out after {}s",
-                    self.timeout_secs
-                )));
-            },
-            Err(e) => {
-                warn!(task_id = task.id, phase = %phase.name, "ollama request failed: {}", e);
-                return Ok(PhaseOutput::failed(format!("Ollama request failed: {}", e)));
-            },
-        };
-
-        let status = response.status();
-        if !status.is_success() {
-            let body = response.text().await.unwrap_or_default();
-            warn!(
-                task_id = task.id,
-                phase = %phase.name,
-                status = %status,
-                "ollama returned non-200: {}",
-                body
-            );
-            return Ok(PhaseOutput::failed(format!(
-                "Ollama error {}: {}",
-                status, body
-            )));
-        }
-
-        let parsed: OllamaChatResponse = match response.json().await {
-            Ok(v) => v,
+        let mut log = EventLog::new(ctx.stream_tx.clone());
+        let outcome = match self
+            .drive(&mut messages, &tools, self.max_turns, &mut log)
+            .await
+        {
+            Ok(o) => o,
             Err(e) => {
-                warn!(task_id = task.id, phase = %phase.name, "failed to parse ollama response: {}", e);
-                return Ok(PhaseOutput::failed(format!(
-                    "Failed to parse Ollama response: {}",
-                    e
-                )));
+                warn!(task_id = task.id, phase = %phase.name, "{e}");
+                let mut out = PhaseOutput::failed(e.to_string());
+                out.raw_stream = log.raw;
+                return Ok(out);
             },
         };
 
-        let output = parsed.message.content;
+        let success = outcome.text.is_some();
+        let output = outcome
+            .text
+            .unwrap_or_else(|| self.max_turns_message(self.max_turns));
+        log.emit(event::result_line(
+            &output,
+            None,
+            !success,
+            outcome.turns as u64,
+            outcome.input_tokens,
+            outcome.output_tokens,
+        ));
 
         info!(
             task_id = task.id,
             phase = %phase.name,
+            turns = outcome.turns,
             output_len = output.len(),
-            "ollama response received"
+            success,
+            "native agent loop finished"
         );
 
         Ok(PhaseOutput {
-            raw_stream: output.clone(),
+            raw_stream: log.raw,
             output,
             new_session_id: None,
-            success: true,
+            success,
             signal_json: None,
             ran_in_docker: false,
             container_test_results: Vec::new(),
+            failovers: Vec::new(),
+            resources: None,
         })
     }
 
+    /// Chat turns run the same tool loop with the chat's session directory as
+    /// the worktree. History is kept in the session directory between turns.
+    async fn run_chat(&self, request: &ChatRequest, ctx: &ChatContext) -> Result<ChatResponse> {
+        let allowed = if ctx.session_dir.is_empty() {
+            "none".to_string()
+        } else {
+            request.allowed_tools.join(",")
+        };
+        let tools = NativeTools::new(
+            &ctx.session_dir,
+            &ctx.session_dir,
+            self.sandbox_mode.clone(),
+            &allowed,
+            &request.disallowed_tools.join(","),
+        )
+        .in_container(&self.docker_image, None)
+        .with_resource_limits(self.container_memory_mb, self.container_cpus);
+
+        let mut messages = Vec::new();
+        if !request.system_prompt.is_empty() {
+            messages.push(json!({"role": "system", "content": request.system_prompt}));
+        }
+        for m in chat_history::history_for(request, ctx) {
+            messages.push(json!({"role": m.role, "content": m.content}));
+        }
+        messages.push(json!({"role": "user", "content": request.message}));
+
+        let max_turns = if request.max_turns > 0 {
+            request.max_turns
+        } else {
+            self.max_turns
+        };
+        let mut log = EventLog::new(ctx.stream_tx.clone());
+        let outcome = self
+            .drive(&mut messages, &tools, max_turns, &mut log)
+            .await?;
+        let Some(text) = outcome.text else {
+            bail!(self.max_turns_message(max_turns));
+        };
+        log.emit(event::result_line(
+            &text,
+            None,
+            false,
+            outcome.turns as u64,
+            outcome.input_tokens,
+            outcome.output_tokens,
+        ));
+        if let Err(e) = chat_history::append_history(&ctx.session_dir, &request.message, &text) {
+            warn!("failed to save {} chat history: {e}", self.label());
+        }
+
+        Ok(ChatResponse {
+            text,
+            session_id: None,
+            input_tokens: outcome.input_tokens,
+            output_tokens: outcome.output_tokens,
+            cost_usd: 0.0,
+            tool_calls: outcome.tool_calls,
+            raw_stream: log.raw,
+            failovers: Vec::new(),
+        })
+    }
+
+    /// The model is called from the host; only tool runs touch the network,
+    /// and in container mode they sit on the agent network without a proxy.
+    fn enforces_network_policy(&self, phase: &PhaseConfig) -> bool {
+        phase.use_docker && self.sandbox_mode.is_container()
+    }
+
     fn name(&self) -> &str {
-        "ollama"
+        match self.api {
+            ChatApi::Ollama => "ollama",
+            ChatApi::OpenAi => "openai-compatible",
+        }
     }
 
     fn capabilities(&self) -> borg_core::BackendCapabilities {
         borg_core::BackendCapabilities {
             supports_mcp: false,
-            supports_sessions: false,
-            supports_tools: false,
-            supports_streaming: false,
-            supports_sandbox: false,
+            supports_sessions: true,
+            supports_tools: true,
+            supports_streaming: true,
+            supports_sandbox: self.sandbox_mode != SandboxMode::Direct,
             supported_models: vec![self.model.clone()],
         }
     }
@@ -212,4 +659,75 @@ mod tests {
         assert_eq!(backend.model, "mistral");
         assert_eq!(backend.timeout_secs, 300);
     }
+
+    #[test]
+    fn endpoint_depends_on_api() {
+        let ollama = OllamaBackend::new("http://localhost:11434/", "llama3.2").unwrap();
+        assert_eq!(ollama.endpoint(), "http://localhost:11434/api/chat");
+        let openai = OllamaBackend::openai_compatible("http://vllm:8000", "qwen", "").unwrap();
+        assert_eq!(openai.endpoint(), "http://vllm:8000/v1/chat/completions");
+        let openai = OllamaBackend::openai_compatible("http://vllm:8000/v1", "qwen", "").unwrap();
+        assert_eq!(openai.endpoint(), "http://vllm:8000/v1/chat/completions");
+        assert_eq!(openai.name(), "openai-compatible");
+        assert!(openai.capabilities().supports_tools);
+    }
+
+    #[test]
+    fn parses_ollama_tool_calls() {
+        let body = json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "Read", "arguments": {"file_path": "a.txt"}}}]
+            },
+            "prompt_eval_count": 12,
+            "eval_count": 3
+        });
+        let turn = parse_turn(ChatApi::Ollama, &body, 2).unwrap();
+        assert_eq!(turn.input_tokens, 12);
+        assert_eq!(turn.tool_calls[0].id, "call_2_0");
+        assert_eq!(
+            turn.tool_calls[0].arguments,
+            Ok(json!({"file_path": "a.txt"}))
+        );
+        let echoed = assistant_message(ChatApi::Ollama, &turn);
+        assert_eq!(
+            echoed["tool_calls"][0]["function"]["arguments"]["file_path"],
+            "a.txt"
+        );
+    }
+
+    #[test]
+    fn parses_openai_tool_calls_with_string_arguments() {
+        let body = json!({
+            "choices": [{"message": {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [
+                    {"id": "abc", "type": "function", "function": {"name": "Bash", "arguments": "{\"command\":\"ls\"}"}},
+                    {"id": "def", "type": "function", "function": {"name": "Bash", "arguments": "{not json"}}
+                ]
+            }}],
+            "usage": {"prompt_tokens": 7, "completion_tokens": 5}
+        });
+        let turn = parse_turn(ChatApi::OpenAi, &body, 1).unwrap();
+        assert_eq!(turn.content, "");
+        assert_eq!(turn.output_tokens, 5);
+        assert_eq!(turn.tool_calls[0].arguments, Ok(json!({"command": "ls"})));
+        assert!(turn.tool_calls[1].arguments.is_err());
+        let echoed = assistant_message(ChatApi::OpenAi, &turn);
+        assert_eq!(
+            echoed["tool_calls"][0]["function"]["arguments"],
+            "{\"command\":\"ls\"}"
+        );
+        let reply = tool_message(
+            ChatApi::OpenAi,
+            &turn.tool_calls[0],
+            &ToolOutcome {
+                content: "ok".into(),
+                is_error: false,
+            },
+        );
+        assert_eq!(reply["tool_call_id"], "abc");
+    }
 }
@@ -1,4 +1,4 @@
-use std::{future::Future, sync::Arc};
+use std::{collections::HashSet, future::Future, sync::Arc};
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -7,25 +7,91 @@ use borg_core::{
     traits::{
         AgentError, BackendCapabilities, ChatContext, ChatRequest, ChatResponse, RetryPolicy,
     },
-    types::{PhaseConfig, PhaseContext, PhaseOutput, Task},
+    types::{BackendFailover, PhaseConfig, PhaseContext, PhaseOutput, Task},
 };
 use tracing::{info, warn};
 
-/// Wraps any `AgentBackend` with retry/backoff logic.
+/// Ordered fallback backends, keyed by registry name. Shared by every
+/// `ReliableBackend` in a registry; each skips itself when walking the chain.
+#[derive(Clone, Default)]
+pub struct FailoverPolicy {
+    chain: Vec<(String, Arc<dyn AgentBackend>)>,
+    local_only_modes: HashSet<String>,
+    local_backends: HashSet<String>,
+}
+
+impl FailoverPolicy {
+    pub fn new(chain: Vec<(String, Arc<dyn AgentBackend>)>) -> Self {
+        Self {
+            chain,
+            ..Self::default()
+        }
+    }
+
+    /// Tasks and chats in `modes` may only fall back to `local_backends`.
+    pub fn with_local_only_modes(
+        mut self,
+        modes: impl IntoIterator<Item = String>,
+        local_backends: impl IntoIterator<Item = String>,
+    ) -> Self {
+        self.local_only_modes = modes.into_iter().collect();
+        self.local_backends = local_backends.into_iter().collect();
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chain.is_empty()
+    }
+
+    fn fallbacks_for(&self, primary: &str, mode: &str) -> Vec<(String, Arc<dyn AgentBackend>)> {
+        let local_only = self.local_only_modes.contains(mode);
+        self.chain
+            .iter()
+            .filter(|(name, _)| name != primary)
+            .filter(|(name, _)| !local_only || self.local_backends.contains(name))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Wraps any `AgentBackend` with retry/backoff logic, then walks the
+/// failover chain when the error is one another provider may not share.
 pub struct ReliableBackend {
     inner: Arc<dyn AgentBackend>,
     policy: RetryPolicy,
+    registry_name: String,
+    failover: Option<Arc<FailoverPolicy>>,
 }
 
 impl ReliableBackend {
     pub fn new(inner: Arc<dyn AgentBackend>, policy: RetryPolicy) -> Self {
-        Self { inner, policy }
+        let registry_name = inner.name().to_string();
+        Self {
+            inner,
+            policy,
+            registry_name,
+            failover: None,
+        }
     }
 
     pub fn wrap(inner: Arc<dyn AgentBackend>) -> Self {
         Self::new(inner, RetryPolicy::default())
     }
 
+    /// Fall back along `policy` after this backend, registered as `name`, fails.
+    pub fn with_failover(mut self, name: impl Into<String>, policy: Arc<FailoverPolicy>) -> Self {
+        self.registry_name = name.into();
+        self.failover = Some(policy).filter(|p| !p.is_empty());
+        self
+    }
+
+    fn fallbacks(&self, mode: &str) -> Vec<(String, Arc<dyn AgentBackend>)> {
+        self.failover
+            .as_ref()
+            .map(|p| p.fallbacks_for(&self.registry_name, mode))
+            .unwrap_or_default()
+    }
+
     fn compute_backoff(&self, attempt: u32) -> std::time::Duration {
         let base = self.policy.initial_backoff.as_secs_f64()
             * self.policy.backoff_multiplier.powi(attempt as i32);
@@ -70,20 +136,25 @@ impl ReliableBackend {
         }
     }
 
-    async fn retry_loop<T, F, Fut>(&self, label: &str, mut op: F) -> Result<T>
+    async fn retry_loop<T, F, Fut>(
+        &self,
+        label: &str,
+        backend: &Arc<dyn AgentBackend>,
+        op: &mut F,
+    ) -> Result<T>
     where
-        F: FnMut() -> Fut,
+        F: FnMut(Arc<dyn AgentBackend>) -> Fut,
         Fut: Future<Output = Result<T>>,
     {
         let mut last_err = None;
         for attempt in 0..=self.policy.max_retries {
-            match op().await {
+            match op(Arc::clone(backend)).await {
                 Ok(output) => return Ok(output),
                 Err(err) => {
                     let classified = Self::classify_error(&err);
                     if !classified.is_retryable() || attempt == self.policy.max_retries {
                         warn!(
-                            backend = self.inner.name(),
+                            backend = backend.name(),
                             attempt,
                             error = %classified,
                             "{label}: non-retryable error or max retries reached"
@@ -101,7 +172,7 @@ impl ReliableBackend {
                     };
 
                     info!(
-                        backend = self.inner.name(),
+                        backend = backend.name(),
                         attempt,
                         error = %classified,
                         backoff_ms = backoff.as_millis() as u64,
@@ -115,6 +186,65 @@ impl ReliableBackend {
         }
         Err(last_err.unwrap_or_else(|| anyhow::anyhow!("max retries exceeded")))
     }
+
+    /// Run `op` on the wrapped backend, then on each of `fallbacks` in turn.
+    /// `op` receives `true` when running on a fallback. Returns the output
+    /// along with the hops taken to get there.
+    async fn failover_loop<T, F, Fut>(
+        &self,
+        label: &str,
+        mode: &str,
+        fallbacks: Vec<(String, Arc<dyn AgentBackend>)>,
+        mut op: F,
+    ) -> Result<(T, Vec<BackendFailover>)>
+    where
+        F: FnMut(Arc<dyn AgentBackend>, bool) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut err = match self
+            .retry_loop(label, &self.inner, &mut |b| op(b, false))
+            .await
+        {
+            Ok(output) => return Ok((output, Vec::new())),
+            Err(err) => err,
+        };
+        let mut hops = Vec::new();
+        let mut from = self.registry_name.clone();
+        for (name, backend) in fallbacks {
+            let classified = Self::classify_error(&err);
+            if !classified.warrants_failover() {
+                break;
+            }
+            warn!(
+                from = from.as_str(),
+                to = name.as_str(),
+                mode,
+                error = %classified,
+                "{label}: failing over to next backend"
+            );
+            hops.push(BackendFailover {
+                from: from.clone(),
+                to: name.clone(),
+                reason: classified.to_string(),
+            });
+            match self.retry_loop(label, &backend, &mut |b| op(b, true)).await {
+                Ok(output) => return Ok((output, hops)),
+                Err(e) => {
+                    err = e;
+                    from = name;
+                },
+            }
+        }
+        if !hops.is_empty() {
+            let tried: Vec<&str> = hops.iter().map(|h| h.to.as_str()).collect();
+            err = err.context(format!(
+                "{} failed; fallbacks tried: {}",
+                self.registry_name,
+                tried.join(", ")
+            ));
+        }
+        Err(err)
+    }
 }
 
 #[async_trait]
@@ -125,32 +255,60 @@ impl AgentBackend for ReliableBackend {
         phase: &PhaseConfig,
         ctx: PhaseContext,
     ) -> Result<PhaseOutput> {
-        let task = task.clone();
-        let phase = phase.clone();
-        self.retry_loop("phase", || {
-            let ctx = ctx.clone();
-            let task = task.clone();
-            let phase = phase.clone();
-            async move { self.inner.run_phase(&task, &phase, ctx).await }
-        })
-        .await
+        let mode = task.mode.clone();
+        let mut fallbacks = self.fallbacks(&mode);
+        if ctx.egress_proxy.is_some() {
+            // Never fail over to a backend that would run unrestricted.
+            fallbacks.retain(|(_, b)| b.enforces_network_policy(phase));
+        }
+        let (mut output, failovers) = self
+            .failover_loop("phase", &mode, fallbacks, |backend, fallback| {
+                let mut ctx = ctx.clone();
+                let mut task = task.clone();
+                let phase = phase.clone();
+                if fallback {
+                    // Sessions and model names are provider-specific.
+                    task.session_id.clear();
+                    ctx.task.session_id.clear();
+                    ctx.model.clear();
+                }
+                async move { backend.run_phase(&task, &phase, ctx).await }
+            })
+            .await?;
+        output.failovers = failovers;
+        Ok(output)
     }
 
     async fn run_chat(&self, request: &ChatRequest, ctx: &ChatContext) -> Result<ChatResponse> {
-        let request = request.clone();
-        let ctx = ctx.clone();
-        self.retry_loop("chat", || {
-            let request = request.clone();
-            let ctx = ctx.clone();
-            async move { self.inner.run_chat(&request, &ctx).await }
-        })
-        .await
+        let fallbacks = self.fallbacks(&ctx.mode);
+        let (mut response, failovers) = self
+            .failover_loop("chat", &ctx.mode, fallbacks, |backend, fallback| {
+                let mut request = request.clone();
+                let mut ctx = ctx.clone();
+                if fallback {
+                    request.model.clear();
+                    ctx.session_id = None;
+                }
+                async move { backend.run_chat(&request, &ctx).await }
+            })
+            .await?;
+        if !failovers.is_empty() {
+            // The session belongs to the fallback backend; resuming it on the
+            // primary next turn would fail or pick up the wrong conversation.
+            response.session_id = None;
+        }
+        response.failovers = failovers;
+        Ok(response)
     }
 
     fn capabilities(&self) -> BackendCapabilities {
         self.inner.capabilities()
     }
 
+    fn enforces_network_policy(&self, phase: &PhaseConfig) -> bool {
+        self.inner.enforces_network_policy(phase)
+    }
+
     fn name(&self) -> &str {
         self.inner.name()
     }
@@ -223,6 +381,194 @@ mod tests {
         assert!(b10 <= std::time::Duration::from_secs(61));
     }
 
+    #[test]
+    fn only_quota_auth_and_outage_errors_fail_over() {
+        assert!(AgentError::InsufficientBalance.warrants_failover());
+        assert!(AgentError::AuthError.warrants_failover());
+        assert!(AgentError::ServerError { status: 503 }.warrants_failover());
+        assert!(!AgentError::ContextOverflow.warrants_failover());
+        assert!(!AgentError::Timeout.warrants_failover());
+    }
+
+    /// Answers chats and phases with its name, or fails with `error` when
+    /// set. Only the stub named "container" enforces network policy.
+    struct ChatStub {
+        name: &'static str,
+        error: Option<&'static str>,
+    }
+
+    #[async_trait]
+    impl AgentBackend for ChatStub {
+        async fn run_phase(
+            &self,
+            _task: &Task,
+            _phase: &PhaseConfig,
+            _ctx: PhaseContext,
+        ) -> Result<PhaseOutput> {
+            match self.error {
+                Some(e) => anyhow::bail!("{e}"),
+                None => Ok(PhaseOutput {
+                    success: true,
+                    ..PhaseOutput::failed(self.name)
+                }),
+            }
+        }
+        async fn run_chat(&self, _req: &ChatRequest, ctx: &ChatContext) -> Result<ChatResponse> {
+            assert!(ctx.session_id.is_none() || self.name == "claude");
+            match self.error {
+                Some(e) => anyhow::bail!("{e}"),
+                None => Ok(ChatResponse {
+                    text: self.name.to_string(),
+                    session_id: Some(format!("{}-session", self.name)),
+                    ..Default::default()
+                }),
+            }
+        }
+        fn enforces_network_policy(&self, _phase: &PhaseConfig) -> bool {
+            self.name == "container"
+        }
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    fn stub(name: &'static str, error: Option<&'static str>) -> Arc<dyn AgentBackend> {
+        Arc::new(ChatStub { name, error })
+    }
+
+    fn chat(
+        primary: Arc<dyn AgentBackend>,
+        chain: Vec<(String, Arc<dyn AgentBackend>)>,
+        mode: &str,
+    ) -> Result<ChatResponse> {
+        let policy = Arc::new(
+            FailoverPolicy::new(chain)
+                .with_local_only_modes(["lawborg".to_string()], ["local".to_string()]),
+        );
+        let reliable = ReliableBackend::new(
+            primary,
+            RetryPolicy {
+                max_retries: 0,
+                ..Default::default()
+            },
+        )
+        .with_failover("claude", policy);
+        let request = ChatRequest {
+            message: "hi".into(),
+            conversation_history: vec![],
+            system_prompt: String::new(),
+            model: "claude-sonnet-4-6".into(),
+            allowed_tools: vec![],
+            disallowed_tools: vec![],
+            mcp_servers_json: serde_json::Value::Null,
+            max_turns: 1,
+            max_budget_usd: None,
+        };
+        let ctx = ChatContext {
+            session_dir: String::new(),
+            session_id: Some("claude-session".into()),
+            oauth_token: String::new(),
+            provider_env: Default::default(),
+            stream_tx: None,
+            borg_api_url: String::new(),
+            borg_api_token: String::new(),
+            project_id: 0,
+            workspace_id: 0,
+            mode: mode.into(),
+            chat_thread: None,
+            api_keys: Default::default(),
+            knowledge_dir: String::new(),
+        };
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(reliable.run_chat(&request, &ctx))
+    }
+
+    #[test]
+    fn quota_exhaustion_moves_to_next_backend() {
+        let claude = stub("claude", Some("insufficient balance"));
+        let chain = vec![
+            ("claude".to_string(), claude.clone()),
+            ("codex".to_string(), stub("codex", Some("401 unauthorized"))),
+            ("local".to_string(), stub("local", None)),
+        ];
+        let reply = chat(claude, chain, "sweborg").unwrap();
+        assert_eq!(reply.text, "local");
+        let hops: Vec<(&str, &str)> = reply
+            .failovers
+            .iter()
+            .map(|h| (h.from.as_str(), h.to.as_str()))
+            .collect();
+        assert_eq!(hops, [("claude", "codex"), ("codex", "local")]);
+        assert_eq!(reply.session_id, None);
+    }
+
+    #[test]
+    fn primary_replies_keep_their_session() {
+        let chain = vec![("codex".to_string(), stub("codex", None))];
+        let reply = chat(stub("claude", None), chain, "sweborg").unwrap();
+        assert!(reply.failovers.is_empty());
+        assert_eq!(reply.session_id.as_deref(), Some("claude-session"));
+    }
+
+    #[test]
+    fn local_only_modes_skip_hosted_fallbacks() {
+        let claude = stub("claude", Some("insufficient balance"));
+        let chain = vec![
+            ("codex".to_string(), stub("codex", None)),
+            ("local".to_string(), stub("local", None)),
+        ];
+        assert_eq!(
+            chat(claude.clone(), chain.clone(), "lawborg").unwrap().text,
+            "local"
+        );
+        assert_eq!(chat(claude, chain, "sweborg").unwrap().text, "codex");
+    }
+
+    #[test]
+    fn request_errors_do_not_fail_over() {
+        let claude = stub("claude", Some("context window too long"));
+        let chain = vec![("codex".to_string(), stub("codex", None))];
+        assert!(chat(claude, chain, "sweborg").is_err());
+    }
+
+    #[test]
+    fn egress_restricted_phases_skip_unenforcing_fallbacks() {
+        let policy = Arc::new(FailoverPolicy::new(vec![
+            ("codex".to_string(), stub("codex", None)),
+            ("container".to_string(), stub("container", None)),
+        ]));
+        let reliable = ReliableBackend::new(
+            stub("claude", Some("insufficient balance")),
+            RetryPolicy {
+                max_retries: 0,
+                ..Default::default()
+            },
+        )
+        .with_failover("claude", policy);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let run = |ctx: PhaseContext| {
+            rt.block_on(reliable.run_phase(&Task::default(), &PhaseConfig::default(), ctx))
+                .unwrap()
+                .output
+        };
+        assert_eq!(run(PhaseContext::default()), "codex");
+        let restricted = PhaseContext {
+            egress_proxy: Some(borg_core::types::EgressProxy {
+                task_id: 1,
+                port: 3132,
+                token: "t".into(),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(run(restricted), "container");
+    }
+
     struct DummyBackend;
 
     #[async_trait]
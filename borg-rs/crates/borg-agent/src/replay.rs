@@ -0,0 +1,546 @@
+//! Deterministic stand-ins for a model backend. `ReplayBackend` plays back
+//! recorded phase runs (stream events, file edits, signals and verdicts) so
+//! the pipeline can be driven end to end offline; `RecordingBackend` wraps a
+//! real backend and captures its runs into the same fixture format.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use borg_core::{
+    agent::AgentBackend,
+    git::Git,
+    traits::{BackendCapabilities, ChatContext, ChatRequest, ChatResponse},
+    types::{AgentSignal, PhaseCompletionVerdict, PhaseConfig, PhaseContext, PhaseOutput, Task},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Phase name used for chat turns in fixtures.
+pub const CHAT_PHASE: &str = "chat";
+
+const REPLAY_AUTHOR: (&str, &str) = ("Borg Replay", "replay@borg.invalid");
+const MAX_SNAPSHOT_FILE_BYTES: u64 = 1024 * 1024;
+const SNAPSHOT_SKIP_DIRS: &[&str] = &[".git", ".borg", "node_modules", "target"];
+
+/// A recorded session: steps are consumed in order, matched by phase name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayFixture {
+    #[serde(default)]
+    pub steps: Vec<ReplayStep>,
+}
+
+impl ReplayFixture {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("read replay fixture {}", path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("parse replay fixture {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("write replay fixture {}", path.display()))
+    }
+}
+
+/// One backend invocation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayStep {
+    /// Phase this step answers: `implement`, `lint_fix_0`, `rebase_fix`, `chat`, ...
+    pub phase: String,
+    /// NDJSON stream lines in Claude stream-json format.
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Files written relative to the work dir.
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    #[serde(default)]
+    pub deleted: Vec<String>,
+    /// Commit the work dir with this message after applying edits.
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// Written to `.borg/signal.json`.
+    #[serde(default)]
+    pub signal: Option<AgentSignal>,
+    /// Written to `.borg/phase-verdict.json`, bound to the live gate token.
+    #[serde(default)]
+    pub verdict: Option<ReplayVerdict>,
+    #[serde(default = "default_true")]
+    pub success: bool,
+    /// Fail the call with this error instead of producing output.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// The agent-authored part of a phase verdict; the binding fields (task,
+/// phase, attempt, gate token) are filled in from the live context.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayVerdict {
+    #[serde(default = "default_true")]
+    pub ready_to_advance: bool,
+    #[serde(default)]
+    pub rationale: String,
+    #[serde(default)]
+    pub missing_requirements: Vec<String>,
+}
+
+/// Plays back a `ReplayFixture`. Each call takes the first unconsumed step
+/// for its phase, so retries of the same phase see successive steps.
+pub struct ReplayBackend {
+    steps: Mutex<VecDeque<ReplayStep>>,
+}
+
+impl ReplayBackend {
+    pub fn new(fixture: ReplayFixture) -> Self {
+        Self {
+            steps: Mutex::new(fixture.steps.into()),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(ReplayFixture::load(path.as_ref())?))
+    }
+
+    /// Phases of steps not yet played back.
+    pub fn remaining(&self) -> Vec<String> {
+        self.lock().iter().map(|s| s.phase.clone()).collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<ReplayStep>> {
+        self.steps.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn take(&self, phase: &str) -> Result<ReplayStep> {
+        let mut steps = self.lock();
+        steps
+            .iter()
+            .position(|s| s.phase == phase)
+            .and_then(|i| steps.remove(i))
+            .with_context(|| format!("replay: no recorded step left for phase '{phase}'"))
+    }
+}
+
+/// Resolve a fixture path inside `work_dir`, rejecting escapes.
+fn fixture_path(work_dir: &str, rel: &str) -> Result<PathBuf> {
+    let rel_path = Path::new(rel);
+    if rel_path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("replay: path '{rel}' must be relative to the work dir");
+    }
+    Ok(Path::new(work_dir).join(rel_path))
+}
+
+fn write_control_file(work_dir: &str, name: &str, body: &impl Serialize) -> Result<()> {
+    let dir = Path::new(work_dir).join(".borg");
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join(name), serde_json::to_string(body)?)?;
+    Ok(())
+}
+
+fn apply_step(step: &ReplayStep, ctx: &PhaseContext, phase: &PhaseConfig) -> Result<()> {
+    let work_dir = ctx.work_dir.as_str();
+    for (rel, content) in &step.files {
+        let path = fixture_path(work_dir, rel)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content).with_context(|| format!("replay: write {rel}"))?;
+    }
+    for rel in &step.deleted {
+        let path = fixture_path(work_dir, rel)?;
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e).with_context(|| format!("replay: delete {rel}"));
+            }
+        }
+    }
+    if let Some(message) = &step.commit {
+        Git::new(work_dir).commit_all(work_dir, message, Some(REPLAY_AUTHOR))?;
+    }
+    if let Some(signal) = &step.signal {
+        write_control_file(work_dir, "signal.json", signal)?;
+    }
+    if let Some(verdict) = &step.verdict {
+        let verdict = PhaseCompletionVerdict {
+            task_id: ctx.task.id,
+            phase: phase.name.clone(),
+            attempt: ctx.phase_attempt,
+            gate_token: ctx.phase_gate_token.clone(),
+            ready_to_advance: verdict.ready_to_advance,
+            rationale: verdict.rationale.clone(),
+            missing_requirements: verdict.missing_requirements.clone(),
+        };
+        write_control_file(work_dir, "phase-verdict.json", &verdict)?;
+    }
+    Ok(())
+}
+
+fn emit(tx: Option<&tokio::sync::mpsc::UnboundedSender<String>>, events: &[String]) {
+    if let Some(tx) = tx {
+        for line in events {
+            let _ = tx.send(line.clone());
+        }
+    }
+}
+
+#[async_trait]
+impl AgentBackend for ReplayBackend {
+    async fn run_phase(
+        &self,
+        _task: &Task,
+        phase: &PhaseConfig,
+        ctx: PhaseContext,
+    ) -> Result<PhaseOutput> {
+        let step = self.take(&phase.name)?;
+        info!(
+            task_id = ctx.task.id,
+            phase = phase.name.as_str(),
+            "replaying recorded phase"
+        );
+        emit(ctx.stream_tx.as_ref(), &step.events);
+        if let Some(error) = &step.error {
+            bail!("{error}");
+        }
+        apply_step(&step, &ctx, phase)?;
+        let raw_stream = step.events.join("\n");
+        let (output, session_id) = crate::event::parse_stream(&raw_stream);
+        Ok(PhaseOutput {
+            output,
+            new_session_id: session_id,
+            raw_stream,
+            success: step.success,
+            signal_json: None,
+            ran_in_docker: false,
+            container_test_results: Vec::new(),
+            failovers: Vec::new(),
+            resources: None,
+        })
+    }
+
+    async fn run_chat(&self, _request: &ChatRequest, ctx: &ChatContext) -> Result<ChatResponse> {
+        let step = self.take(CHAT_PHASE)?;
+        emit(ctx.stream_tx.as_ref(), &step.events);
+        if let Some(error) = &step.error {
+            bail!("{error}");
+        }
+        let raw_stream = step.events.join("\n");
+        let (text, session_id) = crate::event::parse_stream(&raw_stream);
+        Ok(ChatResponse {
+            text,
+            session_id,
+            raw_stream,
+            ..Default::default()
+        })
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            supports_mcp: false,
+            supports_sessions: true,
+            supports_tools: true,
+            supports_streaming: true,
+            supports_sandbox: false,
+            supported_models: vec![],
+        }
+    }
+
+    /// Replays never leave the host's memory, so any policy holds.
+    fn enforces_network_policy(&self, _phase: &PhaseConfig) -> bool {
+        true
+    }
+
+    fn name(&self) -> &str {
+        "replay"
+    }
+}
+
+/// Wraps a backend and appends each run to `<dir>/task-<id>.json` (phases) or
+/// `<dir>/chat-<thread>.json` (chat turns). Those files load directly into
+/// `ReplayBackend::from_file`.
+pub struct RecordingBackend {
+    inner: Arc<dyn AgentBackend>,
+    dir: PathBuf,
+    write_lock: Mutex<()>,
+}
+
+impl RecordingBackend {
+    pub fn new(inner: Arc<dyn AgentBackend>, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    fn append(&self, file: &str, step: ReplayStep) {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let path = self.dir.join(file);
+        let mut fixture = if path.exists() {
+            ReplayFixture::load(&path).unwrap_or_default()
+        } else {
+            ReplayFixture::default()
+        };
+        fixture.steps.push(step);
+        if let Err(e) = fixture.save(&path) {
+            warn!("recording backend: {e}");
+        }
+    }
+}
+
+/// Text files under `dir`, keyed by relative path. Skips VCS, build output
+/// and pipeline control files, which are captured separately.
+fn snapshot_tree(dir: &str) -> BTreeMap<String, String> {
+    let mut files = BTreeMap::new();
+    if dir.is_empty() {
+        return files;
+    }
+    let root = Path::new(dir);
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                let name = entry.file_name();
+                if !SNAPSHOT_SKIP_DIRS.iter().any(|s| name == *s) {
+                    stack.push(path);
+                }
+            } else if meta.is_file() && meta.len() <= MAX_SNAPSHOT_FILE_BYTES {
+                if let (Ok(rel), Ok(content)) =
+                    (path.strip_prefix(root), std::fs::read_to_string(&path))
+                {
+                    files.insert(rel.to_string_lossy().to_string(), content);
+                }
+            }
+        }
+    }
+    files
+}
+
+fn head_commit(work_dir: &str) -> Option<String> {
+    Git::new(work_dir).rev_parse_head().ok()
+}
+
+fn last_commit_subject(work_dir: &str) -> Option<String> {
+    let out = std::process::Command::new("git")
+        .args(["log", "-1", "--format=%s"])
+        .current_dir(work_dir)
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+fn read_control_file<T: serde::de::DeserializeOwned>(work_dir: &str, name: &str) -> Option<T> {
+    let raw = std::fs::read_to_string(Path::new(work_dir).join(".borg").join(name)).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[async_trait]
+impl AgentBackend for RecordingBackend {
+    async fn run_phase(
+        &self,
+        task: &Task,
+        phase: &PhaseConfig,
+        ctx: PhaseContext,
+    ) -> Result<PhaseOutput> {
+        let work_dir = ctx.work_dir.clone();
+        let before = snapshot_tree(&work_dir);
+        let head_before = head_commit(&work_dir);
+        let result = self.inner.run_phase(task, phase, ctx).await;
+
+        let after = snapshot_tree(&work_dir);
+        let files = after
+            .iter()
+            .filter(|(path, content)| before.get(*path) != Some(*content))
+            .map(|(path, content)| (path.clone(), content.clone()))
+            .collect();
+        let deleted = before
+            .keys()
+            .filter(|path| !after.contains_key(*path))
+            .cloned()
+            .collect();
+        let head_after = head_commit(&work_dir);
+        let commit = if head_after.is_some() && head_after != head_before {
+            last_commit_subject(&work_dir)
+        } else {
+            None
+        };
+        let verdict = read_control_file::<PhaseCompletionVerdict>(&work_dir, "phase-verdict.json")
+            .map(|v| ReplayVerdict {
+                ready_to_advance: v.ready_to_advance,
+                rationale: v.rationale,
+                missing_requirements: v.missing_requirements,
+            });
+        let mut signal = read_control_file::<AgentSignal>(&work_dir, "signal.json");
+        let (events, success, error) = match &result {
+            Ok(out) => {
+                if signal.is_none() {
+                    signal = out
+                        .signal_json
+                        .as_deref()
+                        .and_then(|s| serde_json::from_str(s).ok());
+                }
+                let events = out.raw_stream.lines().map(str::to_string).collect();
+                (events, out.success, None)
+            },
+            Err(e) => (Vec::new(), false, Some(e.to_string())),
+        };
+        self.append(
+            &format!("task-{}.json", task.id),
+            ReplayStep {
+                phase: phase.name.clone(),
+                events,
+                files,
+                deleted,
+                commit,
+                signal,
+                verdict,
+                success,
+                error,
+            },
+        );
+        result
+    }
+
+    async fn run_chat(&self, request: &ChatRequest, ctx: &ChatContext) -> Result<ChatResponse> {
+        let result = self.inner.run_chat(request, ctx).await;
+        let (events, error) = match &result {
+            Ok(resp) => (resp.raw_stream.lines().map(str::to_string).collect(), None),
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        };
+        let thread = ctx.chat_thread.as_deref().unwrap_or("default");
+        self.append(
+            &format!("chat-{}.json", sanitize(thread)),
+            ReplayStep {
+                phase: CHAT_PHASE.into(),
+                events,
+                files: BTreeMap::new(),
+                deleted: Vec::new(),
+                commit: None,
+                signal: None,
+                verdict: None,
+                success: error.is_none(),
+                error,
+            },
+        );
+        result
+    }
+
+    fn capabilities(&self) -> BackendCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn enforces_network_policy(&self, phase: &PhaseConfig) -> bool {
+        self.inner.enforces_network_policy(phase)
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(phase: &str, error: Option<&str>) -> ReplayStep {
+        serde_json::from_value(serde_json::json!({ "phase": phase, "error": error })).unwrap()
+    }
+
+    #[test]
+    fn take_matches_by_phase_in_order() {
+        let backend = ReplayBackend::new(ReplayFixture {
+            steps: vec![
+                step("implement", Some("first")),
+                step("lint_fix_0", None),
+                step("implement", Some("second")),
+            ],
+        });
+        assert_eq!(
+            backend.take("implement").unwrap().error.as_deref(),
+            Some("first")
+        );
+        assert_eq!(
+            backend.take("implement").unwrap().error.as_deref(),
+            Some("second")
+        );
+        assert!(backend.take("implement").is_err());
+        assert_eq!(backend.remaining(), vec!["lint_fix_0"]);
+    }
+
+    #[test]
+    fn step_defaults_to_success() {
+        let s = step("implement", None);
+        assert!(s.success);
+        assert!(s.files.is_empty() && s.commit.is_none() && s.verdict.is_none());
+    }
+
+    #[test]
+    fn fixture_paths_stay_inside_work_dir() {
+        assert!(fixture_path("/w", "src/lib.rs").is_ok());
+        assert!(fixture_path("/w", "../escape").is_err());
+        assert!(fixture_path("/w", "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn snapshot_skips_vcs_and_control_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for rel in ["a.txt", "src/b.rs", ".git/HEAD", ".borg/signal.json"] {
+            let path = root.join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, rel).unwrap();
+        }
+        let files = snapshot_tree(&root.to_string_lossy());
+        assert_eq!(
+            files.keys().cloned().collect::<Vec<_>>(),
+            vec!["a.txt", "src/b.rs"]
+        );
+    }
+
+    #[test]
+    fn fixture_round_trips_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/task-1.json");
+        let fixture = ReplayFixture {
+            steps: vec![step("implement", None)],
+        };
+        fixture.save(&path).unwrap();
+        let loaded = ReplayFixture::load(&path).unwrap();
+        assert_eq!(loaded.steps.len(), 1);
+        assert_eq!(loaded.steps[0].phase, "implement");
+    }
+}
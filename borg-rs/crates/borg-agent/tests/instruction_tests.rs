@@ -49,6 +49,10 @@ fn make_ctx() -> PhaseContext {
             lint_cmd: String::new(),
             backend: String::new(),
             repo_slug: String::new(),
+            container_image: String::new(),
+            container_file: String::new(),
+            forge: String::new(),
+            forge_url: String::new(),
         },
         data_dir: String::new(),
         session_dir: String::new(),
@@ -67,6 +71,7 @@ fn make_ctx() -> PhaseContext {
         knowledge_files: vec![],
         knowledge_dir: String::new(),
         agent_network: None,
+        container_image: None,
         prior_research: vec![],
         revision_count: 0,
         experimental_domains: false,
@@ -79,6 +84,7 @@ fn make_ctx() -> PhaseContext {
         knowledge_repo_paths: vec![],
         clarification_resume_reuses_prior_review: false,
         clarification_resume_question: String::new(),
+        egress_proxy: None,
     }
 }
 
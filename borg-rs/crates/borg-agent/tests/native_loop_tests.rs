@@ -0,0 +1,321 @@
+// End-to-end tests for the native tool-calling loop in OllamaBackend, driven
+// by a scripted chat server that replays canned responses in order.
+
+use std::sync::{Arc, Mutex};
+
+use borg_agent::OllamaBackend;
+use borg_core::{
+    agent::AgentBackend,
+    traits::{ChatContext, ChatRequest},
+    types::{PhaseConfig, PhaseContext, RepoConfig, Task},
+};
+use chrono::Utc;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+fn make_task() -> Task {
+    Task {
+        id: 1,
+        title: "Draft memo".to_string(),
+        description: "Write the memo".to_string(),
+        repo_path: String::new(),
+        branch: String::new(),
+        status: "impl".to_string(),
+        attempt: 1,
+        max_attempts: 3,
+        last_error: String::new(),
+        created_by: String::new(),
+        notify_chat: String::new(),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        session_id: String::new(),
+        mode: "lawborg".to_string(),
+        backend: String::new(),
+        workspace_id: 0,
+        project_id: 0,
+        task_type: String::new(),
+        requires_exhaustive_corpus_review: false,
+        started_at: None,
+        completed_at: None,
+        duration_secs: None,
+        review_status: None,
+        revision_count: 0,
+        chat_thread: String::new(),
+    }
+}
+
+fn make_ctx(work_dir: &str, stream_tx: tokio::sync::mpsc::UnboundedSender<String>) -> PhaseContext {
+    PhaseContext {
+        task: make_task(),
+        repo_config: RepoConfig {
+            path: work_dir.to_string(),
+            test_cmd: String::new(),
+            prompt_file: String::new(),
+            mode: String::new(),
+            is_self: false,
+            auto_merge: false,
+            lint_cmd: String::new(),
+            backend: String::new(),
+            repo_slug: String::new(),
+            container_image: String::new(),
+            container_file: String::new(),
+            forge: String::new(),
+            forge_url: String::new(),
+        },
+        data_dir: String::new(),
+        session_dir: String::new(),
+        work_dir: work_dir.to_string(),
+        oauth_token: String::new(),
+        model: String::new(),
+        pending_messages: vec![],
+        phase_attempt: 1,
+        phase_gate_token: "gate".to_string(),
+        system_prompt_suffix: String::new(),
+        user_coauthor: String::new(),
+        stream_tx: Some(stream_tx),
+        setup_script: String::new(),
+        api_keys: Default::default(),
+        disallowed_tools: "Bash".to_string(),
+        knowledge_files: vec![],
+        knowledge_dir: String::new(),
+        agent_network: None,
+        container_image: None,
+        prior_research: vec![],
+        revision_count: 0,
+        experimental_domains: false,
+        isolated: false,
+        borg_api_url: String::new(),
+        borg_api_token: String::new(),
+        chat_context: vec![],
+        github_token: String::new(),
+        github_token_is_user: false,
+        knowledge_repo_paths: vec![],
+        clarification_resume_reuses_prior_review: false,
+        clarification_resume_question: String::new(),
+        egress_proxy: None,
+    }
+}
+
+/// Serve `responses` in order, one per HTTP request, recording request bodies.
+async fn scripted_server(responses: Vec<Value>) -> (String, Arc<Mutex<Vec<Value>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    tokio::spawn(async move {
+        for response in responses {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 8192];
+            let body_start = loop {
+                let n = sock.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+            let headers = String::from_utf8_lossy(&buf[..body_start]).to_ascii_lowercase();
+            let len: usize = headers
+                .lines()
+                .find_map(|l| l.strip_prefix("content-length:"))
+                .map(|v| v.trim().parse().unwrap())
+                .unwrap_or(0);
+            while buf.len() < body_start + len {
+                let n = sock.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            seen.lock()
+                .unwrap()
+                .push(serde_json::from_slice(&buf[body_start..body_start + len]).unwrap());
+            let body = response.to_string();
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            sock.write_all(reply.as_bytes()).await.unwrap();
+        }
+    });
+    (format!("http://{addr}"), requests)
+}
+
+fn phase() -> PhaseConfig {
+    PhaseConfig {
+        name: "implement".into(),
+        instruction: "Write the memo.".into(),
+        allowed_tools: "Read,Glob,Grep,Write,Edit,Bash".into(),
+        ..PhaseConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn openai_loop_executes_tools_and_streams_events() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("facts.txt"), "client: Acme\n").unwrap();
+    let (url, requests) = scripted_server(vec![
+        json!({"choices": [{"message": {"role": "assistant", "content": null, "tool_calls": [
+            {"id": "c1", "type": "function", "function": {"name": "Read", "arguments": "{\"file_path\":\"facts.txt\"}"}}
+        ]}}]}),
+        json!({"choices": [{"message": {"role": "assistant", "content": "", "tool_calls": [
+            {"id": "c2", "type": "function", "function": {"name": "Write", "arguments": "{\"file_path\":\".borg/signal.json\",\"content\":\"{\\\"status\\\":\\\"done\\\"}\"}"}}
+        ]}}]}),
+        json!({"choices": [{"message": {"role": "assistant", "content": "Memo drafted."}}],
+               "usage": {"prompt_tokens": 10, "completion_tokens": 4}}),
+    ])
+    .await;
+
+    let backend = OllamaBackend::openai_compatible(url, "local-model", "secret").unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let ctx = make_ctx(dir.path().to_str().unwrap(), tx);
+    let out = backend
+        .run_phase(&make_task(), &phase(), ctx)
+        .await
+        .unwrap();
+
+    assert!(out.success, "{}", out.output);
+    assert_eq!(out.output, "Memo drafted.");
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join(".borg/signal.json")).unwrap(),
+        "{\"status\":\"done\"}"
+    );
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 3);
+    let offered: Vec<&str> = requests[0]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["function"]["name"].as_str().unwrap())
+        .collect();
+    assert!(!offered.contains(&"Bash"), "disallowed tool was offered");
+    let tool_reply = requests[1]["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["role"] == "tool")
+        .unwrap();
+    assert_eq!(tool_reply["tool_call_id"], "c1");
+    assert!(tool_reply["content"]
+        .as_str()
+        .unwrap()
+        .contains("client: Acme"));
+
+    let mut kinds = Vec::new();
+    while let Ok(line) = rx.try_recv() {
+        let event: Value = serde_json::from_str(&line).unwrap();
+        kinds.push(event["type"].as_str().unwrap().to_string());
+    }
+    assert_eq!(kinds.first().map(String::as_str), Some("system"));
+    assert_eq!(kinds.last().map(String::as_str), Some("result"));
+    assert_eq!(kinds.iter().filter(|k| *k == "user").count(), 2);
+    assert_eq!(
+        borg_agent::event::parse_stream(&out.raw_stream).0,
+        "Memo drafted."
+    );
+}
+
+#[tokio::test]
+async fn ollama_loop_stops_at_max_turns() {
+    let dir = tempfile::tempdir().unwrap();
+    let call = json!({"message": {"role": "assistant", "content": "", "tool_calls": [
+        {"function": {"name": "Glob", "arguments": {"pattern": "*"}}}
+    ]}});
+    let (url, requests) = scripted_server(vec![call.clone(), call]).await;
+
+    let backend = OllamaBackend::new(url, "llama3.2")
+        .unwrap()
+        .with_max_turns(2);
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let ctx = make_ctx(dir.path().to_str().unwrap(), tx);
+    let out = backend
+        .run_phase(&make_task(), &phase(), ctx)
+        .await
+        .unwrap();
+
+    assert!(!out.success);
+    assert!(out.output.contains("after 2 turns"), "{}", out.output);
+    let requests = requests.lock().unwrap();
+    let tool_reply = requests[1]["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["role"] == "tool")
+        .unwrap();
+    assert_eq!(tool_reply["tool_name"], "Glob");
+}
+
+#[tokio::test]
+async fn chat_turns_use_session_dir_and_carry_history() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("notes.md"), "deadline: friday\n").unwrap();
+    let (url, requests) = scripted_server(vec![
+        json!({"message": {"role": "assistant", "content": "", "tool_calls": [
+            {"function": {"name": "Read", "arguments": {"file_path": "notes.md"}}}
+        ]}, "prompt_eval_count": 20, "eval_count": 5}),
+        json!({"message": {"role": "assistant", "content": "The deadline is Friday."},
+               "prompt_eval_count": 30, "eval_count": 6}),
+        json!({"message": {"role": "assistant", "content": "Yes, Friday."}}),
+    ])
+    .await;
+
+    let backend = OllamaBackend::new(url, "llama3.2").unwrap();
+    let session_dir = dir.path().to_str().unwrap().to_string();
+    let ctx = ChatContext {
+        session_dir: session_dir.clone(),
+        session_id: None,
+        oauth_token: String::new(),
+        provider_env: Default::default(),
+        stream_tx: None,
+        borg_api_url: String::new(),
+        borg_api_token: String::new(),
+        project_id: 0,
+        workspace_id: 0,
+        mode: String::new(),
+        chat_thread: None,
+        api_keys: Default::default(),
+        knowledge_dir: String::new(),
+    };
+    let request = |message: &str| ChatRequest {
+        message: message.to_string(),
+        conversation_history: vec![],
+        system_prompt: "You are Borg.".into(),
+        model: String::new(),
+        allowed_tools: vec![],
+        disallowed_tools: vec!["Bash".into()],
+        mcp_servers_json: Value::Null,
+        max_turns: 8,
+        max_budget_usd: None,
+    };
+
+    let first = backend
+        .run_chat(&request("When is the deadline?"), &ctx)
+        .await
+        .unwrap();
+    assert_eq!(first.text, "The deadline is Friday.");
+    assert_eq!((first.input_tokens, first.output_tokens), (50, 11));
+    assert_eq!(first.tool_calls.len(), 1);
+    assert_eq!(first.tool_calls[0].tool_name, "Read");
+    assert!(first.tool_calls[0].success);
+
+    let second = backend.run_chat(&request("Sure?"), &ctx).await.unwrap();
+    assert_eq!(second.text, "Yes, Friday.");
+    let requests = requests.lock().unwrap();
+    let contents: Vec<&str> = requests[2]["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        contents,
+        vec![
+            "You are Borg.",
+            "When is the deadline?",
+            "The deadline is Friday.",
+            "Sure?"
+        ]
+    );
+}
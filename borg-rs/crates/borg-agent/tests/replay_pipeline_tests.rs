@@ -0,0 +1,520 @@
+// End-to-end pipeline tests driven by `Pipeline::tick` against ReplayBackend.
+// Each test starts its own throwaway Postgres cluster (socket only, no
+// server or network needed) next to its git repo and data dir, so the tick
+// loop only ever sees the test's own tasks. Set TEST_DATABASE_URL to run
+// against an existing server instead.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc, Mutex, MutexGuard, Once,
+    },
+    time::Duration,
+};
+
+use borg_agent::{event, replay::ReplayFixture, ReplayBackend};
+use borg_core::{
+    agent::AgentBackend,
+    config::Config,
+    db::Db,
+    pgcompat::Connection,
+    pipeline::Pipeline,
+    registry::RegistryBuilder,
+    sandbox::SandboxMode,
+    types::{IntegrationType, PhaseConfig, PhaseType, PipelineMode, RepoConfig, Task},
+};
+use chrono::Utc;
+use serde_json::json;
+use tempfile::TempDir;
+
+static INIT: Once = Once::new();
+
+/// Held for a whole test: the harness changes the working directory and the
+/// environment, which are shared by every test thread in the process.
+static PROCESS_STATE: Mutex<()> = Mutex::new(());
+
+fn init(database_url: &str) -> MutexGuard<'static, ()> {
+    let guard = PROCESS_STATE.lock().unwrap_or_else(|e| e.into_inner());
+    INIT.call_once(|| {
+        // Task session dirs are relative to the working directory.
+        let cwd = std::env::temp_dir().join("borg-replay-tests");
+        std::fs::create_dir_all(&cwd).unwrap();
+        std::env::set_current_dir(&cwd).unwrap();
+        borg_core::modes::register_modes(borg_domains::all_modes());
+    });
+    // Config::from_env insists on a database URL even though the tests
+    // hand the pipeline their own Db.
+    std::env::set_var("DATABASE_URL", database_url);
+    guard
+}
+
+fn run(program: &str, args: &[&str]) {
+    let out = Command::new(program)
+        .args(args)
+        .output()
+        .unwrap_or_else(|e| panic!("{program} (is Postgres installed?): {e}"));
+    assert!(
+        out.status.success(),
+        "{program} {args:?}: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+/// Private Postgres cluster that listens only on a socket in its own
+/// directory. Stopped when dropped.
+struct PgCluster {
+    data: PathBuf,
+    url: String,
+}
+
+impl PgCluster {
+    fn start(dir: &Path) -> Self {
+        let data = dir.join("pgdata");
+        let data_str = data.to_string_lossy().to_string();
+        let log = dir.join("postgres.log").to_string_lossy().to_string();
+        run(
+            "initdb",
+            &["-D", &data_str, "-U", "borg", "--auth=trust", "--no-sync"],
+        );
+        let options = format!("-k {} -c listen_addresses='' -F", dir.display());
+        run(
+            "pg_ctl",
+            &["-D", &data_str, "-l", &log, "-o", &options, "-w", "start"],
+        );
+        let url = format!("host={} user=borg dbname=postgres", dir.display());
+        Self { data, url }
+    }
+}
+
+impl Drop for PgCluster {
+    fn drop(&mut self) {
+        let _ = Command::new("pg_ctl")
+            .arg("-D")
+            .arg(&self.data)
+            .args(["-m", "immediate", "stop"])
+            .output();
+    }
+}
+
+/// A fresh database: a private cluster by default, or a new database on the
+/// TEST_DATABASE_URL server.
+fn isolated_db(name: &str, dir: &Path) -> (Db, String, Option<PgCluster>) {
+    let Ok(base) = std::env::var("TEST_DATABASE_URL") else {
+        let cluster = PgCluster::start(dir);
+        let mut db = Db::open(&cluster.url).expect("open private cluster");
+        db.migrate().expect("migrate");
+        return (db, cluster.url.clone(), Some(cluster));
+    };
+    let admin = Connection::open(&base).expect("open postgres admin connection");
+    let session = admin.session();
+    session
+        .execute_batch(&format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)"))
+        .unwrap();
+    session
+        .execute_batch(&format!("CREATE DATABASE {name}"))
+        .unwrap();
+    let (prefix, _) = base.rsplit_once('/').unwrap();
+    let url = format!("{prefix}/{name}");
+    let mut db = Db::open(&url).expect("open isolated db");
+    db.migrate().expect("migrate");
+    (db, url, None)
+}
+
+fn git(dir: &str, args: &[&str]) -> String {
+    let out = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "git {args:?}: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8_lossy(&out.stdout).trim().to_string()
+}
+
+struct Harness {
+    repo: String,
+    db: Arc<Db>,
+    pipeline: Arc<Pipeline>,
+    backend: Arc<ReplayBackend>,
+    _cluster: Option<PgCluster>,
+    _dir: TempDir,
+    _process_state: MutexGuard<'static, ()>,
+}
+
+fn harness(name: &str, fixture: ReplayFixture, test_cmd: &str, lint_cmd: &str) -> Harness {
+    let dir = tempfile::tempdir().unwrap();
+    let (db, database_url, cluster) = isolated_db(name, dir.path());
+    let process_state = init(&database_url);
+    let repo = dir.path().join("repo").to_string_lossy().to_string();
+    std::fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "-q", "-b", "main"]);
+    git(&repo, &["config", "user.name", "Replay Test"]);
+    git(&repo, &["config", "user.email", "replay@example.com"]);
+    std::fs::write(format!("{repo}/README.md"), "# demo\n").unwrap();
+    std::fs::write(format!("{repo}/.gitignore"), ".borg/\n").unwrap();
+    git(&repo, &["add", "-A"]);
+    git(&repo, &["commit", "-q", "-m", "init"]);
+
+    let db = Arc::new(db);
+    let backend = Arc::new(ReplayBackend::new(fixture));
+    let mut backends: HashMap<String, Arc<dyn AgentBackend>> = HashMap::new();
+    backends.insert("replay".into(), backend.clone());
+    let registry = Arc::new(RegistryBuilder::new().backends(backends).build());
+
+    let mut config = Config::from_env().unwrap();
+    config.data_dir = dir.path().join("data").to_string_lossy().to_string();
+    config.backend = "replay".into();
+    config.continuous_mode = false;
+    config.pipeline_agent_cooldown_s = 0;
+    config.mirror_refresh_interval_s = 0;
+    config.self_update_enabled = false;
+    config.watched_repos = vec![RepoConfig {
+        path: repo.clone(),
+        test_cmd: test_cmd.into(),
+        prompt_file: String::new(),
+        mode: "sweborg".into(),
+        is_self: false,
+        auto_merge: false,
+        lint_cmd: lint_cmd.into(),
+        backend: String::new(),
+        repo_slug: String::new(),
+        container_image: String::new(),
+        container_file: String::new(),
+        forge: String::new(),
+        forge_url: String::new(),
+    }];
+
+    let (pipeline, _events) = Pipeline::new(
+        Arc::clone(&db),
+        registry,
+        Arc::new(config),
+        SandboxMode::Direct,
+        Arc::new(AtomicBool::new(false)),
+        false,
+        Arc::new(AtomicU64::new(0)),
+    );
+    Harness {
+        repo,
+        db,
+        pipeline: Arc::new(pipeline),
+        backend,
+        _cluster: cluster,
+        _dir: dir,
+        _process_state: process_state,
+    }
+}
+
+impl Harness {
+    fn add_task(&self, mode: &str) -> i64 {
+        let task = Task {
+            id: 0,
+            title: "Add a greeting".into(),
+            description: "Create hello.txt containing a greeting.".into(),
+            repo_path: self.repo.clone(),
+            branch: String::new(),
+            status: "backlog".into(),
+            attempt: 0,
+            max_attempts: 5,
+            last_error: String::new(),
+            created_by: String::new(),
+            notify_chat: String::new(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            session_id: String::new(),
+            mode: mode.into(),
+            backend: String::new(),
+            workspace_id: 0,
+            project_id: 0,
+            task_type: String::new(),
+            requires_exhaustive_corpus_review: false,
+            started_at: None,
+            completed_at: None,
+            duration_secs: None,
+            review_status: None,
+            revision_count: 0,
+            chat_thread: String::new(),
+        };
+        self.db.insert_task(&task).unwrap()
+    }
+
+    fn task(&self, id: i64) -> Task {
+        self.db.get_task(id).unwrap().unwrap()
+    }
+
+    /// Tick until the task reaches one of `statuses`; returns the status path.
+    async fn run_until(&self, id: i64, statuses: &[&str]) -> Vec<String> {
+        let mut seen = vec![self.task(id).status];
+        for _ in 0..40 {
+            Arc::clone(&self.pipeline).tick().await.unwrap();
+            self.wait_idle().await;
+            let status = self.task(id).status;
+            if seen.last() != Some(&status) {
+                seen.push(status.clone());
+            }
+            if statuses.contains(&status.as_str()) {
+                return seen;
+            }
+        }
+        panic!("task #{id} never reached {statuses:?}; went {seen:?}");
+    }
+
+    async fn wait_idle(&self) {
+        let mut idle_polls = 0;
+        for _ in 0..2000 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            if self.pipeline.active_agent_count() == 0 {
+                idle_polls += 1;
+                if idle_polls >= 3 {
+                    return;
+                }
+            } else {
+                idle_polls = 0;
+            }
+        }
+        panic!("pipeline never went idle");
+    }
+
+    fn event_kinds(&self, id: i64) -> Vec<String> {
+        let mut events = self.db.list_task_events(id, 500).unwrap();
+        events.reverse();
+        events.into_iter().map(|e| e.kind).collect()
+    }
+}
+
+fn fixture(steps: serde_json::Value) -> ReplayFixture {
+    serde_json::from_value(json!({ "steps": steps })).unwrap()
+}
+
+fn done(text: &str) -> Vec<String> {
+    vec![
+        event::assistant_text_line(text),
+        event::result_line(text, None, false, 1, 10, 5),
+    ]
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sweborg_task_runs_implement_validate_lint_fix_rebase() {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/sweborg_lifecycle.json"
+    );
+    let h = harness(
+        "borg_replay_lifecycle",
+        ReplayFixture::load(std::path::Path::new(path)).unwrap(),
+        "grep -q hello hello.txt",
+        "! grep -q ' $' hello.txt",
+    );
+    let id = h.add_task("sweborg");
+
+    let path = h.run_until(id, &["done", "failed", "blocked"]).await;
+    assert_eq!(
+        path,
+        vec![
+            "backlog",
+            "implement",
+            "validate",
+            "lint_fix",
+            "rebase",
+            "done"
+        ]
+    );
+    assert!(
+        h.backend.remaining().is_empty(),
+        "{:?}",
+        h.backend.remaining()
+    );
+
+    let task = h.task(id);
+    assert_eq!(task.session_id, "replay-session-2");
+    let worktree = format!("{}/.worktrees/task-{id}", h.repo);
+    assert_eq!(task.repo_path, worktree);
+    assert_eq!(
+        std::fs::read_to_string(format!("{worktree}/hello.txt")).unwrap(),
+        "hello world\n"
+    );
+    let log = git(&worktree, &["log", "--format=%s"]);
+    assert_eq!(
+        log.lines().collect::<Vec<_>>(),
+        vec!["fix: lint errors", "feat: add greeting", "init"]
+    );
+
+    let outputs = h.db.get_task_outputs(id).unwrap();
+    let implement = outputs.iter().find(|o| o.phase == "implement").unwrap();
+    assert_eq!(implement.output, "Added hello.txt with the greeting.");
+    assert!(outputs
+        .iter()
+        .any(|o| o.phase == "validate" && o.exit_code == 0));
+
+    let kinds = h.event_kinds(id);
+    assert!(kinds.contains(&"phase.advanced".to_string()), "{kinds:?}");
+    assert!(kinds.contains(&"task.completed".to_string()), "{kinds:?}");
+    let queued = h.db.get_queued_branches_for_repo(&worktree).unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].branch, format!("task-{id}"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn failed_runs_and_failing_tests_are_retried() {
+    let h = harness(
+        "borg_replay_retries",
+        fixture(json!([
+            {"phase": "implement", "error": "agent process exited with status 137"},
+            {"phase": "implement", "events": done("Wrote a farewell."),
+             "files": {"hello.txt": "goodbye\n"}, "commit": "feat: farewell",
+             "verdict": {"rationale": "file written"}},
+            {"phase": "implement", "events": done("Switched to a greeting."),
+             "files": {"hello.txt": "hello\n"}, "commit": "fix: greeting",
+             "verdict": {"rationale": "greeting present"}},
+        ])),
+        "grep -q hello hello.txt",
+        "",
+    );
+    let id = h.add_task("sweborg");
+
+    let path = h.run_until(id, &["done", "failed", "blocked"]).await;
+    assert_eq!(path.last().map(String::as_str), Some("done"));
+    assert!(h.backend.remaining().is_empty());
+    assert_eq!(h.task(id).attempt, 2);
+
+    let kinds = h.event_kinds(id);
+    let retries = kinds
+        .iter()
+        .filter(|k| *k == "task.retry_scheduled")
+        .count();
+    assert_eq!(retries, 2, "{kinds:?}");
+    let outputs = h.db.get_task_outputs(id).unwrap();
+    assert!(outputs
+        .iter()
+        .any(|o| o.phase == "validate" && o.exit_code != 0));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn blocked_signal_pauses_task() {
+    let h = harness(
+        "borg_replay_blocked",
+        fixture(json!([
+            {"phase": "implement", "events": done("Need credentials."),
+             "signal": {"status": "blocked", "reason": "missing API credentials",
+                        "question": "Which staging account should I use?"}},
+        ])),
+        "",
+        "",
+    );
+    let id = h.add_task("sweborg");
+
+    h.run_until(id, &["blocked", "failed", "done"]).await;
+    let task = h.task(id);
+    assert_eq!(task.status, "blocked");
+    assert!(task.last_error.contains("Which staging account"));
+    assert!(h.event_kinds(id).contains(&"agent.blocked".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn abandon_signal_fails_without_retry() {
+    let h = harness(
+        "borg_replay_abandon",
+        fixture(json!([
+            {"phase": "implement", "events": done("Duplicate."),
+             "signal": {"status": "abandon", "reason": "duplicate of task #12"}},
+        ])),
+        "",
+        "",
+    );
+    let id = h.add_task("sweborg");
+
+    h.run_until(id, &["failed", "blocked", "done"]).await;
+    let task = h.task(id);
+    assert_eq!(task.status, "failed");
+    assert_eq!(task.attempt, 0);
+    assert!(task.last_error.contains("duplicate of task #12"));
+}
+
+fn review_mode() -> PipelineMode {
+    let agent = |name: &str, next: &str| PhaseConfig {
+        name: name.into(),
+        label: name.into(),
+        instruction: "Draft the memo.".into(),
+        allowed_tools: "Read,Write".into(),
+        next: next.into(),
+        ..PhaseConfig::default()
+    };
+    PipelineMode {
+        name: "replay_review".into(),
+        label: "Replay Review".into(),
+        category: String::new(),
+        phases: vec![
+            PhaseConfig {
+                name: "backlog".into(),
+                phase_type: PhaseType::Setup,
+                next: "draft".into(),
+                ..PhaseConfig::default()
+            },
+            agent("draft", "human_review"),
+            PhaseConfig {
+                name: "human_review".into(),
+                phase_type: PhaseType::HumanReview,
+                revision_target: "draft".into(),
+                next: "finalize".into(),
+                ..PhaseConfig::default()
+            },
+            agent("finalize", "done"),
+        ],
+        seed_modes: vec![],
+        initial_status: "backlog".into(),
+        uses_docker: false,
+        uses_test_cmd: false,
+        integration: IntegrationType::None,
+        default_max_attempts: 3,
+        egress_allowlist: Vec::new(),
+        worker_labels: Vec::new(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn human_review_parks_until_reviewer_acts() {
+    let verdict = json!({"rationale": "memo drafted"});
+    let h = harness(
+        "borg_replay_review",
+        fixture(json!([
+            {"phase": "draft", "events": done("First draft."),
+             "files": {"memo.md": "draft 1\n"}, "verdict": verdict},
+            {"phase": "draft", "events": done("Second draft with summary."),
+             "files": {"memo.md": "draft 2\n\nSummary.\n"}, "verdict": verdict},
+            {"phase": "finalize", "events": done("Finalized."), "verdict": verdict},
+        ])),
+        "",
+        "",
+    );
+    h.db.set_config(
+        "custom_modes",
+        &serde_json::to_string(&vec![review_mode()]).unwrap(),
+    )
+    .unwrap();
+    let id = h.add_task("replay_review");
+
+    h.run_until(id, &["human_review"]).await;
+    for _ in 0..3 {
+        Arc::clone(&h.pipeline).tick().await.unwrap();
+        h.wait_idle().await;
+    }
+    assert_eq!(h.task(id).status, "human_review");
+    assert_eq!(h.backend.remaining(), vec!["draft", "finalize"]);
+
+    h.db.request_task_revision(id, "draft", "Add a summary.")
+        .unwrap();
+    h.run_until(id, &["human_review"]).await;
+    assert_eq!(h.task(id).revision_count, 1);
+    assert_eq!(h.backend.remaining(), vec!["finalize"]);
+
+    h.db.set_review_status(id, "approved").unwrap();
+    h.db.update_task_status(id, "finalize", None).unwrap();
+    h.run_until(id, &["done", "failed"]).await;
+    assert_eq!(h.task(id).status, "done");
+    assert!(h.backend.remaining().is_empty());
+}
@@ -0,0 +1,481 @@
+//! Affected-package detection for the validate phase.
+//!
+//! Maps the paths a task changed to the packages that contain them (and
+//! the workspace packages depending on those), then narrows the repo's
+//! `test_cmd` to just those. Sources, first match wins:
+//!
+//! - `.borg/affected.toml`, an explicit path → command mapping;
+//! - Cargo workspace members, read with `cargo metadata`;
+//! - npm/pnpm workspaces from the root `package.json`.
+//!
+//! Anything that cannot be attributed to a package (lockfiles, root
+//! manifests, unmapped paths) means the full suite runs instead.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+    process::Command,
+};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::git::Git;
+
+/// A test command narrowed to the affected packages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopedTests {
+    pub packages: Vec<String>,
+    pub test_cmd: String,
+}
+
+/// Paths changed in `work_dir` since it forked from `base`, committed or
+/// not, including untracked files.
+pub fn changed_paths(git: &Git, base: &str) -> Result<Vec<String>> {
+    let dir = git.repo_path.as_str();
+    let fork = git.exec(dir, &["merge-base", base, "HEAD"])?;
+    if !fork.success() {
+        return Err(anyhow!(
+            "git merge-base {base} HEAD: {}",
+            fork.combined_output()
+        ));
+    }
+    let diff = git.exec(dir, &["diff", "--name-only", fork.stdout.trim()])?;
+    let untracked = git.exec(dir, &["ls-files", "--others", "--exclude-standard"])?;
+    if !diff.success() || !untracked.success() {
+        return Err(anyhow!("git diff --name-only: {}", diff.combined_output()));
+    }
+    let paths: BTreeSet<String> = diff
+        .stdout
+        .lines()
+        .chain(untracked.stdout.lines())
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok(paths.into_iter().collect())
+}
+
+/// Scoped test command for `changed`, or `None` when the full `test_cmd`
+/// has to run.
+pub fn scope(work_dir: &Path, test_cmd: &str, changed: &[String]) -> Option<ScopedTests> {
+    if changed.is_empty() {
+        return None;
+    }
+    if let Ok(raw) = std::fs::read_to_string(work_dir.join(".borg/affected.toml")) {
+        return match toml::from_str::<AffectedToml>(&raw) {
+            Ok(mapping) => mapping.scope(changed),
+            Err(e) => {
+                tracing::warn!("{}/.borg/affected.toml: {e}", work_dir.display());
+                None
+            },
+        };
+    }
+    if test_cmd.contains("cargo test") && work_dir.join("Cargo.toml").exists() {
+        let packages = cargo_packages(&cargo_metadata(work_dir)?);
+        let names = affected_packages(&packages, changed, CARGO_ROOT_FILES)?;
+        return cargo_scoped(test_cmd, &names);
+    }
+    let root_manifest = std::fs::read_to_string(work_dir.join("package.json")).ok()?;
+    let root_manifest: Value = serde_json::from_str(&root_manifest).ok()?;
+    let packages = js_packages(work_dir, &root_manifest);
+    let names = affected_packages(&packages, changed, JS_ROOT_FILES)?;
+    js_scoped(test_cmd, &names)
+}
+
+// ── .borg/affected.toml ─────────────────────────────────────────────────
+
+/// ```toml
+/// ignore = ["docs/"]
+///
+/// [[target]]
+/// name = "api"
+/// paths = ["services/api/", "libs/proto/"]
+/// test = "make -C services/api test"
+/// ```
+#[derive(Debug, Deserialize)]
+struct AffectedToml {
+    #[serde(default)]
+    ignore: Vec<String>,
+    #[serde(default, rename = "target")]
+    targets: Vec<AffectedTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AffectedTarget {
+    name: String,
+    paths: Vec<String>,
+    test: String,
+}
+
+impl AffectedToml {
+    fn scope(&self, changed: &[String]) -> Option<ScopedTests> {
+        let mut hit = BTreeSet::new();
+        for path in changed {
+            if self.ignore.iter().any(|p| path.starts_with(p.as_str())) {
+                continue;
+            }
+            let mut matched = false;
+            for (i, target) in self.targets.iter().enumerate() {
+                if target.paths.iter().any(|p| path.starts_with(p.as_str())) {
+                    hit.insert(i);
+                    matched = true;
+                }
+            }
+            if !matched {
+                return None;
+            }
+        }
+        if hit.is_empty() {
+            return None;
+        }
+        let targets: Vec<&AffectedTarget> = hit.into_iter().map(|i| &self.targets[i]).collect();
+        Some(ScopedTests {
+            packages: targets.iter().map(|t| t.name.clone()).collect(),
+            test_cmd: targets
+                .iter()
+                .map(|t| format!("({})", t.test))
+                .collect::<Vec<_>>()
+                .join(" && "),
+        })
+    }
+}
+
+// ── Workspace packages ──────────────────────────────────────────────────
+
+/// Changes to these force a full run: they affect every package.
+const CARGO_ROOT_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+    ".cargo/",
+];
+const JS_ROOT_FILES: &[&str] = &[
+    "package.json",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "pnpm-workspace.yaml",
+    "yarn.lock",
+    "tsconfig.json",
+];
+
+#[derive(Debug, Clone)]
+struct Package {
+    name: String,
+    /// Directory relative to the repo root, without a trailing slash.
+    dir: String,
+    /// Names of the workspace packages it depends on.
+    deps: Vec<String>,
+}
+
+/// Packages owning the changed paths plus everything in the workspace that
+/// depends on them. `None` if a path belongs to no package or is a root file.
+fn affected_packages(
+    packages: &[Package],
+    changed: &[String],
+    root_files: &[&str],
+) -> Option<Vec<String>> {
+    if packages.is_empty() {
+        return None;
+    }
+    let mut hit = BTreeSet::new();
+    for path in changed {
+        if root_files.iter().any(|f| path == f || path.starts_with(f)) {
+            return None;
+        }
+        let owner = packages
+            .iter()
+            .filter(|p| p.dir.is_empty() || path.starts_with(&format!("{}/", p.dir)))
+            .max_by_key(|p| p.dir.len())?;
+        hit.insert(owner.name.clone());
+    }
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for p in packages {
+        for dep in &p.deps {
+            dependents.entry(dep).or_default().push(&p.name);
+        }
+    }
+    let mut stack: Vec<String> = hit.iter().cloned().collect();
+    while let Some(name) = stack.pop() {
+        for &user in dependents.get(name.as_str()).into_iter().flatten() {
+            if hit.insert(user.to_string()) {
+                stack.push(user.to_string());
+            }
+        }
+    }
+    // Every package affected: scoping saves nothing.
+    if hit.len() == packages.len() {
+        return None;
+    }
+    Some(hit.into_iter().collect())
+}
+
+fn cargo_metadata(work_dir: &Path) -> Option<Value> {
+    let out = Command::new("cargo")
+        .args([
+            "metadata",
+            "--no-deps",
+            "--format-version",
+            "1",
+            "--offline",
+        ])
+        .current_dir(work_dir)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    serde_json::from_slice(&out.stdout).ok()
+}
+
+fn cargo_packages(metadata: &Value) -> Vec<Package> {
+    let root = metadata["workspace_root"].as_str().unwrap_or_default();
+    let Some(packages) = metadata["packages"].as_array() else {
+        return Vec::new();
+    };
+    packages
+        .iter()
+        .filter_map(|p| {
+            let manifest = Path::new(p["manifest_path"].as_str()?);
+            let dir = manifest.parent()?.strip_prefix(root).ok()?;
+            Some(Package {
+                name: p["name"].as_str()?.to_string(),
+                dir: dir.to_string_lossy().trim_end_matches('/').to_string(),
+                deps: p["dependencies"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|d| d["path"].is_string())
+                    .filter_map(|d| d["name"].as_str().map(str::to_string))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// `cargo test --workspace …` → `cargo test -p a -p b …`. Commands that
+/// already pick packages, or that need shell quoting, are left alone.
+fn cargo_scoped(test_cmd: &str, packages: &[String]) -> Option<ScopedTests> {
+    if test_cmd.contains(['\'', '"'])
+        || test_cmd.contains("--manifest-path")
+        || test_cmd
+            .split_whitespace()
+            .any(|t| t == "-p" || t.starts_with("--package"))
+    {
+        return None;
+    }
+    let mut tokens: Vec<String> = Vec::new();
+    let mut prev = "";
+    for token in test_cmd.split_whitespace() {
+        if token == "--workspace" || token == "--all" {
+            continue;
+        }
+        tokens.push(token.to_string());
+        if prev == "cargo" && token == "test" {
+            for name in packages {
+                tokens.push("-p".into());
+                tokens.push(name.clone());
+            }
+        }
+        prev = token;
+    }
+    Some(ScopedTests {
+        packages: packages.to_vec(),
+        test_cmd: tokens.join(" "),
+    })
+}
+
+/// Packages listed by the root manifest's `workspaces` (an array, or an
+/// object with `packages`). Patterns may be literal directories or end in
+/// a single `/*`.
+fn js_packages(work_dir: &Path, root_manifest: &Value) -> Vec<Package> {
+    let patterns = root_manifest["workspaces"]
+        .as_array()
+        .or_else(|| root_manifest["workspaces"]["packages"].as_array())
+        .cloned()
+        .unwrap_or_default();
+    let mut dirs = Vec::new();
+    for pattern in patterns.iter().filter_map(Value::as_str) {
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        if let Some(parent) = pattern.strip_suffix("/*") {
+            let Ok(entries) = std::fs::read_dir(work_dir.join(parent)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.path().is_dir() {
+                    dirs.push(format!("{parent}/{}", entry.file_name().to_string_lossy()));
+                }
+            }
+        } else if !pattern.contains('*') {
+            dirs.push(pattern.to_string());
+        }
+    }
+    let mut packages: Vec<Package> = dirs
+        .into_iter()
+        .filter_map(|dir| {
+            let raw = std::fs::read_to_string(work_dir.join(&dir).join("package.json")).ok()?;
+            let manifest: Value = serde_json::from_str(&raw).ok()?;
+            Some(Package {
+                name: manifest["name"].as_str()?.to_string(),
+                dir,
+                deps: ["dependencies", "devDependencies", "peerDependencies"]
+                    .iter()
+                    .filter_map(|k| manifest[*k].as_object())
+                    .flat_map(|deps| deps.keys().cloned())
+                    .collect(),
+            })
+        })
+        .collect();
+    let names: BTreeSet<String> = packages.iter().map(|p| p.name.clone()).collect();
+    for p in &mut packages {
+        p.deps.retain(|d| names.contains(d));
+    }
+    packages
+}
+
+/// `npm test` / `npm run test` gain `-w <pkg>` flags, `pnpm test` gains
+/// `--filter <pkg>`. Other runners (yarn, turbo, …) keep the full command.
+fn js_scoped(test_cmd: &str, packages: &[String]) -> Option<ScopedTests> {
+    let tokens: Vec<&str> = test_cmd.split_whitespace().collect();
+    if test_cmd.contains(['\'', '"', '&', ';', '|']) {
+        return None;
+    }
+    let scoped = match tokens.as_slice() {
+        ["npm", "test", rest @ ..] | ["npm", "run", "test", rest @ ..] => {
+            let script = &tokens[..tokens.len() - rest.len()];
+            let flags = packages.iter().map(|p| format!("-w {p}"));
+            script
+                .iter()
+                .map(|t| t.to_string())
+                .chain(flags)
+                .chain(rest.iter().map(|t| t.to_string()))
+                .collect::<Vec<_>>()
+        },
+        ["pnpm", rest @ ..] if rest.first().is_some_and(|t| *t == "test" || *t == "run") => {
+            let flags = packages.iter().map(|p| format!("--filter {p}"));
+            std::iter::once("pnpm".to_string())
+                .chain(flags)
+                .chain(rest.iter().map(|t| t.to_string()))
+                .collect::<Vec<_>>()
+        },
+        _ => return None,
+    };
+    Some(ScopedTests {
+        packages: packages.to_vec(),
+        test_cmd: scoped.join(" "),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn paths(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn cargo_workspace() -> Vec<Package> {
+        cargo_packages(&json!({
+            "workspace_root": "/repo",
+            "packages": [
+                {
+                    "name": "core",
+                    "manifest_path": "/repo/crates/core/Cargo.toml",
+                    "dependencies": [{ "name": "serde" }],
+                },
+                {
+                    "name": "server",
+                    "manifest_path": "/repo/crates/server/Cargo.toml",
+                    "dependencies": [{ "name": "core", "path": "/repo/crates/core" }],
+                },
+                {
+                    "name": "cli",
+                    "manifest_path": "/repo/crates/cli/Cargo.toml",
+                    "dependencies": [],
+                },
+            ],
+        }))
+    }
+
+    #[test]
+    fn cargo_change_pulls_in_dependents() {
+        let packages = cargo_workspace();
+        let names = affected_packages(
+            &packages,
+            &paths(&["crates/core/src/lib.rs"]),
+            CARGO_ROOT_FILES,
+        );
+        assert_eq!(names, Some(paths(&["core", "server"])));
+
+        let scoped = cargo_scoped(
+            "cargo test --workspace -- --test-threads=2",
+            &paths(&["cli"]),
+        );
+        assert_eq!(
+            scoped.map(|s| s.test_cmd),
+            Some("cargo test -p cli -- --test-threads=2".to_string())
+        );
+    }
+
+    #[test]
+    fn root_files_and_unowned_paths_force_full_run() {
+        let packages = cargo_workspace();
+        for changed in [
+            &["Cargo.lock"][..],
+            &["scripts/release.sh"],
+            &["crates/cli/x.rs", ".cargo/config.toml"],
+        ] {
+            assert_eq!(
+                affected_packages(&packages, &paths(changed), CARGO_ROOT_FILES),
+                None,
+                "{changed:?}"
+            );
+        }
+        assert_eq!(cargo_scoped("cargo test -p core", &paths(&["cli"])), None);
+    }
+
+    #[test]
+    fn js_runners_get_workspace_filters() {
+        let names = paths(&["@acme/web"]);
+        assert_eq!(
+            js_scoped("npm run test -- --ci", &names).map(|s| s.test_cmd),
+            Some("npm run test -w @acme/web -- --ci".to_string())
+        );
+        assert_eq!(
+            js_scoped("pnpm test", &names).map(|s| s.test_cmd),
+            Some("pnpm --filter @acme/web test".to_string())
+        );
+        assert_eq!(js_scoped("yarn test", &names), None);
+    }
+
+    #[test]
+    fn affected_toml_maps_paths_to_commands() {
+        let mapping: AffectedToml = toml::from_str(
+            r#"
+            ignore = ["docs/"]
+
+            [[target]]
+            name = "api"
+            paths = ["services/api/"]
+            test = "make -C services/api test"
+
+            [[target]]
+            name = "web"
+            paths = ["web/"]
+            test = "npm --prefix web test"
+            "#,
+        )
+        .expect("parse");
+        assert_eq!(
+            mapping.scope(&paths(&["services/api/main.go", "docs/api.md"])),
+            Some(ScopedTests {
+                packages: paths(&["api"]),
+                test_cmd: "(make -C services/api test)".to_string(),
+            })
+        );
+        assert_eq!(mapping.scope(&paths(&["Makefile"])), None);
+        assert_eq!(mapping.scope(&paths(&["docs/index.md"])), None);
+    }
+}
@@ -27,6 +27,13 @@ pub trait AgentBackend: Send + Sync {
         BackendCapabilities::default()
     }
 
+    /// Whether runs of `phase` stay on the network the pipeline picked
+    /// (`ctx.agent_network`, with `ctx.egress_proxy` as the only way out).
+    /// Egress-restricted runs are refused on backends that can't promise this.
+    fn enforces_network_policy(&self, _phase: &PhaseConfig) -> bool {
+        false
+    }
+
     /// Backend identifier (e.g. "claude", "agent-sdk", "codex", "ollama").
     fn name(&self) -> &str;
 }
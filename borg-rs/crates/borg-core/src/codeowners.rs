@@ -0,0 +1,297 @@
+//! CODEOWNERS and per-repo merge policy.
+//!
+//! Both map path patterns to review requirements. CODEOWNERS names who to
+//! request reviews from, and `.borg/merge-policy.toml` decides whether a
+//! branch needs a human approval regardless of the repo's `auto_merge`:
+//!
+//! ```toml
+//! [[rule]]
+//! paths = ["migrations/"]
+//! approval = "human"
+//!
+//! [[rule]]
+//! paths = ["docs/", "*.md"]
+//! approval = "auto"
+//! ```
+//!
+//! Both files are read from the branch being merged into, so a task cannot
+//! loosen the rules for its own change. Patterns follow CODEOWNERS
+//! (gitignore) syntax: a leading or inner `/` anchors to the repo root, a
+//! trailing `/` matches only directories, and `*`, `?` and `**` are globs.
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::git::Git;
+
+/// Where forges look for a CODEOWNERS file, in lookup order.
+const CODEOWNERS_PATHS: &[&str] = &[
+    ".github/CODEOWNERS",
+    ".gitlab/CODEOWNERS",
+    ".gitea/CODEOWNERS",
+    "CODEOWNERS",
+    "docs/CODEOWNERS",
+];
+
+pub const MERGE_POLICY_PATH: &str = ".borg/merge-policy.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeOwners {
+    rules: Vec<(String, Vec<String>)>,
+}
+
+impl CodeOwners {
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(|line| line.split_once(" #").map_or(line, |(rule, _)| rule).trim())
+            // Comments and GitLab `[Section]` headers.
+            .filter(|line| {
+                !line.is_empty()
+                    && !line.starts_with('#')
+                    && !line.starts_with('[')
+                    && !line.starts_with("^[")
+            })
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let pattern = words.next()?.to_string();
+                let owners = words
+                    .filter(|w| w.contains('@'))
+                    .map(str::to_string)
+                    .collect();
+                Some((pattern, owners))
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// The first CODEOWNERS file found at `rev`, if any.
+    pub fn load(git: &Git, rev: &str) -> Option<Self> {
+        CODEOWNERS_PATHS
+            .iter()
+            .find_map(|path| git.show_file(rev, path))
+            .map(|text| Self::parse(&text))
+    }
+
+    /// Owners of `path`. The last matching rule wins, and a rule without
+    /// owners leaves the path unowned.
+    pub fn owners_for(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| path_matches(pattern, path))
+            .map_or(&[], |(_, owners)| owners.as_slice())
+    }
+
+    /// Everyone owning at least one of `paths`, once each, in first-seen order.
+    pub fn owners_for_paths(&self, paths: &[String]) -> Vec<String> {
+        let mut owners: Vec<String> = Vec::new();
+        for owner in paths.iter().flat_map(|p| self.owners_for(p)) {
+            if !owners.contains(owner) {
+                owners.push(owner.clone());
+            }
+        }
+        owners
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Approval {
+    /// Always wait for a human, even with `auto_merge` on.
+    Human,
+    /// May merge without review when every changed path matches.
+    Auto,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PolicyRule {
+    pub paths: Vec<String>,
+    pub approval: Approval,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MergePolicy {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<PolicyRule>,
+}
+
+impl MergePolicy {
+    /// The policy at `rev`, or an empty policy if there is none. A file that
+    /// does not parse is an error, so callers can hold changes rather than
+    /// drop its `human` rules.
+    pub fn load(git: &Git, rev: &str) -> Result<Self> {
+        let Some(text) = git.show_file(rev, MERGE_POLICY_PATH) else {
+            return Ok(Self::default());
+        };
+        toml::from_str(&text).map_err(|e| anyhow!("{MERGE_POLICY_PATH} at {rev}: {e}"))
+    }
+
+    pub fn has_human_rules(&self) -> bool {
+        self.rules.iter().any(|r| r.approval == Approval::Human)
+    }
+
+    /// The approval the rules require for a change touching `changed`, with
+    /// the reason, or `None` to leave it to the repo's `auto_merge`. Any path
+    /// under a `human` rule holds the change; it is `auto` only when every
+    /// path is covered by an `auto` rule.
+    pub fn evaluate(&self, changed: &[String]) -> Option<(Approval, String)> {
+        let matching = |approval: Approval, path: &str| {
+            self.rules
+                .iter()
+                .filter(|r| r.approval == approval)
+                .flat_map(|r| &r.paths)
+                .find(|pattern| path_matches(pattern, path))
+        };
+        for path in changed {
+            if let Some(pattern) = matching(Approval::Human, path) {
+                return Some((
+                    Approval::Human,
+                    format!("{path} matches `{pattern}`, which needs human approval"),
+                ));
+            }
+        }
+        if !changed.is_empty()
+            && changed
+                .iter()
+                .all(|p| matching(Approval::Auto, p).is_some())
+        {
+            return Some((
+                Approval::Auto,
+                "every changed path may merge without review".to_string(),
+            ));
+        }
+        None
+    }
+}
+
+/// Whether a CODEOWNERS-style `pattern` covers the repo-relative `path`,
+/// either the file itself or a directory containing it.
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let dir_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    let anchored = pattern.contains('/');
+    let pattern: Vec<&str> = pattern.trim_start_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    let starts = if anchored { 0 } else { path.len() - 1 };
+    (0..=starts).any(|start| {
+        // A match ending before the last segment names a directory.
+        (start + 1..=path.len())
+            .filter(|&end| !dir_only || end < path.len())
+            .any(|end| segments_match(&pattern, &path[start..end]))
+    })
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| segments_match(rest, &path[i..])),
+        Some((first, rest)) => {
+            !path.is_empty() && glob(first, path[0]) && segments_match(rest, &path[1..])
+        },
+    }
+}
+
+/// `*` and `?` wildcards within one path segment.
+fn glob(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn matches_codeowners_patterns() {
+        assert!(path_matches("*", "src/main.rs"));
+        assert!(path_matches("*.md", "docs/guide/intro.md"));
+        assert!(!path_matches("*.md", "src/lib.rs"));
+        assert!(path_matches("migrations/", "borg-rs/migrations/0001.sql"));
+        assert!(!path_matches("migrations/", "migrations"));
+        assert!(path_matches("/docs/", "docs/a.md"));
+        assert!(!path_matches("/docs/", "api/docs/a.md"));
+        assert!(path_matches("apps/web", "apps/web/src/index.ts"));
+        assert!(!path_matches("apps/web", "lib/apps/web/x.ts"));
+        assert!(path_matches("docs/**/*.png", "docs/img/deep/logo.png"));
+        assert!(path_matches("src/*_test.go", "src/cart_test.go"));
+        assert!(!path_matches("src/*_test.go", "src/sub/cart_test.go"));
+    }
+
+    #[test]
+    fn last_matching_owner_rule_wins() {
+        let owners = CodeOwners::parse(
+            "# default owners\n\
+             *       @acme/core\n\
+             [Docs]\n\
+             docs/   @writer docs@acme.dev  # inline comment\n\
+             /crates/borg-server/ @alice @acme/api\n\
+             docs/generated/\n",
+        );
+        assert_eq!(owners.owners_for("Cargo.toml"), ["@acme/core"]);
+        assert_eq!(
+            owners.owners_for("docs/guide.md"),
+            ["@writer", "docs@acme.dev"]
+        );
+        assert!(owners.owners_for("docs/generated/api.md").is_empty());
+        assert_eq!(
+            owners.owners_for_paths(&paths(&[
+                "crates/borg-server/src/main.rs",
+                "README.md",
+                "crates/borg-server/Cargo.toml",
+            ])),
+            vec!["@alice", "@acme/api", "@acme/core"]
+        );
+    }
+
+    #[test]
+    fn merge_policy_holds_migrations_and_passes_docs() {
+        let policy: MergePolicy = toml::from_str(
+            r#"
+            [[rule]]
+            paths = ["migrations/"]
+            approval = "human"
+
+            [[rule]]
+            paths = ["docs/", "*.md"]
+            approval = "auto"
+            "#,
+        )
+        .unwrap();
+        let (approval, reason) = policy
+            .evaluate(&paths(&["README.md", "migrations/0011_x.up.sql"]))
+            .unwrap();
+        assert_eq!(approval, Approval::Human);
+        assert!(reason.contains("migrations/0011_x.up.sql"));
+        assert_eq!(
+            policy
+                .evaluate(&paths(&["README.md", "docs/setup.md"]))
+                .map(|(a, _)| a),
+            Some(Approval::Auto)
+        );
+        assert_eq!(policy.evaluate(&paths(&["README.md", "src/lib.rs"])), None);
+        assert_eq!(policy.evaluate(&[]), None);
+    }
+}
@@ -24,7 +24,14 @@ pub struct Config {
     pub pipeline_repo: String,
     pub pipeline_test_cmd: String,
     pub pipeline_lint_cmd: String,
+    /// Validate runs only the tests of packages the task's diff touches
+    /// before the full suite.
+    pub affected_tests: bool,
     pub backend: String,
+    /// Ordered backend names to fall back to when the selected one fails.
+    pub backend_fallbacks: Vec<String>,
+    /// Modes whose tasks and chats may only fall back to local models.
+    pub local_only_fallback_modes: Vec<String>,
     pub pipeline_admin_chat: String,
     pub release_interval_mins: u32,
     pub continuous_mode: bool,
@@ -41,6 +48,14 @@ pub struct Config {
     pub web_bind: String,
     pub web_port: u16,
     pub proxy_port: u16,
+    /// Port of the egress allowlist proxy for sandboxed agents (0 = disabled).
+    pub egress_proxy_port: u16,
+    /// Bind address of the egress proxy; must be reachable from the internal
+    /// agent network (e.g. its gateway 172.31.0.1 or 0.0.0.0).
+    pub egress_proxy_bind: String,
+    /// Shared secret `borg-worker` nodes present to register and poll for
+    /// jobs. Empty disables remote workers.
+    pub worker_token: String,
     pub dashboard_dist_dir: String,
 
     // Container / sandbox
@@ -48,8 +63,17 @@ pub struct Config {
     pub container_memory_mb: u64,
     /// CPU quota for docker run --cpus (0.0 = no limit).
     pub container_cpus: f64,
-    /// "auto" (default), "bwrap", "docker", or "none".
+    /// "auto" (default), "bwrap", "docker", "podman", or "none"; container
+    /// backends accept a "+gvisor" suffix to run under runsc.
     pub sandbox_backend: String,
+    /// Idle warm containers kept per image/repo (0 = no warm pool).
+    pub warm_pool_size: u32,
+    /// Phases a warm container serves before it is destroyed (default 1).
+    pub warm_pool_max_uses: u32,
+    /// Seconds an idle warm container lives before it is destroyed.
+    pub warm_pool_idle_ttl_s: u64,
+    /// Memory cap across idle warm containers; the oldest are evicted first.
+    pub warm_pool_max_idle_memory_mb: u64,
 
     // Pipeline tuning
     pub pipeline_max_backlog: u32,
@@ -61,6 +85,9 @@ pub struct Config {
     pub mirror_refresh_interval_s: i64,
     /// Min seconds between automated agent spawns per task (default 120).
     pub pipeline_agent_cooldown_s: i64,
+    /// Seconds between polls of open PRs for new review comments (default
+    /// 120, 0 disables).
+    pub pr_review_poll_interval_s: i64,
 
     // Git attribution
     pub git_author_name: String,
@@ -74,6 +101,16 @@ pub struct Config {
     /// GitHub token for pushing branches and creating PRs.
     /// Users can override this per-account via the `github_token` user setting.
     pub github_token: String,
+    /// API token for repos whose forge is GitLab.
+    pub gitlab_token: String,
+    /// API token for repos whose forge is Gitea/Forgejo.
+    pub gitea_token: String,
+    /// Secret for inbound forge webhooks: the HMAC key for GitHub/Gitea
+    /// signatures and the token GitLab sends. Empty disables webhooks.
+    pub webhook_secret: String,
+    /// Issues carrying this label become tasks when a webhook reports them
+    /// (default "borg", empty disables).
+    pub webhook_issue_label: String,
 
     pub watched_repos: Vec<RepoConfig>,
 
@@ -156,6 +193,9 @@ pub struct Config {
     pub cloudflare_access_email_header: String,
     /// Emails that should be treated as Borg admins in Cloudflare Access mode.
     pub cloudflare_admin_emails: Vec<String>,
+    /// Peer addresses of reverse proxies whose `X-Forwarded-For`/`X-Real-IP`
+    /// headers are trusted for the client address. Set via TRUSTED_PROXIES.
+    pub trusted_proxies: Vec<String>,
 
     // Email ingestion
     /// IMAP server for polling inbound emails (leave empty to disable).
@@ -179,6 +219,18 @@ pub struct Config {
     /// Enable at-rest encryption for secrets (API keys, credentials) via ChaCha20-Poly1305.
     /// Set BORG_SECRET_ENCRYPTION=true to enable. Default: false for backwards compat.
     pub secret_encryption: bool,
+    /// Envelope encryption for the secret keyring: "" keeps data keys on disk
+    /// under `data_dir`, "aws" wraps them with AWS KMS, "vault" with a Vault
+    /// transit key. Set via BORG_SECRET_KMS.
+    pub secret_kms: String,
+    /// AWS KMS key id/ARN, or the Vault transit key name.
+    pub secret_kms_key_id: String,
+    pub secret_kms_region: String,
+    pub vault_addr: String,
+    pub vault_token: String,
+    /// Apply pending schema migrations at startup. When false (BORG_AUTO_MIGRATE=false)
+    /// the server refuses to start until `borg-server migrate up` has been run.
+    pub auto_migrate: bool,
 }
 
 impl Config {
@@ -425,6 +477,10 @@ fn parse_watched_repos(
             lint_cmd: pipeline_lint_cmd.to_string(),
             backend: String::new(),
             repo_slug: slug_from_remote(pipeline_repo),
+            container_image: String::new(),
+            container_file: String::new(),
+            forge: String::new(),
+            forge_url: String::new(),
         });
     }
 
@@ -476,6 +532,10 @@ fn parse_watched_repos(
             lint_cmd,
             backend: String::new(),
             repo_slug,
+            container_image: String::new(),
+            container_file: String::new(),
+            forge: String::new(),
+            forge_url: String::new(),
         });
     }
 
@@ -734,6 +794,7 @@ impl Config {
             ("pipeline_repo", self.pipeline_repo.clone()),
             ("pipeline_test_cmd", self.pipeline_test_cmd.clone()),
             ("pipeline_lint_cmd", self.pipeline_lint_cmd.clone()),
+            ("affected_tests", self.affected_tests.to_string()),
             ("backend", self.backend.clone()),
             ("pipeline_admin_chat", self.pipeline_admin_chat.clone()),
             (
@@ -776,12 +837,17 @@ impl Config {
                 "mirror_refresh_interval_s",
                 self.mirror_refresh_interval_s.to_string(),
             ),
+            (
+                "pr_review_poll_interval_s",
+                self.pr_review_poll_interval_s.to_string(),
+            ),
             ("git_author_name", self.git_author_name.clone()),
             ("git_author_email", self.git_author_email.clone()),
             ("git_committer_name", self.git_committer_name.clone()),
             ("git_committer_email", self.git_committer_email.clone()),
             ("git_claude_coauthor", self.git_claude_coauthor.to_string()),
             ("git_user_coauthor", self.git_user_coauthor.clone()),
+            ("webhook_issue_label", self.webhook_issue_label.clone()),
             ("build_cmd", "cargo build --release".into()),
             ("self_update_enabled", self.self_update_enabled.to_string()),
             ("observer_config", self.observer_config.clone()),
@@ -876,6 +942,7 @@ impl Config {
         c.pipeline_repo = get_str("pipeline_repo", &c.pipeline_repo);
         c.pipeline_test_cmd = get_str("pipeline_test_cmd", &c.pipeline_test_cmd);
         c.pipeline_lint_cmd = get_str("pipeline_lint_cmd", &c.pipeline_lint_cmd);
+        c.affected_tests = get_bool("affected_tests", c.affected_tests);
         c.pipeline_admin_chat = get_str("pipeline_admin_chat", &c.pipeline_admin_chat);
         c.container_setup = get_str("container_setup", &c.container_setup);
         c.sandbox_backend = get_str("sandbox_backend", &c.sandbox_backend);
@@ -919,6 +986,7 @@ impl Config {
         );
         c.experimental_domains = get_bool("experimental_domains", c.experimental_domains);
         c.build_cmd = get_str("build_cmd", &c.build_cmd);
+        c.webhook_issue_label = get_str("webhook_issue_label", &c.webhook_issue_label);
         c.self_update_enabled = get_bool("self_update_enabled", c.self_update_enabled);
         c.continuous_mode = get_bool("continuous_mode", c.continuous_mode);
         c.git_claude_coauthor = get_bool("git_claude_coauthor", c.git_claude_coauthor);
@@ -939,6 +1007,7 @@ impl Config {
         load_u64!("container_memory_mb", c.container_memory_mb);
         load_u64!("pipeline_tick_s", c.pipeline_tick_s);
         load_i64!("mirror_refresh_interval_s", c.mirror_refresh_interval_s);
+        load_i64!("pr_review_poll_interval_s", c.pr_review_poll_interval_s);
         if let Some(v) = get("container_cpus").and_then(|s| s.parse::<f64>().ok()) {
             c.container_cpus = v;
         }
@@ -958,6 +1027,10 @@ impl Config {
                         lint_cmd: String::new(),
                         backend: row.backend.unwrap_or_default(),
                         repo_slug: row.repo_slug,
+                        container_image: row.container_image,
+                        container_file: row.container_file,
+                        forge: row.forge,
+                        forge_url: row.forge_url,
                     });
                 }
                 c.watched_repos = repos;
@@ -1016,6 +1089,7 @@ impl Config {
 
         let web_port = get_u16("WEB_PORT", &dotenv, 3131);
         let proxy_port = get_u16("PROXY_PORT", &dotenv, web_port.saturating_add(1));
+        let egress_proxy_port = get_u16("EGRESS_PROXY_PORT", &dotenv, proxy_port.saturating_add(1));
 
         Ok(Config {
             telegram_token: get_str("TELEGRAM_BOT_TOKEN", &dotenv, ""),
@@ -1037,7 +1111,17 @@ impl Config {
             pipeline_repo,
             pipeline_test_cmd,
             pipeline_lint_cmd,
+            affected_tests: get_bool("AFFECTED_TESTS", &dotenv, false),
             backend,
+            backend_fallbacks: get_csv("BACKEND_FALLBACKS", &dotenv),
+            local_only_fallback_modes: {
+                let modes = get_csv("LOCAL_ONLY_FALLBACK_MODES", &dotenv);
+                if modes.is_empty() {
+                    vec!["lawborg".into(), "legal".into()]
+                } else {
+                    modes
+                }
+            },
             pipeline_admin_chat: get_str("PIPELINE_ADMIN_CHAT", &dotenv, ""),
             release_interval_mins: get_u32("RELEASE_INTERVAL_MINS", &dotenv, 180),
             continuous_mode: get_bool("CONTINUOUS_MODE", &dotenv, false),
@@ -1050,6 +1134,9 @@ impl Config {
             web_bind: get_str("WEB_BIND", &dotenv, "127.0.0.1"),
             web_port,
             proxy_port,
+            egress_proxy_port,
+            egress_proxy_bind: get_str("EGRESS_PROXY_BIND", &dotenv, "172.31.0.1"),
+            worker_token: get_str("BORG_WORKER_TOKEN", &dotenv, ""),
             dashboard_dist_dir: get_str("DASHBOARD_DIST_DIR", &dotenv, "dashboard/dist"),
             container_setup: get_str("CONTAINER_SETUP", &dotenv, ""),
             container_memory_mb: get_u64("CONTAINER_MEMORY_MB", &dotenv, 2048),
@@ -1057,6 +1144,10 @@ impl Config {
                 .and_then(|s| s.parse::<f64>().ok())
                 .unwrap_or(2.0),
             sandbox_backend: get_str("SANDBOX_BACKEND", &dotenv, "auto"),
+            warm_pool_size: get_u32("WARM_POOL_SIZE", &dotenv, 0),
+            warm_pool_max_uses: get_u32("WARM_POOL_MAX_USES", &dotenv, 1).max(1),
+            warm_pool_idle_ttl_s: get_u64("WARM_POOL_IDLE_TTL_S", &dotenv, 1800),
+            warm_pool_max_idle_memory_mb: get_u64("WARM_POOL_MAX_IDLE_MEMORY_MB", &dotenv, 4096),
             pipeline_max_backlog: get_u32("PIPELINE_MAX_BACKLOG", &dotenv, 5),
             pipeline_seed_cooldown_s: get_i64("PIPELINE_SEED_COOLDOWN_S", &dotenv, 3600),
             proposal_promote_threshold: get_i64("PIPELINE_PROPOSAL_THRESHOLD", &dotenv, 8),
@@ -1064,6 +1155,7 @@ impl Config {
             remote_check_interval_s: get_i64("REMOTE_CHECK_INTERVAL_S", &dotenv, 300),
             mirror_refresh_interval_s: get_i64("MIRROR_REFRESH_INTERVAL_S", &dotenv, 60),
             pipeline_agent_cooldown_s: get_i64("PIPELINE_AGENT_COOLDOWN_S", &dotenv, 120),
+            pr_review_poll_interval_s: get_i64("PR_REVIEW_POLL_INTERVAL_S", &dotenv, 120),
             git_author_name: get_str("GIT_AUTHOR_NAME", &dotenv, ""),
             git_author_email: get_str("GIT_AUTHOR_EMAIL", &dotenv, ""),
             git_committer_name: get_str("GIT_COMMITTER_NAME", &dotenv, ""),
@@ -1073,6 +1165,12 @@ impl Config {
             github_token: get("GH_TOKEN", &dotenv)
                 .or_else(|| get("GITHUB_TOKEN", &dotenv))
                 .unwrap_or_default(),
+            gitlab_token: get_str("GITLAB_TOKEN", &dotenv, ""),
+            gitea_token: get("GITEA_TOKEN", &dotenv)
+                .or_else(|| get("FORGEJO_TOKEN", &dotenv))
+                .unwrap_or_default(),
+            webhook_secret: get_str("FORGE_WEBHOOK_SECRET", &dotenv, ""),
+            webhook_issue_label: get_str("WEBHOOK_ISSUE_LABEL", &dotenv, "borg"),
             watched_repos,
             build_cmd: "cargo build --release".into(),
             self_update_enabled: get_bool("SELF_UPDATE_ENABLED", &dotenv, false),
@@ -1142,6 +1240,7 @@ impl Config {
                 "cf-access-authenticated-user-email",
             ),
             cloudflare_admin_emails: get_csv("CLOUDFLARE_ADMIN_EMAILS", &dotenv),
+            trusted_proxies: get_csv("TRUSTED_PROXIES", &dotenv),
             imap_host: get_str("IMAP_HOST", &dotenv, ""),
             imap_port: get_u16("IMAP_PORT", &dotenv, 993),
             imap_user: get_str("IMAP_USER", &dotenv, ""),
@@ -1154,6 +1253,16 @@ impl Config {
             smtp_pass: get_str("SMTP_PASS", &dotenv, ""),
             enforce_retrieval_protocol: get_bool("ENFORCE_RETRIEVAL_PROTOCOL", &dotenv, true),
             secret_encryption: get_bool("BORG_SECRET_ENCRYPTION", &dotenv, false),
+            secret_kms: get_str("BORG_SECRET_KMS", &dotenv, ""),
+            secret_kms_key_id: get_str("BORG_SECRET_KMS_KEY_ID", &dotenv, "borg"),
+            secret_kms_region: get_str(
+                "BORG_SECRET_KMS_REGION",
+                &dotenv,
+                get_str("AWS_REGION", &dotenv, "us-east-1").as_str(),
+            ),
+            vault_addr: get_str("VAULT_ADDR", &dotenv, "http://127.0.0.1:8200"),
+            vault_token: get_str("VAULT_TOKEN", &dotenv, ""),
+            auto_migrate: get_bool("BORG_AUTO_MIGRATE", &dotenv, true),
         })
     }
 }
@@ -6,15 +6,28 @@ use serde_json;
 
 use crate::{
     linked_credentials::LinkedCredentialBundle,
-    pgcompat as pg,
-    pgcompat::{params, Connection, ConnectionGuard, Mutex, OptionalExtension},
+    migrations, pgcompat as pg,
+    pgcompat::{params, Connection, ConnectionGuard, OptionalExtension},
+    traits::SecretStore,
     types::{Proposal, QueueEntry, Task},
 };
 
-const SCHEMA_SQL: &str = include_str!("../../../schema.pg.sql");
+/// Advisory lock key held while migrations run (ASCII "borgmigr").
+const MIGRATION_LOCK_KEY: i64 = 0x626f_7267_6d69_6772;
 
+/// Process-wide secret store used for `api_keys`, `linked_credentials` and
+/// `cloud_connections` secrets once `BORG_SECRET_ENCRYPTION` is enabled.
+static SECRET_STORE: std::sync::OnceLock<std::sync::Arc<dyn SecretStore>> =
+    std::sync::OnceLock::new();
+
+/// Handle to the Postgres pool. Every call checks out its own pooled
+/// session, so queries from different requests run concurrently. Most
+/// methods are still synchronous: they drive the query through
+/// `block_in_place`, which parks the calling runtime worker until it returns.
+/// Only the hot list and search reads (the `async fn` methods) and
+/// [`Db::client`] await a client instead.
 pub struct Db {
-    conn: Mutex<Connection>,
+    pool: Connection,
 }
 
 // ── Auxiliary types ───────────────────────────────────────────────────────
@@ -39,6 +52,42 @@ pub struct TaskOutput {
     pub created_at: DateTime<Utc>,
 }
 
+/// Pass/fail history of one test in one repo.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TestFlakiness {
+    pub repo_path: String,
+    pub test_name: String,
+    pub passes: i64,
+    pub failures: i64,
+    pub flaky_runs: i64,
+    pub last_status: String,
+    pub quarantined_at: String,
+    pub updated_at: String,
+}
+
+/// Resource usage recorded for one agent phase run (one `task_outputs` row).
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PhaseResourceUsage {
+    pub task_output_id: i64,
+    pub task_id: i64,
+    pub phase: String,
+    #[serde(flatten)]
+    pub usage: crate::resources::ResourceUsage,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A failing test recorded for one phase attempt of a task.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TaskTestFailure {
+    pub id: i64,
+    pub task_id: i64,
+    pub phase: String,
+    pub attempt: i64,
+    #[serde(flatten)]
+    pub failure: crate::test_report::TestFailure,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct TaskMessage {
     pub id: i64,
@@ -60,6 +109,10 @@ pub struct RepoRow {
     pub prompt_file: String,
     pub auto_merge: bool,
     pub repo_slug: String,
+    pub container_image: String,
+    pub container_file: String,
+    pub forge: String,
+    pub forge_url: String,
 }
 
 #[derive(serde::Serialize)]
@@ -97,6 +150,16 @@ pub struct ApiKeyEntry {
     pub created_at: String,
 }
 
+/// Rows rewritten by [`Db::reencrypt_secrets`], per table.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SecretRotationReport {
+    pub api_keys: usize,
+    pub linked_credentials: usize,
+    pub cloud_connections: usize,
+    /// Values that could not be decrypted with any loaded key and were left as-is.
+    pub skipped: usize,
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct LinkedCredentialEntry {
     pub id: i64,
@@ -184,6 +247,31 @@ pub struct UsageSummary {
     pub total_cost_usd: f64,
     pub message_count: i64,
     pub task_count: i64,
+    pub resources: ResourceUsageSummary,
+}
+
+/// Agent resource usage over a time range, in total and per phase name.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ResourceUsageSummary {
+    pub phase_runs: i64,
+    pub wall_ms: i64,
+    pub cpu_ms: i64,
+    pub max_peak_memory_bytes: i64,
+    pub disk_written_bytes: i64,
+    pub by_phase: Vec<PhaseResourceTotals>,
+    /// Heaviest individual runs by CPU time, to spot runaway test suites.
+    pub top_runs: Vec<PhaseResourceUsage>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct PhaseResourceTotals {
+    pub phase: String,
+    pub runs: i64,
+    pub wall_ms: i64,
+    pub cpu_ms: i64,
+    pub max_peak_memory_bytes: i64,
+    pub avg_peak_memory_bytes: i64,
+    pub disk_written_bytes: i64,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -250,6 +338,48 @@ pub struct ProjectShareLinkRow {
     pub created_by: Option<i64>,
     pub revoked: bool,
     pub created_at: String,
+    #[serde(skip)]
+    pub password_hash: String,
+    pub password_protected: bool,
+    /// "full" exposes tasks and documents, "documents" only documents.
+    pub scope: String,
+    /// Restricts the link to these project files; empty means all non-privileged files.
+    pub document_ids: Vec<i64>,
+    /// Combined view + download cap; 0 means unlimited.
+    pub max_uses: i64,
+    pub view_count: i64,
+    pub download_count: i64,
+}
+
+impl ProjectShareLinkRow {
+    pub fn allows_tasks(&self) -> bool {
+        self.scope != "documents"
+    }
+
+    pub fn allows_document(&self, file_id: i64) -> bool {
+        self.document_ids.is_empty() || self.document_ids.contains(&file_id)
+    }
+}
+
+/// Access policy for a new share link.
+#[derive(Debug, Clone, Default)]
+pub struct ShareLinkPolicy {
+    pub password_hash: String,
+    pub scope: String,
+    pub document_ids: Vec<i64>,
+    pub max_uses: i64,
+}
+
+#[derive(Debug, serde::Serialize, Clone)]
+pub struct ShareLinkAccessRow {
+    pub id: i64,
+    pub link_id: i64,
+    pub action: String,
+    pub file_id: Option<i64>,
+    pub status: i64,
+    pub ip: String,
+    pub user_agent: String,
+    pub created_at: String,
 }
 
 #[derive(serde::Serialize, Clone)]
@@ -298,7 +428,7 @@ pub struct ProjectFilePageCursor {
     pub id: i64,
 }
 
-#[derive(Debug, serde::Serialize, Clone)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
 pub struct KnowledgeFile {
     pub id: i64,
     pub workspace_id: i64,
@@ -327,6 +457,28 @@ pub struct KnowledgeRepo {
     pub created_at: String,
 }
 
+/// Per-workspace retention windows. `None` (or 0) keeps that category forever.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct RetentionPolicy {
+    pub workspace_id: i64,
+    pub chat_messages_days: Option<i64>,
+    pub task_outputs_days: Option<i64>,
+    pub project_files_days_after_close: Option<i64>,
+    pub updated_by: Option<i64>,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LegalHoldRow {
+    pub id: i64,
+    pub project_id: i64,
+    pub reason: String,
+    pub created_by: Option<i64>,
+    pub created_at: String,
+    pub released_by: Option<i64>,
+    pub released_at: Option<String>,
+}
+
 #[derive(Debug, serde::Serialize, Clone)]
 pub struct AuditEvent {
     pub id: i64,
@@ -564,13 +716,39 @@ fn push_theme_term(out: &mut Vec<ThemeTerm>, term: String, occurrences: i64, doc
     });
 }
 
+const SHARE_LINK_COLUMNS: &str = "id, project_id, token, label, expires_at, created_by, revoked, \
+     created_at, password_hash, scope, document_ids, max_uses, view_count, download_count";
+
+fn row_to_share_link(row: &pg::Row<'_>) -> pg::Result<ProjectShareLinkRow> {
+    let revoked_int: i64 = row.get(6)?;
+    let password_hash: String = row.get(8)?;
+    let document_ids: String = row.get(10)?;
+    Ok(ProjectShareLinkRow {
+        id: row.get(0)?,
+        project_id: row.get(1)?,
+        token: row.get(2)?,
+        label: row.get(3)?,
+        expires_at: row.get(4)?,
+        created_by: row.get(5)?,
+        revoked: revoked_int != 0,
+        created_at: row.get(7)?,
+        password_protected: !password_hash.is_empty(),
+        password_hash,
+        scope: row.get(9)?,
+        document_ids: serde_json::from_str(&document_ids).unwrap_or_default(),
+        max_uses: row.get(11)?,
+        view_count: row.get(12)?,
+        download_count: row.get(13)?,
+    })
+}
+
 fn row_to_cloud_connection(row: &pg::Row<'_>) -> pg::Result<CloudConnection> {
     Ok(CloudConnection {
         id: row.get(0)?,
         project_id: row.get(1)?,
         provider: row.get(2)?,
-        access_token: row.get(3)?,
-        refresh_token: row.get(4)?,
+        access_token: Db::decrypt_secret(&row.get::<_, String>(3)?),
+        refresh_token: Db::decrypt_secret(&row.get::<_, String>(4)?),
         token_expiry: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
         account_email: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
         account_id: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
@@ -687,6 +865,55 @@ fn row_to_task_output(row: &pg::Row<'_>) -> pg::Result<TaskOutput> {
     })
 }
 
+fn row_to_phase_resource_usage(row: &pg::Row<'_>) -> pg::Result<PhaseResourceUsage> {
+    let created_at_str: String = row.get(8)?;
+    Ok(PhaseResourceUsage {
+        task_output_id: row.get(0)?,
+        task_id: row.get(1)?,
+        phase: row.get(2)?,
+        usage: crate::resources::ResourceUsage {
+            wall_ms: row.get(3)?,
+            cpu_ms: row.get(4)?,
+            peak_memory_bytes: row.get(5)?,
+            disk_written_bytes: row.get(6)?,
+            source: row.get(7)?,
+        },
+        created_at: parse_ts(&created_at_str),
+    })
+}
+
+fn row_to_task_test_failure(row: &pg::Row<'_>) -> pg::Result<TaskTestFailure> {
+    let line: i64 = row.get(6)?;
+    let created_at_str: String = row.get(9)?;
+    Ok(TaskTestFailure {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        phase: row.get(2)?,
+        attempt: row.get(3)?,
+        failure: crate::test_report::TestFailure {
+            name: row.get(4)?,
+            file: row.get(5)?,
+            line: u32::try_from(line).unwrap_or(0),
+            message: row.get(7)?,
+            stack: row.get(8)?,
+        },
+        created_at: parse_ts(&created_at_str),
+    })
+}
+
+fn row_to_test_flakiness(row: &pg::Row<'_>) -> pg::Result<TestFlakiness> {
+    Ok(TestFlakiness {
+        repo_path: row.get(0)?,
+        test_name: row.get(1)?,
+        passes: row.get(2)?,
+        failures: row.get(3)?,
+        flaky_runs: row.get(4)?,
+        last_status: row.get(5)?,
+        quarantined_at: row.get(6)?,
+        updated_at: row.get(7)?,
+    })
+}
+
 fn row_to_task_message(row: &pg::Row<'_>) -> pg::Result<TaskMessage> {
     let created_at_str: String = row.get(4)?;
     Ok(TaskMessage {
@@ -711,6 +938,10 @@ fn row_to_repo(row: &pg::Row<'_>) -> pg::Result<RepoRow> {
         prompt_file: row.get(6)?,
         auto_merge: auto_merge_int != 0,
         repo_slug: row.get(8).unwrap_or_default(),
+        container_image: row.get(9).unwrap_or_default(),
+        container_file: row.get(10).unwrap_or_default(),
+        forge: row.get(11).unwrap_or_default(),
+        forge_url: row.get(12).unwrap_or_default(),
     })
 }
 
@@ -742,6 +973,17 @@ fn row_to_legacy_event(row: &pg::Row<'_>) -> pg::Result<LegacyEvent> {
     })
 }
 
+fn row_to_retention_policy(row: &pg::Row<'_>) -> pg::Result<RetentionPolicy> {
+    Ok(RetentionPolicy {
+        workspace_id: row.get(0)?,
+        chat_messages_days: row.get(1)?,
+        task_outputs_days: row.get(2)?,
+        project_files_days_after_close: row.get(3)?,
+        updated_by: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
 const PROJECT_COLS: &str = "id, workspace_id, name, mode, repo_path, client_name, case_number, jurisdiction, \
     matter_type, opposing_counsel, deadline, privilege_level, status, default_template_id, created_at, session_privileged";
 
@@ -829,22 +1071,155 @@ impl Db {
     pub fn open(database_url: &str) -> Result<Self> {
         let conn = Connection::open(database_url)
             .with_context(|| format!("failed to open Postgres database at {database_url:?}"))?;
-        Ok(Self {
-            conn: Mutex::new(conn),
-        })
+        Ok(Self { pool: conn })
+    }
+
+    fn session(&self) -> ConnectionGuard {
+        self.pool.session()
+    }
+
+    /// Checks out a pooled client for async queries and explicit
+    /// transactions.
+    pub async fn client(&self) -> Result<pg::AsyncSession> {
+        self.pool.client().await.context("postgres pool checkout")
+    }
+
+    pub fn pool_status(&self) -> pg::PoolStatus {
+        self.pool.status()
     }
 
+    /// Applies every pending migration, then runs data backfills.
     pub fn migrate(&mut self) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        conn.execute_batch(SCHEMA_SQL)
-            .context("failed to apply clean-break Postgres schema")?;
+        self.migrate_up(None, false)?;
+        let conn = self.session();
         Self::backfill_workspaces(&conn).context("workspace backfill")?;
         Ok(())
     }
 
+    fn ensure_migrations_table(conn: &ConnectionGuard) -> Result<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (\
+               version BIGINT PRIMARY KEY, \
+               name TEXT NOT NULL, \
+               checksum TEXT NOT NULL, \
+               applied_at TEXT NOT NULL DEFAULT (to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS'))\
+             )",
+        )
+        .context("create schema_migrations")?;
+        Ok(())
+    }
+
+    pub fn applied_migrations(&self) -> Result<Vec<migrations::AppliedMigration>> {
+        let conn = self.session();
+        Self::ensure_migrations_table(&conn)?;
+        Self::load_applied_migrations(&conn)
+    }
+
+    fn load_applied_migrations(
+        conn: &ConnectionGuard,
+    ) -> Result<Vec<migrations::AppliedMigration>> {
+        let mut stmt = conn.prepare(
+            "SELECT version, name, checksum, applied_at FROM schema_migrations ORDER BY version",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(migrations::AppliedMigration {
+                    version: row.get(0)?,
+                    name: row.get(1)?,
+                    checksum: row.get(2)?,
+                    applied_at: row.get(3)?,
+                })
+            })?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("load schema_migrations")?;
+        Ok(rows)
+    }
+
+    pub fn migration_status(&self) -> Result<Vec<migrations::MigrationStatus>> {
+        let applied = self.applied_migrations()?;
+        Ok(migrations::status(migrations::MIGRATIONS, &applied))
+    }
+
+    /// Applies pending migrations up to `target` (all when `None`). With
+    /// `dry_run` the plan is returned without touching the schema.
+    pub fn migrate_up(
+        &self,
+        target: Option<i64>,
+        dry_run: bool,
+    ) -> Result<Vec<migrations::MigrationStep>> {
+        self.migrate_up_with(migrations::MIGRATIONS, target, dry_run)
+    }
+
+    /// Reverts applied migrations newer than `target`, newest first.
+    pub fn migrate_down(
+        &self,
+        target: i64,
+        dry_run: bool,
+    ) -> Result<Vec<migrations::MigrationStep>> {
+        self.migrate_down_with(migrations::MIGRATIONS, target, dry_run)
+    }
+
+    pub fn migrate_up_with(
+        &self,
+        set: &[migrations::Migration],
+        target: Option<i64>,
+        dry_run: bool,
+    ) -> Result<Vec<migrations::MigrationStep>> {
+        self.run_migration_plan(dry_run, |applied| migrations::plan_up(set, applied, target))
+    }
+
+    pub fn migrate_down_with(
+        &self,
+        set: &[migrations::Migration],
+        target: i64,
+        dry_run: bool,
+    ) -> Result<Vec<migrations::MigrationStep>> {
+        self.run_migration_plan(dry_run, |applied| {
+            migrations::plan_down(set, applied, target)
+        })
+    }
+
+    /// Plans and runs migrations under a session advisory lock so concurrent
+    /// instances starting against the same database apply each step once.
+    fn run_migration_plan(
+        &self,
+        dry_run: bool,
+        plan: impl FnOnce(&[migrations::AppliedMigration]) -> Result<Vec<migrations::MigrationStep>>,
+    ) -> Result<Vec<migrations::MigrationStep>> {
+        let conn = self.session();
+        Self::ensure_migrations_table(&conn)?;
+        conn.execute_batch(&format!("SELECT pg_advisory_lock({MIGRATION_LOCK_KEY})"))
+            .context("acquire migration lock")?;
+        let result = (|| {
+            let applied = Self::load_applied_migrations(&conn)?;
+            let steps = plan(&applied)?;
+            if dry_run {
+                return Ok(steps);
+            }
+            for step in &steps {
+                let label = format!("{:04}_{}", step.version, step.name);
+                let tx = conn.transaction()?;
+                conn.execute_batch(&step.sql)
+                    .with_context(|| format!("migration {label} ({:?})", step.direction))?;
+                match step.direction {
+                    migrations::Direction::Up => tx.execute(
+                        "INSERT INTO schema_migrations (version, name, checksum) VALUES (?1, ?2, ?3)",
+                        params![step.version, step.name, step.checksum],
+                    )?,
+                    migrations::Direction::Down => tx.execute(
+                        "DELETE FROM schema_migrations WHERE version = ?1",
+                        params![step.version],
+                    )?,
+                };
+                tx.commit()?;
+                tracing::info!("migration {label} applied ({:?})", step.direction);
+            }
+            Ok(steps)
+        })();
+        let _ = conn.execute_batch(&format!("SELECT pg_advisory_unlock({MIGRATION_LOCK_KEY})"));
+        result
+    }
+
     fn get_or_create_workspace(
         conn: &ConnectionGuard,
         name: &str,
@@ -988,10 +1363,7 @@ impl Db {
     // ── Pipeline Tasks ────────────────────────────────────────────────────
 
     pub fn get_task(&self, id: i64) -> Result<Option<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 &format!("SELECT {TASK_COLS} FROM pipeline_tasks WHERE id = ?1"),
@@ -1004,10 +1376,7 @@ impl Db {
     }
 
     pub fn list_active_tasks(&self) -> Result<Vec<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = format!(
             "SELECT {TASK_COLS} FROM pipeline_tasks \
              WHERE status NOT IN ('done', 'merged', 'failed', 'blocked', 'pending_review', 'human_review', 'purged') \
@@ -1031,10 +1400,13 @@ impl Db {
     }
 
     pub fn insert_task(&self, task: &Task) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        self.insert_stacked_task(task, 0)
+    }
+
+    /// Insert a task already stacked on `base_task_id` (0 = unstacked), so
+    /// dispatch never sees it without its base.
+    pub fn insert_stacked_task(&self, task: &Task, base_task_id: i64) -> Result<i64> {
+        let conn = self.session();
         let created_at = task.created_at.format("%Y-%m-%d %H:%M:%S").to_string();
         let project_id = if task.project_id == 0 {
             None
@@ -1063,8 +1435,8 @@ impl Db {
             "INSERT INTO pipeline_tasks \
              (title, description, repo_path, branch, status, attempt, max_attempts, \
               last_error, created_by, notify_chat, created_at, session_id, mode, backend, workspace_id, project_id, task_type, \
-              requires_exhaustive_corpus_review, chat_thread) \
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+              requires_exhaustive_corpus_review, chat_thread, base_task_id) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
             params![
                 task.title,
                 task.description,
@@ -1093,6 +1465,7 @@ impl Db {
                     0i64
                 },
                 &task.chat_thread,
+                base_task_id,
             ],
         )
         .context("insert_task")?;
@@ -1100,10 +1473,7 @@ impl Db {
     }
 
     pub fn update_task_status(&self, id: i64, status: &str, error: Option<&str>) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let updated_at = now_str();
         conn.execute(
             "UPDATE pipeline_tasks SET status = ?1, last_error = COALESCE(?2, last_error), \
@@ -1115,10 +1485,7 @@ impl Db {
     }
 
     pub fn mark_task_started(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let now = now_str();
         conn.execute(
             "UPDATE pipeline_tasks SET started_at = COALESCE(started_at, ?1) WHERE id = ?2",
@@ -1129,10 +1496,7 @@ impl Db {
     }
 
     pub fn mark_task_completed(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let now = now_str();
         conn.execute(
             "UPDATE pipeline_tasks SET completed_at = ?1, \
@@ -1147,10 +1511,7 @@ impl Db {
     }
 
     pub fn set_review_status(&self, id: i64, status: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET review_status = ?1, updated_at = ?2 WHERE id = ?3",
             params![status, now_str(), id],
@@ -1160,10 +1521,7 @@ impl Db {
     }
 
     pub fn request_task_revision(&self, id: i64, target_phase: &str, feedback: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let tx = conn
             .transaction()
             .context("request_task_revision transaction")?;
@@ -1186,10 +1544,7 @@ impl Db {
     }
 
     pub fn increment_revision_count(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET revision_count = revision_count + 1, updated_at = ?1 WHERE id = ?2",
             params![now_str(), id],
@@ -1199,7 +1554,7 @@ impl Db {
     }
 
     pub fn get_task_revision_count(&self, id: i64) -> i64 {
-        let Ok(conn) = self.conn.lock() else { return 0 };
+        let conn = self.session();
         conn.query_row(
             "SELECT revision_count FROM pipeline_tasks WHERE id = ?1",
             params![id],
@@ -1209,10 +1564,7 @@ impl Db {
     }
 
     pub fn update_task_branch(&self, id: i64, branch: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET branch = ?1 WHERE id = ?2",
             params![branch, id],
@@ -1222,10 +1574,7 @@ impl Db {
     }
 
     pub fn update_task_repo_path(&self, id: i64, repo_path: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET repo_path = ?1 WHERE id = ?2",
             params![repo_path, id],
@@ -1235,10 +1584,7 @@ impl Db {
     }
 
     pub fn update_task_session(&self, id: i64, session_id: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET session_id = ?1 WHERE id = ?2",
             params![session_id, id],
@@ -1248,10 +1594,7 @@ impl Db {
     }
 
     pub fn update_task_description(&self, id: i64, title: &str, description: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET title = ?1, description = ?2 WHERE id = ?3",
             params![title, description, id],
@@ -1261,10 +1604,7 @@ impl Db {
     }
 
     pub fn requeue_task(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let updated_at = now_str();
         conn.execute(
             "UPDATE pipeline_tasks SET status = 'backlog', attempt = 0, \
@@ -1276,10 +1616,7 @@ impl Db {
     }
 
     pub fn increment_attempt(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET attempt = attempt + 1 WHERE id = ?1",
             params![id],
@@ -1288,11 +1625,71 @@ impl Db {
         Ok(())
     }
 
+    /// Task-level egress allowlist (comma-separated domains, empty = none).
+    pub fn get_task_egress_allowlist(&self, id: i64) -> Result<String> {
+        let conn = self.session();
+        let list: Option<String> = conn
+            .query_row(
+                "SELECT egress_allowlist FROM pipeline_tasks WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("get_task_egress_allowlist")?;
+        Ok(list.unwrap_or_default())
+    }
+
+    pub fn set_task_egress_allowlist(&self, id: i64, domains: &[String]) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "UPDATE pipeline_tasks SET egress_allowlist = ?1 WHERE id = ?2",
+            params![domains.join(","), id],
+        )
+        .context("set_task_egress_allowlist")?;
+        Ok(())
+    }
+
+    /// Task whose branch this task is stacked on (0 = none).
+    pub fn get_task_base(&self, id: i64) -> Result<i64> {
+        let conn = self.session();
+        let base: Option<i64> = conn
+            .query_row(
+                "SELECT base_task_id FROM pipeline_tasks WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("get_task_base")?;
+        Ok(base.unwrap_or_default())
+    }
+
+    pub fn set_task_base(&self, id: i64, base_task_id: i64) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "UPDATE pipeline_tasks SET base_task_id = ?1 WHERE id = ?2",
+            params![base_task_id, id],
+        )
+        .context("set_task_base")?;
+        Ok(())
+    }
+
+    /// Tasks stacked on another task, as `(task_id, base_task_id)` pairs.
+    pub fn list_stacked_tasks(&self) -> Result<Vec<(i64, i64)>> {
+        let conn = self.session();
+        let mut stmt = conn.prepare(
+            "SELECT id, base_task_id FROM pipeline_tasks \
+             WHERE base_task_id <> 0 AND status NOT IN ('merged', 'failed', 'purged') \
+             ORDER BY id ASC",
+        )?;
+        let pairs = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("list_stacked_tasks")?;
+        Ok(pairs)
+    }
+
     pub fn update_task_backend(&self, id: i64, backend: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET backend = ?1 WHERE id = ?2",
             params![
@@ -1309,10 +1706,7 @@ impl Db {
     }
 
     pub fn update_task_structured_data(&self, id: i64, data: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET structured_data = ?1 WHERE id = ?2",
             params![data, id],
@@ -1322,10 +1716,7 @@ impl Db {
     }
 
     pub fn get_task_structured_data(&self, id: i64) -> Result<String> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let data: String = conn
             .query_row(
                 "SELECT structured_data FROM pipeline_tasks WHERE id = ?1",
@@ -1339,10 +1730,7 @@ impl Db {
     // ── Proposals ─────────────────────────────────────────────────────────
 
     pub fn list_proposals(&self, repo_path: &str) -> Result<Vec<Proposal>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, repo_path, title, description, rationale, status, created_at, \
              triage_score, triage_impact, triage_feasibility, triage_risk, triage_effort, \
@@ -1357,10 +1745,7 @@ impl Db {
     }
 
     pub fn list_all_proposals(&self, repo_path: Option<&str>) -> Result<Vec<Proposal>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = if repo_path.is_some() {
             "SELECT id, repo_path, title, description, rationale, status, created_at, \
              triage_score, triage_impact, triage_feasibility, triage_risk, triage_effort, \
@@ -1388,10 +1773,7 @@ impl Db {
     }
 
     pub fn get_proposal(&self, id: i64) -> Result<Option<Proposal>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 "SELECT id, repo_path, title, description, rationale, status, created_at, \
@@ -1407,10 +1789,7 @@ impl Db {
     }
 
     pub fn task_stats(&self) -> Result<(i64, i64, i64, i64)> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let total: i64 = conn
             .query_row("SELECT COUNT(*) FROM pipeline_tasks", [], |r| r.get(0))
             .context("task_stats total")?;
@@ -1439,7 +1818,7 @@ impl Db {
     }
 
     pub fn count_tasks_with_status(&self, status: &str) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let n: i64 = conn
             .query_row(
                 "SELECT COUNT(*) FROM pipeline_tasks WHERE status = ?1",
@@ -1450,17 +1829,18 @@ impl Db {
         Ok(n)
     }
 
-    pub fn project_task_status_counts(&self, project_id: i64) -> Result<ProjectTaskCounts> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
-        let mut stmt = conn.prepare(
-            "SELECT status, COUNT(*) FROM pipeline_tasks WHERE project_id = ?1 GROUP BY status",
-        )?;
+    pub async fn project_task_status_counts(&self, project_id: i64) -> Result<ProjectTaskCounts> {
+        let client = self.client().await?;
+        let rows = client
+            .query_map(
+                "SELECT status, COUNT(*) FROM pipeline_tasks WHERE project_id = ?1 GROUP BY status",
+                params![project_id],
+                |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)),
+            )
+            .await
+            .context("project_task_status_counts")?;
         let mut counts = ProjectTaskCounts::default();
-        let rows = stmt.query_map(params![project_id], |r| {
-            Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?))
-        })?;
-        for row in rows {
-            let (status, n) = row?;
+        for (status, n) in rows {
             match status.as_str() {
                 "running" | "backlog" => counts.active += n,
                 "human_review" => counts.review += n,
@@ -1474,7 +1854,7 @@ impl Db {
     }
 
     pub fn count_queue_with_status(&self, status: &str) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let n: i64 = conn
             .query_row(
                 "SELECT COUNT(*) FROM integration_queue WHERE status = ?1",
@@ -1486,10 +1866,7 @@ impl Db {
     }
 
     pub fn insert_proposal(&self, proposal: &Proposal) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let created_at = proposal.created_at.format("%Y-%m-%d %H:%M:%S").to_string();
         let id = conn
             .execute_returning_id(
@@ -1518,10 +1895,7 @@ impl Db {
     }
 
     pub fn update_proposal_status(&self, id: i64, status: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE proposals SET status = ?1 WHERE id = ?2",
             params![status, id],
@@ -1540,10 +1914,7 @@ impl Db {
         effort: i64,
         reasoning: &str,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE proposals SET triage_score=?1, triage_impact=?2, triage_feasibility=?3, \
              triage_risk=?4, triage_effort=?5, triage_reasoning=?6 WHERE id=?7",
@@ -1556,10 +1927,7 @@ impl Db {
     // ── Projects ──────────────────────────────────────────────────────────
 
     pub fn list_projects(&self) -> Result<Vec<ProjectRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = format!("SELECT {PROJECT_COLS} FROM projects ORDER BY id DESC");
         let mut stmt = conn.prepare(&sql)?;
         let projects = stmt
@@ -1569,26 +1937,19 @@ impl Db {
         Ok(projects)
     }
 
-    pub fn list_projects_in_workspace(&self, workspace_id: i64) -> Result<Vec<ProjectRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+    pub async fn list_projects_in_workspace(&self, workspace_id: i64) -> Result<Vec<ProjectRow>> {
+        let client = self.client().await?;
         let sql =
             format!("SELECT {PROJECT_COLS} FROM projects WHERE workspace_id = ?1 ORDER BY id DESC");
-        let mut stmt = conn.prepare(&sql)?;
-        let projects = stmt
-            .query_map(params![workspace_id], row_to_project)?
-            .collect::<pg::Result<Vec<_>>>()
+        let projects = client
+            .query_map(&sql, params![workspace_id], row_to_project)
+            .await
             .context("list_projects_in_workspace")?;
         Ok(projects)
     }
 
     pub fn search_projects(&self, query: &str) -> Result<Vec<ProjectRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let pattern = format!("%{query}%");
         let sql = format!(
             "SELECT {PROJECT_COLS} FROM projects \
@@ -1609,10 +1970,7 @@ impl Db {
         workspace_id: i64,
         query: &str,
     ) -> Result<Vec<ProjectRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let pattern = format!("%{query}%");
         let sql = format!(
             "SELECT {PROJECT_COLS} FROM projects \
@@ -1629,10 +1987,7 @@ impl Db {
     }
 
     pub fn get_project(&self, id: i64) -> Result<Option<ProjectRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = format!("SELECT {PROJECT_COLS} FROM projects WHERE id=?1");
         let project = conn
             .query_row(&sql, params![id], row_to_project)
@@ -1646,10 +2001,7 @@ impl Db {
         workspace_id: i64,
         id: i64,
     ) -> Result<Option<ProjectRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = format!("SELECT {PROJECT_COLS} FROM projects WHERE id=?1 AND workspace_id = ?2");
         let project = conn
             .query_row(&sql, params![id, workspace_id], row_to_project)
@@ -1669,10 +2021,7 @@ impl Db {
         matter_type: &str,
         privilege_level: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let created_at = now_str();
         let id = conn.execute_returning_id(
             "INSERT INTO projects (name, mode, repo_path, client_name, jurisdiction, matter_type, \
@@ -1708,10 +2057,7 @@ impl Db {
         repo_path: Option<&str>,
         default_template_id: Option<Option<i64>>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut sets = Vec::new();
         let mut vals: Vec<Box<dyn pg::ToSql>> = Vec::new();
         let mut idx = 1;
@@ -1735,6 +2081,17 @@ impl Db {
         maybe_set!(status, "status");
         maybe_set!(repo_path, "repo_path");
 
+        // Retention windows for closed projects count from closed_at.
+        match status {
+            Some("closed") => {
+                sets.push(format!("closed_at = COALESCE(closed_at, ?{idx})"));
+                vals.push(Box::new(now_str()));
+                idx += 1;
+            },
+            Some(_) => sets.push("closed_at = NULL".to_string()),
+            None => {},
+        }
+
         if let Some(dl) = deadline {
             sets.push(format!("deadline = ?{}", idx));
             vals.push(Box::new(dl.map(|s| s.to_string())));
@@ -1764,10 +2121,7 @@ impl Db {
     }
 
     pub fn delete_project(&self, id: i64) -> Result<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let tx = conn.transaction().context("delete_project transaction")?;
 
         tx.execute("DELETE FROM embeddings WHERE project_id=?1", params![id])
@@ -1826,10 +2180,7 @@ impl Db {
         role: &str,
         granted_by: i64,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let created_at = now_str();
         let id = conn
             .execute_returning_id(
@@ -1843,10 +2194,7 @@ impl Db {
     }
 
     pub fn remove_project_share(&self, project_id: i64, user_id: i64) -> Result<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let affected = conn
             .execute(
                 "DELETE FROM project_shares WHERE project_id = ?1 AND user_id = ?2",
@@ -1857,10 +2205,7 @@ impl Db {
     }
 
     pub fn list_project_shares(&self, project_id: i64) -> Result<Vec<ProjectShareRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT ps.id, ps.project_id, ps.user_id, ps.role, ps.granted_by, \
                     u.username, u.display_name, ps.created_at \
@@ -1890,10 +2235,7 @@ impl Db {
         project_id: i64,
         user_id: i64,
     ) -> Result<Option<ProjectShareRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let row = conn
             .query_row(
                 "SELECT ps.id, ps.project_id, ps.user_id, ps.role, ps.granted_by, \
@@ -1920,10 +2262,7 @@ impl Db {
     }
 
     pub fn list_user_shared_projects(&self, user_id: i64) -> Result<Vec<(ProjectRow, String)>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql =
             "SELECT p.id, p.workspace_id, p.name, p.mode, p.repo_path, p.client_name, \
              p.case_number, p.jurisdiction, p.matter_type, p.opposing_counsel, p.deadline, \
@@ -1945,10 +2284,7 @@ impl Db {
     }
 
     pub fn list_projects_shared_with_user(&self, user_id: i64) -> Result<Vec<SharedProjectRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = "SELECT p.id, p.workspace_id, p.name, p.mode, p.repo_path, p.client_name, \
              p.case_number, p.jurisdiction, p.matter_type, p.opposing_counsel, p.deadline, \
              p.privilege_level, p.status, p.default_template_id, p.created_at, p.session_privileged, \
@@ -1996,18 +2332,37 @@ impl Db {
         label: &str,
         expires_at: &str,
         created_by: i64,
+        policy: &ShareLinkPolicy,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let created_at = now_str();
-        let id = conn.execute_returning_id(
-            "INSERT INTO project_share_links (project_id, token, label, expires_at, created_by, created_at) \
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![project_id, token, label, expires_at, created_by, created_at],
-        )
-        .context("create_project_share_link")?;
+        let scope = if policy.scope.is_empty() {
+            "full"
+        } else {
+            policy.scope.as_str()
+        };
+        let document_ids =
+            serde_json::to_string(&policy.document_ids).unwrap_or_else(|_| "[]".into());
+        let id = conn
+            .execute_returning_id(
+                "INSERT INTO project_share_links \
+                (project_id, token, label, expires_at, created_by, created_at, \
+                 password_hash, scope, document_ids, max_uses) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    project_id,
+                    token,
+                    label,
+                    expires_at,
+                    created_by,
+                    created_at,
+                    policy.password_hash,
+                    scope,
+                    document_ids,
+                    policy.max_uses
+                ],
+            )
+            .context("create_project_share_link")?;
         Ok(id)
     }
 
@@ -2015,67 +2370,110 @@ impl Db {
         &self,
         token: &str,
     ) -> Result<Option<ProjectShareLinkRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let row = conn
             .query_row(
-                "SELECT id, project_id, token, label, expires_at, created_by, revoked, created_at \
-                 FROM project_share_links WHERE token = ?1 AND revoked = 0",
+                &format!("SELECT {SHARE_LINK_COLUMNS} FROM project_share_links WHERE token = ?1 AND revoked = 0"),
                 params![token],
-                |row| {
-                    let revoked_int: i64 = row.get(6)?;
-                    Ok(ProjectShareLinkRow {
-                        id: row.get(0)?,
-                        project_id: row.get(1)?,
-                        token: row.get(2)?,
-                        label: row.get(3)?,
-                        expires_at: row.get(4)?,
-                        created_by: row.get(5)?,
-                        revoked: revoked_int != 0,
-                        created_at: row.get(7)?,
-                    })
-                },
+                row_to_share_link,
             )
             .optional()
             .context("get_project_share_link_by_token")?;
         Ok(row)
     }
 
+    pub fn get_project_share_link(&self, id: i64) -> Result<Option<ProjectShareLinkRow>> {
+        let conn = self.session();
+        let row = conn
+            .query_row(
+                &format!("SELECT {SHARE_LINK_COLUMNS} FROM project_share_links WHERE id = ?1"),
+                params![id],
+                row_to_share_link,
+            )
+            .optional()
+            .context("get_project_share_link")?;
+        Ok(row)
+    }
+
     pub fn list_project_share_links(&self, project_id: i64) -> Result<Vec<ProjectShareLinkRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SHARE_LINK_COLUMNS} FROM project_share_links \
+             WHERE project_id = ?1 ORDER BY created_at DESC"
+        ))?;
+        let rows = stmt
+            .query_map(params![project_id], row_to_share_link)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("list_project_share_links")?;
+        Ok(rows)
+    }
+
+    /// Count one use of a share link against its `max_uses` cap. Returns false
+    /// (and counts nothing) once the cap is exhausted.
+    pub fn consume_share_link_use(&self, id: i64, download: bool) -> Result<bool> {
+        let conn = self.session();
+        let (views, downloads) = if download { (0i64, 1i64) } else { (1, 0) };
+        let affected = conn
+            .execute(
+                "UPDATE project_share_links \
+                 SET view_count = view_count + ?2, download_count = download_count + ?3 \
+                 WHERE id = ?1 AND (max_uses = 0 OR view_count + download_count < max_uses)",
+                params![id, views, downloads],
+            )
+            .context("consume_share_link_use")?;
+        Ok(affected > 0)
+    }
+
+    pub fn log_share_link_access(
+        &self,
+        link_id: i64,
+        action: &str,
+        file_id: Option<i64>,
+        status: i64,
+        ip: &str,
+        user_agent: &str,
+    ) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "INSERT INTO project_share_link_access \
+                (link_id, action, file_id, status, ip, user_agent, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![link_id, action, file_id, status, ip, user_agent, now_str()],
+        )
+        .context("log_share_link_access")?;
+        Ok(())
+    }
+
+    pub fn list_share_link_access(
+        &self,
+        link_id: i64,
+        limit: i64,
+    ) -> Result<Vec<ShareLinkAccessRow>> {
+        let conn = self.session();
         let mut stmt = conn.prepare(
-            "SELECT id, project_id, token, label, expires_at, created_by, revoked, created_at \
-             FROM project_share_links WHERE project_id = ?1 ORDER BY created_at DESC",
+            "SELECT id, link_id, action, file_id, status, ip, user_agent, created_at \
+             FROM project_share_link_access WHERE link_id = ?1 ORDER BY id DESC LIMIT ?2",
         )?;
         let rows = stmt
-            .query_map(params![project_id], |row| {
-                let revoked_int: i64 = row.get(6)?;
-                Ok(ProjectShareLinkRow {
+            .query_map(params![link_id, limit], |row| {
+                Ok(ShareLinkAccessRow {
                     id: row.get(0)?,
-                    project_id: row.get(1)?,
-                    token: row.get(2)?,
-                    label: row.get(3)?,
-                    expires_at: row.get(4)?,
-                    created_by: row.get(5)?,
-                    revoked: revoked_int != 0,
+                    link_id: row.get(1)?,
+                    action: row.get(2)?,
+                    file_id: row.get(3)?,
+                    status: row.get(4)?,
+                    ip: row.get(5)?,
+                    user_agent: row.get(6)?,
                     created_at: row.get(7)?,
                 })
             })?
             .collect::<pg::Result<Vec<_>>>()
-            .context("list_project_share_links")?;
+            .context("list_share_link_access")?;
         Ok(rows)
     }
 
     pub fn revoke_project_share_link(&self, id: i64) -> Result<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let affected = conn
             .execute(
                 "UPDATE project_share_links SET revoked = 1 WHERE id = ?1",
@@ -2095,10 +2493,7 @@ impl Db {
         title: &str,
         content: &str,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         // Delete existing entry for this task+file, then re-insert
         conn.execute(
             "DELETE FROM legal_fts WHERE task_id = ?1 AND file_path = ?2",
@@ -2112,10 +2507,7 @@ impl Db {
     }
 
     pub fn fts_remove_task(&self, task_id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute("DELETE FROM legal_fts WHERE task_id = ?1", params![task_id])?;
         Ok(())
     }
@@ -2126,10 +2518,7 @@ impl Db {
         project_id: Option<i64>,
         limit: i64,
     ) -> Result<Vec<FtsResult>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = if project_id.is_some() {
             "SELECT project_id, task_id, file_path, \
                     left(title, 240) as title_snip, \
@@ -2178,33 +2567,26 @@ impl Db {
         Ok(results)
     }
 
-    pub fn list_project_tasks(&self, project_id: i64) -> Result<Vec<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+    pub async fn list_project_tasks(&self, project_id: i64) -> Result<Vec<Task>> {
+        let client = self.client().await?;
         let sql = format!(
             "SELECT {TASK_COLS} FROM pipeline_tasks WHERE project_id = ?1 ORDER BY id DESC"
         );
-        let mut stmt = conn.prepare(&sql)?;
-        let tasks = stmt
-            .query_map(params![project_id], row_to_task)?
-            .collect::<pg::Result<Vec<_>>>()
+        let tasks = client
+            .query_map(&sql, params![project_id], row_to_task)
+            .await
             .context("list_project_tasks")?;
         Ok(tasks)
     }
 
-    pub fn list_project_files(&self, project_id: i64) -> Result<Vec<ProjectFileRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        let mut stmt = conn.prepare(&format!(
+    pub async fn list_project_files(&self, project_id: i64) -> Result<Vec<ProjectFileRow>> {
+        let client = self.client().await?;
+        let sql = format!(
             "SELECT {PROJECT_FILE_COLS} FROM project_files WHERE project_id=?1 ORDER BY id ASC"
-        ))?;
-        let files = stmt
-            .query_map(params![project_id], row_to_project_file)?
-            .collect::<pg::Result<Vec<_>>>()
+        );
+        let files = client
+            .query_map(&sql, params![project_id], row_to_project_file)
+            .await
             .context("list_project_files")?;
         Ok(files)
     }
@@ -2219,10 +2601,7 @@ impl Db {
         has_text: Option<bool>,
         privileged_only: Option<bool>,
     ) -> Result<(Vec<ProjectFileMetaRow>, i64)> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let trimmed_query = query.map(str::trim).filter(|q| !q.is_empty());
         let mut base_where = vec!["project_id = ?".to_string()];
         let mut base_params: Vec<Box<dyn pg::types::ToSql>> = vec![Box::new(project_id)];
@@ -2323,10 +2702,7 @@ impl Db {
         query: &str,
         limit: i64,
     ) -> Result<Vec<ProjectFileMetaRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let q = query.trim().to_lowercase();
         if q.is_empty() {
             return Ok(Vec::new());
@@ -2353,10 +2729,7 @@ impl Db {
         project_id: i64,
         file_id: i64,
     ) -> Result<Option<ProjectFileRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             &format!("SELECT {PROJECT_FILE_COLS} FROM project_files WHERE id=?1 AND project_id=?2"),
             params![file_id, project_id],
@@ -2367,10 +2740,7 @@ impl Db {
     }
 
     pub fn delete_project_file(&self, project_id: i64, file_id: i64) -> Result<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let n = conn
             .execute(
                 "DELETE FROM project_files WHERE id = ?1 AND project_id = ?2",
@@ -2381,10 +2751,7 @@ impl Db {
     }
 
     pub fn delete_all_project_files(&self, project_id: i64) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let tx = conn
             .transaction()
             .context("delete_all_project_files transaction")?;
@@ -2418,10 +2785,7 @@ impl Db {
         project_id: i64,
         source_path: &str,
     ) -> Result<Option<ProjectFileRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             &format!(
                 "SELECT {PROJECT_FILE_COLS} FROM project_files \
@@ -2445,10 +2809,7 @@ impl Db {
         content_hash: &str,
         privileged: bool,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let created_at = now_str();
         let id = conn.execute_returning_id(
             "INSERT INTO project_files \
@@ -2494,10 +2855,7 @@ impl Db {
     }
 
     pub fn is_session_privileged(&self, project_id: i64) -> Result<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let priv_int: i64 = conn
             .query_row(
                 "SELECT session_privileged FROM projects WHERE id = ?1",
@@ -2509,10 +2867,7 @@ impl Db {
     }
 
     pub fn set_session_privileged(&self, project_id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE projects SET session_privileged = 1 WHERE id = ?1",
             params![project_id],
@@ -2529,7 +2884,7 @@ impl Db {
         if content_hash.trim().is_empty() {
             return Ok(None);
         }
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         conn.query_row(
             &format!("SELECT {PROJECT_FILE_COLS} FROM project_files WHERE project_id=?1 AND content_hash=?2 ORDER BY id ASC LIMIT 1"),
             params![project_id, content_hash],
@@ -2540,10 +2895,7 @@ impl Db {
     }
 
     pub fn update_project_file_text(&self, file_id: i64, text: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let (project_id, old_chars): (i64, i64) = conn.query_row(
             "SELECT project_id, COALESCE(length(extracted_text), 0)::bigint FROM project_files WHERE id = ?1",
             params![file_id],
@@ -2578,10 +2930,7 @@ impl Db {
     }
 
     pub fn get_project_file_stats(&self, project_id: i64) -> Result<ProjectFileStats> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let stats = conn
             .query_row(
                 "SELECT project_id, total_files, total_bytes, privileged_files, text_files, text_chars, updated_at \
@@ -2618,7 +2967,7 @@ impl Db {
         is_zip: bool,
         privileged: bool,
     ) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let now = now_str();
         let id = conn.execute_returning_id(
             "INSERT INTO upload_sessions \
@@ -2641,7 +2990,7 @@ impl Db {
     }
 
     pub fn get_upload_session(&self, session_id: i64) -> Result<Option<UploadSession>> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         conn.query_row(
             "SELECT id, project_id, file_name, mime_type, file_size, chunk_size, total_chunks, \
                     uploaded_bytes, is_zip, privileged, status, stored_path, error, created_at, updated_at \
@@ -2658,7 +3007,7 @@ impl Db {
         project_id: Option<i64>,
         limit: i64,
     ) -> Result<Vec<UploadSession>> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let lim = limit.clamp(1, 500);
         let sql = if project_id.is_some() {
             "SELECT id, project_id, file_name, mime_type, file_size, chunk_size, total_chunks, uploaded_bytes, \
@@ -2686,7 +3035,7 @@ impl Db {
         &self,
         project_id: Option<i64>,
     ) -> Result<HashMap<String, i64>> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let sql = if project_id.is_some() {
             "SELECT status, COUNT(*) FROM upload_sessions WHERE project_id=?1 GROUP BY status"
         } else {
@@ -2717,7 +3066,7 @@ impl Db {
     }
 
     pub fn count_active_upload_sessions(&self, project_id: i64) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let count = conn
             .query_row(
                 "SELECT COUNT(*) FROM upload_sessions \
@@ -2730,7 +3079,7 @@ impl Db {
     }
 
     pub fn list_uploaded_chunks(&self, session_id: i64) -> Result<Vec<i64>> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT chunk_index FROM upload_session_chunks WHERE session_id=?1 ORDER BY chunk_index ASC",
         )?;
@@ -2747,7 +3096,7 @@ impl Db {
         chunk_index: i64,
         size_bytes: i64,
     ) -> Result<()> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let now = now_str();
         conn.execute(
             "INSERT INTO upload_session_chunks (session_id, chunk_index, size_bytes, created_at) \
@@ -2774,7 +3123,7 @@ impl Db {
         stored_path: Option<&str>,
         error: Option<&str>,
     ) -> Result<()> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         conn.execute(
             "UPDATE upload_sessions \
              SET status = ?1, \
@@ -2795,7 +3144,7 @@ impl Db {
         min_document_count: i64,
     ) -> Result<ThemeSummary> {
         const MAX_THEME_DOCUMENTS: i64 = 5_000;
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let mut keyword_counts: HashMap<String, i64> = HashMap::new();
         let mut keyword_docs: HashMap<String, i64> = HashMap::new();
         let mut phrase_counts: HashMap<String, i64> = HashMap::new();
@@ -2897,7 +3246,7 @@ impl Db {
         min_document_count: i64,
     ) -> Result<ThemeSummary> {
         const MAX_THEME_DOCUMENTS: i64 = 5_000;
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let mut keyword_counts: HashMap<String, i64> = HashMap::new();
         let mut keyword_docs: HashMap<String, i64> = HashMap::new();
         let mut phrase_counts: HashMap<String, i64> = HashMap::new();
@@ -2996,7 +3345,9 @@ impl Db {
         account_email: &str,
         account_id: &str,
     ) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
+        let access_token = Self::encrypt_secret(access_token)?;
+        let refresh_token = Self::encrypt_secret(refresh_token)?;
         let id = conn.execute_returning_id(
             "INSERT INTO cloud_connections \
              (project_id, provider, access_token, refresh_token, token_expiry, account_email, account_id, created_at) \
@@ -3008,7 +3359,7 @@ impl Db {
     }
 
     pub fn list_cloud_connections(&self, project_id: i64) -> Result<Vec<CloudConnection>> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, project_id, provider, access_token, refresh_token, token_expiry, \
                     account_email, account_id, created_at \
@@ -3023,7 +3374,7 @@ impl Db {
     }
 
     pub fn get_cloud_connection(&self, id: i64) -> Result<Option<CloudConnection>> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         conn.query_row(
             "SELECT id, project_id, provider, access_token, refresh_token, token_expiry, \
                     account_email, account_id, created_at \
@@ -3042,7 +3393,9 @@ impl Db {
         refresh_token: &str,
         token_expiry: &str,
     ) -> Result<()> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
+        let access_token = Self::encrypt_secret(access_token)?;
+        let refresh_token = Self::encrypt_secret(refresh_token)?;
         conn.execute(
             "UPDATE cloud_connections SET access_token=?1, refresh_token=?2, token_expiry=?3 WHERE id=?4",
             params![access_token, refresh_token, token_expiry, id],
@@ -3051,7 +3404,7 @@ impl Db {
     }
 
     pub fn delete_cloud_connection(&self, id: i64) -> Result<()> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         conn.execute("DELETE FROM cloud_connections WHERE id=?1", params![id])
             .context("delete_cloud_connection")?;
         Ok(())
@@ -3060,7 +3413,7 @@ impl Db {
     // ── Knowledge files ───────────────────────────────────────────────────
 
     pub fn total_knowledge_file_bytes(&self) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let total = conn
             .query_row(
                 "SELECT COALESCE(SUM(size_bytes), 0)::bigint FROM knowledge_files",
@@ -3072,7 +3425,7 @@ impl Db {
     }
 
     pub fn total_knowledge_file_bytes_in_workspace(&self, workspace_id: i64) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let total = conn
             .query_row(
                 "SELECT COALESCE(SUM(size_bytes), 0)::bigint FROM knowledge_files WHERE workspace_id = ?1",
@@ -3084,10 +3437,7 @@ impl Db {
     }
 
     pub fn list_knowledge_files(&self) -> Result<Vec<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, workspace_id, file_name, description, size_bytes, \"inline\", created_at, \
                     tags, category, jurisdiction, project_id, user_id \
@@ -3105,10 +3455,7 @@ impl Db {
         &self,
         workspace_id: i64,
     ) -> Result<Vec<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, workspace_id, file_name, description, size_bytes, \"inline\", created_at, \
                     tags, category, jurisdiction, project_id, user_id \
@@ -3130,10 +3477,7 @@ impl Db {
         limit: i64,
         offset: i64,
     ) -> Result<(Vec<KnowledgeFile>, i64)> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut where_clauses = vec!["1=1".to_string()];
         let mut params_vec: Vec<Box<dyn pg::types::ToSql>> = Vec::new();
 
@@ -3196,10 +3540,7 @@ impl Db {
         limit: i64,
         offset: i64,
     ) -> Result<(Vec<KnowledgeFile>, i64)> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut where_clauses = vec![
             "workspace_id = ?".to_string(),
             "user_id IS NULL".to_string(),
@@ -3264,10 +3605,7 @@ impl Db {
         jurisdiction: Option<&str>,
         limit: i64,
     ) -> Result<Vec<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut where_clauses = vec!["workspace_id = ?".to_string()];
         let mut params_vec: Vec<Box<dyn pg::types::ToSql>> = vec![Box::new(workspace_id)];
         if let Some(q) = query.map(str::trim).filter(|q| !q.is_empty()) {
@@ -3305,10 +3643,7 @@ impl Db {
     }
 
     pub fn get_knowledge_file(&self, id: i64) -> Result<Option<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             "SELECT id, workspace_id, file_name, description, size_bytes, \"inline\", created_at, \
                     tags, category, jurisdiction, project_id, user_id \
@@ -3325,10 +3660,7 @@ impl Db {
         workspace_id: i64,
         id: i64,
     ) -> Result<Option<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             "SELECT id, workspace_id, file_name, description, size_bytes, \"inline\", created_at, \
                     tags, category, jurisdiction, project_id, user_id \
@@ -3345,10 +3677,7 @@ impl Db {
         category: Option<&str>,
         jurisdiction: Option<&str>,
     ) -> Result<Vec<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut where_clauses = Vec::new();
         let mut params_vec: Vec<Box<dyn pg::types::ToSql>> = Vec::new();
         if let Some(category) = category.map(str::trim).filter(|c| !c.is_empty()) {
@@ -3387,10 +3716,7 @@ impl Db {
         category: Option<&str>,
         jurisdiction: Option<&str>,
     ) -> Result<Vec<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut where_clauses = vec!["workspace_id = ?".to_string()];
         let mut params_vec: Vec<Box<dyn pg::types::ToSql>> = vec![Box::new(workspace_id)];
         if let Some(category) = category.map(str::trim).filter(|c| !c.is_empty()) {
@@ -3446,10 +3772,7 @@ impl Db {
         size_bytes: i64,
         inline: bool,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let id = conn.execute_returning_id(
             "INSERT INTO knowledge_files (workspace_id, user_id, file_name, description, size_bytes, \"inline\") \
              VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
@@ -3459,19 +3782,13 @@ impl Db {
     }
 
     pub fn delete_knowledge_file(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute("DELETE FROM knowledge_files WHERE id=?1", params![id])?;
         Ok(())
     }
 
     pub fn delete_knowledge_file_in_workspace(&self, workspace_id: i64, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "DELETE FROM knowledge_files WHERE id=?1 AND workspace_id = ?2",
             params![id, workspace_id],
@@ -3480,10 +3797,7 @@ impl Db {
     }
 
     pub fn delete_all_knowledge_files(&self) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let deleted = conn
             .execute("DELETE FROM knowledge_files", [])
             .context("delete_all_knowledge_files")?;
@@ -3491,10 +3805,7 @@ impl Db {
     }
 
     pub fn delete_all_knowledge_files_in_workspace(&self, workspace_id: i64) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let deleted = conn
             .execute(
                 "DELETE FROM knowledge_files WHERE workspace_id = ?1 AND user_id IS NULL",
@@ -3514,10 +3825,7 @@ impl Db {
         limit: i64,
         offset: i64,
     ) -> Result<(Vec<KnowledgeFile>, i64)> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut where_clauses = vec!["workspace_id = ?".to_string(), "user_id = ?".to_string()];
         let mut params_vec: Vec<Box<dyn pg::types::ToSql>> =
             vec![Box::new(workspace_id), Box::new(user_id)];
@@ -3566,10 +3874,7 @@ impl Db {
         workspace_id: i64,
         user_id: i64,
     ) -> Result<Vec<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, workspace_id, file_name, description, size_bytes, \"inline\", created_at, \
                     tags, category, jurisdiction, project_id, user_id \
@@ -3589,10 +3894,7 @@ impl Db {
         user_id: i64,
         id: i64,
     ) -> Result<Option<KnowledgeFile>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             "SELECT id, workspace_id, file_name, description, size_bytes, \"inline\", created_at, \
                     tags, category, jurisdiction, project_id, user_id \
@@ -3610,10 +3912,7 @@ impl Db {
         user_id: i64,
         id: i64,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "DELETE FROM knowledge_files WHERE id=?1 AND workspace_id = ?2 AND user_id = ?3",
             params![id, workspace_id, user_id],
@@ -3622,10 +3921,7 @@ impl Db {
     }
 
     pub fn delete_all_user_knowledge_files(&self, workspace_id: i64, user_id: i64) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let deleted = conn
             .execute(
                 "DELETE FROM knowledge_files WHERE workspace_id = ?1 AND user_id = ?2",
@@ -3636,7 +3932,7 @@ impl Db {
     }
 
     pub fn total_user_knowledge_bytes(&self, workspace_id: i64, user_id: i64) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let total = conn
             .query_row(
                 "SELECT COALESCE(SUM(size_bytes), 0)::bigint FROM knowledge_files WHERE workspace_id = ?1 AND user_id = ?2",
@@ -3656,10 +3952,7 @@ impl Db {
         category: Option<&str>,
         jurisdiction: Option<&str>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         if let Some(d) = description {
             conn.execute(
                 "UPDATE knowledge_files SET description=?1 WHERE id=?2",
@@ -3703,10 +3996,7 @@ impl Db {
         category: Option<&str>,
         jurisdiction: Option<&str>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         if let Some(d) = description {
             conn.execute(
                 "UPDATE knowledge_files SET description=?1 WHERE id=?2 AND workspace_id = ?3",
@@ -3750,10 +4040,7 @@ impl Db {
         file_path: &str,
         embedding: &[f32],
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let hash = crate::knowledge::hash_chunk(chunk_text);
         let blob = crate::knowledge::embedding_to_bytes(embedding);
         conn.execute(
@@ -3767,10 +4054,7 @@ impl Db {
     }
 
     pub fn remove_task_embeddings(&self, task_id: i64) -> Result<usize> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let n = conn
             .execute(
                 "DELETE FROM embeddings WHERE task_id = ?1",
@@ -3780,31 +4064,18 @@ impl Db {
         Ok(n)
     }
 
-    pub fn search_embeddings(
+    /// Brute-force cosine scan over stored embeddings. Rows are fetched on an
+    /// async pooled client and scored on the blocking pool, so a large table
+    /// never ties up a runtime worker.
+    pub async fn search_embeddings(
         &self,
         query_embedding: &[f32],
         limit: usize,
         project_id: Option<i64>,
     ) -> Result<Vec<crate::knowledge::EmbeddingSearchResult>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let client = self.client().await?;
         let cap = limit.clamp(1, 5000);
-        let (sql, params_vec): (String, Vec<Box<dyn pg::types::ToSql>>) = match project_id {
-            Some(pid) => (
-                "SELECT id, project_id, task_id, chunk_text, file_path, embedding FROM embeddings WHERE project_id = ?1".to_string(),
-                vec![Box::new(pid) as Box<dyn pg::types::ToSql>],
-            ),
-            None => (
-                "SELECT id, project_id, task_id, chunk_text, file_path, embedding FROM embeddings".to_string(),
-                vec![],
-            ),
-        };
-        let mut stmt = conn.prepare(&sql)?;
-        let params_refs: Vec<&dyn pg::types::ToSql> =
-            params_vec.iter().map(|p| p.as_ref()).collect();
-        let rows = stmt.query_map(params_refs.as_slice(), |row: &pg::Row| {
+        let map_row = |row: &pg::Row| {
             Ok((
                 row.get::<_, Option<i64>>(1)?,
                 row.get::<_, Option<i64>>(2)?,
@@ -3812,38 +4083,66 @@ impl Db {
                 row.get::<_, String>(4)?,
                 row.get::<_, Vec<u8>>(5)?,
             ))
-        })?;
-
-        let mut results = Vec::with_capacity(cap.min(128));
-        let flush_at = cap.saturating_mul(4).max(cap + 8);
-        for row in rows {
-            let (pid, tid, text, path, blob) = row.context("search_embeddings row")?;
-            let emb = crate::knowledge::bytes_to_embedding(&blob);
-            let score = crate::knowledge::cosine_similarity(query_embedding, &emb);
-            results.push(crate::knowledge::EmbeddingSearchResult {
-                chunk_text: text,
-                file_path: path,
-                project_id: pid,
-                task_id: tid,
-                score,
-            });
-            if results.len() >= flush_at {
-                results.sort_by(|a, b| {
-                    b.score
-                        .partial_cmp(&a.score)
-                        .unwrap_or(std::cmp::Ordering::Equal)
+        };
+        let rows = match project_id {
+            Some(pid) => {
+                client
+                    .query_map(
+                        "SELECT id, project_id, task_id, chunk_text, file_path, embedding FROM embeddings WHERE project_id = ?1",
+                        params![pid],
+                        map_row,
+                    )
+                    .await
+            },
+            None => {
+                client
+                    .query_map(
+                        "SELECT id, project_id, task_id, chunk_text, file_path, embedding FROM embeddings",
+                        [],
+                        map_row,
+                    )
+                    .await
+            },
+        }
+        .context("search_embeddings")?;
+        drop(client);
+
+        // Scoring is CPU-bound, so it runs on the blocking pool rather than
+        // holding up the runtime worker that awaited the rows.
+        let query = query_embedding.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let mut results = Vec::with_capacity(cap.min(128));
+            let flush_at = cap.saturating_mul(4).max(cap + 8);
+            for (pid, tid, text, path, blob) in rows {
+                let emb = crate::knowledge::bytes_to_embedding(&blob);
+                let score = crate::knowledge::cosine_similarity(&query, &emb);
+                results.push(crate::knowledge::EmbeddingSearchResult {
+                    chunk_text: text,
+                    file_path: path,
+                    project_id: pid,
+                    task_id: tid,
+                    score,
                 });
-                results.truncate(cap);
+                if results.len() >= flush_at {
+                    results.sort_by(|a, b| {
+                        b.score
+                            .partial_cmp(&a.score)
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    });
+                    results.truncate(cap);
+                }
             }
-        }
 
-        results.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
-        results.truncate(cap);
-        Ok(results)
+            results.sort_by(|a, b| {
+                b.score
+                    .partial_cmp(&a.score)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            results.truncate(cap);
+            results
+        })
+        .await
+        .context("search_embeddings scoring")
     }
 
     pub fn list_recent_project_files(
@@ -3852,10 +4151,7 @@ impl Db {
         limit: i64,
         require_text: bool,
     ) -> Result<Vec<ProjectFileRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let lim = limit.clamp(1, 100);
         let sql = if require_text {
             format!(
@@ -3884,10 +4180,7 @@ impl Db {
         project_id: i64,
         limit: i64,
     ) -> Result<Vec<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let lim = limit.clamp(1, 50);
         let sql = format!(
             "SELECT {TASK_COLS} FROM pipeline_tasks \
@@ -3903,7 +4196,7 @@ impl Db {
     }
 
     pub fn embedding_count(&self) -> i64 {
-        let Ok(conn) = self.conn.lock() else { return 0 };
+        let conn = self.session();
         conn.query_row("SELECT COUNT(*) FROM embeddings", [], |r: &pg::Row| {
             r.get(0)
         })
@@ -3922,10 +4215,7 @@ impl Db {
         treatment: &str,
         checked_at: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let id = conn.execute_returning_id(
             "INSERT INTO citation_verifications (task_id, citation_text, citation_type, status, source, treatment, checked_at) \
              VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
@@ -3935,10 +4225,7 @@ impl Db {
     }
 
     pub fn get_task_citations(&self, task_id: i64) -> Result<Vec<CitationVerification>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, citation_text, citation_type, status, source, treatment, checked_at, created_at \
              FROM citation_verifications WHERE task_id = ?1 ORDER BY id"
@@ -3963,10 +4250,7 @@ impl Db {
     }
 
     pub fn delete_task_citations(&self, task_id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "DELETE FROM citation_verifications WHERE task_id = ?1",
             params![task_id],
@@ -3975,10 +4259,7 @@ impl Db {
     }
 
     pub fn get_top_scored_proposals(&self, threshold: i64, limit: i64) -> Result<Vec<Proposal>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, repo_path, title, description, rationale, status, created_at, \
              triage_score, triage_impact, triage_feasibility, triage_risk, triage_effort, \
@@ -3994,7 +4275,7 @@ impl Db {
     }
 
     pub fn count_unscored_proposals(&self) -> i64 {
-        let Ok(conn) = self.conn.lock() else { return 0 };
+        let conn = self.session();
         conn.query_row(
             "SELECT COUNT(*) FROM proposals WHERE status='proposed' AND triage_score=0",
             [],
@@ -4004,10 +4285,7 @@ impl Db {
     }
 
     pub fn list_untriaged_proposals(&self) -> Result<Vec<Proposal>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, repo_path, title, description, rationale, status, created_at, \
              triage_score, triage_impact, triage_feasibility, triage_risk, triage_effort, \
@@ -4023,18 +4301,16 @@ impl Db {
 
     // ── Merge Queue ───────────────────────────────────────────────────────
 
-    pub fn list_queue(&self) -> Result<Vec<QueueEntry>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        let mut stmt = conn.prepare(
-            "SELECT id, task_id, branch, repo_path, status, queued_at, pr_number \
-             FROM integration_queue WHERE status = 'queued' ORDER BY id ASC",
-        )?;
-        let entries = stmt
-            .query_map([], row_to_queue_entry)?
-            .collect::<pg::Result<Vec<_>>>()
+    pub async fn list_queue(&self) -> Result<Vec<QueueEntry>> {
+        let client = self.client().await?;
+        let entries = client
+            .query_map(
+                "SELECT id, task_id, branch, repo_path, status, queued_at, pr_number \
+                 FROM integration_queue WHERE status = 'queued' ORDER BY id ASC",
+                [],
+                row_to_queue_entry,
+            )
+            .await
             .context("list_queue")?;
         Ok(entries)
     }
@@ -4046,10 +4322,7 @@ impl Db {
         repo_path: &str,
         pr_number: i64,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let queued_at = now_str();
         let id = conn.execute_returning_id(
             "INSERT INTO integration_queue (task_id, branch, repo_path, status, queued_at, pr_number) \
@@ -4072,7 +4345,7 @@ impl Db {
         repo_path: &str,
         pr_number: i64,
     ) -> Result<i64> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let existing: Option<i64> = conn
             .query_row(
                 "SELECT id FROM integration_queue \
@@ -4120,10 +4393,7 @@ impl Db {
         status: &str,
         error_msg: &str,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE integration_queue SET status = ?1, error_msg = ?2 WHERE id = ?3",
             params![status, error_msg, id],
@@ -4133,10 +4403,7 @@ impl Db {
     }
 
     pub fn get_queued_branches_for_repo(&self, repo_path: &str) -> Result<Vec<QueueEntry>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, branch, repo_path, status, queued_at, pr_number \
              FROM integration_queue WHERE repo_path = ?1 AND status = 'queued' ORDER BY task_id ASC",
@@ -4148,11 +4415,51 @@ impl Db {
         Ok(entries)
     }
 
+    /// Queued entries for a watched repo, including those recorded against
+    /// its task worktrees (`<repo>/.worktrees/task-N`).
+    pub fn get_queued_branches_under_repo(&self, repo_path: &str) -> Result<Vec<QueueEntry>> {
+        let conn = self.session();
+        let prefix = format!("{repo_path}/.worktrees/");
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, branch, repo_path, status, queued_at, pr_number \
+             FROM integration_queue WHERE (repo_path = ?1 OR repo_path LIKE ?2) AND status = 'queued' \
+             ORDER BY task_id ASC",
+        )?;
+        let entries = stmt
+            .query_map(params![repo_path, format!("{prefix}%")], row_to_queue_entry)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("get_queued_branches_under_repo")?;
+        Ok(entries
+            .into_iter()
+            .filter(|e| e.repo_path == repo_path || e.repo_path.starts_with(&prefix))
+            .collect())
+    }
+
+    /// Entries whose PR is open and may still receive review: `queued` or
+    /// `pending_review`, for a watched repo and its task worktrees.
+    pub fn get_reviewable_queue_entries_under_repo(
+        &self,
+        repo_path: &str,
+    ) -> Result<Vec<QueueEntry>> {
+        let conn = self.session();
+        let prefix = format!("{repo_path}/.worktrees/");
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, branch, repo_path, status, queued_at, pr_number \
+             FROM integration_queue WHERE (repo_path = ?1 OR repo_path LIKE ?2) \
+             AND status IN ('queued', 'pending_review') ORDER BY task_id ASC",
+        )?;
+        let entries = stmt
+            .query_map(params![repo_path, format!("{prefix}%")], row_to_queue_entry)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("get_reviewable_queue_entries_under_repo")?;
+        Ok(entries
+            .into_iter()
+            .filter(|e| e.repo_path == repo_path || e.repo_path.starts_with(&prefix))
+            .collect())
+    }
+
     pub fn get_queue_entries_for_task(&self, task_id: i64) -> Result<Vec<QueueEntry>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, branch, repo_path, status, queued_at, pr_number \
              FROM integration_queue WHERE task_id = ?1 ORDER BY id ASC",
@@ -4165,7 +4472,7 @@ impl Db {
     }
 
     pub fn get_unknown_retries(&self, id: i64) -> i64 {
-        let Ok(conn) = self.conn.lock() else { return 0 };
+        let conn = self.session();
         conn.query_row(
             "SELECT unknown_retries FROM integration_queue WHERE id = ?1",
             params![id],
@@ -4175,10 +4482,7 @@ impl Db {
     }
 
     pub fn increment_unknown_retries(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE integration_queue SET unknown_retries = unknown_retries + 1 WHERE id = ?1",
             params![id],
@@ -4188,10 +4492,7 @@ impl Db {
     }
 
     pub fn reset_unknown_retries(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE integration_queue SET unknown_retries = 0 WHERE id = ?1",
             params![id],
@@ -4210,10 +4511,7 @@ impl Db {
         raw_stream: &str,
         exit_code: i64,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let created_at = now_str();
         let id = conn.execute_returning_id(
             "INSERT INTO task_outputs (task_id, phase, output, raw_stream, exit_code, created_at) \
@@ -4225,10 +4523,7 @@ impl Db {
     }
 
     pub fn purge_task_data(&self, task_id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
 
         // Delete vector embeddings
         conn.execute(
@@ -4248,10 +4543,7 @@ impl Db {
     }
 
     pub fn get_task_outputs(&self, task_id: i64) -> Result<Vec<TaskOutput>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, phase, output, raw_stream, exit_code, created_at \
              FROM task_outputs WHERE task_id = ?1 ORDER BY id ASC",
@@ -4263,13 +4555,100 @@ impl Db {
         Ok(outputs)
     }
 
+    pub fn insert_task_output_resources(
+        &self,
+        task_output_id: i64,
+        task_id: i64,
+        phase: &str,
+        usage: &crate::resources::ResourceUsage,
+    ) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "INSERT INTO task_output_resources \
+             (task_output_id, task_id, phase, wall_ms, cpu_ms, peak_memory_bytes, \
+              disk_written_bytes, source, created_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                task_output_id,
+                task_id,
+                phase,
+                usage.wall_ms,
+                usage.cpu_ms,
+                usage.peak_memory_bytes,
+                usage.disk_written_bytes,
+                usage.source,
+                now_str()
+            ],
+        )
+        .context("insert_task_output_resources")?;
+        Ok(())
+    }
+
+    pub fn get_task_resource_usage(&self, task_id: i64) -> Result<Vec<PhaseResourceUsage>> {
+        let conn = self.session();
+        let mut stmt = conn.prepare(
+            "SELECT task_output_id, task_id, phase, wall_ms, cpu_ms, peak_memory_bytes, \
+             disk_written_bytes, source, created_at \
+             FROM task_output_resources WHERE task_id = ?1 ORDER BY task_output_id ASC",
+        )?;
+        let rows = stmt
+            .query_map(params![task_id], row_to_phase_resource_usage)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("get_task_resource_usage")?;
+        Ok(rows)
+    }
+
+    pub fn insert_test_failures(
+        &self,
+        task_id: i64,
+        phase: &str,
+        attempt: i64,
+        failures: &[crate::test_report::TestFailure],
+    ) -> Result<()> {
+        let conn = self.session();
+        let created_at = now_str();
+        for f in failures {
+            conn.execute(
+                "INSERT INTO task_test_failures \
+                 (task_id, phase, attempt, test_name, file, line, message, stack, created_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    task_id,
+                    phase,
+                    attempt,
+                    f.name,
+                    f.file,
+                    f.line as i64,
+                    f.message,
+                    f.stack,
+                    created_at
+                ],
+            )
+            .context("insert_test_failures")?;
+        }
+        Ok(())
+    }
+
+    /// Failures from the latest recorded attempt of each phase.
+    pub fn get_task_test_failures(&self, task_id: i64) -> Result<Vec<TaskTestFailure>> {
+        let conn = self.session();
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, phase, attempt, test_name, file, line, message, stack, created_at \
+             FROM task_test_failures f WHERE task_id = ?1 AND attempt = \
+             (SELECT MAX(attempt) FROM task_test_failures WHERE task_id = f.task_id AND phase = f.phase) \
+             ORDER BY id ASC",
+        )?;
+        let rows = stmt
+            .query_map(params![task_id], row_to_task_test_failure)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("get_task_test_failures")?;
+        Ok(rows)
+    }
+
     // ── Task Messages ─────────────────────────────────────────────────────
 
     pub fn insert_task_message(&self, task_id: i64, role: &str, content: &str) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let created_at = now_str();
         let id = conn
             .execute_returning_id(
@@ -4281,27 +4660,22 @@ impl Db {
         Ok(id)
     }
 
-    pub fn get_task_messages(&self, task_id: i64) -> Result<Vec<TaskMessage>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        let mut stmt = conn.prepare(
-            "SELECT id, task_id, role, content, created_at, delivered_phase \
-             FROM task_messages WHERE task_id = ?1 ORDER BY id ASC",
-        )?;
-        let messages = stmt
-            .query_map(params![task_id], row_to_task_message)?
-            .collect::<pg::Result<Vec<_>>>()
+    pub async fn get_task_messages(&self, task_id: i64) -> Result<Vec<TaskMessage>> {
+        let client = self.client().await?;
+        let messages = client
+            .query_map(
+                "SELECT id, task_id, role, content, created_at, delivered_phase \
+                 FROM task_messages WHERE task_id = ?1 ORDER BY id ASC",
+                params![task_id],
+                row_to_task_message,
+            )
+            .await
             .context("get_task_messages")?;
         Ok(messages)
     }
 
     pub fn get_pending_task_messages(&self, task_id: i64) -> Result<Vec<TaskMessage>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, role, content, created_at, delivered_phase \
              FROM task_messages WHERE task_id = ?1 AND delivered_phase IS NULL ORDER BY id ASC",
@@ -4314,10 +4688,7 @@ impl Db {
     }
 
     pub fn mark_messages_delivered(&self, task_id: i64, phase: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE task_messages SET delivered_phase = ?1 \
              WHERE task_id = ?2 AND delivered_phase IS NULL",
@@ -4327,6 +4698,200 @@ impl Db {
         Ok(())
     }
 
+    // ── PR Review Comments ────────────────────────────────────────────────
+
+    /// Forge comment ids already turned into revision feedback for a task.
+    pub fn seen_pr_review_comment_ids(&self, task_id: i64) -> Result<HashSet<String>> {
+        let conn = self.session();
+        let mut stmt =
+            conn.prepare("SELECT comment_id FROM pr_review_comments WHERE task_id = ?1")?;
+        let ids = stmt
+            .query_map(params![task_id], |r| r.get::<_, String>(0))?
+            .collect::<pg::Result<HashSet<_>>>()
+            .context("seen_pr_review_comment_ids")?;
+        Ok(ids)
+    }
+
+    /// Record review comments fed into revision round `revision`.
+    pub fn record_pr_review_comments(
+        &self,
+        task_id: i64,
+        comments: &[(String, String)],
+        revision: i64,
+    ) -> Result<()> {
+        let conn = self.session();
+        for (comment_id, author) in comments {
+            conn.execute(
+                "INSERT INTO pr_review_comments (task_id, comment_id, author, revision, created_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT (task_id, comment_id) DO NOTHING",
+                params![task_id, comment_id, author, revision, now_str()],
+            )
+            .context("record_pr_review_comments")?;
+        }
+        Ok(())
+    }
+
+    /// Mark a PR's existing comments as seen without acting on them, on the
+    /// task's first review sync. An empty id is recorded too, so a PR that
+    /// had no comments yet also counts as synced.
+    pub fn seed_pr_review_comments(
+        &self,
+        task_id: i64,
+        comments: &[(String, String)],
+    ) -> Result<()> {
+        let conn = self.session();
+        let now = now_str();
+        let marker = (String::new(), String::new());
+        for (comment_id, author) in comments.iter().chain(std::iter::once(&marker)) {
+            conn.execute(
+                "INSERT INTO pr_review_comments (task_id, comment_id, author, revision, replied_at, created_at) \
+                 VALUES (?1, ?2, ?3, 0, ?4, ?4) ON CONFLICT (task_id, comment_id) DO NOTHING",
+                params![task_id, comment_id, author, now],
+            )
+            .context("seed_pr_review_comments")?;
+        }
+        Ok(())
+    }
+
+    /// Review comments addressed by a revision but not yet answered on the PR.
+    pub fn count_unreplied_pr_review_comments(&self, task_id: i64) -> Result<i64> {
+        let conn = self.session();
+        conn.query_row(
+            "SELECT COUNT(*) FROM pr_review_comments WHERE task_id = ?1 AND replied_at = ''",
+            params![task_id],
+            |r| r.get(0),
+        )
+        .context("count_unreplied_pr_review_comments")
+    }
+
+    pub fn mark_pr_review_comments_replied(&self, task_id: i64) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "UPDATE pr_review_comments SET replied_at = ?1 WHERE task_id = ?2 AND replied_at = ''",
+            params![now_str(), task_id],
+        )
+        .context("mark_pr_review_comments_replied")?;
+        Ok(())
+    }
+
+    // ── Test Flakiness ────────────────────────────────────────────────────
+
+    /// Add one run's outcome for each test to its history.
+    pub fn record_test_results(&self, repo_path: &str, results: &[(String, &str)]) -> Result<()> {
+        let conn = self.session();
+        for (test_name, status) in results {
+            let (passes, failures) = if *status == "passed" { (1, 0) } else { (0, 1) };
+            conn.execute(
+                "INSERT INTO test_flakiness (repo_path, test_name, passes, failures, last_status, updated_at) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6) ON CONFLICT (repo_path, test_name) DO UPDATE SET \
+                 passes = test_flakiness.passes + EXCLUDED.passes, \
+                 failures = test_flakiness.failures + EXCLUDED.failures, \
+                 last_status = EXCLUDED.last_status, updated_at = EXCLUDED.updated_at",
+                params![repo_path, test_name, passes, failures, *status, now_str()],
+            )
+            .context("record_test_results")?;
+        }
+        Ok(())
+    }
+
+    /// Note that `test_name` failed and then passed on a re-run, and
+    /// quarantine it once that has happened `quarantine_after` times.
+    /// Returns true if the test is now quarantined.
+    pub fn record_flaky_run(
+        &self,
+        repo_path: &str,
+        test_name: &str,
+        quarantine_after: i64,
+    ) -> Result<bool> {
+        let conn = self.session();
+        let now = now_str();
+        conn.execute(
+            "UPDATE test_flakiness SET flaky_runs = flaky_runs + 1, \
+             quarantined_at = CASE WHEN quarantined_at = '' AND flaky_runs + 1 >= ?1 THEN ?2 ELSE quarantined_at END \
+             WHERE repo_path = ?3 AND test_name = ?4",
+            params![quarantine_after, now, repo_path, test_name],
+        )
+        .context("record_flaky_run")?;
+        let quarantined: Option<String> = conn
+            .query_row(
+                "SELECT quarantined_at FROM test_flakiness WHERE repo_path = ?1 AND test_name = ?2",
+                params![repo_path, test_name],
+                |r| r.get(0),
+            )
+            .optional()
+            .context("record_flaky_run select")?;
+        Ok(quarantined.is_some_and(|q| !q.is_empty()))
+    }
+
+    pub fn get_test_flakiness(
+        &self,
+        repo_path: &str,
+        test_name: &str,
+    ) -> Result<Option<TestFlakiness>> {
+        let conn = self.session();
+        conn.query_row(
+            "SELECT repo_path, test_name, passes, failures, flaky_runs, last_status, quarantined_at, updated_at \
+             FROM test_flakiness WHERE repo_path = ?1 AND test_name = ?2",
+            params![repo_path, test_name],
+            row_to_test_flakiness,
+        )
+        .optional()
+        .context("get_test_flakiness")
+    }
+
+    pub fn quarantined_tests(&self, repo_path: &str) -> Result<Vec<String>> {
+        let conn = self.session();
+        let mut stmt = conn.prepare(
+            "SELECT test_name FROM test_flakiness \
+             WHERE repo_path = ?1 AND quarantined_at <> '' ORDER BY test_name",
+        )?;
+        let names = stmt
+            .query_map(params![repo_path], |r| r.get(0))?
+            .collect::<pg::Result<Vec<String>>>()
+            .context("quarantined_tests")?;
+        Ok(names)
+    }
+
+    /// Quarantine or release a test by hand. Quarantining a test with no
+    /// recorded runs creates its row.
+    pub fn set_test_quarantined(
+        &self,
+        repo_path: &str,
+        test_name: &str,
+        quarantined: bool,
+    ) -> Result<()> {
+        let conn = self.session();
+        let at = if quarantined {
+            now_str()
+        } else {
+            String::new()
+        };
+        conn.execute(
+            "INSERT INTO test_flakiness (repo_path, test_name, quarantined_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4) ON CONFLICT (repo_path, test_name) DO UPDATE SET \
+             quarantined_at = EXCLUDED.quarantined_at, updated_at = EXCLUDED.updated_at",
+            params![repo_path, test_name, at, now_str()],
+        )
+        .context("set_test_quarantined")?;
+        Ok(())
+    }
+
+    /// Tests with the most flaky re-runs, then the most failures. Empty
+    /// `repo_path` covers every repo.
+    pub fn list_flaky_tests(&self, repo_path: &str, limit: i64) -> Result<Vec<TestFlakiness>> {
+        let conn = self.session();
+        let mut stmt = conn.prepare(
+            "SELECT repo_path, test_name, passes, failures, flaky_runs, last_status, quarantined_at, updated_at \
+             FROM test_flakiness WHERE (?1 = '' OR repo_path = ?1) AND (flaky_runs > 0 OR quarantined_at <> '') \
+             ORDER BY flaky_runs DESC, failures DESC, test_name ASC LIMIT ?2",
+        )?;
+        let rows = stmt
+            .query_map(params![repo_path, limit], row_to_test_flakiness)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("list_flaky_tests")?;
+        Ok(rows)
+    }
+
     // ── Knowledge Repos ───────────────────────────────────────────────────
 
     fn row_to_knowledge_repo(row: &pg::Row<'_>) -> pg::Result<KnowledgeRepo> {
@@ -4348,10 +4913,7 @@ impl Db {
         workspace_id: i64,
         user_id: Option<i64>,
     ) -> Result<Vec<KnowledgeRepo>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = if user_id.is_some() {
             conn.prepare(
                 "SELECT id, workspace_id, user_id, url, name, local_path, status, error_msg, created_at \
@@ -4376,10 +4938,7 @@ impl Db {
     }
 
     pub fn list_all_knowledge_repos(&self) -> Result<Vec<KnowledgeRepo>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, workspace_id, user_id, url, name, local_path, status, error_msg, created_at \
              FROM knowledge_repos ORDER BY id ASC",
@@ -4399,10 +4958,7 @@ impl Db {
         url: &str,
         name: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         Ok(conn.execute_returning_id(
             "INSERT INTO knowledge_repos (workspace_id, user_id, url, name, status) VALUES (?1, ?2, ?3, ?4, 'pending')",
             params![workspace_id, user_id, url, name],
@@ -4416,10 +4972,7 @@ impl Db {
         local_path: &str,
         error_msg: &str,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE knowledge_repos SET status = ?1, local_path = ?2, error_msg = ?3 WHERE id = ?4",
             params![status, local_path, error_msg, id],
@@ -4428,10 +4981,7 @@ impl Db {
     }
 
     pub fn delete_knowledge_repo(&self, id: i64, workspace_id: i64) -> Result<String> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let local_path: Option<String> = conn
             .query_row(
                 "SELECT local_path FROM knowledge_repos WHERE id = ?1 AND workspace_id = ?2",
@@ -4459,10 +5009,7 @@ impl Db {
         backend: Option<&str>,
         repo_slug: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let auto_merge_int: i64 = if auto_merge { 1 } else { 0 };
         conn.execute(
             "INSERT INTO repos (path, name, mode, test_cmd, prompt_file, auto_merge, backend, repo_slug) \
@@ -4498,13 +5045,10 @@ impl Db {
     }
 
     pub fn list_repos(&self) -> Result<Vec<RepoRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
-            "SELECT id, path, name, mode, backend, test_cmd, prompt_file, auto_merge, repo_slug \
-             FROM repos ORDER BY id ASC",
+            "SELECT id, path, name, mode, backend, test_cmd, prompt_file, auto_merge, repo_slug, \
+             container_image, container_file, forge, forge_url FROM repos ORDER BY id ASC",
         )?;
         let repos = stmt
             .query_map([], row_to_repo)?
@@ -4514,14 +5058,11 @@ impl Db {
     }
 
     pub fn get_repo_by_path(&self, path: &str) -> Result<Option<RepoRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
-                "SELECT id, path, name, mode, backend, test_cmd, prompt_file, auto_merge, repo_slug \
-                 FROM repos WHERE path = ?1",
+                "SELECT id, path, name, mode, backend, test_cmd, prompt_file, auto_merge, repo_slug, \
+                 container_image, container_file, forge, forge_url FROM repos WHERE path = ?1",
                 params![path],
                 row_to_repo,
             )
@@ -4531,10 +5072,7 @@ impl Db {
     }
 
     pub fn update_repo_backend(&self, id: i64, backend: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE repos SET backend = ?1 WHERE id = ?2",
             params![
@@ -4550,6 +5088,26 @@ impl Db {
         Ok(())
     }
 
+    pub fn update_repo_container(&self, id: i64, image: &str, file: &str) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "UPDATE repos SET container_image = ?1, container_file = ?2 WHERE id = ?3",
+            params![image, file, id],
+        )
+        .context("update_repo_container")?;
+        Ok(())
+    }
+
+    pub fn update_repo_forge(&self, id: i64, forge: &str, forge_url: &str) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "UPDATE repos SET forge = ?1, forge_url = ?2 WHERE id = ?3",
+            params![forge, forge_url, id],
+        )
+        .context("update_repo_forge")?;
+        Ok(())
+    }
+
     // ── Pipeline Events ───────────────────────────────────────────────────
 
     pub fn log_event(
@@ -4571,10 +5129,7 @@ impl Db {
         kind: &str,
         payload: &serde_json::Value,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let payload_str = payload.to_string();
         let created_at = now_str();
         let id = conn.execute_returning_id(
@@ -4586,11 +5141,246 @@ impl Db {
         Ok(id)
     }
 
+    // ── Workspace chat backend ────────────────────────────────────────────
+
+    /// Backend name used for this workspace's chat; empty means the default.
+    pub fn get_workspace_chat_backend(&self, workspace_id: i64) -> Result<String> {
+        let conn = self.session();
+        let backend: Option<String> = conn
+            .query_row(
+                "SELECT chat_backend FROM workspaces WHERE id = ?1",
+                params![workspace_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("get_workspace_chat_backend")?;
+        Ok(backend.unwrap_or_default())
+    }
+
+    pub fn set_workspace_chat_backend(&self, workspace_id: i64, backend: &str) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "UPDATE workspaces SET chat_backend = ?1 WHERE id = ?2",
+            params![backend, workspace_id],
+        )
+        .context("set_workspace_chat_backend")?;
+        Ok(())
+    }
+
+    // ── Retention & legal holds ───────────────────────────────────────────
+
+    pub fn get_retention_policy(&self, workspace_id: i64) -> Result<Option<RetentionPolicy>> {
+        let conn = self.session();
+        conn.query_row(
+            "SELECT workspace_id, chat_messages_days, task_outputs_days, \
+             project_files_days_after_close, updated_by, updated_at \
+             FROM retention_policies WHERE workspace_id = ?1",
+            params![workspace_id],
+            row_to_retention_policy,
+        )
+        .optional()
+        .context("get_retention_policy")
+    }
+
+    pub fn list_retention_policies(&self) -> Result<Vec<RetentionPolicy>> {
+        let conn = self.session();
+        let mut stmt = conn.prepare(
+            "SELECT workspace_id, chat_messages_days, task_outputs_days, \
+             project_files_days_after_close, updated_by, updated_at \
+             FROM retention_policies ORDER BY workspace_id",
+        )?;
+        let rows = stmt
+            .query_map([], row_to_retention_policy)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("list_retention_policies")?;
+        Ok(rows)
+    }
+
+    pub fn upsert_retention_policy(&self, policy: &RetentionPolicy) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "INSERT INTO retention_policies \
+             (workspace_id, chat_messages_days, task_outputs_days, project_files_days_after_close, updated_by, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6) \
+             ON CONFLICT (workspace_id) DO UPDATE SET \
+               chat_messages_days = EXCLUDED.chat_messages_days, \
+               task_outputs_days = EXCLUDED.task_outputs_days, \
+               project_files_days_after_close = EXCLUDED.project_files_days_after_close, \
+               updated_by = EXCLUDED.updated_by, \
+               updated_at = EXCLUDED.updated_at",
+            params![
+                policy.workspace_id,
+                policy.chat_messages_days,
+                policy.task_outputs_days,
+                policy.project_files_days_after_close,
+                policy.updated_by,
+                now_str()
+            ],
+        )
+        .context("upsert_retention_policy")?;
+        Ok(())
+    }
+
+    pub fn create_legal_hold(
+        &self,
+        project_id: i64,
+        reason: &str,
+        created_by: Option<i64>,
+    ) -> Result<i64> {
+        let conn = self.session();
+        conn.execute_returning_id(
+            "INSERT INTO legal_holds (project_id, reason, created_by, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![project_id, reason, created_by, now_str()],
+        )
+        .context("create_legal_hold")
+    }
+
+    pub fn release_legal_hold(
+        &self,
+        project_id: i64,
+        hold_id: i64,
+        released_by: Option<i64>,
+    ) -> Result<bool> {
+        let conn = self.session();
+        let n = conn
+            .execute(
+                "UPDATE legal_holds SET released_at = ?1, released_by = ?2 \
+                 WHERE id = ?3 AND project_id = ?4 AND released_at IS NULL",
+                params![now_str(), released_by, hold_id, project_id],
+            )
+            .context("release_legal_hold")?;
+        Ok(n > 0)
+    }
+
+    pub fn list_legal_holds(&self, project_id: i64) -> Result<Vec<LegalHoldRow>> {
+        let conn = self.session();
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, reason, created_by, created_at, released_by, released_at \
+             FROM legal_holds WHERE project_id = ?1 ORDER BY id DESC",
+        )?;
+        let rows = stmt
+            .query_map(params![project_id], |row| {
+                Ok(LegalHoldRow {
+                    id: row.get(0)?,
+                    project_id: row.get(1)?,
+                    reason: row.get(2)?,
+                    created_by: row.get(3)?,
+                    created_at: row.get(4)?,
+                    released_by: row.get(5)?,
+                    released_at: row.get(6)?,
+                })
+            })?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("list_legal_holds")?;
+        Ok(rows)
+    }
+
+    pub fn project_has_legal_hold(&self, project_id: i64) -> Result<bool> {
+        let conn = self.session();
+        let held: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM legal_holds WHERE project_id = ?1 AND released_at IS NULL)",
+                params![project_id],
+                |row| row.get(0),
+            )
+            .context("project_has_legal_hold")?;
+        Ok(held)
+    }
+
+    /// Deletes workspace chat messages older than `cutoff`. Covers the
+    /// workspace's web threads (`chat_prefix*`) and its project chats, except
+    /// those of projects under an active legal hold.
+    pub fn purge_workspace_chat_messages(
+        &self,
+        workspace_id: i64,
+        chat_prefix: &str,
+        cutoff: &str,
+    ) -> Result<usize> {
+        let conn = self.session();
+        let n = conn
+            .execute(
+                "DELETE FROM messages m WHERE m.timestamp < ?2 \
+                 AND (m.chat_jid LIKE ?3 || '%' \
+                      OR m.chat_jid IN (SELECT 'project:' || p.id FROM projects p WHERE p.workspace_id = ?1)) \
+                 AND m.chat_jid NOT IN ( \
+                      SELECT 'project:' || h.project_id FROM legal_holds h WHERE h.released_at IS NULL \
+                      UNION ALL \
+                      SELECT ?3 || 'web:project-' || h.project_id FROM legal_holds h WHERE h.released_at IS NULL)",
+                params![workspace_id, cutoff, chat_prefix],
+            )
+            .context("purge_workspace_chat_messages")?;
+        Ok(n)
+    }
+
+    /// Finished tasks in the workspace last touched before `cutoff`, skipping
+    /// projects under an active legal hold and tasks already purged since
+    /// their last update.
+    pub fn list_tasks_for_retention(&self, workspace_id: i64, cutoff: &str) -> Result<Vec<Task>> {
+        let conn = self.session();
+        let sql = format!(
+            "SELECT {TASK_COLS} FROM pipeline_tasks \
+             WHERE workspace_id = ?1 AND updated_at < ?2 \
+             AND status IN ('done', 'merged', 'failed', 'purged') \
+             AND (retention_purged_at IS NULL OR retention_purged_at < updated_at) \
+             AND (project_id IS NULL OR project_id NOT IN \
+                  (SELECT project_id FROM legal_holds WHERE released_at IS NULL)) \
+             ORDER BY id"
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let tasks = stmt
+            .query_map(params![workspace_id, cutoff], row_to_task)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("list_tasks_for_retention")?;
+        Ok(tasks)
+    }
+
+    /// Record that retention has purged `task_id`, without bumping
+    /// `updated_at`.
+    pub fn mark_task_retention_purged(&self, task_id: i64) -> Result<()> {
+        let conn = self.session();
+        conn.execute(
+            "UPDATE pipeline_tasks SET retention_purged_at = ?2 WHERE id = ?1",
+            params![task_id, now_str()],
+        )
+        .context("mark_task_retention_purged")?;
+        Ok(())
+    }
+
+    pub fn delete_task_outputs_before(&self, task_id: i64, cutoff: &str) -> Result<usize> {
+        let conn = self.session();
+        let n = conn
+            .execute(
+                "DELETE FROM task_outputs WHERE task_id = ?1 AND created_at < ?2",
+                params![task_id, cutoff],
+            )
+            .context("delete_task_outputs_before")?;
+        Ok(n)
+    }
+
+    /// Closed projects whose closure predates `cutoff` and have no active hold.
+    pub fn list_closed_projects_for_retention(
+        &self,
+        workspace_id: i64,
+        cutoff: &str,
+    ) -> Result<Vec<i64>> {
+        let conn = self.session();
+        let mut stmt = conn.prepare(
+            "SELECT p.id FROM projects p \
+             WHERE p.workspace_id = ?1 AND p.status = 'closed' \
+             AND p.closed_at IS NOT NULL AND p.closed_at < ?2 \
+             AND NOT EXISTS (SELECT 1 FROM legal_holds h WHERE h.project_id = p.id AND h.released_at IS NULL) \
+             AND EXISTS (SELECT 1 FROM project_files f WHERE f.project_id = p.id) \
+             ORDER BY p.id",
+        )?;
+        let ids = stmt
+            .query_map(params![workspace_id, cutoff], |row| row.get(0))?
+            .collect::<pg::Result<Vec<i64>>>()
+            .context("list_closed_projects_for_retention")?;
+        Ok(ids)
+    }
+
     pub fn list_project_events(&self, project_id: i64, limit: i64) -> Result<Vec<AuditEvent>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, project_id, actor, kind, payload, created_at \
              FROM pipeline_events WHERE project_id = ?1 \
@@ -4615,7 +5405,7 @@ impl Db {
     }
 
     pub fn list_task_events(&self, task_id: i64, limit: i64) -> Result<Vec<AuditEvent>> {
-        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, project_id, actor, kind, payload, created_at \
              FROM pipeline_events WHERE task_id = ?1 \
@@ -4642,10 +5432,7 @@ impl Db {
     // ── Users ─────────────────────────────────────────────────────────────
 
     pub fn get_user_default_workspace_id(&self, user_id: i64) -> Result<Option<i64>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 "SELECT default_workspace_id FROM users WHERE id = ?1",
@@ -4658,10 +5445,7 @@ impl Db {
     }
 
     pub fn set_user_default_workspace_id(&self, user_id: i64, workspace_id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE users SET default_workspace_id = ?1 WHERE id = ?2",
             params![workspace_id, user_id],
@@ -4671,10 +5455,7 @@ impl Db {
     }
 
     pub fn set_preferred_admin_workspace(&self, user_id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let preferred = conn
             .query_row(
                 "SELECT id FROM workspaces \
@@ -4695,10 +5476,7 @@ impl Db {
     }
 
     pub fn list_user_workspaces(&self, user_id: i64) -> Result<Vec<WorkspaceMembershipRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let default_workspace_id = conn
             .query_row(
                 "SELECT default_workspace_id FROM users WHERE id = ?1",
@@ -4737,10 +5515,7 @@ impl Db {
         user_id: i64,
         workspace_id: i64,
     ) -> Result<Option<WorkspaceMembershipRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let default_workspace_id = conn
             .query_row(
                 "SELECT default_workspace_id FROM users WHERE id = ?1",
@@ -4774,10 +5549,7 @@ impl Db {
     }
 
     pub fn user_has_workspace_access(&self, user_id: i64, workspace_id: i64) -> Result<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let exists = conn
             .query_row(
                 "SELECT 1 FROM workspace_memberships WHERE user_id = ?1 AND workspace_id = ?2",
@@ -4791,10 +5563,7 @@ impl Db {
     }
 
     pub fn get_workspace(&self, workspace_id: i64) -> Result<Option<WorkspaceRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             "SELECT id, name, slug, kind, owner_user_id, created_at FROM workspaces WHERE id = ?1",
             params![workspace_id],
@@ -4805,10 +5574,7 @@ impl Db {
     }
 
     pub fn get_system_workspace(&self) -> Result<Option<WorkspaceRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             "SELECT id, name, slug, kind, owner_user_id, created_at FROM workspaces WHERE kind = 'system' ORDER BY id ASC LIMIT 1",
             [],
@@ -4819,10 +5585,7 @@ impl Db {
     }
 
     pub fn get_first_workspace_by_kind(&self, kind: &str) -> Result<Option<WorkspaceRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             "SELECT id, name, slug, kind, owner_user_id, created_at FROM workspaces WHERE kind = ?1 ORDER BY id ASC LIMIT 1",
             params![kind],
@@ -4833,10 +5596,7 @@ impl Db {
     }
 
     pub fn list_all_workspaces(&self) -> Result<Vec<WorkspaceRow>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, name, slug, kind, owner_user_id, created_at FROM workspaces ORDER BY kind, name, id",
         )?;
@@ -4853,10 +5613,7 @@ impl Db {
         kind: &str,
         owner_user_id: Option<i64>,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let base_slug = unique_slug(name, 0);
         let slug = if base_slug.is_empty() {
             format!("workspace-{}", Utc::now().timestamp())
@@ -4887,10 +5644,7 @@ impl Db {
     }
 
     pub fn add_workspace_member(&self, workspace_id: i64, user_id: i64, role: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "INSERT INTO workspace_memberships (workspace_id, user_id, role) VALUES (?1, ?2, ?3) \
              ON CONFLICT (workspace_id, user_id) DO UPDATE SET role = EXCLUDED.role",
@@ -4901,10 +5655,7 @@ impl Db {
     }
 
     pub fn ensure_system_workspace_membership(&self, user_id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let system_ws = conn
             .query_row(
                 "SELECT id FROM workspaces WHERE kind = 'system' ORDER BY id ASC LIMIT 1",
@@ -4924,10 +5675,7 @@ impl Db {
     }
 
     pub fn ensure_admin_workspace_memberships(&self, user_id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare("SELECT id FROM workspaces ORDER BY id ASC")?;
         let workspace_ids = stmt
             .query_map([], |row| row.get::<_, i64>(0))?
@@ -4943,10 +5691,7 @@ impl Db {
     }
 
     pub fn count_users(&self) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let count: i64 = conn
             .query_row("SELECT COUNT(*) FROM users", params![], |row| row.get(0))
             .context("count_users")?;
@@ -4954,10 +5699,7 @@ impl Db {
     }
 
     pub fn count_admin_users(&self) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let count: i64 = conn
             .query_row(
                 "SELECT COUNT(*) FROM users WHERE is_admin = true",
@@ -4975,10 +5717,7 @@ impl Db {
         password_hash: &str,
         is_admin: bool,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let id: i64 = conn
             .query_row(
                 "INSERT INTO users (username, display_name, password_hash, is_admin) \
@@ -5017,10 +5756,7 @@ impl Db {
         &self,
         username: &str,
     ) -> Result<Option<(i64, String, String, String, bool)>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 "SELECT id, username, display_name, password_hash, is_admin FROM users WHERE username = ?1",
@@ -5042,10 +5778,7 @@ impl Db {
         {
             return Ok(Some((id, username, display_name, is_admin)));
         }
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 "SELECT u.id, u.username, u.display_name, u.is_admin \
@@ -5062,10 +5795,7 @@ impl Db {
     }
 
     pub fn get_user_by_id(&self, id: i64) -> Result<Option<(i64, String, String, bool)>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 "SELECT id, username, display_name, is_admin FROM users WHERE id = ?1",
@@ -5078,10 +5808,7 @@ impl Db {
     }
 
     pub fn set_user_admin(&self, id: i64, is_admin: bool) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE users SET is_admin = ?1 WHERE id = ?2",
             params![is_admin, id],
@@ -5091,10 +5818,7 @@ impl Db {
     }
 
     pub fn list_users(&self) -> Result<Vec<(i64, String, String, bool, String)>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, username, display_name, is_admin, created_at FROM users ORDER BY id",
         )?;
@@ -5114,20 +5838,14 @@ impl Db {
     }
 
     pub fn delete_user(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute("DELETE FROM users WHERE id = ?1", params![id])
             .context("delete_user")?;
         Ok(())
     }
 
     pub fn update_user_password(&self, id: i64, password_hash: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE users SET password_hash = ?1 WHERE id = ?2",
             params![password_hash, id],
@@ -5139,10 +5857,7 @@ impl Db {
     // ── User Settings ────────────────────────────────────────────────────
 
     pub fn get_user_setting(&self, user_id: i64, key: &str) -> Result<Option<String>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 "SELECT value FROM user_settings WHERE user_id = ?1 AND key = ?2",
@@ -5155,10 +5870,7 @@ impl Db {
     }
 
     pub fn set_user_setting(&self, user_id: i64, key: &str, value: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "INSERT INTO user_settings (user_id, key, value) VALUES (?1, ?2, ?3) \
              ON CONFLICT(user_id, key) DO UPDATE SET value = excluded.value",
@@ -5169,10 +5881,7 @@ impl Db {
     }
 
     pub fn get_all_user_settings(&self, user_id: i64) -> Result<HashMap<String, String>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare("SELECT key, value FROM user_settings WHERE user_id = ?1")?;
         let rows = stmt
             .query_map(params![user_id], |row| {
@@ -5186,10 +5895,7 @@ impl Db {
     }
 
     pub fn delete_user_setting(&self, user_id: i64, key: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "DELETE FROM user_settings WHERE user_id = ?1 AND key = ?2",
             params![user_id, key],
@@ -5201,10 +5907,7 @@ impl Db {
     // ── Config ────────────────────────────────────────────────────────────
 
     pub fn get_config(&self, key: &str) -> Result<Option<String>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 "SELECT value FROM config WHERE key = ?1",
@@ -5224,10 +5927,7 @@ impl Db {
     }
 
     pub fn set_config(&self, key: &str, value: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let updated_at = now_str();
         conn.execute(
             "INSERT INTO config (key, value, updated_at) VALUES (?1, ?2, ?3) \
@@ -5247,10 +5947,7 @@ impl Db {
         message: &str,
         metadata: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let ts = Utc::now().timestamp();
         let id = conn
             .execute_returning_id(
@@ -5263,10 +5960,7 @@ impl Db {
     }
 
     pub fn get_recent_events(&self, limit: i64) -> Result<Vec<LegacyEvent>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, ts, level, category, message, metadata \
              FROM events ORDER BY ts DESC, id DESC LIMIT ?1",
@@ -5287,10 +5981,7 @@ impl Db {
         notify_chat: &str,
         mode: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let system_workspace_id: Option<i64> = conn
             .query_row(
                 "SELECT id FROM workspaces WHERE kind = 'system' ORDER BY id ASC LIMIT 1",
@@ -5321,10 +6012,7 @@ impl Db {
 
     /// Return "done" tasks that have no integration_queue entry (orphaned after restart).
     pub fn list_done_tasks_without_queue(&self) -> Result<Vec<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = format!(
             "SELECT {TASK_COLS} FROM pipeline_tasks \
              WHERE status = 'done' \
@@ -5344,10 +6032,7 @@ impl Db {
 
     /// Reset integration_queue entries stuck in "merging" where the task is not yet merged.
     pub fn reset_stale_merging_queue(&self) -> Result<usize> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let n = conn.execute(
             "UPDATE integration_queue SET status = 'queued' \
              WHERE status = 'merging' \
@@ -5358,7 +6043,7 @@ impl Db {
     }
 
     pub fn active_task_count(&self) -> i64 {
-        let Ok(conn) = self.conn.lock() else { return 0 };
+        let conn = self.session();
         conn.query_row(
             "SELECT COUNT(*) FROM pipeline_tasks WHERE status NOT IN ('done','merged','failed','blocked','pending_review','human_review','purged')",
             [],
@@ -5368,10 +6053,7 @@ impl Db {
     }
 
     pub fn get_recent_merged_tasks(&self, limit: i64) -> Result<Vec<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let sql = format!(
             "SELECT {TASK_COLS} FROM pipeline_tasks WHERE status = 'merged' ORDER BY id DESC LIMIT ?1"
         );
@@ -5384,10 +6066,7 @@ impl Db {
     }
 
     pub fn recycle_failed_tasks(&self, repo_path: &str) -> Result<usize> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let n = conn
             .execute(
                 "UPDATE pipeline_tasks SET status='backlog', attempt=0, last_error='' \
@@ -5399,10 +6078,7 @@ impl Db {
     }
 
     pub fn reset_task_attempt(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET attempt=0 WHERE id=?1",
             params![id],
@@ -5427,69 +6103,54 @@ impl Db {
 
     // ── Full Task List ────────────────────────────────────────────────────
 
-    pub fn list_all_tasks(&self, repo_path: Option<&str>) -> Result<Vec<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        let sql = if repo_path.is_some() {
-            format!(
+    pub async fn list_all_tasks(&self, repo_path: Option<&str>) -> Result<Vec<Task>> {
+        let client = self.client().await?;
+        let tasks = if let Some(repo_path) = repo_path {
+            let sql = format!(
                 "SELECT {TASK_COLS} FROM pipeline_tasks \
                  WHERE repo_path = ?1 \
                  ORDER BY id DESC"
-            )
-        } else {
-            format!("SELECT {TASK_COLS} FROM pipeline_tasks ORDER BY id DESC")
-        };
-        let mut stmt = conn.prepare(&sql)?;
-        let tasks = if let Some(repo_path) = repo_path {
-            stmt.query_map(params![repo_path], row_to_task)?
-                .collect::<pg::Result<Vec<_>>>()
+            );
+            client
+                .query_map(&sql, params![repo_path], row_to_task)
+                .await
         } else {
-            stmt.query_map([], row_to_task)?
-                .collect::<pg::Result<Vec<_>>>()
+            let sql = format!("SELECT {TASK_COLS} FROM pipeline_tasks ORDER BY id DESC");
+            client.query_map(&sql, [], row_to_task).await
         }
         .context("list_all_tasks")?;
         Ok(tasks)
     }
 
-    pub fn list_all_tasks_in_workspace(
+    pub async fn list_all_tasks_in_workspace(
         &self,
         workspace_id: i64,
         repo_path: Option<&str>,
     ) -> Result<Vec<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        let sql = if repo_path.is_some() {
-            format!(
+        let client = self.client().await?;
+        let tasks = if let Some(repo_path) = repo_path {
+            let sql = format!(
                 "SELECT {TASK_COLS} FROM pipeline_tasks \
                  WHERE workspace_id = ?1 AND repo_path = ?2 \
                  ORDER BY id DESC"
-            )
+            );
+            client
+                .query_map(&sql, params![workspace_id, repo_path], row_to_task)
+                .await
         } else {
-            format!(
+            let sql = format!(
                 "SELECT {TASK_COLS} FROM pipeline_tasks WHERE workspace_id = ?1 ORDER BY id DESC"
-            )
-        };
-        let mut stmt = conn.prepare(&sql)?;
-        let tasks = if let Some(repo_path) = repo_path {
-            stmt.query_map(params![workspace_id, repo_path], row_to_task)?
-                .collect::<pg::Result<Vec<_>>>()
-        } else {
-            stmt.query_map(params![workspace_id], row_to_task)?
-                .collect::<pg::Result<Vec<_>>>()
+            );
+            client
+                .query_map(&sql, params![workspace_id], row_to_task)
+                .await
         }
         .context("list_all_tasks_in_workspace")?;
         Ok(tasks)
     }
 
     pub fn get_task_in_workspace(&self, workspace_id: i64, id: i64) -> Result<Option<Task>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 &format!(
@@ -5566,10 +6227,7 @@ impl Db {
         is_bot_message: bool,
         raw_stream: Option<&str>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let ts = now_str();
         conn.execute(
             "INSERT INTO messages (id, chat_jid, sender, sender_name, content, timestamp, is_from_me, is_bot_message, raw_stream) \
@@ -5585,10 +6243,7 @@ impl Db {
 
     /// List all chat threads (distinct chat_jid values) with msg count and last timestamp.
     pub fn get_chat_threads(&self) -> Result<Vec<(String, i64, String)>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT chat_jid, COUNT(*) as msg_count, MAX(timestamp) as last_ts \
              FROM messages GROUP BY chat_jid ORDER BY last_ts DESC",
@@ -5607,17 +6262,12 @@ impl Db {
     }
 
     /// Get messages for a specific chat thread, newest last.
-    pub fn get_chat_messages(&self, chat_jid: &str, limit: i64) -> Result<Vec<ChatMessage>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        let mut stmt = conn.prepare(
-            "SELECT id, chat_jid, sender, sender_name, content, timestamp, is_from_me, is_bot_message, raw_stream \
-             FROM messages WHERE chat_jid = ?1 ORDER BY timestamp ASC LIMIT ?2",
-        )?;
-        let rows = stmt
-            .query_map(params![chat_jid, limit], |row| {
+    pub async fn get_chat_messages(&self, chat_jid: &str, limit: i64) -> Result<Vec<ChatMessage>> {
+        let client = self.client().await?;
+        let sql = "SELECT id, chat_jid, sender, sender_name, content, timestamp, is_from_me, is_bot_message, raw_stream \
+                   FROM messages WHERE chat_jid = ?1 ORDER BY timestamp ASC LIMIT ?2";
+        let rows = client
+            .query_map(sql, params![chat_jid, limit], |row| {
                 Ok(ChatMessage {
                     id: row.get(0)?,
                     chat_jid: row.get(1)?,
@@ -5629,8 +6279,8 @@ impl Db {
                     is_bot_message: row.get::<_, i64>(7)? != 0,
                     raw_stream: row.get(8)?,
                 })
-            })?
-            .collect::<pg::Result<Vec<_>>>()
+            })
+            .await
             .context("get_chat_messages")?;
         Ok(rows)
     }
@@ -5638,10 +6288,7 @@ impl Db {
     // ── Registered groups ─────────────────────────────────────────────────
 
     pub fn get_all_groups(&self) -> Result<Vec<RegisteredGroup>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT jid, name, folder, trigger_pattern, requires_trigger FROM registered_groups ORDER BY added_at ASC",
         )?;
@@ -5670,10 +6317,7 @@ impl Db {
         trigger_pattern: &str,
         requires_trigger: bool,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "INSERT INTO registered_groups (jid, name, folder, trigger_pattern, requires_trigger) \
              VALUES (?1, ?2, ?3, ?4, ?5) \
@@ -5686,10 +6330,7 @@ impl Db {
     }
 
     pub fn unregister_group(&self, jid: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute("DELETE FROM registered_groups WHERE jid = ?1", params![jid])
             .context("unregister_group")?;
         Ok(())
@@ -5698,10 +6339,7 @@ impl Db {
     // ── Chat sessions ─────────────────────────────────────────────────────
 
     pub fn get_session(&self, folder: &str) -> Result<Option<String>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             "SELECT session_id FROM sessions WHERE folder = ?1",
             params![folder],
@@ -5712,10 +6350,7 @@ impl Db {
     }
 
     pub fn set_session(&self, folder: &str, session_id: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "INSERT INTO sessions (folder, session_id, created_at) VALUES (?1, ?2, ?3) \
              ON CONFLICT(folder) DO UPDATE SET session_id=excluded.session_id, created_at=excluded.created_at",
@@ -5727,10 +6362,7 @@ impl Db {
     }
 
     pub fn get_seed_cooldowns(&self) -> Result<HashMap<(String, String), i64>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn
             .prepare("SELECT folder, session_id FROM sessions WHERE folder LIKE 'seed:%'")
             .context("get_seed_cooldowns")?;
@@ -5759,10 +6391,7 @@ impl Db {
     }
 
     pub fn expire_sessions(&self, max_age_hours: i64) -> Result<usize> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let n = conn
             .execute(
                 "DELETE FROM sessions \
@@ -5784,10 +6413,7 @@ impl Db {
         trigger_msg_id: &str,
         folder: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let id = conn.execute_returning_id(
             "INSERT INTO chat_agent_runs (jid, status, transport, original_id, trigger_msg_id, folder) \
              VALUES (?1, 'running', ?2, ?3, ?4, ?5)",
@@ -5804,10 +6430,7 @@ impl Db {
         new_session_id: &str,
         last_msg_timestamp: &str,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE chat_agent_runs SET status='completed', output=?1, new_session_id=?2, \
              last_msg_timestamp=?3, completed_at=?4 WHERE id=?5",
@@ -5818,10 +6441,7 @@ impl Db {
     }
 
     pub fn mark_chat_agent_run_delivered(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE chat_agent_runs SET status='delivered' WHERE id=?1",
             params![id],
@@ -5831,10 +6451,7 @@ impl Db {
     }
 
     pub fn get_undelivered_runs(&self, jid: &str) -> Result<Vec<ChatAgentRun>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, jid, status, transport, original_id, trigger_msg_id, folder, \
              output, new_session_id, last_msg_timestamp, started_at, completed_at \
@@ -5848,10 +6465,7 @@ impl Db {
     }
 
     pub fn fail_chat_agent_run(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE chat_agent_runs SET status='failed', completed_at=?1 WHERE id=?2",
             params![now_str(), id],
@@ -5861,10 +6475,7 @@ impl Db {
     }
 
     pub fn has_running_chat_agent(&self, jid: &str) -> Result<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let count: i64 = conn
             .query_row(
                 "SELECT COUNT(*) FROM chat_agent_runs WHERE jid=?1 AND status='running'",
@@ -5876,10 +6487,7 @@ impl Db {
     }
 
     pub fn abandon_running_agents(&self) -> Result<usize> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let n = conn
             .execute(
                 "UPDATE chat_agent_runs SET status='abandoned' WHERE status='running'",
@@ -5895,10 +6503,7 @@ impl Db {
         since_ts: &str,
         limit: i64,
     ) -> Result<Vec<ChatMessage>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, chat_jid, sender, sender_name, content, timestamp, is_from_me, is_bot_message, raw_stream \
              FROM messages WHERE chat_jid=?1 AND timestamp > ?2 ORDER BY timestamp ASC LIMIT ?3",
@@ -5932,10 +6537,7 @@ impl Db {
         since_ts: Option<i64>,
         limit: i64,
     ) -> Result<Vec<LegacyEvent>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut where_clauses = Vec::new();
         let mut params_vec: Vec<Box<dyn pg::types::ToSql>> = Vec::new();
         if let Some(category) = category.map(str::trim).filter(|c| !c.is_empty()) {
@@ -6060,7 +6662,21 @@ impl Db {
         })
     }
 
-    fn encrypt_secret(secret: &str) -> String {
+    /// Route secret encryption through `store` for the rest of the process.
+    /// Values written earlier with `BORG_MASTER_KEY` (`enc:v1:`) stay readable.
+    pub fn install_secret_store(store: std::sync::Arc<dyn SecretStore>) {
+        if SECRET_STORE.set(store).is_err() {
+            tracing::warn!("secret store already installed; ignoring replacement");
+        }
+    }
+
+    /// Seal `secret` with the installed store, or `BORG_MASTER_KEY` when no
+    /// store is installed. Plaintext is only stored when neither is configured;
+    /// a configured key that fails to encrypt is an error, never a fallback.
+    fn encrypt_secret(secret: &str) -> Result<String> {
+        if let Some(store) = SECRET_STORE.get() {
+            return store.encrypt(secret).context("encrypt secret");
+        }
         if let Some(key_bytes) = Self::master_key_bytes() {
             use aes_gcm::{
                 aead::{Aead, AeadCore, KeyInit, OsRng},
@@ -6069,20 +6685,30 @@ impl Db {
             let key = aes_gcm::Key::<Aes256Gcm>::from_slice(&key_bytes);
             let cipher = Aes256Gcm::new(key);
             let nonce = Aes256Gcm::generate_nonce(&mut OsRng); // 96-bits
-            if let Ok(ciphertext) = cipher.encrypt(&nonce, secret.as_bytes()) {
-                let mut combined = nonce.to_vec();
-                combined.extend_from_slice(&ciphertext);
-                use base64::Engine;
-                return format!(
-                    "enc:v1:{}",
-                    base64::engine::general_purpose::STANDARD.encode(&combined)
-                );
-            }
+            let ciphertext = cipher
+                .encrypt(&nonce, secret.as_bytes())
+                .map_err(|e| anyhow::anyhow!("encrypt secret: {e}"))?;
+            let mut combined = nonce.to_vec();
+            combined.extend_from_slice(&ciphertext);
+            use base64::Engine;
+            return Ok(format!(
+                "enc:v1:{}",
+                base64::engine::general_purpose::STANDARD.encode(&combined)
+            ));
         }
-        secret.to_string()
+        Ok(secret.to_string())
     }
 
     fn decrypt_secret(secret: &str) -> String {
+        if crate::secrets::is_encrypted_value(secret) {
+            if let Some(store) = SECRET_STORE.get() {
+                match store.decrypt(secret) {
+                    Ok(plain) => return plain,
+                    Err(e) => tracing::warn!("secret store decrypt failed: {e}"),
+                }
+            }
+            return secret.to_string();
+        }
         if let Some(encoded) = secret.strip_prefix("enc:v1:") {
             if let Some(key_bytes) = Self::master_key_bytes() {
                 use base64::Engine;
@@ -6115,11 +6741,8 @@ impl Db {
         key_name: &str,
         key_value: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        let encrypted_value = Self::encrypt_secret(key_value);
+        let conn = self.session();
+        let encrypted_value = Self::encrypt_secret(key_value)?;
         let id = conn.execute_returning_id(
             "INSERT INTO api_keys (owner, provider, key_name, key_value) VALUES (?1, ?2, ?3, ?4) \
              ON CONFLICT(owner, provider) DO UPDATE SET key_name=excluded.key_name, key_value=excluded.key_value",
@@ -6135,11 +6758,8 @@ impl Db {
         key_name: &str,
         key_value: &str,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
-        let encrypted_value = Self::encrypt_secret(key_value);
+        let conn = self.session();
+        let encrypted_value = Self::encrypt_secret(key_value)?;
         let owner = format!("workspace:{workspace_id}");
         let id = conn.execute_returning_id(
             "INSERT INTO api_keys (workspace_id, owner, provider, key_name, key_value) VALUES (?1, ?2, ?3, ?4, ?5) \
@@ -6150,10 +6770,7 @@ impl Db {
     }
 
     pub fn get_api_key(&self, owner: &str, provider: &str) -> Result<Option<String>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         // Try owner-specific first, then fall back to global
         let result = conn
             .query_row(
@@ -6183,10 +6800,7 @@ impl Db {
     }
 
     pub fn get_api_key_exact(&self, owner: &str, provider: &str) -> Result<Option<String>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let result = conn
             .query_row(
                 "SELECT key_value FROM api_keys WHERE owner = ?1 AND provider = ?2",
@@ -6199,10 +6813,7 @@ impl Db {
     }
 
     pub fn list_api_keys(&self, owner: &str) -> Result<Vec<ApiKeyEntry>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, workspace_id, owner, provider, key_name, created_at FROM api_keys \
              WHERE owner = ?1 OR owner = 'global' ORDER BY provider",
@@ -6224,10 +6835,7 @@ impl Db {
     }
 
     pub fn list_workspace_api_keys(&self, workspace_id: i64) -> Result<Vec<ApiKeyEntry>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let owner = format!("workspace:{workspace_id}");
         let mut stmt = conn.prepare(
             "SELECT id, workspace_id, owner, provider, key_name, created_at FROM api_keys \
@@ -6250,19 +6858,13 @@ impl Db {
     }
 
     pub fn delete_api_key(&self, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute("DELETE FROM api_keys WHERE id = ?1", params![id])?;
         Ok(())
     }
 
     pub fn delete_workspace_api_key(&self, workspace_id: i64, id: i64) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let owner = format!("workspace:{workspace_id}");
         conn.execute(
             "DELETE FROM api_keys WHERE id = ?1 AND owner = ?2",
@@ -6272,10 +6874,7 @@ impl Db {
     }
 
     pub fn list_user_linked_credentials(&self, user_id: i64) -> Result<Vec<LinkedCredentialEntry>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, user_id, provider, auth_kind, account_email, account_label, status, \
                     expires_at, last_validated_at, last_used_at, last_error, created_at, updated_at \
@@ -6305,10 +6904,7 @@ impl Db {
     }
 
     pub fn list_all_linked_credentials(&self) -> Result<Vec<LinkedCredentialEntry>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, user_id, provider, auth_kind, account_email, account_label, status, \
                     expires_at, last_validated_at, last_used_at, last_error, created_at, updated_at \
@@ -6342,10 +6938,7 @@ impl Db {
         user_id: i64,
         provider: &str,
     ) -> Result<Option<LinkedCredentialSecret>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let row = conn
             .query_row(
                 "SELECT id, user_id, provider, auth_kind, account_email, account_label, status, \
@@ -6398,13 +6991,10 @@ impl Db {
         last_error: &str,
         bundle: &LinkedCredentialBundle,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let bundle_json =
             serde_json::to_string(bundle).context("encode linked credential bundle")?;
-        let encrypted_bundle = Self::encrypt_secret(&bundle_json);
+        let encrypted_bundle = Self::encrypt_secret(&bundle_json)?;
         let id = conn.execute_returning_id(
             "INSERT INTO linked_credentials \
                 (user_id, provider, auth_kind, account_email, account_label, credential_bundle, \
@@ -6451,15 +7041,12 @@ impl Db {
         last_error: &str,
         bundle: Option<&LinkedCredentialBundle>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let encrypted_bundle = match bundle {
             Some(bundle) => {
                 let bundle_json =
                     serde_json::to_string(bundle).context("encode linked credential bundle")?;
-                Some(Self::encrypt_secret(&bundle_json))
+                Some(Self::encrypt_secret(&bundle_json)?)
             },
             None => None,
         };
@@ -6492,10 +7079,7 @@ impl Db {
     }
 
     pub fn touch_user_linked_credential_used(&self, user_id: i64, provider: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let now = chrono::Utc::now().to_rfc3339();
         conn.execute(
             "UPDATE linked_credentials SET last_used_at = ?3, updated_at = to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS') \
@@ -6506,10 +7090,7 @@ impl Db {
     }
 
     pub fn delete_user_linked_credential(&self, user_id: i64, provider: &str) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "DELETE FROM linked_credentials WHERE user_id = ?1 AND provider = ?2",
             params![user_id, provider],
@@ -6517,13 +7098,174 @@ impl Db {
         Ok(())
     }
 
+    // ── Secret re-encryption ──────────────────────────────────────────────
+
+    /// Rewrite every secret column that the installed store reports as stale
+    /// (plaintext, `enc:v1:`, `enc1:` or an older `enc2:` key id) so it is
+    /// sealed with the active key. Works in id-ordered batches so the shared
+    /// connection is never held for a whole table.
+    pub fn reencrypt_secrets(&self, batch_size: i64) -> Result<SecretRotationReport> {
+        let Some(store) = SECRET_STORE.get() else {
+            anyhow::bail!("no secret store installed; enable BORG_SECRET_ENCRYPTION");
+        };
+        let store = store.as_ref();
+        let mut skipped = 0;
+        let api_keys =
+            self.reencrypt_table(store, "api_keys", &["key_value"], batch_size, &mut skipped)?;
+        let linked_credentials = self.reencrypt_table(
+            store,
+            "linked_credentials",
+            &["credential_bundle"],
+            batch_size,
+            &mut skipped,
+        )?;
+        let cloud_connections = self.reencrypt_table(
+            store,
+            "cloud_connections",
+            &["access_token", "refresh_token"],
+            batch_size,
+            &mut skipped,
+        )?;
+        let report = SecretRotationReport {
+            api_keys,
+            linked_credentials,
+            cloud_connections,
+            skipped,
+        };
+        Ok(report)
+    }
+
+    fn reencrypt_table(
+        &self,
+        store: &dyn SecretStore,
+        table: &str,
+        columns: &[&str],
+        batch_size: i64,
+        skipped: &mut usize,
+    ) -> Result<usize> {
+        let select_cols = columns
+            .iter()
+            .map(|c| format!("COALESCE({c}, '')"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let select_sql =
+            format!("SELECT id, {select_cols} FROM {table} WHERE id > ?1 ORDER BY id ASC LIMIT ?2");
+        let mut rewritten = 0usize;
+        let mut after_id = 0i64;
+        loop {
+            let batch: Vec<(i64, Vec<String>)> = {
+                let conn = self.session();
+                let mut stmt = conn.prepare(&select_sql)?;
+                let rows = stmt.query_map(params![after_id, batch_size.max(1)], |row| {
+                    let mut values = Vec::with_capacity(columns.len());
+                    for i in 0..columns.len() {
+                        values.push(row.get::<_, String>(i + 1)?);
+                    }
+                    Ok((row.get::<_, i64>(0)?, values))
+                })?;
+                rows.collect::<pg::Result<Vec<_>>>()
+                    .with_context(|| format!("reencrypt scan {table}"))?
+            };
+            let Some((last_id, _)) = batch.last() else {
+                break;
+            };
+            after_id = *last_id;
+
+            for (id, values) in batch {
+                match self.reencrypt_row(store, table, columns, id, values)? {
+                    Some(true) => rewritten += 1,
+                    Some(false) => {},
+                    None => *skipped += 1,
+                }
+            }
+        }
+        Ok(rewritten)
+    }
+
+    /// Re-seal one row. The update only applies if the row still holds the
+    /// values that were read, so a secret written concurrently is never
+    /// overwritten with a stale one; on a lost race the row is re-read and
+    /// retried. Returns `Some(true)` if rewritten, `Some(false)` if nothing
+    /// was stale (or the row is gone), and `None` if a value could not be
+    /// decrypted or the row kept changing.
+    fn reencrypt_row(
+        &self,
+        store: &dyn SecretStore,
+        table: &str,
+        columns: &[&str],
+        id: i64,
+        mut values: Vec<String>,
+    ) -> Result<Option<bool>> {
+        const ATTEMPTS: usize = 3;
+        let assignments = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("{c} = ?{}", i + 2))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let unchanged = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| format!("COALESCE({c}, '') = ?{}", i + 2 + columns.len()))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let update_sql = format!("UPDATE {table} SET {assignments} WHERE id = ?1 AND {unchanged}");
+        let select_sql = format!(
+            "SELECT {} FROM {table} WHERE id = ?1",
+            columns
+                .iter()
+                .map(|c| format!("COALESCE({c}, '')"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        for _ in 0..ATTEMPTS {
+            if !values.iter().any(|v| store.needs_rotation(v)) {
+                return Ok(Some(false));
+            }
+            let mut sealed = Vec::with_capacity(values.len());
+            for value in &values {
+                if !store.needs_rotation(value) {
+                    sealed.push(value.clone());
+                    continue;
+                }
+                let plain = Self::decrypt_secret(value);
+                if crate::secrets::is_encrypted_value(&plain) || plain.starts_with("enc:v1:") {
+                    return Ok(None);
+                }
+                sealed.push(store.encrypt(&plain)?);
+            }
+            let mut update_params = params![id];
+            update_params.extend(sealed.iter().map(pg::to_param));
+            update_params.extend(values.iter().map(pg::to_param));
+            let updated = self
+                .session()
+                .execute(&update_sql, update_params)
+                .with_context(|| format!("reencrypt update {table} id={id}"))?;
+            if updated > 0 {
+                return Ok(Some(true));
+            }
+            let current = self
+                .session()
+                .query_row(&select_sql, params![id], |row| {
+                    (0..columns.len())
+                        .map(|i| row.get::<_, String>(i))
+                        .collect::<pg::Result<Vec<_>>>()
+                })
+                .optional()
+                .with_context(|| format!("reencrypt reread {table} id={id}"))?;
+            match current {
+                Some(current) => values = current,
+                None => return Ok(Some(false)),
+            }
+        }
+        Ok(None)
+    }
+
     // ── Cron scheduling ───────────────────────────────────────────────────
 
     pub fn list_cron_jobs(&self) -> Result<Vec<crate::cron::CronJob>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, name, schedule, job_type, config, project_id, enabled, \
              last_run, next_run, created_at \
@@ -6537,10 +7279,7 @@ impl Db {
     }
 
     pub fn get_cron_job(&self, id: i64) -> Result<Option<crate::cron::CronJob>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.query_row(
             "SELECT id, name, schedule, job_type, config, project_id, enabled, \
              last_run, next_run, created_at \
@@ -6560,10 +7299,7 @@ impl Db {
         config: &serde_json::Value,
         project_id: Option<i64>,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let config_str = serde_json::to_string(config).unwrap_or_else(|_| "{}".into());
         let next_run = crate::cron::compute_next_run(schedule, Utc::now())
             .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string());
@@ -6594,10 +7330,7 @@ impl Db {
         project_id: Option<Option<i64>>,
         enabled: Option<bool>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut sets = Vec::new();
         let mut vals: Vec<Box<dyn pg::ToSql>> = Vec::new();
         let mut idx = 1;
@@ -6657,10 +7390,7 @@ impl Db {
     }
 
     pub fn delete_cron_job(&self, id: i64) -> Result<bool> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let n = conn
             .execute("DELETE FROM cron_jobs WHERE id = ?1", params![id])
             .context("delete_cron_job")?;
@@ -6668,10 +7398,7 @@ impl Db {
     }
 
     pub fn list_due_cron_jobs(&self) -> Result<Vec<crate::cron::CronJob>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let now = now_str();
         let mut stmt = conn.prepare(
             "SELECT id, name, schedule, job_type, config, project_id, enabled, \
@@ -6693,10 +7420,7 @@ impl Db {
         last_run: &DateTime<Utc>,
         next_run: Option<&DateTime<Utc>>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let last_str = last_run.format("%Y-%m-%d %H:%M:%S").to_string();
         let next_str = next_run.map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string());
         conn.execute(
@@ -6708,10 +7432,7 @@ impl Db {
     }
 
     pub fn insert_cron_run(&self, job_id: i64) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let id = conn
             .execute_returning_id(
                 "INSERT INTO cron_runs (job_id, status) VALUES (?1, 'running')",
@@ -6729,10 +7450,7 @@ impl Db {
         error: Option<&str>,
         task_id: Option<i64>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let finished = now_str();
         conn.execute(
             "UPDATE cron_runs SET status = ?1, result = ?2, error = ?3, \
@@ -6744,10 +7462,7 @@ impl Db {
     }
 
     pub fn list_cron_runs(&self, job_id: i64, limit: i64) -> Result<Vec<crate::cron::CronRun>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, job_id, started_at, finished_at, status, result, error, task_id \
              FROM cron_runs WHERE job_id = ?1 \
@@ -6771,10 +7486,7 @@ impl Db {
         cost_usd: f64,
         model: &str,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE messages SET input_tokens = ?1, output_tokens = ?2, \
              cost_usd = ?3, model = ?4 WHERE chat_jid = ?5 AND id = ?6",
@@ -6798,10 +7510,7 @@ impl Db {
         output_tokens: i64,
         cost_usd: f64,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE pipeline_tasks SET \
              total_input_tokens = COALESCE(total_input_tokens, 0) + ?1, \
@@ -6819,10 +7528,7 @@ impl Db {
         from: Option<DateTime<Utc>>,
         to: Option<DateTime<Utc>>,
     ) -> Result<UsageSummary> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
 
         let mut where_clauses = Vec::new();
         let mut params_vec: Vec<Box<dyn pg::types::ToSql>> = Vec::new();
@@ -6842,7 +7548,7 @@ impl Db {
             params_vec.iter().map(|p| p.as_ref()).collect();
 
         let msg_sql = format!(
-            "SELECT COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0), \
+            "SELECT COALESCE(SUM(input_tokens), 0)::BIGINT, COALESCE(SUM(output_tokens), 0)::BIGINT, \
              COALESCE(SUM(cost_usd), 0), COUNT(*) FROM messages{where_sql}"
         );
         let (msg_input, msg_output, msg_cost, msg_count): (i64, i64, f64, i64) = conn
@@ -6868,7 +7574,8 @@ impl Db {
             task_params.iter().map(|p| p.as_ref()).collect();
 
         let task_sql = format!(
-            "SELECT COALESCE(SUM(total_input_tokens), 0), COALESCE(SUM(total_output_tokens), 0), \
+            "SELECT COALESCE(SUM(total_input_tokens), 0)::BIGINT, \
+             COALESCE(SUM(total_output_tokens), 0)::BIGINT, \
              COALESCE(SUM(total_cost_usd), 0), COUNT(*) FROM pipeline_tasks{task_where_sql}"
         );
         let (task_input, task_output, task_cost, task_count): (i64, i64, f64, i64) = conn
@@ -6877,12 +7584,89 @@ impl Db {
             })
             .context("get_usage_summary tasks")?;
 
+        let resources = self.get_resource_usage_summary(from, to)?;
+
         Ok(UsageSummary {
             total_input_tokens: msg_input + task_input,
             total_output_tokens: msg_output + task_output,
             total_cost_usd: msg_cost + task_cost,
             message_count: msg_count,
             task_count,
+            resources,
+        })
+    }
+
+    pub fn get_resource_usage_summary(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<ResourceUsageSummary> {
+        let conn = self.session();
+
+        let mut where_clauses = Vec::new();
+        let mut params_vec: Vec<Box<dyn pg::types::ToSql>> = Vec::new();
+        if let Some(from) = from {
+            where_clauses.push("created_at >= ?".to_string());
+            params_vec.push(Box::new(from.format("%Y-%m-%d %H:%M:%S").to_string()));
+        }
+        if let Some(to) = to {
+            where_clauses.push("created_at <= ?".to_string());
+            params_vec.push(Box::new(to.format("%Y-%m-%d %H:%M:%S").to_string()));
+        }
+        let where_sql = if where_clauses.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", where_clauses.join(" AND "))
+        };
+        let param_refs: Vec<&dyn pg::types::ToSql> =
+            params_vec.iter().map(|p| p.as_ref()).collect();
+
+        let phase_sql = format!(
+            "SELECT phase, COUNT(*), COALESCE(SUM(wall_ms), 0)::BIGINT, \
+             COALESCE(SUM(cpu_ms), 0)::BIGINT, COALESCE(MAX(peak_memory_bytes), 0), \
+             COALESCE(AVG(peak_memory_bytes), 0)::BIGINT, \
+             COALESCE(SUM(disk_written_bytes), 0)::BIGINT \
+             FROM task_output_resources{where_sql} GROUP BY phase ORDER BY 4 DESC"
+        );
+        let mut stmt = conn.prepare(&phase_sql)?;
+        let by_phase = stmt
+            .query_map(param_refs.as_slice(), |r| {
+                Ok(PhaseResourceTotals {
+                    phase: r.get(0)?,
+                    runs: r.get(1)?,
+                    wall_ms: r.get(2)?,
+                    cpu_ms: r.get(3)?,
+                    max_peak_memory_bytes: r.get(4)?,
+                    avg_peak_memory_bytes: r.get(5)?,
+                    disk_written_bytes: r.get(6)?,
+                })
+            })?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("get_resource_usage_summary phases")?;
+
+        let top_sql = format!(
+            "SELECT task_output_id, task_id, phase, wall_ms, cpu_ms, peak_memory_bytes, \
+             disk_written_bytes, source, created_at \
+             FROM task_output_resources{where_sql} ORDER BY cpu_ms DESC LIMIT 10"
+        );
+        let mut stmt = conn.prepare(&top_sql)?;
+        let top_runs = stmt
+            .query_map(param_refs.as_slice(), row_to_phase_resource_usage)?
+            .collect::<pg::Result<Vec<_>>>()
+            .context("get_resource_usage_summary top runs")?;
+
+        Ok(ResourceUsageSummary {
+            phase_runs: by_phase.iter().map(|p| p.runs).sum(),
+            wall_ms: by_phase.iter().map(|p| p.wall_ms).sum(),
+            cpu_ms: by_phase.iter().map(|p| p.cpu_ms).sum(),
+            max_peak_memory_bytes: by_phase
+                .iter()
+                .map(|p| p.max_peak_memory_bytes)
+                .max()
+                .unwrap_or(0),
+            disk_written_bytes: by_phase.iter().map(|p| p.disk_written_bytes).sum(),
+            by_phase,
+            top_runs,
         })
     }
 
@@ -6896,10 +7680,7 @@ impl Db {
         chat_key: Option<&str>,
         input_summary: Option<&str>,
     ) -> Result<i64> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let id = conn
             .execute_returning_id(
                 "INSERT INTO tool_calls (run_id, tool_name, task_id, chat_key, input_summary) \
@@ -6918,10 +7699,7 @@ impl Db {
         success: bool,
         error: Option<&str>,
     ) -> Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         conn.execute(
             "UPDATE tool_calls SET output_summary = ?1, duration_ms = ?2, \
              success = ?3, error = ?4 WHERE id = ?5",
@@ -6936,10 +7714,7 @@ impl Db {
         task_id: i64,
         limit: i64,
     ) -> Result<Vec<crate::tool_calls::ToolCallEvent>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, chat_key, run_id, tool_name, input_summary, \
              output_summary, started_at, duration_ms, success, error \
@@ -6958,10 +7733,7 @@ impl Db {
         chat_key: &str,
         limit: i64,
     ) -> Result<Vec<crate::tool_calls::ToolCallEvent>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, chat_key, run_id, tool_name, input_summary, \
              output_summary, started_at, duration_ms, success, error \
@@ -6980,10 +7752,7 @@ impl Db {
         run_id: &str,
         limit: i64,
     ) -> Result<Vec<crate::tool_calls::ToolCallEvent>> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|_| anyhow::anyhow!("db mutex poisoned"))?;
+        let conn = self.session();
         let mut stmt = conn.prepare(
             "SELECT id, task_id, chat_key, run_id, tool_name, input_summary, \
              output_summary, started_at, duration_ms, success, error \
@@ -0,0 +1,320 @@
+//! Egress allowlists for sandboxed agents.
+//!
+//! When a task's mode or the task itself carries a domain allowlist, the
+//! pipeline puts the agent container on the internal (no-gateway) network and
+//! issues a per-run grant for the egress proxy in borg-server. The proxy is
+//! then the only way out, and it only connects to allowlisted hosts.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::RngCore;
+
+/// Domain allowlist. An entry `github.com` matches `github.com` and any
+/// subdomain; a leading `*.` (`*.github.com`) matches subdomains only.
+/// Matching hosts are reachable on 443 (CONNECT) and 80 (plain HTTP); an
+/// entry with a port (`github.com:22`) opens that port as well.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EgressPolicy {
+    domains: Vec<String>,
+}
+
+impl EgressPolicy {
+    pub fn new<I, S>(domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut out: Vec<String> = Vec::new();
+        for d in domains {
+            let d = match normalize_entry(d.as_ref()) {
+                (host, _) if host.is_empty() => continue,
+                (host, Some(port)) if port != 80 && port != 443 => {
+                    if host.contains(':') {
+                        format!("[{host}]:{port}")
+                    } else {
+                        format!("{host}:{port}")
+                    }
+                },
+                (host, _) => host,
+            };
+            if !out.contains(&d) {
+                out.push(d);
+            }
+        }
+        Self { domains: out }
+    }
+
+    /// Parse a comma/whitespace-separated list as stored on tasks.
+    pub fn parse(list: &str) -> Self {
+        Self::new(list.split(|c: char| c == ',' || c.is_whitespace()))
+    }
+
+    /// Union of two allowlists (mode + task).
+    pub fn merge(&self, other: &Self) -> Self {
+        Self::new(self.domains.iter().chain(other.domains.iter()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    pub fn domains(&self) -> &[String] {
+        &self.domains
+    }
+
+    pub fn allows(&self, host: &str) -> bool {
+        let host = normalize_host(host);
+        !host.is_empty()
+            && self
+                .domains
+                .iter()
+                .any(|entry| host_matches(&normalize_host(entry), &host))
+    }
+
+    /// Whether `host:port` may be reached. `default_port` is the scheme's
+    /// port (443 for CONNECT, 80 for plain HTTP); any other port must be
+    /// named by a matching entry.
+    pub fn allows_port(&self, host: &str, port: u16, default_port: u16) -> bool {
+        if port == default_port {
+            return self.allows(host);
+        }
+        let host = normalize_host(host);
+        !host.is_empty()
+            && self
+                .domains
+                .iter()
+                .any(|entry| match normalize_entry(entry) {
+                    (pattern, Some(p)) => p == port && host_matches(&pattern, &host),
+                    _ => false,
+                })
+    }
+}
+
+fn host_matches(entry: &str, host: &str) -> bool {
+    if let Some(suffix) = entry.strip_prefix("*.") {
+        host.len() > suffix.len() && host.ends_with(&format!(".{suffix}"))
+    } else {
+        host == entry || host.ends_with(&format!(".{entry}"))
+    }
+}
+
+fn normalize_host(raw: &str) -> String {
+    normalize_entry(raw).0
+}
+
+/// Lowercase, strip a trailing dot, brackets around IPv6 literals and any
+/// `scheme://` or path a user pasted into the allowlist. Returns the host
+/// and the port, if one was given.
+fn normalize_entry(raw: &str) -> (String, Option<u16>) {
+    let mut s = raw.trim().to_ascii_lowercase();
+    if let Some((_, rest)) = s.split_once("://") {
+        s = rest.to_string();
+    }
+    if let Some(i) = s.find('/') {
+        s.truncate(i);
+    }
+    let (host, port) = if let Some(rest) = s.strip_prefix('[') {
+        match rest.split_once(']') {
+            Some((host, after)) => (host, after.strip_prefix(':')),
+            None => (rest, None),
+        }
+    } else if s.matches(':').count() == 1 {
+        match s.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (s.as_str(), None),
+        }
+    } else {
+        (s.as_str(), None)
+    };
+    (
+        host.trim_end_matches('.').to_string(),
+        port.and_then(|p| p.parse().ok()),
+    )
+}
+
+/// Addresses the proxy must never connect to on an agent's behalf, even if
+/// an allowlisted name resolves there: loopback, private, link-local.
+pub fn is_internal_addr(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                // 100.64.0.0/10 carrier-grade NAT
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64)
+        },
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_internal_addr(&IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || (first & 0xfe00) == 0xfc00 // unique local
+                || (first & 0xffc0) == 0xfe80 // link local
+        },
+    }
+}
+
+/// Proxy credentials handed to one agent run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressGrant {
+    pub task_id: i64,
+    pub run_id: String,
+    pub policy: EgressPolicy,
+}
+
+struct GrantEntry {
+    grant: EgressGrant,
+    expires_at: Instant,
+}
+
+/// Live proxy grants keyed by token. Shared between the pipeline, which
+/// issues a grant per phase run, and the egress proxy, which checks them.
+#[derive(Default)]
+pub struct EgressGrants {
+    grants: Mutex<HashMap<String, GrantEntry>>,
+}
+
+impl EgressGrants {
+    /// Register a grant and return its token. Grants expire after `ttl`
+    /// even if the run never revokes them.
+    pub fn issue(&self, task_id: i64, run_id: &str, policy: EgressPolicy, ttl: Duration) -> String {
+        let mut bytes = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+        let mut grants = self.grants.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        grants.retain(|_, g| g.expires_at > now);
+        grants.insert(
+            token.clone(),
+            GrantEntry {
+                grant: EgressGrant {
+                    task_id,
+                    run_id: run_id.to_string(),
+                    policy,
+                },
+                expires_at: now + ttl,
+            },
+        );
+        token
+    }
+
+    pub fn lookup(&self, token: &str) -> Option<EgressGrant> {
+        let grants = self.grants.lock().unwrap_or_else(|e| e.into_inner());
+        grants
+            .get(token)
+            .filter(|g| g.expires_at > Instant::now())
+            .map(|g| g.grant.clone())
+    }
+
+    /// Drop every grant held by `task_id` (called when a phase run ends).
+    pub fn revoke_task(&self, task_id: i64) {
+        let mut grants = self.grants.lock().unwrap_or_else(|e| e.into_inner());
+        grants.retain(|_, g| g.grant.task_id != task_id);
+    }
+}
+
+/// Env vars that route an agent's HTTP(S) traffic through the egress proxy.
+/// `host` is how the agent reaches borg (gateway IP in a container,
+/// 127.0.0.1 otherwise); traffic to borg itself bypasses the proxy.
+pub fn proxy_env(proxy: &crate::types::EgressProxy, host: &str) -> Vec<(String, String)> {
+    let url = format!(
+        "http://task-{}:{}@{host}:{}",
+        proxy.task_id, proxy.token, proxy.port
+    );
+    let no_proxy = format!("{host},localhost,127.0.0.1");
+    let mut env = Vec::new();
+    for key in ["HTTP_PROXY", "HTTPS_PROXY", "http_proxy", "https_proxy"] {
+        env.push((key.to_string(), url.clone()));
+    }
+    for key in ["NO_PROXY", "no_proxy"] {
+        env.push((key.to_string(), no_proxy.clone()));
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_domain_matches_itself_and_subdomains() {
+        let p = EgressPolicy::parse("crates.io, github.com");
+        assert!(p.allows("crates.io"));
+        assert!(p.allows("static.crates.io"));
+        assert!(p.allows("GitHub.com."));
+        assert!(!p.allows("evilgithub.com"));
+        assert!(!p.allows("github.com.evil.net"));
+    }
+
+    #[test]
+    fn wildcard_matches_subdomains_only() {
+        let p = EgressPolicy::new(["*.courtlistener.com"]);
+        assert!(p.allows("www.courtlistener.com"));
+        assert!(!p.allows("courtlistener.com"));
+    }
+
+    #[test]
+    fn entries_are_normalized_and_deduplicated() {
+        let p = EgressPolicy::parse("https://GitHub.com/org, github.com:443 github.com");
+        assert_eq!(p.domains(), ["github.com"]);
+        let merged = p.merge(&EgressPolicy::parse("crates.io"));
+        assert_eq!(merged.domains(), ["github.com", "crates.io"]);
+    }
+
+    #[test]
+    fn ports_beyond_the_scheme_default_must_be_listed() {
+        let p = EgressPolicy::parse("crates.io, github.com:22, [2606:4700::1111]:8443");
+        assert_eq!(
+            p.domains(),
+            ["crates.io", "github.com:22", "[2606:4700::1111]:8443"]
+        );
+        assert!(p.allows_port("crates.io", 443, 443));
+        assert!(p.allows_port("crates.io", 80, 80));
+        assert!(!p.allows_port("crates.io", 22, 443));
+        assert!(!p.allows_port("crates.io", 80, 443));
+        assert!(p.allows_port("ssh.github.com", 22, 443));
+        assert!(p.allows_port("github.com", 443, 443));
+        assert!(!p.allows_port("github.com", 2222, 443));
+        assert!(p.allows_port("2606:4700::1111", 8443, 443));
+    }
+
+    #[test]
+    fn internal_addresses_are_flagged() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.30.0.1",
+            "169.254.169.254",
+            "::1",
+            "fd00::1",
+        ] {
+            assert!(is_internal_addr(&ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["140.82.112.3", "2606:4700::1111"] {
+            assert!(!is_internal_addr(&ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn grants_expire_and_revoke_by_task() {
+        let grants = EgressGrants::default();
+        let policy = EgressPolicy::parse("crates.io");
+        let a = grants.issue(1, "run-a", policy.clone(), Duration::from_secs(60));
+        let b = grants.issue(2, "run-b", policy.clone(), Duration::from_secs(60));
+        let expired = grants.issue(3, "run-c", policy, Duration::ZERO);
+        assert_eq!(grants.lookup(&a).map(|g| g.run_id), Some("run-a".into()));
+        assert!(grants.lookup(&expired).is_none());
+        grants.revoke_task(1);
+        assert!(grants.lookup(&a).is_none());
+        assert!(grants.lookup(&b).is_some());
+    }
+}
@@ -286,4 +286,110 @@ impl Git {
         }
         Ok(())
     }
+
+    /// Local default branch: the one `origin/HEAD` names, else `main`, else
+    /// `master`.
+    pub fn default_branch(&self) -> Option<String> {
+        let origin_head = self
+            .exec(
+                &self.repo_path,
+                &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+            )
+            .ok()
+            .filter(|r| r.success())
+            .and_then(|r| r.stdout.trim().strip_prefix("origin/").map(str::to_string));
+        origin_head
+            .into_iter()
+            .chain(["main".to_string(), "master".to_string()])
+            .find(|b| self.rev_parse(&format!("refs/heads/{b}")).is_ok())
+    }
+
+    /// True if `ancestor` is reachable from `descendant`.
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        let result = self.exec(
+            &self.repo_path,
+            &["merge-base", "--is-ancestor", ancestor, descendant],
+        )?;
+        match result.exit_code {
+            0 => Ok(true),
+            1 => Ok(false),
+            _ => Err(anyhow!(
+                "git merge-base --is-ancestor {ancestor} {descendant} failed: {}",
+                result.combined_output()
+            )),
+        }
+    }
+
+    /// Fast-forward `branch` from `old` to `new`. If `branch` is checked out
+    /// in the main worktree its files are updated too; otherwise only the ref
+    /// moves. Fails if `branch` no longer points at `old`.
+    pub fn fast_forward(&self, branch: &str, new: &str, old: &str) -> Result<()> {
+        if !self.is_ancestor(old, new)? {
+            return Err(anyhow!("{new} is not a fast-forward of {branch}"));
+        }
+        let head = self.exec(
+            &self.repo_path,
+            &["symbolic-ref", "--quiet", "--short", "HEAD"],
+        )?;
+        let bare = self.exec(&self.repo_path, &["rev-parse", "--is-bare-repository"])?;
+        let result = if head.stdout.trim() == branch && bare.stdout.trim() != "true" {
+            if self.rev_parse(branch)? != old {
+                return Err(anyhow!("{branch} moved while merging"));
+            }
+            self.exec(&self.repo_path, &["merge", "--ff-only", new])?
+        } else {
+            let refname = format!("refs/heads/{branch}");
+            self.exec(&self.repo_path, &["update-ref", &refname, new, old])?
+        };
+        if !result.success() {
+            return Err(anyhow!(
+                "fast-forward {branch} to {new} failed: {}",
+                result.combined_output()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Paths `head` changes relative to its merge base with `base`. A rename
+    /// lists both its old and new path.
+    pub fn changed_files(&self, base: &str, head: &str) -> Result<Vec<String>> {
+        let range = format!("{base}...{head}");
+        let result = self.exec(
+            &self.repo_path,
+            &["diff", "--name-only", "--no-renames", &range],
+        )?;
+        if !result.success() {
+            return Err(anyhow!(
+                "git diff --name-only {range} failed: {}",
+                result.combined_output()
+            ));
+        }
+        Ok(result
+            .stdout
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Contents of `path` at `rev`, or `None` if it does not exist there.
+    pub fn show_file(&self, rev: &str, path: &str) -> Option<String> {
+        self.exec(&self.repo_path, &["show", &format!("{rev}:{path}")])
+            .ok()
+            .filter(|r| r.success())
+            .map(|r| r.stdout)
+    }
+
+    /// `origin` URL when it is a repository on this filesystem (e.g. a bare
+    /// remote on a shared disk).
+    pub fn local_origin(&self) -> Option<String> {
+        let result = self
+            .exec(&self.repo_path, &["remote", "get-url", "origin"])
+            .ok()
+            .filter(|r| r.success())?;
+        let url = result.stdout.trim();
+        let path = url.strip_prefix("file://").unwrap_or(url);
+        std::path::Path::new(path).is_dir().then(|| url.to_string())
+    }
 }
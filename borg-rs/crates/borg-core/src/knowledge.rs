@@ -736,6 +736,7 @@ pub async fn get_prior_research(
     match embed_client.embed_query(query).await {
         Ok(query_emb) => db
             .search_embeddings(&query_emb, limit, project_id)
+            .await
             .unwrap_or_default()
             .into_iter()
             .filter(|r| r.score > 0.5)
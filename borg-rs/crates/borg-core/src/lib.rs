@@ -1,13 +1,18 @@
+pub mod affected;
 pub mod agent;
 pub mod chat;
+pub mod codeowners;
 pub mod config;
 pub mod cron;
 pub mod db;
+pub mod egress;
 pub mod email;
+pub mod forge;
 pub mod git;
 pub mod ipc;
 pub mod knowledge;
 pub mod linked_credentials;
+pub mod migrations;
 pub mod modes;
 pub mod observer;
 pub mod parser;
@@ -15,14 +20,21 @@ pub mod pgcompat;
 pub mod pipeline;
 mod pipeline_maintenance;
 pub mod registry;
+pub mod repo_image;
+pub mod resources;
+pub mod retention;
 pub mod sandbox;
 pub mod secrets;
 pub mod sidecar;
 pub mod stream;
 pub mod telegram;
+pub mod test_report;
 pub mod tool_calls;
 pub mod traits;
 pub mod types;
+pub mod warm_pool;
+pub mod webhook;
+pub mod worker;
 
 pub use traits::*;
 pub use types::*;
@@ -0,0 +1,367 @@
+//! Versioned schema migrations.
+//!
+//! `schema.pg.sql` is frozen as migration 1 (the baseline). Every later schema
+//! change is a numbered `migrations/NNNN_name.up.sql`, optionally paired with a
+//! `.down.sql`, registered in [`MIGRATIONS`]. Applied versions and the checksum
+//! of the SQL that ran are recorded in `schema_migrations`, so an edited
+//! migration is caught instead of silently diverging between instances.
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: Option<&'static str>,
+}
+
+impl Migration {
+    pub fn checksum(&self) -> String {
+        checksum(self.up)
+    }
+}
+
+/// All known migrations in ascending version order.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        up: include_str!("../../../schema.pg.sql"),
+        down: None,
+    },
+    Migration {
+        version: 2,
+        name: "retention_policies",
+        up: include_str!("../../../migrations/0002_retention_policies.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0002_retention_policies.down.sql"
+        )),
+    },
+    Migration {
+        version: 3,
+        name: "workspace_chat_backend",
+        up: include_str!("../../../migrations/0003_workspace_chat_backend.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0003_workspace_chat_backend.down.sql"
+        )),
+    },
+    Migration {
+        version: 4,
+        name: "task_egress_allowlist",
+        up: include_str!("../../../migrations/0004_task_egress_allowlist.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0004_task_egress_allowlist.down.sql"
+        )),
+    },
+    Migration {
+        version: 5,
+        name: "task_output_resources",
+        up: include_str!("../../../migrations/0005_task_output_resources.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0005_task_output_resources.down.sql"
+        )),
+    },
+    Migration {
+        version: 6,
+        name: "repo_container_image",
+        up: include_str!("../../../migrations/0006_repo_container_image.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0006_repo_container_image.down.sql"
+        )),
+    },
+    Migration {
+        version: 7,
+        name: "repo_forge",
+        up: include_str!("../../../migrations/0007_repo_forge.up.sql"),
+        down: Some(include_str!("../../../migrations/0007_repo_forge.down.sql")),
+    },
+    Migration {
+        version: 8,
+        name: "pr_review_comments",
+        up: include_str!("../../../migrations/0008_pr_review_comments.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0008_pr_review_comments.down.sql"
+        )),
+    },
+    Migration {
+        version: 9,
+        name: "task_base_task",
+        up: include_str!("../../../migrations/0009_task_base_task.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0009_task_base_task.down.sql"
+        )),
+    },
+    Migration {
+        version: 10,
+        name: "test_flakiness",
+        up: include_str!("../../../migrations/0010_test_flakiness.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0010_test_flakiness.down.sql"
+        )),
+    },
+    Migration {
+        version: 11,
+        name: "task_test_failures",
+        up: include_str!("../../../migrations/0011_task_test_failures.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0011_task_test_failures.down.sql"
+        )),
+    },
+    Migration {
+        version: 12,
+        name: "task_retention_purged",
+        up: include_str!("../../../migrations/0012_task_retention_purged.up.sql"),
+        down: Some(include_str!(
+            "../../../migrations/0012_task_retention_purged.down.sql"
+        )),
+    },
+];
+
+pub fn checksum(sql: &str) -> String {
+    hex::encode(Sha256::digest(sql.as_bytes()))
+}
+
+/// A row from `schema_migrations`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the SQL in this build no longer matches the recorded checksum.
+    Modified,
+    /// Recorded in the database but unknown to this build (e.g. after a downgrade).
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: String,
+    pub state: MigrationState,
+    pub applied_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// One migration to run, with the SQL that will be executed.
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStep {
+    pub version: i64,
+    pub name: String,
+    pub direction: Direction,
+    pub checksum: String,
+    pub sql: String,
+}
+
+pub fn status(migrations: &[Migration], applied: &[AppliedMigration]) -> Vec<MigrationStatus> {
+    let mut out: Vec<MigrationStatus> = migrations
+        .iter()
+        .map(|m| {
+            let row = applied.iter().find(|a| a.version == m.version);
+            let state = match row {
+                None => MigrationState::Pending,
+                Some(a) if a.checksum != m.checksum() => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: m.version,
+                name: m.name.to_string(),
+                state,
+                applied_at: row.map(|a| a.applied_at.clone()),
+            }
+        })
+        .collect();
+    for a in applied {
+        if !migrations.iter().any(|m| m.version == a.version) {
+            out.push(MigrationStatus {
+                version: a.version,
+                name: a.name.clone(),
+                state: MigrationState::Unknown,
+                applied_at: Some(a.applied_at.clone()),
+            });
+        }
+    }
+    out.sort_by_key(|s| s.version);
+    out
+}
+
+fn check_unmodified(migrations: &[Migration], applied: &[AppliedMigration]) -> Result<()> {
+    let modified: Vec<String> = status(migrations, applied)
+        .into_iter()
+        .filter(|s| s.state == MigrationState::Modified)
+        .map(|s| format!("{:04}_{}", s.version, s.name))
+        .collect();
+    if !modified.is_empty() {
+        bail!(
+            "applied migrations were edited after they ran: {}; add a new migration instead",
+            modified.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Pending migrations up to and including `target` (all of them when `None`).
+pub fn plan_up(
+    migrations: &[Migration],
+    applied: &[AppliedMigration],
+    target: Option<i64>,
+) -> Result<Vec<MigrationStep>> {
+    check_unmodified(migrations, applied)?;
+    let mut versions: Vec<i64> = migrations.iter().map(|m| m.version).collect();
+    versions.dedup();
+    if versions.len() != migrations.len() || !versions.windows(2).all(|w| w[0] < w[1]) {
+        bail!("migrations must have unique, ascending versions");
+    }
+    Ok(migrations
+        .iter()
+        .filter(|m| target.is_none_or(|t| m.version <= t))
+        .filter(|m| !applied.iter().any(|a| a.version == m.version))
+        .map(|m| MigrationStep {
+            version: m.version,
+            name: m.name.to_string(),
+            direction: Direction::Up,
+            checksum: m.checksum(),
+            sql: m.up.to_string(),
+        })
+        .collect())
+}
+
+/// Applied migrations above `target`, newest first, using their down SQL.
+pub fn plan_down(
+    migrations: &[Migration],
+    applied: &[AppliedMigration],
+    target: i64,
+) -> Result<Vec<MigrationStep>> {
+    check_unmodified(migrations, applied)?;
+    let mut to_revert: Vec<&AppliedMigration> =
+        applied.iter().filter(|a| a.version > target).collect();
+    to_revert.sort_by_key(|a| std::cmp::Reverse(a.version));
+    to_revert
+        .into_iter()
+        .map(|a| {
+            let Some(m) = migrations.iter().find(|m| m.version == a.version) else {
+                bail!(
+                    "migration {:04}_{} is not known to this build; cannot revert it",
+                    a.version,
+                    a.name
+                );
+            };
+            let Some(down) = m.down else {
+                bail!(
+                    "migration {:04}_{} has no down migration",
+                    m.version,
+                    m.name
+                );
+            };
+            Ok(MigrationStep {
+                version: m.version,
+                name: m.name.to_string(),
+                direction: Direction::Down,
+                checksum: m.checksum(),
+                sql: down.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "baseline",
+            up: "CREATE TABLE a (id BIGINT);",
+            down: None,
+        },
+        Migration {
+            version: 2,
+            name: "add_b",
+            up: "CREATE TABLE b (id BIGINT);",
+            down: Some("DROP TABLE b;"),
+        },
+        Migration {
+            version: 3,
+            name: "add_c",
+            up: "CREATE TABLE c (id BIGINT);",
+            down: Some("DROP TABLE c;"),
+        },
+    ];
+
+    fn applied(m: &Migration) -> AppliedMigration {
+        AppliedMigration {
+            version: m.version,
+            name: m.name.to_string(),
+            checksum: m.checksum(),
+            applied_at: "2026-01-01 00:00:00".into(),
+        }
+    }
+
+    #[test]
+    fn plan_up_returns_pending_in_order() {
+        let done = vec![applied(&FIXTURE[0])];
+        let steps = plan_up(FIXTURE, &done, None).unwrap();
+        assert_eq!(
+            steps.iter().map(|s| s.version).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        let steps = plan_up(FIXTURE, &done, Some(2)).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].sql, "CREATE TABLE b (id BIGINT);");
+    }
+
+    #[test]
+    fn edited_migration_is_rejected() {
+        let mut row = applied(&FIXTURE[1]);
+        row.checksum = checksum("CREATE TABLE b (id INT);");
+        let done = vec![applied(&FIXTURE[0]), row];
+        let st = status(FIXTURE, &done);
+        assert_eq!(st[1].state, MigrationState::Modified);
+        assert_eq!(st[2].state, MigrationState::Pending);
+        let err = plan_up(FIXTURE, &done, None).unwrap_err().to_string();
+        assert!(err.contains("0002_add_b"), "{err}");
+    }
+
+    #[test]
+    fn plan_down_reverts_newest_first_and_stops_at_baseline() {
+        let done: Vec<_> = FIXTURE.iter().map(applied).collect();
+        let steps = plan_down(FIXTURE, &done, 1).unwrap();
+        assert_eq!(
+            steps.iter().map(|s| s.version).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+        assert_eq!(steps[0].direction, Direction::Down);
+        assert!(plan_down(FIXTURE, &done, 0).is_err());
+    }
+
+    #[test]
+    fn unknown_applied_versions_are_reported() {
+        let mut done: Vec<_> = FIXTURE.iter().map(applied).collect();
+        done.push(AppliedMigration {
+            version: 9,
+            name: "future".into(),
+            checksum: String::new(),
+            applied_at: String::new(),
+        });
+        let st = status(FIXTURE, &done);
+        assert_eq!(st.last().map(|s| s.state), Some(MigrationState::Unknown));
+        assert!(plan_up(FIXTURE, &done, None).unwrap().is_empty());
+        assert!(plan_down(FIXTURE, &done, 3).is_err());
+    }
+}
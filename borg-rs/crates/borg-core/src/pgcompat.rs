@@ -1,6 +1,6 @@
 use std::{cell::Cell, fmt, marker::PhantomData, sync::Arc, time::Duration};
 
-use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
+use deadpool_postgres::{GenericClient, Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
 use tokio_postgres::{types::ToSql as PgToSql, NoTls, Row as PgRow};
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -264,13 +264,15 @@ impl Connection {
         let conn = Self {
             pool: Arc::new(pool),
         };
-        conn.guard()
+        conn.session()
             .execute_batch("SELECT 1")
             .map_err(|e| Error::ConfigParse(format!("startup connectivity check failed: {e}")))?;
         Ok(conn)
     }
 
-    fn guard(&self) -> ConnectionGuard {
+    /// Checks out a pooled client for the synchronous, blocking API. Each
+    /// call gets its own session, so callers never serialize on one another.
+    pub fn session(&self) -> ConnectionGuard {
         let client = match block_on(self.pool.get()) {
             Ok(client) => client,
             Err(_) => {
@@ -283,38 +285,37 @@ impl Connection {
         };
         ConnectionGuard::ready(client)
     }
-}
-
-type PoolClient = deadpool_postgres::Object;
-
-pub struct Mutex<T> {
-    inner: T,
-}
 
-impl<T> Mutex<T> {
-    pub fn new(inner: T) -> Self {
-        Self { inner }
+    /// Checks out a pooled client without blocking the calling worker thread.
+    pub async fn client(&self) -> Result<AsyncSession> {
+        let client = match self.pool.get().await {
+            Ok(client) => client,
+            Err(_) => self.pool.get().await?,
+        };
+        Ok(AsyncSession { client })
     }
-}
-
-pub struct PoisonError<T> {
-    inner: T,
-}
 
-impl<T> PoisonError<T> {
-    pub fn into_inner(self) -> T {
-        self.inner
+    pub fn status(&self) -> PoolStatus {
+        let status = self.pool.status();
+        PoolStatus {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+        }
     }
 }
 
-pub type LockResult<T> = std::result::Result<T, PoisonError<T>>;
-
-impl Mutex<Connection> {
-    pub fn lock(&self) -> LockResult<ConnectionGuard> {
-        Ok(self.inner.guard())
-    }
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct PoolStatus {
+    pub max_size: usize,
+    pub size: usize,
+    pub available: usize,
+    pub waiting: usize,
 }
 
+type PoolClient = deadpool_postgres::Object;
+
 enum GuardState {
     Ready(Option<PoolClient>),
     Failed(String),
@@ -491,6 +492,144 @@ impl Drop for Transaction<'_> {
     }
 }
 
+/// A pooled client checked out for async use. Queries await the server
+/// instead of parking a runtime worker, and the client returns to the pool
+/// when the session is dropped.
+pub struct AsyncSession {
+    client: PoolClient,
+}
+
+impl AsyncSession {
+    pub async fn execute_batch(&self, sql: &str) -> Result<()> {
+        self.client.batch_execute(&translate_sql(sql)).await?;
+        Ok(())
+    }
+
+    pub async fn execute<P: ParamsLike>(&self, sql: &str, params: P) -> Result<usize> {
+        async_execute(&self.client, sql, params.into_params()).await
+    }
+
+    pub async fn execute_returning_id<P: ParamsLike>(&self, sql: &str, params: P) -> Result<i64> {
+        async_execute_returning_id(&self.client, sql, params.into_params()).await
+    }
+
+    pub async fn query_row<P: ParamsLike, T>(
+        &self,
+        sql: &str,
+        params: P,
+        mapper: impl FnOnce(&Row<'_>) -> Result<T>,
+    ) -> Result<T> {
+        let row = async_query_opt(&self.client, sql, params.into_params()).await?;
+        mapper(&Row::new(row.ok_or(Error::QueryReturnedNoRows)?))
+    }
+
+    pub async fn query_map<P: ParamsLike, T>(
+        &self,
+        sql: &str,
+        params: P,
+        mapper: impl FnMut(&Row<'_>) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let rows = async_query(&self.client, sql, params.into_params()).await?;
+        map_rows(rows, mapper)
+    }
+
+    /// Starts an explicit transaction. It rolls back on drop unless
+    /// [`AsyncTransaction::commit`] is called.
+    pub async fn transaction(&mut self) -> Result<AsyncTransaction<'_>> {
+        let tx = self.client.transaction().await?;
+        Ok(AsyncTransaction { tx })
+    }
+}
+
+pub struct AsyncTransaction<'a> {
+    tx: deadpool_postgres::Transaction<'a>,
+}
+
+impl AsyncTransaction<'_> {
+    pub async fn execute<P: ParamsLike>(&self, sql: &str, params: P) -> Result<usize> {
+        async_execute(&self.tx, sql, params.into_params()).await
+    }
+
+    pub async fn execute_returning_id<P: ParamsLike>(&self, sql: &str, params: P) -> Result<i64> {
+        async_execute_returning_id(&self.tx, sql, params.into_params()).await
+    }
+
+    pub async fn query_row<P: ParamsLike, T>(
+        &self,
+        sql: &str,
+        params: P,
+        mapper: impl FnOnce(&Row<'_>) -> Result<T>,
+    ) -> Result<T> {
+        let row = async_query_opt(&self.tx, sql, params.into_params()).await?;
+        mapper(&Row::new(row.ok_or(Error::QueryReturnedNoRows)?))
+    }
+
+    pub async fn query_map<P: ParamsLike, T>(
+        &self,
+        sql: &str,
+        params: P,
+        mapper: impl FnMut(&Row<'_>) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let rows = async_query(&self.tx, sql, params.into_params()).await?;
+        map_rows(rows, mapper)
+    }
+
+    pub async fn commit(self) -> Result<()> {
+        self.tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn rollback(self) -> Result<()> {
+        self.tx.rollback().await?;
+        Ok(())
+    }
+}
+
+async fn async_execute<C: GenericClient>(
+    client: &C,
+    sql: &str,
+    params: Vec<Param>,
+) -> Result<usize> {
+    let translated = translate_sql(sql);
+    let refs = pg_refs(&params);
+    Ok(client.execute(translated.as_str(), &refs).await? as usize)
+}
+
+async fn async_execute_returning_id<C: GenericClient>(
+    client: &C,
+    sql: &str,
+    params: Vec<Param>,
+) -> Result<i64> {
+    let returning_sql = append_returning_id(&translate_sql(sql));
+    let refs = pg_refs(&params);
+    let row = client.query_one(returning_sql.as_str(), &refs).await?;
+    Ok(row.try_get(0)?)
+}
+
+async fn async_query_opt<C: GenericClient>(
+    client: &C,
+    sql: &str,
+    params: Vec<Param>,
+) -> Result<Option<PgRow>> {
+    let translated = translate_sql(sql);
+    let refs = pg_refs(&params);
+    Ok(client.query_opt(translated.as_str(), &refs).await?)
+}
+
+async fn async_query<C: GenericClient>(
+    client: &C,
+    sql: &str,
+    params: Vec<Param>,
+) -> Result<Vec<PgRow>> {
+    let translated = translate_sql(sql);
+    let refs = pg_refs(&params);
+    Ok(client.query(translated.as_str(), &refs).await?)
+}
+
+fn map_rows<T>(rows: Vec<PgRow>, mut mapper: impl FnMut(&Row<'_>) -> Result<T>) -> Result<Vec<T>> {
+    rows.into_iter().map(|row| mapper(&Row::new(row))).collect()
+}
+
 pub struct Row<'a> {
     row: PgRow,
     _marker: PhantomData<&'a ()>,
@@ -535,6 +674,8 @@ impl RowIndex for u32 {
     }
 }
 
+/// Drives `f` for the sync API. On a multi-thread runtime this parks the
+/// current worker with `block_in_place` for the whole query.
 fn block_on<F: std::future::Future>(f: F) -> F::Output {
     if let Ok(handle) = tokio::runtime::Handle::try_current() {
         tokio::task::block_in_place(|| handle.block_on(f))
@@ -1,14 +1,14 @@
 use anyhow::Result;
 use tracing::{info, warn};
 
-use crate::{git::Git, pipeline::Pipeline, sandbox::SandboxMode, types::Task};
+use crate::{git::Git, pipeline::Pipeline, types::Task};
 
 impl Pipeline {
     // ── Health monitoring ─────────────────────────────────────────────────
 
     pub async fn check_health(&self) -> Result<()> {
-        // In Docker mode, repos are not checked out on the host; skip host-side health checks.
-        if self.sandbox_mode == SandboxMode::Docker {
+        // In container mode, repos are not checked out on the host; skip host-side health checks.
+        if self.sandbox_mode.is_container() {
             return Ok(());
         }
 
@@ -0,0 +1,501 @@
+//! Per-repository agent images.
+//!
+//! A repo can pin a prebuilt image (`RepoConfig::container_image`) or ship a
+//! container file (`RepoConfig::container_file`, else `.borg/Containerfile`
+//! or `.borg/Dockerfile`). Container files are read from the repo's default
+//! branch, never from a task worktree, and built by borg-server from a clean
+//! export of the file's directory at that commit. The tag is derived from
+//! the directory's git tree, so an unchanged definition is built once and any
+//! committed edit produces a new tag. Repos with neither use the global
+//! `CONTAINER_IMAGE`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::{git::Git, sandbox::Sandbox, types::RepoConfig};
+
+/// Container files looked up in the repo when `container_file` is unset.
+pub const DEFAULT_CONTAINER_FILES: &[&str] = &[".borg/Containerfile", ".borg/Dockerfile"];
+
+/// Refs tried, in order, for the commit container files are read from.
+const SOURCE_REFS: &[&str] = &["origin/main", "origin/master", "main", "master"];
+
+/// A failed build is not retried for the same tag until this has passed.
+const FAILED_BUILD_BACKOFF: Duration = Duration::from_secs(600);
+
+/// A container file as committed on the repo's default branch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImageSource {
+    /// Default-branch commit the file was read from.
+    pub commit: String,
+    /// Repo-relative path of the container file.
+    pub file: String,
+    /// Repo-relative build context (the file's directory; empty = root).
+    pub context: String,
+    /// `borg-repo-<name>:<hash>`.
+    pub tag: String,
+}
+
+/// Commit at the tip of `repo`'s default branch, if it is a git repo.
+pub fn default_branch_commit(repo: &RepoConfig) -> Option<String> {
+    let git = Git::new(&repo.path);
+    let start = git.resolve_start_ref(SOURCE_REFS).ok()?;
+    git.rev_parse(&format!("{start}^{{commit}}")).ok()
+}
+
+/// Locate `repo`'s container file at `commit` and derive its tag. Files
+/// that exist only in a worktree or the index are ignored.
+pub fn image_source(repo: &RepoConfig, commit: &str) -> Result<Option<ImageSource>> {
+    let git = Git::new(&repo.path);
+    let candidates: Vec<&str> = if repo.container_file.is_empty() {
+        DEFAULT_CONTAINER_FILES.to_vec()
+    } else {
+        vec![repo.container_file.as_str()]
+    };
+    let is_blob = |path: &str| -> Result<bool> {
+        let r = git.exec(&repo.path, &["cat-file", "-t", &format!("{commit}:{path}")])?;
+        Ok(r.success() && r.stdout.trim() == "blob")
+    };
+    let mut found = None;
+    for path in candidates {
+        if is_blob(path)? {
+            found = Some(path);
+            break;
+        }
+    }
+    let Some(file) = found else {
+        return Ok(None);
+    };
+    let context = Path::new(file)
+        .parent()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tree = git.rev_parse(&format!("{commit}:{context}"))?;
+    Ok(Some(ImageSource {
+        commit: commit.to_string(),
+        file: file.to_string(),
+        tag: image_tag(&repo.path, &tree, file),
+        context,
+    }))
+}
+
+/// Tag for a build of `file` from the context tree `tree`:
+/// `borg-repo-<name>:<hash>`. The tree id covers the file's contents.
+pub fn image_tag(repo_path: &str, tree: &str, file: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(tree.as_bytes());
+    hasher.update([0]);
+    hasher.update(file.as_bytes());
+    let digest = hex::encode(hasher.finalize());
+    format!("{}:{}", image_name(repo_path), &digest[..16])
+}
+
+/// Docker-safe repository name derived from the repo directory name.
+fn image_name(repo_path: &str) -> String {
+    let base = Path::new(repo_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut name: String = base
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    name = name
+        .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_string();
+    if name.is_empty() {
+        name = "repo".to_string();
+    }
+    format!("borg-repo-{name}")
+}
+
+/// Write `source.context` at `source.commit` into `dest`, which must exist.
+fn export_context(repo_path: &str, source: &ImageSource, dest: &Path) -> Result<()> {
+    let tree = format!("{}:{}", source.commit, source.context);
+    let mut archive = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo_path)
+        .args(["archive", "--format=tar", &tree])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("spawn git archive")?;
+    let Some(tar_in) = archive.stdout.take() else {
+        bail!("git archive has no stdout");
+    };
+    let untar = std::process::Command::new("tar")
+        .arg("-x")
+        .arg("-C")
+        .arg(dest)
+        .stdin(tar_in)
+        .stderr(Stdio::piped())
+        .output()
+        .context("spawn tar")?;
+    let archived = archive.wait().context("wait for git archive")?;
+    if !archived.success() {
+        bail!("git archive {tree} failed");
+    }
+    if !untar.status.success() {
+        bail!(
+            "extract {tree}: {}",
+            String::from_utf8_lossy(&untar.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct BuildState {
+    /// Per-tag lock so concurrent phases wait on a single build.
+    locks: HashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    /// Tags known to exist locally.
+    built: HashSet<String>,
+    /// Tags whose last build failed, and when.
+    failed: HashMap<String, Instant>,
+    /// Resolved sources by (repo path, configured file, commit).
+    sources: HashMap<(String, String, String), Option<ImageSource>>,
+}
+
+/// Builds and caches repo images. One instance is shared by the pipeline.
+#[derive(Default)]
+pub struct RepoImages {
+    state: Mutex<BuildState>,
+}
+
+impl RepoImages {
+    fn lock(&self) -> std::sync::MutexGuard<'_, BuildState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Image to run `repo`'s agents and tests in. `Ok(None)` means the repo
+    /// defines no image and the global default applies.
+    pub async fn resolve(&self, repo: &RepoConfig) -> Result<Option<String>> {
+        if !repo.container_image.is_empty() {
+            return Ok(Some(repo.container_image.clone()));
+        }
+        let Some(source) = self.source(repo).await? else {
+            return Ok(None);
+        };
+        self.ensure_built(&repo.path, &source).await?;
+        Ok(Some(source.tag))
+    }
+
+    /// `repo`'s container file at its default-branch tip, cached by commit.
+    async fn source(&self, repo: &RepoConfig) -> Result<Option<ImageSource>> {
+        let cfg = repo.clone();
+        let commit = tokio::task::spawn_blocking(move || default_branch_commit(&cfg))
+            .await
+            .context("resolve default branch")?;
+        let Some(commit) = commit else {
+            return Ok(None);
+        };
+        let key = (repo.path.clone(), repo.container_file.clone(), commit);
+        if let Some(source) = self.lock().sources.get(&key) {
+            return Ok(source.clone());
+        }
+        let (cfg, commit) = (repo.clone(), key.2.clone());
+        let source = tokio::task::spawn_blocking(move || image_source(&cfg, &commit))
+            .await
+            .context("locate container file")??;
+        self.lock().sources.insert(key, source.clone());
+        Ok(source)
+    }
+
+    /// Build `source.tag` unless it already exists locally.
+    pub async fn ensure_built(&self, repo_path: &str, source: &ImageSource) -> Result<()> {
+        let tag = source.tag.as_str();
+        let build_lock = {
+            let mut state = self.lock();
+            if state.built.contains(tag) {
+                return Ok(());
+            }
+            if let Some(at) = state.failed.get(tag) {
+                if at.elapsed() < FAILED_BUILD_BACKOFF {
+                    bail!("previous build of {tag} failed; retrying after backoff");
+                }
+            }
+            Arc::clone(state.locks.entry(tag.to_string()).or_default())
+        };
+        let _guard = build_lock.lock().await;
+        {
+            let state = self.lock();
+            if state.built.contains(tag) {
+                return Ok(());
+            }
+            if state
+                .failed
+                .get(tag)
+                .is_some_and(|at| at.elapsed() < FAILED_BUILD_BACKOFF)
+            {
+                bail!("build of {tag} failed; retrying after backoff");
+            }
+        }
+        if image_exists(tag).await {
+            self.lock().built.insert(tag.to_string());
+            return Ok(());
+        }
+
+        info!(tag, file = %source.file, commit = %source.commit, "building repo image");
+        let started = Instant::now();
+        let out = self.build(repo_path, source).await;
+        let mut state = self.lock();
+        state.locks.remove(tag);
+        let out = match out {
+            Ok(out) => out,
+            Err(e) => {
+                state.failed.insert(tag.to_string(), Instant::now());
+                return Err(e);
+            },
+        };
+        if !out.status.success() {
+            state.failed.insert(tag.to_string(), Instant::now());
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+            let tail: Vec<&str> = tail.into_iter().rev().collect();
+            warn!(tag, "repo image build failed");
+            bail!("build of {tag} failed:\n{}", tail.join("\n"));
+        }
+        state.failed.remove(tag);
+        state.built.insert(tag.to_string());
+        info!(tag, secs = started.elapsed().as_secs(), "repo image built");
+        Ok(())
+    }
+
+    /// Export the committed context to a temp dir and build it there.
+    async fn build(&self, repo_path: &str, source: &ImageSource) -> Result<std::process::Output> {
+        let dir = tempfile::Builder::new()
+            .prefix("borg-image-")
+            .tempdir()
+            .context("create build context dir")?;
+        let (path, src, dest) = (
+            repo_path.to_string(),
+            source.clone(),
+            dir.path().to_path_buf(),
+        );
+        tokio::task::spawn_blocking(move || export_context(&path, &src, &dest))
+            .await
+            .context("export build context")??;
+        let name = Path::new(&source.file)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Command::new(Sandbox::cli())
+            .arg("build")
+            .arg("-t")
+            .arg(&source.tag)
+            .arg("-f")
+            .arg(dir.path().join(name))
+            .arg("--label")
+            .arg("borg-repo-image=1")
+            .arg(dir.path())
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .with_context(|| format!("spawn {} build", Sandbox::cli()))
+    }
+
+    /// Forget cached build results so the next `resolve` re-checks the engine.
+    pub fn invalidate(&self) {
+        let mut state = self.lock();
+        state.built.clear();
+        state.failed.clear();
+    }
+}
+
+async fn image_exists(tag: &str) -> bool {
+    Command::new(Sandbox::cli())
+        .args(["image", "inspect", tag])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn temp_repo(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "borg-repo-image-{label}-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(dir.join(".borg")).unwrap();
+        git(&dir, &["init", "-q", "-b", "main"]);
+        dir
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let out = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args([
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "git {args:?}: {out:?}");
+    }
+
+    fn commit(dir: &Path) -> String {
+        git(dir, &["add", "-A"]);
+        git(dir, &["commit", "-q", "--allow-empty", "-m", "x"]);
+        default_branch_commit(&repo(dir)).unwrap()
+    }
+
+    fn repo(path: &Path) -> RepoConfig {
+        RepoConfig {
+            path: path.to_string_lossy().to_string(),
+            ..RepoConfig::default()
+        }
+    }
+
+    fn found(cfg: &RepoConfig, commit: &str) -> Option<String> {
+        image_source(cfg, commit).unwrap().map(|s| s.file)
+    }
+
+    #[test]
+    fn container_file_prefers_configured_then_defaults() {
+        let dir = temp_repo("find");
+        let mut cfg = repo(&dir);
+        let c = commit(&dir);
+        assert_eq!(found(&cfg, &c), None);
+
+        std::fs::write(dir.join(".borg/Dockerfile"), "FROM a").unwrap();
+        let c = commit(&dir);
+        assert_eq!(found(&cfg, &c).as_deref(), Some(".borg/Dockerfile"));
+        std::fs::write(dir.join(".borg/Containerfile"), "FROM b").unwrap();
+        let c = commit(&dir);
+        assert_eq!(found(&cfg, &c).as_deref(), Some(".borg/Containerfile"));
+
+        std::fs::write(dir.join("Dockerfile"), "FROM c").unwrap();
+        let c = commit(&dir);
+        cfg.container_file = "Dockerfile".into();
+        let source = image_source(&cfg, &c).unwrap().unwrap();
+        assert_eq!(
+            (source.file.as_str(), source.context.as_str()),
+            ("Dockerfile", "")
+        );
+        cfg.container_file = "missing/Dockerfile".into();
+        assert_eq!(found(&cfg, &c), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn uncommitted_and_worktree_files_are_ignored() {
+        let dir = temp_repo("worktree");
+        let cfg = repo(&dir);
+        let c = commit(&dir);
+        std::fs::write(dir.join(".borg/Containerfile"), "FROM evil").unwrap();
+        git(&dir, &["add", "-A"]);
+        assert_eq!(found(&cfg, &c), None);
+
+        // A commit on a task branch does not move the default branch.
+        git(&dir, &["checkout", "-q", "-b", "task-1"]);
+        git(&dir, &["commit", "-q", "-m", "agent"]);
+        assert_eq!(default_branch_commit(&cfg).as_deref(), Some(c.as_str()));
+        assert_eq!(found(&cfg, &c), None);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn image_tag_changes_with_file_and_context() {
+        let dir = temp_repo("My Repo");
+        let cfg = repo(&dir);
+        std::fs::write(dir.join(".borg/Containerfile"), "FROM rust:1").unwrap();
+        let c = commit(&dir);
+        let tag = |c: &str| image_source(&cfg, c).unwrap().unwrap().tag;
+
+        let a = tag(&c);
+        assert!(a.starts_with("borg-repo-borg-repo-image-my-repo-"), "{a}");
+        assert_eq!(a.rsplit(':').next().unwrap().len(), 16);
+
+        // Changes outside the context keep the tag.
+        std::fs::write(dir.join("README"), "hi").unwrap();
+        let c = commit(&dir);
+        assert_eq!(a, tag(&c));
+
+        std::fs::write(dir.join(".borg/setup.sh"), "echo hi").unwrap();
+        let c = commit(&dir);
+        let b = tag(&c);
+        assert_ne!(a, b);
+
+        std::fs::write(dir.join(".borg/Containerfile"), "FROM rust:nightly").unwrap();
+        let c = commit(&dir);
+        assert_ne!(b, tag(&c));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn context_is_exported_from_the_commit() {
+        let dir = temp_repo("export");
+        let cfg = repo(&dir);
+        std::fs::write(dir.join(".borg/Containerfile"), "FROM a").unwrap();
+        let c = commit(&dir);
+        std::fs::write(dir.join(".borg/Containerfile"), "FROM b").unwrap();
+        std::fs::write(dir.join(".borg/untracked"), "x").unwrap();
+
+        let source = image_source(&cfg, &c).unwrap().unwrap();
+        let out = tempfile::tempdir().unwrap();
+        export_context(&cfg.path, &source, out.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(out.path().join("Containerfile")).unwrap(),
+            "FROM a"
+        );
+        assert!(!out.path().join("untracked").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn pinned_image_wins_and_plain_repo_uses_default() {
+        let dir = temp_repo("pin");
+        let images = RepoImages::default();
+        let mut cfg = repo(&dir);
+        commit(&dir);
+        assert_eq!(images.resolve(&cfg).await.unwrap(), None);
+
+        std::fs::write(dir.join(".borg/Containerfile"), "FROM x").unwrap();
+        commit(&dir);
+        cfg.container_image = "ghcr.io/acme/tool@sha256:abc".into();
+        assert_eq!(
+            images.resolve(&cfg).await.unwrap().as_deref(),
+            Some("ghcr.io/acme/tool@sha256:abc")
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
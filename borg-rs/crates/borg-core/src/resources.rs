@@ -0,0 +1,402 @@
+//! Per-phase resource accounting for agent runs.
+//!
+//! Container runs are measured from the container's own cgroup (resolved
+//! through the engine's `inspect` pid). Bwrap runs are moved into a child
+//! cgroup of borg's own when the hierarchy is delegated to us, and otherwise
+//! fall back to summing the process tree from `/proc`. Both are sampled
+//! periodically because a container's cgroup vanishes the moment it exits.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::oneshot, task::JoinHandle};
+use tracing::debug;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+/// Stop walking a worktree after this many entries; a phase that produced
+/// more than this is already an outlier worth looking at.
+const MAX_WORKTREE_ENTRIES: usize = 200_000;
+
+/// What one phase run consumed. Zero means "not measured" for the
+/// backend-supplied fields (`cpu_ms`, `peak_memory_bytes`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub wall_ms: i64,
+    pub cpu_ms: i64,
+    pub peak_memory_bytes: i64,
+    pub disk_written_bytes: i64,
+    /// How CPU/memory were measured: `cgroup`, `proc` or empty.
+    pub source: String,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Sample {
+    cpu_usec: u64,
+    memory_bytes: u64,
+}
+
+enum Target {
+    /// cgroup v2 directory; `owned` ones were created by us and are removed.
+    Cgroup { dir: PathBuf, owned: bool },
+    /// Root pid of a process tree.
+    Tree(u32),
+}
+
+impl Target {
+    fn source(&self) -> &'static str {
+        match self {
+            Target::Cgroup { .. } => "cgroup",
+            Target::Tree(_) => "proc",
+        }
+    }
+
+    fn sample(&self) -> Option<Sample> {
+        match self {
+            Target::Cgroup { dir, .. } => sample_cgroup(dir),
+            Target::Tree(pid) => sample_tree(*pid),
+        }
+    }
+}
+
+/// Background sampler for one agent process or container.
+pub struct ResourceMonitor {
+    stop: Option<oneshot::Sender<()>>,
+    handle: JoinHandle<ResourceUsage>,
+}
+
+impl ResourceMonitor {
+    /// Measure a sandboxed host process (bwrap, direct). Tries to give it a
+    /// dedicated cgroup; falls back to walking its process tree.
+    pub fn process(pid: u32, label: &str) -> Self {
+        let target =
+            match own_cgroup_dir().and_then(|parent| adopt_into_cgroup(&parent, pid, label)) {
+                Some(dir) => Target::Cgroup { dir, owned: true },
+                None => Target::Tree(pid),
+            };
+        Self::spawn(async move { Some(target) })
+    }
+
+    /// Measure a container whose id the engine writes to `cidfile`.
+    pub fn container(cli: &'static str, cidfile: String) -> Self {
+        Self::spawn(async move {
+            let pid = container_pid(cli, &cidfile).await?;
+            match cgroup_dir_of(pid) {
+                Some(dir) if Some(&dir) != own_cgroup_dir().as_ref() => {
+                    Some(Target::Cgroup { dir, owned: false })
+                },
+                _ => Some(Target::Tree(pid)),
+            }
+        })
+    }
+
+    fn spawn<F>(resolve: F) -> Self
+    where
+        F: std::future::Future<Output = Option<Target>> + Send + 'static,
+    {
+        let (stop_tx, mut stop_rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            let target = tokio::select! {
+                t = resolve => t,
+                _ = &mut stop_rx => None,
+            };
+            let Some(target) = target else {
+                return ResourceUsage::default();
+            };
+            let mut peak = 0u64;
+            let mut cpu = 0u64;
+            let mut record = |s: Sample| {
+                peak = peak.max(s.memory_bytes);
+                cpu = cpu.max(s.cpu_usec);
+            };
+            loop {
+                if let Some(s) = target.sample() {
+                    record(s);
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(SAMPLE_INTERVAL) => {},
+                    _ = &mut stop_rx => break,
+                }
+            }
+            if let Some(s) = target.sample() {
+                record(s);
+            }
+            if let Target::Cgroup { dir, owned: true } = &target {
+                if let Err(e) = std::fs::remove_dir(dir) {
+                    debug!("resources: remove {}: {e}", dir.display());
+                }
+            }
+            ResourceUsage {
+                cpu_ms: (cpu / 1000) as i64,
+                peak_memory_bytes: peak as i64,
+                source: target.source().to_string(),
+                ..Default::default()
+            }
+        });
+        Self {
+            stop: Some(stop_tx),
+            handle,
+        }
+    }
+
+    /// Take a final sample and return the totals.
+    pub async fn finish(mut self) -> ResourceUsage {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        (&mut self.handle).await.unwrap_or_default()
+    }
+}
+
+impl Drop for ResourceMonitor {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+/// Bytes in regular files under `dir` modified at or after `since`,
+/// skipping `.git`. An approximation of what a phase wrote to its worktree.
+pub fn worktree_bytes_written(dir: &Path, since: SystemTime) -> u64 {
+    let mut total = 0u64;
+    let mut seen = 0usize;
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(d) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&d) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            seen += 1;
+            if seen > MAX_WORKTREE_ENTRIES {
+                return total;
+            }
+            let Ok(ft) = entry.file_type() else { continue };
+            if ft.is_dir() {
+                if entry.file_name() != ".git" {
+                    stack.push(entry.path());
+                }
+            } else if ft.is_file() {
+                if let Ok(meta) = entry.metadata() {
+                    if meta.modified().map(|m| m >= since).unwrap_or(false) {
+                        total += meta.len();
+                    }
+                }
+            }
+        }
+    }
+    total
+}
+
+async fn container_pid(cli: &str, cidfile: &str) -> Option<u32> {
+    // The engine writes the cidfile once the container is created; it may
+    // still be starting, so poll until it reports a running pid.
+    for _ in 0..60 {
+        if let Ok(cid) = tokio::fs::read_to_string(cidfile).await {
+            let cid = cid.trim();
+            if !cid.is_empty() {
+                let out = tokio::process::Command::new(cli)
+                    .args(["inspect", "-f", "{{.State.Pid}}", cid])
+                    .output()
+                    .await
+                    .ok()?;
+                let pid = String::from_utf8_lossy(&out.stdout)
+                    .trim()
+                    .parse::<u32>()
+                    .unwrap_or(0);
+                if pid > 0 {
+                    return Some(pid);
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    None
+}
+
+/// cgroup v2 directory of `pid`, from the `0::` line of `/proc/<pid>/cgroup`.
+fn cgroup_dir_of(pid: u32) -> Option<PathBuf> {
+    let raw = std::fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    parse_cgroup_v2(&raw).map(|rel| Path::new(CGROUP_ROOT).join(rel.trim_start_matches('/')))
+}
+
+fn own_cgroup_dir() -> Option<PathBuf> {
+    cgroup_dir_of(std::process::id())
+}
+
+fn parse_cgroup_v2(raw: &str) -> Option<&str> {
+    raw.lines().find_map(|l| l.strip_prefix("0::"))
+}
+
+/// Create `<parent>/borg-<label>` and move `pid` into it. Only works when
+/// borg's cgroup is delegated (e.g. systemd `Delegate=yes`).
+fn adopt_into_cgroup(parent: &Path, pid: u32, label: &str) -> Option<PathBuf> {
+    let dir = parent.join(format!("borg-{label}"));
+    std::fs::create_dir(&dir).ok()?;
+    if std::fs::write(dir.join("cgroup.procs"), pid.to_string()).is_err() {
+        let _ = std::fs::remove_dir(&dir);
+        return None;
+    }
+    Some(dir)
+}
+
+fn sample_cgroup(dir: &Path) -> Option<Sample> {
+    let cpu_stat = std::fs::read_to_string(dir.join("cpu.stat")).ok()?;
+    let cpu_usec = parse_cpu_stat(&cpu_stat).unwrap_or(0);
+    let read_u64 = |name: &str| {
+        std::fs::read_to_string(dir.join(name))
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+    };
+    // memory.peak needs Linux 5.19; memory.current sampled is the fallback.
+    let memory_bytes = read_u64("memory.peak")
+        .or_else(|| read_u64("memory.current"))
+        .unwrap_or(0);
+    Some(Sample {
+        cpu_usec,
+        memory_bytes,
+    })
+}
+
+fn parse_cpu_stat(raw: &str) -> Option<u64> {
+    raw.lines()
+        .find_map(|l| l.strip_prefix("usage_usec "))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// Sum CPU (including reaped children) and RSS over `root` and its live
+/// descendants.
+fn sample_tree(root: u32) -> Option<Sample> {
+    let mut stats: HashMap<u32, ProcStat> = HashMap::new();
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if let Some(st) = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .ok()
+            .and_then(|raw| parse_proc_stat(&raw))
+        {
+            stats.insert(pid, st);
+        }
+    }
+    stats.get(&root)?;
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (pid, st) in &stats {
+        children.entry(st.ppid).or_default().push(*pid);
+    }
+    let tick_usec = 1_000_000 / clock_ticks();
+    let page = page_size();
+    let mut sample = Sample::default();
+    let mut stack = vec![root];
+    while let Some(pid) = stack.pop() {
+        if let Some(st) = stats.get(&pid) {
+            sample.cpu_usec += st.cpu_ticks * tick_usec;
+            sample.memory_bytes += st.rss_pages * page;
+        }
+        if let Some(kids) = children.get(&pid) {
+            stack.extend(kids);
+        }
+    }
+    Some(sample)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ProcStat {
+    ppid: u32,
+    cpu_ticks: u64,
+    rss_pages: u64,
+}
+
+fn parse_proc_stat(raw: &str) -> Option<ProcStat> {
+    // The command name may contain spaces and parens; fields resume after
+    // the last ')'. Field 3 (state) is index 0 here.
+    let rest = &raw[raw.rfind(')')? + 2..];
+    let f: Vec<&str> = rest.split_whitespace().collect();
+    let num = |i: usize| f.get(i).and_then(|v| v.parse::<u64>().ok());
+    Some(ProcStat {
+        ppid: num(1)? as u32,
+        // utime + stime + cutime + cstime
+        cpu_ticks: num(11)? + num(12)? + num(13)? + num(14)?,
+        rss_pages: num(21)?,
+    })
+}
+
+fn clock_ticks() -> u64 {
+    // SAFETY: sysconf has no preconditions.
+    let t = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if t > 0 {
+        t as u64
+    } else {
+        100
+    }
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf has no preconditions.
+    let p = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if p > 0 {
+        p as u64
+    } else {
+        4096
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cgroup_v2_line() {
+        let raw = "12:pids:/foo\n0::/system.slice/docker-abc.scope\n";
+        assert_eq!(parse_cgroup_v2(raw), Some("/system.slice/docker-abc.scope"));
+        assert_eq!(parse_cgroup_v2("1:name=systemd:/x\n"), None);
+    }
+
+    #[test]
+    fn parses_cpu_stat_usage() {
+        let raw = "usage_usec 1234567\nuser_usec 1000000\nsystem_usec 234567\n";
+        assert_eq!(parse_cpu_stat(raw), Some(1_234_567));
+    }
+
+    #[test]
+    fn parses_proc_stat_with_spaces_in_comm() {
+        let raw = "4242 (cargo test (x)) S 4200 4242 4200 0 -1 4194560 100 0 0 0 \
+                   70 30 5 5 20 0 1 0 1000 123456789 2048 18446744073709551615";
+        assert_eq!(
+            parse_proc_stat(raw),
+            Some(ProcStat {
+                ppid: 4200,
+                cpu_ticks: 110,
+                rss_pages: 2048,
+            })
+        );
+    }
+
+    #[test]
+    fn samples_own_process_tree() {
+        let s = sample_tree(std::process::id()).unwrap();
+        assert!(s.memory_bytes > 0);
+    }
+
+    #[test]
+    fn counts_only_files_written_since() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("old.txt"), b"old").unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let since = SystemTime::now();
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/new.rs"), b"fn main() {}").unwrap();
+        std::fs::write(dir.path().join(".git/index"), b"ignored").unwrap();
+        assert_eq!(worktree_bytes_written(dir.path(), since), 12);
+    }
+}
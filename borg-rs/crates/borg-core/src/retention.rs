@@ -0,0 +1,68 @@
+//! Shared purge logic for the lawborg `Purge` phase and workspace retention.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use tracing::warn;
+
+use crate::{db::Db, types::Task};
+
+/// Deletes a task's vectors and chat history, its session directory and, for
+/// worktree-backed tasks, the worktree. The task row and its outputs are left
+/// in place so the status and final draft survive.
+pub fn purge_task_artifacts(db: &Db, data_dir: &str, task: &Task) -> Result<()> {
+    db.purge_task_data(task.id)?;
+
+    let session_dir = format!("{data_dir}/sessions/task-{}", task.id);
+    if let Err(e) = std::fs::remove_dir_all(&session_dir) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!(
+                "task #{} failed to remove session dir {}: {}",
+                task.id, session_dir, e
+            );
+        }
+    }
+
+    if task.repo_path.contains(".worktrees") {
+        if let Err(e) = std::fs::remove_dir_all(&task.repo_path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(
+                    "task #{} failed to remove worktree {}: {}",
+                    task.id, task.repo_path, e
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Timestamp (in the DB's `YYYY-MM-DD HH:MM:SS` form) before which data
+/// expires, or `None` when the window is unset or non-positive.
+pub fn retention_cutoff(now: DateTime<Utc>, days: Option<i64>) -> Option<String> {
+    let days = days.filter(|d| *d > 0)?;
+    let cutoff = now.checked_sub_signed(Duration::try_days(days)?)?;
+    Some(cutoff.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cutoff_is_none_for_unset_or_zero_windows() {
+        let now = Utc::now();
+        assert_eq!(retention_cutoff(now, None), None);
+        assert_eq!(retention_cutoff(now, Some(0)), None);
+        assert_eq!(retention_cutoff(now, Some(-5)), None);
+    }
+
+    #[test]
+    fn cutoff_uses_db_timestamp_format() {
+        let now = DateTime::parse_from_rfc3339("2026-04-01T12:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            retention_cutoff(now, Some(90)).as_deref(),
+            Some("2026-01-01 12:30:00")
+        );
+    }
+}
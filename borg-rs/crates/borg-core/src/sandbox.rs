@@ -1,19 +1,29 @@
 //! Process sandbox for pipeline agent phases.
 //!
-//! Supports two isolation backends (preferred order when `auto`):
+//! Supports three isolation backends:
 //! 1. **bwrap** — bubblewrap-based namespace isolation (no daemon, no image).
 //!    Adapted from OpenAI Codex linux-sandbox. Mounts the host filesystem
 //!    read-only with selective read-write bind mounts for working dirs.
 //! 2. **docker** — Docker container via `docker run`.
+//! 3. **podman** — Podman container, usually rootless (no root daemon).
+//!    Opt-in only: `auto` never selects it.
 //!
-//! Set `SANDBOX_BACKEND=auto|bwrap|docker|none` in the environment.
-//! Default is `auto` (bwrap if available, else docker, else direct).
+//! Set `SANDBOX_BACKEND=auto|bwrap|docker|podman|none` in the environment.
+//! Append `+gvisor` to a container backend (`docker+gvisor`, `podman+gvisor`)
+//! to run agents under the gVisor `runsc` runtime.
+//! Default is `auto` (docker if available, else bwrap, else direct).
 
-use std::{path::Path, process::Stdio};
+use std::{
+    path::Path,
+    process::Stdio,
+    sync::{Arc, OnceLock},
+};
 
 use tokio::process::Command;
 use tracing::{info, warn};
 
+use crate::warm_pool::WarmPool;
+
 /// Which sandboxing backend to use.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SandboxMode {
@@ -21,6 +31,8 @@ pub enum SandboxMode {
     Bwrap,
     /// Docker container — requires daemon and a pre-built image.
     Docker,
+    /// Podman container — daemonless, works rootless with the same image.
+    Podman,
     /// No sandboxing — run the process directly on the host.
     Direct,
 }
@@ -29,27 +41,156 @@ impl SandboxMode {
     /// Parse from env/config string. Unknown values fall back to `Auto`
     /// detection logic.
     pub fn from_str_or_auto(s: &str) -> Option<Self> {
-        match s.to_ascii_lowercase().as_str() {
+        let (base, _) = split_runtime_suffix(s);
+        match base.as_str() {
             "bwrap" => Some(Self::Bwrap),
             "docker" => Some(Self::Docker),
+            "podman" => Some(Self::Podman),
             "none" | "direct" => Some(Self::Direct),
             _ => None, // "auto" or unrecognised → detect at runtime
         }
     }
+
+    /// Whether agents run in an OCI container (Docker or Podman).
+    pub fn is_container(&self) -> bool {
+        matches!(self, Self::Docker | Self::Podman)
+    }
+}
+
+/// gVisor's OCI runtime binary.
+pub const GVISOR_RUNTIME: &str = "runsc";
+
+/// Network for rootless Podman agents: outbound internet through
+/// slirp4netns, with the host's loopback services cut off.
+pub const ROOTLESS_NETWORK: &str = "slirp4netns:allow_host_loopback=false";
+
+/// Split `docker+gvisor` into (`docker`, true). A bare `gvisor` means
+/// auto-detect the container engine and run it under gVisor.
+fn split_runtime_suffix(s: &str) -> (String, bool) {
+    let lower = s.trim().to_ascii_lowercase();
+    if lower == "gvisor" {
+        return ("auto".to_string(), true);
+    }
+    match lower.split_once('+') {
+        Some((base, "gvisor")) => (base.to_string(), true),
+        _ => (lower, false),
+    }
+}
+
+/// The container CLI in use and how it was configured. Fixed once at
+/// startup by `Sandbox::detect`; defaults to rootful Docker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerEngine {
+    /// CLI binary: `docker` or `podman`.
+    pub cli: &'static str,
+    /// Podman running without root: no iptables, host reachable via alias.
+    pub rootless: bool,
+    /// OCI runtime override passed as `--runtime` (e.g. `runsc`).
+    pub runtime: Option<String>,
 }
 
+impl ContainerEngine {
+    pub fn docker() -> Self {
+        Self {
+            cli: "docker",
+            rootless: false,
+            runtime: None,
+        }
+    }
+
+    pub fn podman(rootless: bool) -> Self {
+        Self {
+            cli: "podman",
+            rootless,
+            runtime: None,
+        }
+    }
+
+    pub fn with_runtime(mut self, runtime: Option<String>) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    pub fn is_podman(&self) -> bool {
+        self.cli == "podman"
+    }
+
+    pub fn is_rootless_podman(&self) -> bool {
+        self.is_podman() && self.rootless
+    }
+
+    /// Extra `run` flags for this engine: OCI runtime and, for rootless
+    /// Podman, keeping the host uid so bind-mounted worktrees stay writable.
+    pub fn run_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(runtime) = &self.runtime {
+            args.extend(["--runtime".to_string(), runtime.clone()]);
+        }
+        if self.is_rootless_podman() {
+            args.extend(["--userns".to_string(), "keep-id".to_string()]);
+        }
+        args
+    }
+
+    /// `--network`/`--dns` flags for an agent container on `network`
+    /// (`None` = host networking). Rootless Podman cannot filter bridge
+    /// traffic with iptables, so it never joins the agent bridges: isolated
+    /// runs get no network at all and everything else uses `ROOTLESS_NETWORK`.
+    pub fn network_args(&self, network: Option<&str>) -> Vec<String> {
+        let network = if self.is_rootless_podman() {
+            if network == Some(Sandbox::ISOLATED_NETWORK) {
+                return vec!["--network".to_string(), "none".to_string()];
+            }
+            Some(ROOTLESS_NETWORK)
+        } else {
+            network
+        };
+        match network {
+            Some(net) => ["--network", net, "--dns", "8.8.8.8", "--dns", "8.8.4.4"]
+                .map(str::to_string)
+                .to_vec(),
+            None => vec!["--network".to_string(), "host".to_string()],
+        }
+    }
+
+    /// iptables chain for agent egress rules. Rootful Podman has no
+    /// DOCKER-USER chain; rootless Podman cannot touch iptables at all.
+    pub fn filter_chain(&self) -> Option<&'static str> {
+        match (self.is_podman(), self.rootless) {
+            (false, _) => Some("DOCKER-USER"),
+            (true, false) => Some("FORWARD"),
+            (true, true) => None,
+        }
+    }
+}
+
+static ENGINE: OnceLock<ContainerEngine> = OnceLock::new();
+static WARM_POOL: OnceLock<Arc<WarmPool>> = OnceLock::new();
+
 pub struct Sandbox;
 
 impl Sandbox {
-    /// Detect the best available sandbox mode given a preference string.
+    /// Detect the best available sandbox mode given a preference string,
+    /// and fix the container engine used by the rest of this module.
     ///
     /// Preference order when `preferred` is `"auto"` or empty:
-    /// docker → bwrap → direct.
+    /// docker → bwrap → direct. Podman is used only when asked for.
     pub async fn detect(preferred: &str) -> SandboxMode {
-        if let Some(forced) = SandboxMode::from_str_or_auto(preferred) {
-            return forced;
+        let (_, gvisor) = split_runtime_suffix(preferred);
+        let mode = match SandboxMode::from_str_or_auto(preferred) {
+            Some(forced) => forced,
+            None => Self::detect_auto().await,
+        };
+        if mode.is_container() {
+            Self::set_engine(Self::configure_engine(&mode, gvisor).await);
+        } else if gvisor {
+            warn!("sandbox: gVisor requested but {mode:?} is not a container backend; ignoring");
         }
-        // auto — prefer Docker (containerised agents get their own clone)
+        mode
+    }
+
+    async fn detect_auto() -> SandboxMode {
+        // auto — prefer containers (containerised agents get their own clone)
         if Self::docker_available().await {
             info!("sandbox: docker detected, using container sandbox");
             SandboxMode::Docker
@@ -57,11 +198,80 @@ impl Sandbox {
             info!("sandbox: docker not found, falling back to bwrap");
             SandboxMode::Bwrap
         } else {
-            warn!("sandbox: neither docker nor bwrap available, running agents directly (no isolation)");
+            warn!("sandbox: no container engine or bwrap available, running agents directly (no isolation)");
             SandboxMode::Direct
         }
     }
 
+    async fn configure_engine(mode: &SandboxMode, gvisor: bool) -> ContainerEngine {
+        let engine = if *mode == SandboxMode::Podman {
+            let rootless = Self::podman_rootless().await;
+            info!(rootless, "sandbox: using podman");
+            ContainerEngine::podman(rootless)
+        } else {
+            ContainerEngine::docker()
+        };
+        if !gvisor {
+            return engine;
+        }
+        if Self::runtime_available(&engine, GVISOR_RUNTIME).await {
+            info!("sandbox: running agent containers under gVisor ({GVISOR_RUNTIME})");
+            engine.with_runtime(Some(GVISOR_RUNTIME.to_string()))
+        } else {
+            warn!(
+                "sandbox: gVisor requested but {} has no {GVISOR_RUNTIME} runtime; using the default runtime",
+                engine.cli
+            );
+            engine
+        }
+    }
+
+    /// Set the process-wide container engine. Returns false if one was
+    /// already set (the first caller wins).
+    pub fn set_engine(engine: ContainerEngine) -> bool {
+        ENGINE.set(engine).is_ok()
+    }
+
+    /// The container engine chosen at startup (rootful Docker if unset).
+    pub fn engine() -> &'static ContainerEngine {
+        ENGINE.get_or_init(ContainerEngine::docker)
+    }
+
+    /// Install the process-wide warm container pool. Returns false if one
+    /// was already set.
+    pub fn set_warm_pool(pool: Arc<WarmPool>) -> bool {
+        WARM_POOL.set(pool).is_ok()
+    }
+
+    /// The warm container pool, if `WARM_POOL_SIZE` enabled one.
+    pub fn warm_pool() -> Option<&'static Arc<WarmPool>> {
+        WARM_POOL.get()
+    }
+
+    /// CLI binary for container commands (`docker` or `podman`).
+    pub fn cli() -> &'static str {
+        Self::engine().cli
+    }
+
+    /// Address agent containers use to reach services on the host. Rootless
+    /// Podman has no host-side bridge, so it uses Podman's alias, and its
+    /// isolated runs have no network and so no route to the host at all.
+    pub fn host_gateway(isolated: bool) -> Option<&'static str> {
+        match (Self::engine().is_rootless_podman(), isolated) {
+            (true, true) => None,
+            (true, false) => Some("host.containers.internal"),
+            (false, true) => Some("172.31.0.1"),
+            (false, false) => Some("172.30.0.1"),
+        }
+    }
+
+    /// Whether isolated agent containers can reach borg's proxies (model API
+    /// and egress allowlist) on the isolated network's gateway. Rootless
+    /// Podman runs them with no network, so the proxies are out of reach.
+    pub fn isolated_proxies_reachable() -> bool {
+        !Self::engine().is_rootless_podman()
+    }
+
     pub async fn bwrap_available() -> bool {
         // bwrap relies on Linux namespaces; skip detection on other platforms
         if cfg!(not(target_os = "linux")) {
@@ -88,6 +298,54 @@ impl Sandbox {
             .unwrap_or(false)
     }
 
+    pub async fn podman_available() -> bool {
+        Command::new("podman")
+            .arg("version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .map(|s| s.success())
+            .unwrap_or(false)
+    }
+
+    async fn podman_rootless() -> bool {
+        let Ok(out) = Command::new("podman")
+            .args(["info", "--format", "{{.Host.Security.Rootless}}"])
+            .output()
+            .await
+        else {
+            return true;
+        };
+        String::from_utf8_lossy(&out.stdout).trim() != "false"
+    }
+
+    /// Whether `runtime` can be passed to `--runtime`. Docker only accepts
+    /// runtimes registered with the daemon; Podman resolves them on PATH.
+    pub async fn runtime_available(engine: &ContainerEngine, runtime: &str) -> bool {
+        if engine.is_podman() {
+            return Command::new(runtime)
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await
+                .map(|s| s.success())
+                .unwrap_or(false);
+        }
+        let Ok(out) = Command::new(engine.cli)
+            .args(["info", "--format", "{{json .Runtimes}}"])
+            .output()
+            .await
+        else {
+            return false;
+        };
+        serde_json::from_slice::<serde_json::Value>(&out.stdout)
+            .ok()
+            .and_then(|v| v.as_object().map(|m| m.contains_key(runtime)))
+            .unwrap_or(false)
+    }
+
     // --- bwrap backend ---
 
     /// Build bwrap argument list for `command`.
@@ -183,9 +441,10 @@ impl Sandbox {
         cmd
     }
 
-    // --- docker backend ---
+    // --- container backend (docker / podman) ---
 
-    /// Return a `Command` that runs inside a Docker container.
+    /// Return a `Command` that runs inside a container on the configured
+    /// engine (see `Sandbox::engine`).
     ///
     /// `binds`: `(host_path, container_path, read_only)` — bind mounts.
     /// `volumes`: `(volume_name, container_path)` — named Docker volumes.
@@ -195,7 +454,8 @@ impl Sandbox {
     /// `memory_mb`: memory limit in MiB (0 = no limit).
     /// `cpus`: CPU quota (0.0 = no limit).
     /// `network`: bridge network name. `Some(name)` uses that network + Google DNS;
-    ///            `None` falls back to `--network host`.
+    ///            `None` falls back to `--network host`. See `network_args` for
+    ///            rootless Podman.
     pub fn docker_command(
         image: &str,
         binds: &[(&str, &str, bool)],
@@ -207,6 +467,7 @@ impl Sandbox {
         cpus: f64,
         network: Option<&str>,
     ) -> Command {
+        let engine = Self::engine();
         let mut args = vec![
             "run".to_string(),
             "--rm".to_string(),
@@ -216,6 +477,7 @@ impl Sandbox {
             "--label".to_string(),
             "borg-agent=1".to_string(),
         ];
+        args.extend(engine.run_args());
 
         if memory_mb > 0 {
             args.push("--memory".to_string());
@@ -237,13 +499,7 @@ impl Sandbox {
                 ]
                 .map(str::to_string),
             );
-            if let Some(net) = network {
-                args.extend(["--network".to_string(), net.to_string()]);
-                args.extend(["--dns".to_string(), "8.8.8.8".to_string()]);
-                args.extend(["--dns".to_string(), "8.8.4.4".to_string()]);
-            } else {
-                args.extend(["--network".to_string(), "host".to_string()]);
-            }
+            args.extend(engine.network_args(network));
         }
 
         for (host, container, ro) in binds {
@@ -273,15 +529,35 @@ impl Sandbox {
         args.push(image.to_string());
         args.extend_from_slice(command);
 
-        let mut cmd = Command::new("docker");
+        let mut cmd = Command::new(engine.cli);
         cmd.args(args);
         cmd
     }
 
+    /// Rebuild a `docker_command` so the engine writes the container id to
+    /// `cidfile` (used for cleanup and resource accounting).
+    pub fn with_cidfile(cmd: &Command, cidfile: &str) -> Command {
+        Self::with_run_args(cmd, &["--cidfile", cidfile])
+    }
+
+    /// Rebuild a `docker_command` with `extra` inserted right after `run`.
+    pub fn with_run_args(cmd: &Command, extra: &[&str]) -> Command {
+        let mut new_cmd = Command::new(Self::cli());
+        new_cmd.arg("run").args(extra);
+        for arg in cmd.as_std().get_args().skip(1) {
+            new_cmd.arg(arg);
+        }
+        new_cmd
+    }
+
     /// Create the borg-agent-isolated internal network (no internet egress).
+    /// Rootless Podman does not use the agent bridges (see `network_args`).
     pub async fn ensure_isolated_network() -> bool {
+        if Self::engine().is_rootless_podman() {
+            return true;
+        }
         let name = "borg-agent-isolated";
-        let exists = tokio::process::Command::new("docker")
+        let exists = tokio::process::Command::new(Self::cli())
             .args(["network", "inspect", name])
             .stdout(Stdio::null())
             .stderr(Stdio::null())
@@ -294,7 +570,7 @@ impl Sandbox {
             return true;
         }
 
-        let ok = tokio::process::Command::new("docker")
+        let ok = tokio::process::Command::new(Self::cli())
             .args([
                 "network",
                 "create",
@@ -331,8 +607,12 @@ impl Sandbox {
 
     /// Create the borg-agent-net bridge network if it doesn't already exist.
     /// Returns true if the network is available (created or already existed).
+    /// Rootless Podman does not use the agent bridges (see `network_args`).
     pub async fn ensure_agent_network() -> bool {
-        let exists = tokio::process::Command::new("docker")
+        if Self::engine().is_rootless_podman() {
+            return true;
+        }
+        let exists = tokio::process::Command::new(Self::cli())
             .args(["network", "inspect", Self::AGENT_NETWORK])
             .stdout(Stdio::null())
             .stderr(Stdio::null())
@@ -349,7 +629,7 @@ impl Sandbox {
             return true;
         }
 
-        let ok = tokio::process::Command::new("docker")
+        let ok = tokio::process::Command::new(Self::cli())
             .args([
                 "network",
                 "create",
@@ -378,12 +658,21 @@ impl Sandbox {
     }
 
     /// Install iptables rules that block agent containers from reaching localhost and LAN.
-    /// Rules are inserted into the DOCKER-USER chain (Docker's designated chain for user rules).
+    /// Rules are inserted into the DOCKER-USER chain (Docker's designated chain for user rules),
+    /// or FORWARD for rootful Podman. Rootless Podman cannot install rules; its agents run
+    /// without the bridges instead (see `network_args`), so there is nothing to install.
     /// This is idempotent — rules are checked before insertion.
     pub async fn install_network_rules() -> bool {
         if cfg!(not(target_os = "linux")) {
             return false;
         }
+        let Some(chain) = Self::engine().filter_chain() else {
+            info!(
+                "sandbox: rootless podman — agents use {ROOTLESS_NETWORK} (isolated runs: none); \
+                 LAN addresses stay reachable, use docker or rootful podman to filter them"
+            );
+            return true;
+        };
 
         // (source, dest, action) — order matters: ACCEPT for 172.30/16 before DROP for 172.16/12
         let rules: &[(&str, &str, &str)] = &[
@@ -399,7 +688,7 @@ impl Sandbox {
         for (src, dst, action) in rules {
             // Check if rule already exists
             let exists = tokio::process::Command::new("iptables")
-                .args(["-C", "DOCKER-USER", "-s", src, "-d", dst, "-j", action])
+                .args(["-C", chain, "-s", src, "-d", dst, "-j", action])
                 .stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .status()
@@ -412,7 +701,7 @@ impl Sandbox {
             }
 
             let ok = tokio::process::Command::new("iptables")
-                .args(["-I", "DOCKER-USER", "-s", src, "-d", dst, "-j", action])
+                .args(["-I", chain, "-s", src, "-d", dst, "-j", action])
                 .stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .status()
@@ -421,7 +710,7 @@ impl Sandbox {
                 .unwrap_or(false);
 
             if ok {
-                info!("sandbox: iptables DOCKER-USER -s {src} -d {dst} -j {action}");
+                info!("sandbox: iptables {chain} -s {src} -d {dst} -j {action}");
             } else {
                 warn!("sandbox: failed to install iptables rule -s {src} -d {dst} -j {action} (needs CAP_NET_ADMIN?)");
                 all_ok = false;
@@ -435,6 +724,9 @@ impl Sandbox {
         if cfg!(not(target_os = "linux")) {
             return;
         }
+        let Some(chain) = Self::engine().filter_chain() else {
+            return;
+        };
 
         let rules: &[(&str, &str, &str)] = &[
             (Self::AGENT_SUBNET, "172.30.0.0/16", "ACCEPT"),
@@ -447,7 +739,7 @@ impl Sandbox {
 
         for (src, dst, action) in rules {
             let _ = tokio::process::Command::new("iptables")
-                .args(["-D", "DOCKER-USER", "-s", src, "-d", dst, "-j", action])
+                .args(["-D", chain, "-s", src, "-d", dst, "-j", action])
                 .stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .status()
@@ -458,7 +750,7 @@ impl Sandbox {
 
     /// Remove the agent network (best-effort, called on shutdown).
     pub async fn remove_agent_network() {
-        let _ = tokio::process::Command::new("docker")
+        let _ = tokio::process::Command::new(Self::cli())
             .args(["network", "rm", Self::AGENT_NETWORK])
             .stdout(Stdio::null())
             .stderr(Stdio::null())
@@ -470,21 +762,19 @@ impl Sandbox {
     /// Remove any containers with label `borg-agent=1` that are not running.
     /// Call once at startup to clean up orphans from a previous crash.
     pub async fn prune_orphan_containers() {
-        let Ok(out) = tokio::process::Command::new("docker")
-            .args([
-                "ps",
-                "-a",
-                "--filter",
-                "label=borg-agent=1",
-                "--filter",
-                "status=exited",
-                "--filter",
-                "status=dead",
-                "--filter",
-                "status=created",
-                "--format",
-                "{{.ID}}",
-            ])
+        // Podman has no "dead" state and rejects it as a filter value.
+        let mut args = vec!["ps", "-a", "--filter", "label=borg-agent=1"];
+        let states: &[&str] = if Self::engine().is_podman() {
+            &["status=exited", "status=created"]
+        } else {
+            &["status=exited", "status=dead", "status=created"]
+        };
+        for state in states {
+            args.extend(["--filter", state]);
+        }
+        args.extend(["--format", "{{.ID}}"]);
+        let Ok(out) = tokio::process::Command::new(Self::cli())
+            .args(&args)
             .output()
             .await
         else {
@@ -499,7 +789,7 @@ impl Sandbox {
         if ids.is_empty() {
             return;
         }
-        let mut cmd = tokio::process::Command::new("docker");
+        let mut cmd = tokio::process::Command::new(Self::cli());
         cmd.arg("rm").arg("-f");
         cmd.args(&ids);
         cmd.stdout(std::process::Stdio::null())
@@ -542,7 +832,7 @@ impl Sandbox {
             .unwrap_or_default()
             .as_secs();
         // `docker volume create` is idempotent — no-op if already exists.
-        let _ = tokio::process::Command::new("docker")
+        let _ = tokio::process::Command::new(Self::cli())
             .args([
                 "volume",
                 "create",
@@ -571,7 +861,7 @@ impl Sandbox {
         }
 
         // Check if branch volume already exists (avoid redundant copy)
-        let exists = tokio::process::Command::new("docker")
+        let exists = tokio::process::Command::new(Self::cli())
             .args(["volume", "inspect", &branch_vol])
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
@@ -586,7 +876,7 @@ impl Sandbox {
         }
 
         // Check if main cache volume exists
-        let main_exists = tokio::process::Command::new("docker")
+        let main_exists = tokio::process::Command::new(Self::cli())
             .args(["volume", "inspect", &main_vol])
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
@@ -600,7 +890,7 @@ impl Sandbox {
         if main_exists {
             // Copy main → branch using a minimal busybox container.
             // Mount both volumes and rsync/cp the contents.
-            let status = tokio::process::Command::new("docker")
+            let status = tokio::process::Command::new(Self::cli())
                 .args([
                     "run",
                     "--rm",
@@ -646,7 +936,7 @@ impl Sandbox {
             .unwrap_or_default()
             .as_secs();
 
-        let Ok(out) = tokio::process::Command::new("docker")
+        let Ok(out) = tokio::process::Command::new(Self::cli())
             .args([
                 "volume",
                 "ls",
@@ -680,7 +970,7 @@ impl Sandbox {
 
     /// Read the `borg-last-used` label from a volume, returning unix seconds.
     async fn volume_last_used(name: &str) -> Option<u64> {
-        let out = tokio::process::Command::new("docker")
+        let out = tokio::process::Command::new(Self::cli())
             .args([
                 "volume",
                 "inspect",
@@ -698,7 +988,7 @@ impl Sandbox {
     /// List all Docker volumes whose names start with the given prefix.
     /// Returns `(name, size_bytes, last_used_secs)` triples.
     pub async fn list_cache_volumes(prefix: &str) -> Vec<(String, Option<u64>, Option<u64>)> {
-        let Ok(out) = tokio::process::Command::new("docker")
+        let Ok(out) = tokio::process::Command::new(Self::cli())
             .args([
                 "volume",
                 "ls",
@@ -730,7 +1020,7 @@ impl Sandbox {
     }
 
     async fn volume_sizes() -> std::collections::HashMap<String, u64> {
-        let Ok(out) = tokio::process::Command::new("docker")
+        let Ok(out) = tokio::process::Command::new(Self::cli())
             .args(["system", "df", "-v", "--format", "{{json .}}"])
             .output()
             .await
@@ -739,6 +1029,7 @@ impl Sandbox {
         };
 
         // `docker system df -v --format '{{json .}}'` emits one JSON object per entity.
+        // Podman rejects --format with -v, so sizes are simply unknown there.
         // Volume entries have {"Name":"...","Size":"1.2GB",...}.
         let mut map = std::collections::HashMap::new();
         for line in String::from_utf8_lossy(&out.stdout).lines() {
@@ -760,7 +1051,7 @@ impl Sandbox {
 
     /// Remove a named Docker volume. Returns true if successful.
     pub async fn remove_volume(name: &str) -> bool {
-        tokio::process::Command::new("docker")
+        tokio::process::Command::new(Self::cli())
             .args(["volume", "rm", name])
             .stdout(std::process::Stdio::null())
             .stderr(std::process::Stdio::null())
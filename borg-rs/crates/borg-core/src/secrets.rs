@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use base64::Engine;
@@ -10,8 +12,20 @@ use rand::RngCore;
 use crate::traits::SecretStore;
 
 const ENC_PREFIX: &str = "enc1:";
+const ENC_V2_PREFIX: &str = "enc2:";
 const NONCE_LEN: usize = 12;
 
+/// Key id assigned to the pre-rotation `.secret_key` file. `enc1:` values are
+/// always decrypted with this key.
+pub const LEGACY_KEY_ID: &str = "k0";
+const KEYRING_DIR: &str = ".secret_keys";
+const ACTIVE_KEY_FILE: &str = "active";
+
+/// True if `value` carries one of the `EncryptedStore` envelope prefixes.
+pub fn is_encrypted_value(value: &str) -> bool {
+    value.starts_with(ENC_PREFIX) || value.starts_with(ENC_V2_PREFIX)
+}
+
 // ── PlaintextStore ──────────────────────────────────────────────────────
 
 pub struct PlaintextStore;
@@ -35,29 +49,131 @@ impl SecretStore for PlaintextStore {
     }
 }
 
+// ── KeyRing ───────────────────────────────────────────────────────────
+
+/// Set of data keys indexed by key id. New ciphertexts are sealed with the
+/// active key; older keys are kept so existing rows stay readable until the
+/// rotation job has rewritten them.
+#[derive(Clone)]
+pub struct KeyRing {
+    active_id: String,
+    keys: HashMap<String, [u8; 32]>,
+}
+
+impl KeyRing {
+    pub fn single(id: &str, key: [u8; 32]) -> Self {
+        let mut keys = HashMap::new();
+        keys.insert(id.to_string(), key);
+        Self {
+            active_id: id.to_string(),
+            keys,
+        }
+    }
+
+    pub fn insert(&mut self, id: &str, key: [u8; 32]) {
+        self.keys.insert(id.to_string(), key);
+    }
+
+    pub fn set_active(&mut self, id: &str) -> Result<()> {
+        if !self.keys.contains_key(id) {
+            anyhow::bail!("unknown secret key id: {id}");
+        }
+        self.active_id = id.to_string();
+        Ok(())
+    }
+
+    pub fn active_id(&self) -> &str {
+        &self.active_id
+    }
+
+    pub fn key_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.keys.keys().cloned().collect();
+        ids.sort_by_key(|id| key_id_number(id));
+        ids
+    }
+
+    fn next_id(&self) -> String {
+        let max = self.keys.keys().filter_map(|id| key_id_number(id)).max();
+        format!("k{}", max.map(|n| n + 1).unwrap_or(1))
+    }
+}
+
+fn key_id_number(id: &str) -> Option<u32> {
+    id.strip_prefix('k').and_then(|n| n.parse().ok())
+}
+
+fn valid_key_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 32 && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
 // ── EncryptedStore ──────────────────────────────────────────────────────
 
+/// ChaCha20-Poly1305 store. Writes `enc2:<key-id>:<base64(nonce||ct)>` and
+/// still reads the unversioned `enc1:` format using the legacy key.
 pub struct EncryptedStore {
-    cipher: ChaCha20Poly1305,
+    active_id: String,
+    ciphers: HashMap<String, ChaCha20Poly1305>,
 }
 
 impl EncryptedStore {
     pub fn new(key: &[u8; 32]) -> Self {
+        Self::from_keyring(&KeyRing::single(LEGACY_KEY_ID, *key))
+    }
+
+    pub fn from_keyring(ring: &KeyRing) -> Self {
+        let ciphers = ring
+            .keys
+            .iter()
+            .map(|(id, key)| (id.clone(), ChaCha20Poly1305::new(key.into())))
+            .collect();
         Self {
-            cipher: ChaCha20Poly1305::new(key.into()),
+            active_id: ring.active_id.clone(),
+            ciphers,
         }
     }
+
+    pub fn active_key_id(&self) -> &str {
+        &self.active_id
+    }
+
+    fn open(&self, key_id: &str, encoded: &str) -> Result<String> {
+        let cipher = self
+            .ciphers
+            .get(key_id)
+            .ok_or_else(|| anyhow::anyhow!("no secret key loaded for id {key_id}"))?;
+
+        let combined = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .context("base64 decode failed")?;
+
+        if combined.len() < NONCE_LEN {
+            anyhow::bail!("ciphertext too short");
+        }
+
+        let (nonce_bytes, ct) = combined.split_at(NONCE_LEN);
+        let nonce = Nonce::from_slice(nonce_bytes);
+
+        let plaintext = cipher
+            .decrypt(nonce, ct)
+            .map_err(|e| anyhow::anyhow!("decryption failed: {e}"))?;
+
+        String::from_utf8(plaintext).context("decrypted data is not valid UTF-8")
+    }
 }
 
 #[async_trait]
 impl SecretStore for EncryptedStore {
     fn encrypt(&self, plaintext: &str) -> Result<String> {
+        let cipher = self
+            .ciphers
+            .get(&self.active_id)
+            .ok_or_else(|| anyhow::anyhow!("active secret key {} missing", self.active_id))?;
+
         let mut nonce_bytes = [0u8; NONCE_LEN];
         OsRng.fill_bytes(&mut nonce_bytes);
         let nonce = Nonce::from_slice(&nonce_bytes);
 
-        let ciphertext = self
-            .cipher
+        let ciphertext = cipher
             .encrypt(nonce, plaintext.as_bytes())
             .map_err(|e| anyhow::anyhow!("encryption failed: {e}"))?;
 
@@ -66,31 +182,27 @@ impl SecretStore for EncryptedStore {
         combined.extend_from_slice(&ciphertext);
 
         let encoded = base64::engine::general_purpose::STANDARD.encode(&combined);
-        Ok(format!("{ENC_PREFIX}{encoded}"))
+        Ok(format!("{ENC_V2_PREFIX}{}:{encoded}", self.active_id))
     }
 
     fn decrypt(&self, ciphertext: &str) -> Result<String> {
-        let Some(encoded) = ciphertext.strip_prefix(ENC_PREFIX) else {
-            return Ok(ciphertext.to_string());
-        };
-
-        let combined = base64::engine::general_purpose::STANDARD
-            .decode(encoded)
-            .context("base64 decode failed")?;
-
-        if combined.len() < NONCE_LEN {
-            anyhow::bail!("ciphertext too short");
+        if let Some(rest) = ciphertext.strip_prefix(ENC_V2_PREFIX) {
+            let (key_id, encoded) = rest
+                .split_once(':')
+                .ok_or_else(|| anyhow::anyhow!("malformed enc2 value: missing key id"))?;
+            return self.open(key_id, encoded);
         }
+        match ciphertext.strip_prefix(ENC_PREFIX) {
+            Some(encoded) => self.open(LEGACY_KEY_ID, encoded),
+            None => Ok(ciphertext.to_string()),
+        }
+    }
 
-        let (nonce_bytes, ct) = combined.split_at(NONCE_LEN);
-        let nonce = Nonce::from_slice(nonce_bytes);
-
-        let plaintext = self
-            .cipher
-            .decrypt(nonce, ct)
-            .map_err(|e| anyhow::anyhow!("decryption failed: {e}"))?;
-
-        String::from_utf8(plaintext).context("decrypted data is not valid UTF-8")
+    fn needs_rotation(&self, value: &str) -> bool {
+        match value.strip_prefix(ENC_V2_PREFIX) {
+            Some(rest) => rest.split_once(':').map(|(id, _)| id) != Some(self.active_id.as_str()),
+            None => !value.is_empty(),
+        }
     }
 
     async fn store(&self, _key: &str, _secret: &str) -> Result<()> {
@@ -102,6 +214,167 @@ impl SecretStore for EncryptedStore {
     }
 }
 
+// ── Key wrapping (envelope encryption) ──────────────────────────────────
+
+/// External KMS that wraps data keys at rest. In envelope mode only wrapped
+/// keys are written under `data_dir`; the plaintext key exists in memory only.
+#[async_trait]
+pub trait KeyWrapper: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn wrap(&self, key: &[u8; 32]) -> Result<Vec<u8>>;
+    async fn unwrap(&self, wrapped: &[u8]) -> Result<[u8; 32]>;
+}
+
+fn key_from_slice(bytes: &[u8]) -> Result<[u8; 32]> {
+    if bytes.len() != 32 {
+        anyhow::bail!(
+            "unwrapped key has wrong length: expected 32, got {}",
+            bytes.len()
+        );
+    }
+    let mut key = [0u8; 32];
+    key.copy_from_slice(bytes);
+    Ok(key)
+}
+
+/// Wraps data keys with an AWS KMS symmetric key (`Encrypt`/`Decrypt`).
+pub struct AwsKmsWrapper {
+    client: aws_sdk_kms::Client,
+    key_id: String,
+}
+
+impl AwsKmsWrapper {
+    pub async fn new(key_id: &str, region: Option<&str>) -> Self {
+        use aws_config::{BehaviorVersion, Region};
+
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(region) = region.filter(|r| !r.trim().is_empty()) {
+            loader = loader.region(Region::new(region.to_string()));
+        }
+        let shared = loader.load().await;
+        Self {
+            client: aws_sdk_kms::Client::new(&shared),
+            key_id: key_id.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl KeyWrapper for AwsKmsWrapper {
+    fn name(&self) -> &'static str {
+        "aws-kms"
+    }
+
+    async fn wrap(&self, key: &[u8; 32]) -> Result<Vec<u8>> {
+        use aws_sdk_kms::primitives::Blob;
+
+        let out = self
+            .client
+            .encrypt()
+            .key_id(&self.key_id)
+            .plaintext(Blob::new(key.to_vec()))
+            .send()
+            .await
+            .context("kms encrypt")?;
+        let blob = out
+            .ciphertext_blob()
+            .ok_or_else(|| anyhow::anyhow!("kms encrypt returned no ciphertext"))?;
+        Ok(blob.as_ref().to_vec())
+    }
+
+    async fn unwrap(&self, wrapped: &[u8]) -> Result<[u8; 32]> {
+        use aws_sdk_kms::primitives::Blob;
+
+        let out = self
+            .client
+            .decrypt()
+            .key_id(&self.key_id)
+            .ciphertext_blob(Blob::new(wrapped.to_vec()))
+            .send()
+            .await
+            .context("kms decrypt")?;
+        let plaintext = out
+            .plaintext()
+            .ok_or_else(|| anyhow::anyhow!("kms decrypt returned no plaintext"))?;
+        key_from_slice(plaintext.as_ref())
+    }
+}
+
+/// Wraps data keys with a HashiCorp Vault (or OpenBao) transit engine key.
+/// Also serves as the local stand-in for a cloud KMS in development.
+pub struct VaultTransitWrapper {
+    client: reqwest::Client,
+    addr: String,
+    token: String,
+    key_name: String,
+}
+
+impl VaultTransitWrapper {
+    pub fn new(addr: &str, token: &str, key_name: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            addr: addr.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            key_name: key_name.to_string(),
+        }
+    }
+
+    async fn transit(&self, op: &str, body: serde_json::Value) -> Result<serde_json::Value> {
+        let url = format!("{}/v1/transit/{op}/{}", self.addr, self.key_name);
+        let resp = self
+            .client
+            .post(&url)
+            .header("X-Vault-Token", &self.token)
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("vault transit {op}"))?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!("vault transit {op} failed ({status}): {text}");
+        }
+        let json: serde_json::Value = resp.json().await.context("vault transit response")?;
+        Ok(json["data"].clone())
+    }
+}
+
+#[async_trait]
+impl KeyWrapper for VaultTransitWrapper {
+    fn name(&self) -> &'static str {
+        "vault-transit"
+    }
+
+    async fn wrap(&self, key: &[u8; 32]) -> Result<Vec<u8>> {
+        let plaintext = base64::engine::general_purpose::STANDARD.encode(key);
+        let data = self
+            .transit("encrypt", serde_json::json!({ "plaintext": plaintext }))
+            .await?;
+        let ciphertext = data["ciphertext"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("vault transit encrypt returned no ciphertext"))?;
+        Ok(ciphertext.as_bytes().to_vec())
+    }
+
+    async fn unwrap(&self, wrapped: &[u8]) -> Result<[u8; 32]> {
+        let ciphertext =
+            std::str::from_utf8(wrapped).context("wrapped vault key is not valid UTF-8")?;
+        let data = self
+            .transit(
+                "decrypt",
+                serde_json::json!({ "ciphertext": ciphertext.trim() }),
+            )
+            .await?;
+        let plaintext = data["plaintext"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("vault transit decrypt returned no plaintext"))?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(plaintext)
+            .context("vault plaintext base64 decode failed")?;
+        key_from_slice(&bytes)
+    }
+}
+
 // ── SecretKeyManager ────────────────────────────────────────────────────
 
 pub struct SecretKeyManager;
@@ -141,6 +414,121 @@ impl SecretKeyManager {
 
         Ok(key)
     }
+
+    /// Load every data key under `data_dir`. The legacy `.secret_key` file is
+    /// registered as `k0`; rotated keys live in `.secret_keys/<id>.key`, or
+    /// `<id>.wrapped` when a `wrapper` (envelope mode) is configured.
+    /// Generates a first key when none exist.
+    pub async fn load_keyring(data_dir: &str, wrapper: Option<&dyn KeyWrapper>) -> Result<KeyRing> {
+        let root = std::path::Path::new(data_dir);
+        let dir = root.join(KEYRING_DIR);
+        let legacy = root.join(".secret_key");
+
+        let mut ring: Option<KeyRing> = None;
+        let add = |ring: &mut Option<KeyRing>, id: &str, key: [u8; 32]| match ring {
+            Some(r) => r.insert(id, key),
+            None => *ring = Some(KeyRing::single(id, key)),
+        };
+
+        if legacy.exists() {
+            let key = Self::load_or_generate(data_dir)?;
+            add(&mut ring, LEGACY_KEY_ID, key);
+        }
+
+        if dir.is_dir() {
+            let entries = std::fs::read_dir(&dir).context("failed to read keyring directory")?;
+            for entry in entries {
+                let path = entry?.path();
+                let (Some(stem), Some(ext)) = (
+                    path.file_stem().and_then(|s| s.to_str()),
+                    path.extension().and_then(|s| s.to_str()),
+                ) else {
+                    continue;
+                };
+                if !valid_key_id(stem) {
+                    continue;
+                }
+                let bytes = std::fs::read(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                let key = match (ext, wrapper) {
+                    ("key", _) => key_from_slice(&bytes)
+                        .with_context(|| format!("invalid key file {}", path.display()))?,
+                    ("wrapped", Some(w)) => w
+                        .unwrap(&bytes)
+                        .await
+                        .with_context(|| format!("{} failed to unwrap key {stem}", w.name()))?,
+                    ("wrapped", None) => {
+                        anyhow::bail!("secret key {stem} is KMS-wrapped but no KMS is configured")
+                    },
+                    _ => continue,
+                };
+                add(&mut ring, stem, key);
+            }
+        }
+
+        let mut ring = match ring {
+            Some(ring) => ring,
+            None => {
+                let id = "k1";
+                let key = Self::write_new_key(&dir, id, wrapper).await?;
+                Self::write_active_id(&dir, id)?;
+                KeyRing::single(id, key)
+            },
+        };
+
+        if let Ok(active) = std::fs::read_to_string(dir.join(ACTIVE_KEY_FILE)) {
+            ring.set_active(active.trim())
+                .context("active secret key is not loadable")?;
+        }
+
+        Ok(ring)
+    }
+
+    /// Generate a new data key, persist it (wrapped in envelope mode) and make
+    /// it the active key. Returns the new key id. Existing rows keep their old
+    /// key id until the re-encryption job rewrites them.
+    pub async fn rotate(data_dir: &str, wrapper: Option<&dyn KeyWrapper>) -> Result<String> {
+        let ring = Self::load_keyring(data_dir, wrapper).await?;
+        let id = ring.next_id();
+        let dir = std::path::Path::new(data_dir).join(KEYRING_DIR);
+        Self::write_new_key(&dir, &id, wrapper).await?;
+        Self::write_active_id(&dir, &id)?;
+        Ok(id)
+    }
+
+    /// Point the keyring at `id`. Written to a temp file and renamed so a
+    /// crash or a concurrent reader never sees a truncated key id.
+    fn write_active_id(dir: &std::path::Path, id: &str) -> Result<()> {
+        let tmp = dir.join(format!("{ACTIVE_KEY_FILE}.tmp"));
+        std::fs::write(&tmp, id).context("failed to write active key id")?;
+        std::fs::rename(&tmp, dir.join(ACTIVE_KEY_FILE)).context("failed to write active key id")
+    }
+
+    async fn write_new_key(
+        dir: &std::path::Path,
+        id: &str,
+        wrapper: Option<&dyn KeyWrapper>,
+    ) -> Result<[u8; 32]> {
+        std::fs::create_dir_all(dir).context("failed to create keyring directory")?;
+
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+
+        let (path, contents) = match wrapper {
+            Some(w) => (dir.join(format!("{id}.wrapped")), w.wrap(&key).await?),
+            None => (dir.join(format!("{id}.key")), key.to_vec()),
+        };
+        std::fs::write(&path, contents).context("failed to write secret key file")?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+                .context("failed to set key file permissions")?;
+        }
+
+        Ok(key)
+    }
 }
 
 // ── Migration Helper ────────────────────────────────────────────────────
@@ -149,7 +537,7 @@ pub fn migrate_plaintext_secrets(store: &EncryptedStore, values: &[String]) -> V
     values
         .iter()
         .map(|v| {
-            if v.starts_with(ENC_PREFIX) {
+            if is_encrypted_value(v) {
                 v.clone()
             } else {
                 store.encrypt(v).unwrap_or_else(|_| v.clone())
@@ -177,7 +565,7 @@ mod tests {
         let store = EncryptedStore::new(&test_key());
         let original = "sk-ant-REDACTED";
         let encrypted = store.encrypt(original).unwrap();
-        assert!(encrypted.starts_with(ENC_PREFIX));
+        assert!(encrypted.starts_with("enc2:k0:"));
         assert_ne!(encrypted, original);
         let decrypted = store.decrypt(&encrypted).unwrap();
         assert_eq!(decrypted, original);
@@ -234,13 +622,95 @@ mod tests {
             "another-plain".to_string(),
         ];
         let migrated = migrate_plaintext_secrets(&store, &values);
-        assert!(migrated[0].starts_with(ENC_PREFIX));
+        assert!(migrated[0].starts_with(ENC_V2_PREFIX));
         assert_eq!(migrated[1], already_encrypted);
-        assert!(migrated[2].starts_with(ENC_PREFIX));
+        assert!(migrated[2].starts_with(ENC_V2_PREFIX));
         assert_eq!(store.decrypt(&migrated[0]).unwrap(), "plaintext-key");
         assert_eq!(store.decrypt(&migrated[2]).unwrap(), "another-plain");
     }
 
+    #[test]
+    fn legacy_enc1_still_decrypts() {
+        let key = test_key();
+        let cipher = ChaCha20Poly1305::new((&key).into());
+        let nonce_bytes = [7u8; NONCE_LEN];
+        let ct = cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), b"old-secret".as_ref())
+            .unwrap();
+        let mut combined = nonce_bytes.to_vec();
+        combined.extend_from_slice(&ct);
+        let legacy = format!(
+            "{ENC_PREFIX}{}",
+            base64::engine::general_purpose::STANDARD.encode(&combined)
+        );
+
+        let store = EncryptedStore::new(&key);
+        assert_eq!(store.decrypt(&legacy).unwrap(), "old-secret");
+        assert!(store.needs_rotation(&legacy));
+    }
+
+    #[test]
+    fn rotated_keyring_reads_old_and_writes_new() {
+        let mut ring = KeyRing::single("k1", test_key());
+        let old_store = EncryptedStore::from_keyring(&ring);
+        let old_value = old_store.encrypt("rotating").unwrap();
+
+        ring.insert("k2", [9u8; 32]);
+        ring.set_active("k2").unwrap();
+        let store = EncryptedStore::from_keyring(&ring);
+
+        assert!(store.needs_rotation(&old_value));
+        assert_eq!(store.decrypt(&old_value).unwrap(), "rotating");
+        let new_value = store.encrypt("rotating").unwrap();
+        assert!(new_value.starts_with("enc2:k2:"));
+        assert!(!store.needs_rotation(&new_value));
+        assert!(old_store.decrypt(&new_value).is_err());
+    }
+
+    #[tokio::test]
+    async fn keyring_rotation_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().to_str().unwrap();
+        let legacy = SecretKeyManager::load_or_generate(data_dir).unwrap();
+
+        let ring = SecretKeyManager::load_keyring(data_dir, None)
+            .await
+            .unwrap();
+        assert_eq!(ring.active_id(), LEGACY_KEY_ID);
+
+        let id = SecretKeyManager::rotate(data_dir, None).await.unwrap();
+        assert_eq!(id, "k1");
+        let ring = SecretKeyManager::load_keyring(data_dir, None)
+            .await
+            .unwrap();
+        assert_eq!(ring.active_id(), "k1");
+        assert_eq!(ring.key_ids(), vec!["k0".to_string(), "k1".to_string()]);
+
+        let store = EncryptedStore::from_keyring(&ring);
+        let legacy_value = EncryptedStore::new(&legacy).encrypt("v").unwrap();
+        assert_eq!(store.decrypt(&legacy_value).unwrap(), "v");
+
+        assert_eq!(
+            SecretKeyManager::rotate(data_dir, None).await.unwrap(),
+            "k2"
+        );
+    }
+
+    #[tokio::test]
+    async fn keyring_generates_first_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().to_str().unwrap();
+        let ring = SecretKeyManager::load_keyring(data_dir, None)
+            .await
+            .unwrap();
+        assert_eq!(ring.active_id(), "k1");
+        assert!(dir.path().join(".secret_keys/k1.key").exists());
+        let again = SecretKeyManager::load_keyring(data_dir, None)
+            .await
+            .unwrap();
+        assert_eq!(again.key_ids(), vec!["k1".to_string()]);
+    }
+
     #[test]
     fn plaintext_store_identity() {
         let store = PlaintextStore;
@@ -0,0 +1,802 @@
+//! Per-test outcomes parsed from test runner output.
+//!
+//! Understands libtest (`cargo test`), pytest, jest and JUnit XML embedded
+//! in the output, and pulls structured failures out of JUnit XML, libtest
+//! and TAP. Runners it does not recognise yield no cases, and callers treat
+//! the run as opaque.
+
+use std::collections::BTreeMap;
+
+use quick_xml::{events::Event, Reader};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    Failed,
+}
+
+impl TestStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Passed => "passed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub status: TestStatus,
+}
+
+/// Every test the output reports a result for, once each and sorted by
+/// name. A test reported more than once counts as failed if any run failed.
+pub fn parse_test_output(output: &str) -> Vec<TestCase> {
+    let mut cases: BTreeMap<String, TestStatus> = BTreeMap::new();
+    let mut add = |name: &str, status: TestStatus| {
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+        let entry = cases.entry(name.to_string()).or_insert(status);
+        if status == TestStatus::Failed {
+            *entry = TestStatus::Failed;
+        }
+    };
+    for line in output.lines() {
+        if let Some((name, status)) = libtest_line(line)
+            .or_else(|| pytest_line(line))
+            .or_else(|| jest_line(line))
+        {
+            add(name, status);
+        }
+    }
+    for (name, status) in junit_cases(output) {
+        add(&name, status);
+    }
+    cases
+        .into_iter()
+        .map(|(name, status)| TestCase { name, status })
+        .collect()
+}
+
+/// `test foo::bar ... ok` / `... FAILED`. Ignored tests are skipped.
+fn libtest_line(line: &str) -> Option<(&str, TestStatus)> {
+    let (name, result) = line.trim().strip_prefix("test ")?.rsplit_once(" ... ")?;
+    let status = match result.trim() {
+        "ok" => TestStatus::Passed,
+        "FAILED" => TestStatus::Failed,
+        _ => return None,
+    };
+    Some((name, status))
+}
+
+/// Verbose `tests/test_x.py::test_a PASSED [ 10%]` and short-summary
+/// `FAILED tests/test_x.py::test_a - AssertionError` lines.
+fn pytest_line(line: &str) -> Option<(&str, TestStatus)> {
+    let mut words = line.split_whitespace();
+    let (first, second) = (words.next()?, words.next()?);
+    let status = |word: &str| match word {
+        "PASSED" | "XPASS" => Some(TestStatus::Passed),
+        "FAILED" | "ERROR" => Some(TestStatus::Failed),
+        _ => None,
+    };
+    if first.contains("::") {
+        return Some((first, status(second)?));
+    }
+    if second.contains("::") {
+        return Some((second, status(first)?));
+    }
+    None
+}
+
+/// `✓ renders the header (5 ms)` / `✕ rejects bad input (3 ms)`.
+fn jest_line(line: &str) -> Option<(&str, TestStatus)> {
+    let line = line.trim();
+    let (status, rest) = if let Some(rest) = line.strip_prefix('✓') {
+        (TestStatus::Passed, rest)
+    } else {
+        (TestStatus::Failed, line.strip_prefix('✕')?)
+    };
+    let name = match rest.trim_end().strip_suffix(" ms)") {
+        Some(timed) => timed.rsplit_once(" (").map_or(rest, |(name, _)| name),
+        None => rest,
+    };
+    Some((name, status))
+}
+
+/// `<testcase classname=".." name="..">` elements of JUnit XML reports
+/// printed into the output; a `<failure>` or `<error>` child fails the case
+/// and a `<skipped>` one drops it.
+fn junit_cases(output: &str) -> Vec<(String, TestStatus)> {
+    let Some(start) = output.find("<testsuite") else {
+        return Vec::new();
+    };
+    let mut reader = Reader::from_str(&output[start..]);
+    let mut cases = Vec::new();
+    let mut current: Option<(String, Option<TestStatus>)> = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) if e.local_name().as_ref() == b"testcase" => {
+                if let Some((name, status)) = current.take() {
+                    cases.extend(status.map(|s| (name, s)));
+                }
+                let attr = |key: &[u8]| {
+                    e.try_get_attribute(key)
+                        .ok()
+                        .flatten()
+                        .and_then(|a| a.unescape_value().ok())
+                        .map(|v| v.into_owned())
+                        .unwrap_or_default()
+                };
+                let (class, test) = (attr(b"classname"), attr(b"name"));
+                let name = if class.is_empty() {
+                    test
+                } else {
+                    format!("{class}::{test}")
+                };
+                current = Some((name, Some(TestStatus::Passed)));
+            },
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                if let Some((_, status)) = current.as_mut() {
+                    match e.local_name().as_ref() {
+                        b"failure" | b"error" => *status = status.map(|_| TestStatus::Failed),
+                        b"skipped" => *status = None,
+                        _ => {},
+                    }
+                }
+            },
+            Ok(Event::End(e)) if e.local_name().as_ref() == b"testcase" => {
+                if let Some((name, status)) = current.take() {
+                    cases.extend(status.map(|s| (name, s)));
+                }
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {},
+        }
+    }
+    if let Some((name, Some(status))) = current {
+        cases.push((name, status));
+    }
+    cases
+}
+
+/// Most lines of a failure's stack kept for storage and display.
+const STACK_LINES: usize = 15;
+/// Longest failure message kept, in characters.
+const MESSAGE_CHARS: usize = 500;
+
+/// One failing test with where and why it failed, as far as the runner
+/// reported it. `file` is empty and `line` is 0 when unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestFailure {
+    pub name: String,
+    pub file: String,
+    pub line: u32,
+    pub message: String,
+    pub stack: String,
+}
+
+/// Structured failures from JUnit XML, libtest (JSON events or the default
+/// `---- name stdout ----` blocks) and TAP output, in the order reported.
+/// A test reported by more than one format is listed once.
+pub fn parse_failures(output: &str) -> Vec<TestFailure> {
+    let mut failures = junit_failures(output);
+    failures.extend(libtest_json_failures(output));
+    failures.extend(libtest_text_failures(output));
+    failures.extend(tap_failures(output));
+    let mut seen = std::collections::HashSet::new();
+    failures.retain(|f| !f.name.is_empty() && seen.insert(f.name.clone()));
+    for f in &mut failures {
+        if f.file.is_empty() {
+            if let Some((file, line)) =
+                find_location(&f.message).or_else(|| find_location(&f.stack))
+            {
+                f.file = file;
+                f.line = line;
+            }
+        }
+        f.message = truncate_chars(f.message.trim(), MESSAGE_CHARS);
+        f.stack = f
+            .stack
+            .trim()
+            .lines()
+            .take(STACK_LINES)
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    failures
+}
+
+/// A short list for retry prompts: one entry per failure with its location
+/// and message, at most `limit` entries.
+pub fn format_failures(failures: &[TestFailure], limit: usize) -> String {
+    let mut out = format!("Failing tests ({}):\n", failures.len());
+    for f in failures.iter().take(limit) {
+        out.push_str(&format!("- `{}`", f.name));
+        if !f.file.is_empty() {
+            if f.line > 0 {
+                out.push_str(&format!(" at {}:{}", f.file, f.line));
+            } else {
+                out.push_str(&format!(" in {}", f.file));
+            }
+        }
+        let mut message = f.message.lines();
+        if let Some(first) = message.next() {
+            out.push_str(&format!(": {first}"));
+        }
+        for more in message.take(4) {
+            out.push_str(&format!("\n    {more}"));
+        }
+        out.push('\n');
+    }
+    if failures.len() > limit {
+        out.push_str(&format!("- … and {} more\n", failures.len() - limit));
+    }
+    out
+}
+
+fn junit_failures(output: &str) -> Vec<TestFailure> {
+    let Some(start) = output.find("<testsuite") else {
+        return Vec::new();
+    };
+    let mut reader = Reader::from_str(&output[start..]);
+    let mut failures = Vec::new();
+    let mut case: Option<TestFailure> = None;
+    let mut failed = false;
+    let mut in_failure = false;
+    loop {
+        let event = reader.read_event();
+        match event {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                let empty = matches!(event, Ok(Event::Empty(_)));
+                let attr = |key: &[u8]| {
+                    e.try_get_attribute(key)
+                        .ok()
+                        .flatten()
+                        .and_then(|a| a.unescape_value().ok())
+                        .map(|v| v.into_owned())
+                        .unwrap_or_default()
+                };
+                match e.local_name().as_ref() {
+                    b"testcase" => {
+                        if let Some(f) = case.take().filter(|_| failed) {
+                            failures.push(f);
+                        }
+                        let (class, test) = (attr(b"classname"), attr(b"name"));
+                        case = Some(TestFailure {
+                            name: if class.is_empty() {
+                                test
+                            } else {
+                                format!("{class}::{test}")
+                            },
+                            file: attr(b"file"),
+                            line: attr(b"line").parse().unwrap_or(0),
+                            ..TestFailure::default()
+                        });
+                        failed = false;
+                    },
+                    b"failure" | b"error" if case.is_some() => {
+                        failed = true;
+                        in_failure = !empty;
+                        if let Some(f) = case.as_mut() {
+                            if f.message.is_empty() {
+                                f.message = attr(b"message");
+                            }
+                        }
+                    },
+                    _ => {},
+                }
+            },
+            Ok(Event::Text(ref t)) if in_failure => {
+                if let (Some(f), Ok(text)) = (case.as_mut(), t.xml_content()) {
+                    f.stack.push_str(&text);
+                }
+            },
+            Ok(Event::CData(ref t)) if in_failure => {
+                if let (Some(f), Ok(text)) = (case.as_mut(), t.decode()) {
+                    f.stack.push_str(&text);
+                }
+            },
+            Ok(Event::GeneralRef(ref r)) if in_failure => {
+                let entity = r.decode().map(|name| format!("&{name};"));
+                if let (Some(f), Ok(entity)) = (case.as_mut(), entity) {
+                    f.stack
+                        .push_str(&quick_xml::escape::unescape(&entity).unwrap_or_default());
+                }
+            },
+            Ok(Event::End(e)) => match e.local_name().as_ref() {
+                b"failure" | b"error" => in_failure = false,
+                b"testcase" => {
+                    if let Some(f) = case.take().filter(|_| failed) {
+                        failures.push(f);
+                    }
+                    failed = false;
+                },
+                _ => {},
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {},
+        }
+    }
+    if let Some(f) = case.filter(|_| failed) {
+        failures.push(f);
+    }
+    for f in &mut failures {
+        if f.message.is_empty() {
+            f.message = f.stack.trim().lines().next().unwrap_or("").to_string();
+        }
+    }
+    failures
+}
+
+/// `{"type": "test", "event": "failed", "name": .., "stdout": ..}` lines
+/// from `--format json` (libtest) or nextest's libtest-json output.
+fn libtest_json_failures(output: &str) -> Vec<TestFailure> {
+    output
+        .lines()
+        .filter(|line| line.trim_start().starts_with('{'))
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line.trim()).ok())
+        .filter(|v| v["type"] == "test" && v["event"] == "failed")
+        .map(|v| {
+            let stdout = v["stdout"].as_str().unwrap_or("");
+            let mut failure = libtest_panic(stdout);
+            failure.name = v["name"].as_str().unwrap_or("").to_string();
+            if failure.message.is_empty() {
+                failure.message = v["message"].as_str().unwrap_or("").to_string();
+            }
+            failure
+        })
+        .collect()
+}
+
+/// The `---- name stdout ----` blocks libtest prints under `failures:`.
+/// Blocks under `successes:` (`--show-output`) are skipped.
+fn libtest_text_failures(output: &str) -> Vec<TestFailure> {
+    let mut failures = Vec::new();
+    let mut in_failures = false;
+    let mut current: Option<(String, Vec<&str>)> = None;
+    let mut finish = |current: &mut Option<(String, Vec<&str>)>| {
+        if let Some((name, lines)) = current.take() {
+            let mut failure = libtest_panic(&lines.join("\n"));
+            failure.name = name;
+            failures.push(failure);
+        }
+    };
+    for line in output.lines() {
+        let header = line
+            .trim()
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"));
+        match line.trim() {
+            "failures:" | "successes:" => {
+                finish(&mut current);
+                in_failures = line.trim() == "failures:";
+                continue;
+            },
+            l if l.starts_with("test result:") => {
+                finish(&mut current);
+                in_failures = false;
+                continue;
+            },
+            _ => {},
+        }
+        if let Some(name) = header {
+            finish(&mut current);
+            if in_failures {
+                current = Some((name.to_string(), Vec::new()));
+            }
+        } else if let Some((_, lines)) = current.as_mut() {
+            lines.push(line);
+        }
+    }
+    finish(&mut current);
+    failures
+}
+
+/// Location, message and backtrace of a libtest panic report, in either
+/// the `panicked at src/x.rs:1:2:\nmsg` or the older
+/// `panicked at 'msg', src/x.rs:1:2` form.
+fn libtest_panic(text: &str) -> TestFailure {
+    let mut failure = TestFailure {
+        stack: text.to_string(),
+        ..TestFailure::default()
+    };
+    let Some(at) = text.find("panicked at ") else {
+        failure.message = text.trim().lines().next().unwrap_or("").to_string();
+        return failure;
+    };
+    let rest = &text[at + "panicked at ".len()..];
+    let (head, body) = rest.split_once('\n').unwrap_or((rest, ""));
+    let (message, location) = match head.strip_suffix(':') {
+        Some(location) => {
+            let message: Vec<&str> = body
+                .lines()
+                .take_while(|l| !l.starts_with("note:") && !l.starts_with("stack backtrace:"))
+                .collect();
+            (message.join("\n"), location.to_string())
+        },
+        None => match head.rsplit_once(", ") {
+            Some((message, location)) => {
+                (message.trim_matches('\'').to_string(), location.to_string())
+            },
+            None => (head.to_string(), String::new()),
+        },
+    };
+    failure.message = message;
+    if let Some((file, line)) = find_location(&location) {
+        failure.file = file;
+        failure.line = line;
+    }
+    failure.stack = match body.find("stack backtrace:") {
+        Some(bt) => body[bt..].to_string(),
+        None => String::new(),
+    };
+    failure
+}
+
+/// `not ok 3 - name` lines and the YAML diagnostic block after them.
+/// `# SKIP` and `# TODO` results are not failures.
+fn tap_failures(output: &str) -> Vec<TestFailure> {
+    let mut failures = Vec::new();
+    let mut lines = output.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(rest) = line.trim().strip_prefix("not ok") else {
+            continue;
+        };
+        let (desc, directive) = rest.split_once(" # ").unwrap_or((rest, ""));
+        let directive = directive.trim_start().to_ascii_uppercase();
+        if directive.starts_with("SKIP") || directive.starts_with("TODO") {
+            continue;
+        }
+        let desc = desc.trim().trim_start_matches(|c: char| c.is_ascii_digit());
+        let mut failure = TestFailure {
+            name: desc
+                .trim_start()
+                .trim_start_matches("- ")
+                .trim()
+                .to_string(),
+            ..TestFailure::default()
+        };
+        if lines.peek().is_some_and(|l| l.trim() == "---") {
+            lines.next();
+            let mut block = Vec::new();
+            for l in lines.by_ref() {
+                if l.trim() == "..." {
+                    break;
+                }
+                block.push(l);
+            }
+            tap_diagnostics(&block, &mut failure);
+        }
+        failures.push(failure);
+    }
+    failures
+}
+
+/// Fill `message`, `file`, `line` and `stack` from a TAP YAML block. Keys
+/// may be nested (node-tap's `at: {file, line}`), and `|` values continue
+/// on the more indented lines that follow.
+fn tap_diagnostics(block: &[&str], failure: &mut TestFailure) {
+    let indent = |l: &str| l.len() - l.trim_start().len();
+    let mut i = 0;
+    while i < block.len() {
+        let line = block[i];
+        i += 1;
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = if value.starts_with('|') || value.starts_with('>') {
+            let mut text = Vec::new();
+            while i < block.len() && (block[i].trim().is_empty() || indent(block[i]) > indent(line))
+            {
+                text.push(block[i].trim());
+                i += 1;
+            }
+            text.join("\n")
+        } else {
+            value.trim_matches(|c| c == '"' || c == '\'').to_string()
+        };
+        match key.trim() {
+            "message" if failure.message.is_empty() => failure.message = value,
+            "file" if failure.file.is_empty() => failure.file = value,
+            "line" if failure.line == 0 => failure.line = value.parse().unwrap_or(0),
+            "stack" if failure.stack.is_empty() => failure.stack = value,
+            "at" if failure.file.is_empty() && !value.is_empty() => {
+                if let Some((file, line)) = find_location(&value) {
+                    failure.file = file;
+                    failure.line = line;
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// First `path/to/file.ext:line` or Python `File "path", line N` in `text`.
+fn find_location(text: &str) -> Option<(String, u32)> {
+    if let Some(at) = text.find("File \"") {
+        let rest = &text[at + "File \"".len()..];
+        if let Some((file, after)) = rest.split_once("\", line ") {
+            let digits: String = after.chars().take_while(|c| c.is_ascii_digit()).collect();
+            if let Ok(line) = digits.parse() {
+                return Some((file.to_string(), line));
+            }
+        }
+    }
+    text.split(|c: char| c.is_whitespace() || "()'\"`,".contains(c))
+        .find_map(|word| {
+            let mut parts = word.split(':');
+            let file = parts.next()?;
+            let line: u32 = parts.next()?.parse().ok()?;
+            let ext = file.rsplit_once('.')?.1;
+            (ext.chars().all(|c| c.is_ascii_alphanumeric())
+                && ext.chars().any(|c| c.is_ascii_alphabetic()))
+            .then(|| (file.trim_start_matches("file://").to_string(), line))
+        })
+}
+
+fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(max).collect();
+    out.push('…');
+    out
+}
+
+/// `test_cmd` with `tests` excluded, for runners that can skip by name
+/// (libtest `--skip`, pytest `--deselect`).
+pub fn skip_tests(test_cmd: &str, tests: &[String]) -> Option<String> {
+    if tests.is_empty() {
+        return Some(test_cmd.to_string());
+    }
+    if test_cmd.contains("cargo test") {
+        let skips: Vec<String> = tests
+            .iter()
+            .map(|t| format!("--skip {}", shell_quote(t)))
+            .collect();
+        return Some(format!(
+            "{test_cmd}{} {}",
+            libtest_sep(test_cmd),
+            skips.join(" ")
+        ));
+    }
+    if test_cmd.contains("pytest") {
+        let skips: Vec<String> = tests
+            .iter()
+            .map(|t| format!("--deselect {}", shell_quote(t)))
+            .collect();
+        return Some(format!("{test_cmd} {}", skips.join(" ")));
+    }
+    None
+}
+
+/// `test_cmd` narrowed to just `tests`, for runners that can select by name
+/// (libtest exact filters, pytest `-k`).
+pub fn only_tests(test_cmd: &str, tests: &[String]) -> Option<String> {
+    if tests.is_empty() {
+        return None;
+    }
+    if test_cmd.contains("cargo test") {
+        let names: Vec<String> = tests.iter().map(|t| shell_quote(t)).collect();
+        return Some(format!(
+            "{test_cmd}{} --exact {}",
+            libtest_sep(test_cmd),
+            names.join(" ")
+        ));
+    }
+    if test_cmd.contains("pytest") {
+        let names: Vec<&str> = tests
+            .iter()
+            .map(|t| t.rsplit("::").next().unwrap_or(t))
+            .collect();
+        return Some(format!(
+            "{test_cmd} -k {}",
+            shell_quote(&names.join(" or "))
+        ));
+    }
+    None
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// The `--` needed before libtest arguments, unless `test_cmd` has one.
+fn libtest_sep(test_cmd: &str) -> &'static str {
+    if test_cmd.split_whitespace().any(|w| w == "--") {
+        ""
+    } else {
+        " --"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(name: &str, status: TestStatus) -> TestCase {
+        TestCase {
+            name: name.to_string(),
+            status,
+        }
+    }
+
+    #[test]
+    fn parses_libtest_pytest_and_jest() {
+        let output = "\
+running 3 tests
+test db::tests::roundtrip ... ok
+test db::tests::slow ... ignored
+test pipeline::tests::retry ... FAILED
+tests/test_api.py::test_login PASSED                     [ 50%]
+FAILED tests/test_api.py::test_logout - AssertionError: 1 != 2
+  ✓ renders the header (5 ms)
+  ✕ rejects bad input (12 ms)
+";
+        assert_eq!(
+            parse_test_output(output),
+            vec![
+                case("db::tests::roundtrip", TestStatus::Passed),
+                case("pipeline::tests::retry", TestStatus::Failed),
+                case("rejects bad input", TestStatus::Failed),
+                case("renders the header", TestStatus::Passed),
+                case("tests/test_api.py::test_login", TestStatus::Passed),
+                case("tests/test_api.py::test_logout", TestStatus::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_junit_xml_in_output() {
+        let output = r#"jest-junit wrote:
+<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="cart">
+    <testcase classname="cart" name="adds &amp; removes" time="0.01"/>
+    <testcase classname="cart" name="totals" time="0.02">
+      <failure message="expected 3">AssertionError</failure>
+    </testcase>
+    <testcase classname="cart" name="later"><skipped/></testcase>
+  </testsuite>
+</testsuites>"#;
+        assert_eq!(
+            parse_test_output(output),
+            vec![
+                case("cart::adds & removes", TestStatus::Passed),
+                case("cart::totals", TestStatus::Failed),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_libtest_failures_text_and_json() {
+        let output = r#"running 2 tests
+test cart::tests::totals ... FAILED
+test cart::tests::adds ... ok
+
+failures:
+
+---- cart::tests::totals stdout ----
+
+thread 'cart::tests::totals' panicked at src/cart.rs:42:9:
+assertion `left == right` failed
+  left: 3
+ right: 4
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+
+failures:
+    cart::tests::totals
+
+test result: FAILED. 1 passed; 1 failed; 0 ignored
+{ "type": "test", "name": "net::tests::timeout", "event": "failed", "stdout": "thread 'net::tests::timeout' panicked at 'timed out', src/net.rs:7:5\n" }
+"#;
+        let failures = parse_failures(output);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].name, "net::tests::timeout");
+        assert_eq!(
+            (failures[0].file.as_str(), failures[0].line),
+            ("src/net.rs", 7)
+        );
+        assert_eq!(failures[0].message, "timed out");
+        assert_eq!(failures[1].name, "cart::tests::totals");
+        assert_eq!(
+            (failures[1].file.as_str(), failures[1].line),
+            ("src/cart.rs", 42)
+        );
+        assert_eq!(
+            failures[1].message,
+            "assertion `left == right` failed\n  left: 3\n right: 4"
+        );
+    }
+
+    #[test]
+    fn parses_tap_and_junit_failures() {
+        let output = r#"TAP version 13
+ok 1 - adds items
+not ok 2 - applies discount
+  ---
+  message: 'expected 90 to equal 80'
+  at:
+    file: test/cart.test.js
+    line: 31
+  stack: |-
+    AssertionError: expected 90 to equal 80
+        at Context.<anonymous> (test/cart.test.js:31:12)
+  ...
+not ok 3 - ships abroad # SKIP no network
+<testsuite name="api">
+  <testcase classname="tests.test_api" name="test_logout" file="tests/test_api.py" line="18">
+    <failure message="AssertionError: 1 != 2">Traceback (most recent call last):
+  File "tests/test_api.py", line 20, in test_logout</failure>
+  </testcase>
+</testsuite>"#;
+        let failures = parse_failures(output);
+        let names: Vec<&str> = failures.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["tests.test_api::test_logout", "applies discount"]
+        );
+        assert_eq!(
+            (failures[0].file.as_str(), failures[0].line),
+            ("tests/test_api.py", 18)
+        );
+        assert_eq!(failures[0].message, "AssertionError: 1 != 2");
+        assert!(failures[0].stack.starts_with("Traceback"));
+        assert_eq!(
+            (failures[1].file.as_str(), failures[1].line),
+            ("test/cart.test.js", 31)
+        );
+        assert_eq!(failures[1].message, "expected 90 to equal 80");
+        assert_eq!(failures[1].stack.lines().count(), 2);
+
+        assert_eq!(
+            format_failures(&failures, 1),
+            "Failing tests (2):\n\
+             - `tests.test_api::test_logout` at tests/test_api.py:18: AssertionError: 1 != 2\n\
+             - … and 1 more\n"
+        );
+    }
+
+    #[test]
+    fn skips_quarantined_tests_by_runner() {
+        let tests = vec!["net::tests::flaky".to_string()];
+        assert_eq!(
+            skip_tests("cargo test --workspace", &tests).as_deref(),
+            Some("cargo test --workspace -- --skip 'net::tests::flaky'")
+        );
+        assert_eq!(
+            skip_tests("cargo test -- --test-threads=1", &tests).as_deref(),
+            Some("cargo test -- --test-threads=1 --skip 'net::tests::flaky'")
+        );
+        assert_eq!(
+            skip_tests("python -m pytest -q", &["t.py::test_x".to_string()]).as_deref(),
+            Some("python -m pytest -q --deselect 't.py::test_x'")
+        );
+        assert_eq!(skip_tests("npm test", &tests), None);
+    }
+
+    #[test]
+    fn selects_named_tests_by_runner() {
+        let tests = vec!["a::one".to_string(), "b::two".to_string()];
+        assert_eq!(
+            only_tests("cargo test --workspace -- --skip 'q'", &tests).as_deref(),
+            Some("cargo test --workspace -- --skip 'q' --exact 'a::one' 'b::two'")
+        );
+        assert_eq!(
+            only_tests(
+                "pytest -q",
+                &["t.py::test_x".to_string(), "t.py::test_y".to_string()]
+            )
+            .as_deref(),
+            Some("pytest -q -k 'test_x or test_y'")
+        );
+        assert_eq!(only_tests("npm test", &tests), None);
+        assert_eq!(only_tests("cargo test", &[]), None);
+    }
+}
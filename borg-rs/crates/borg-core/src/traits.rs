@@ -66,6 +66,8 @@ pub struct ChatResponse {
     pub cost_usd: f64,
     pub tool_calls: Vec<ToolCallRecord>,
     pub raw_stream: String,
+    /// Failover hops taken before a backend produced this reply.
+    pub failovers: Vec<crate::types::BackendFailover>,
 }
 
 /// A recorded tool call for observability.
@@ -173,6 +175,11 @@ pub trait MessageChannel: Send + Sync {
 pub trait SecretStore: Send + Sync {
     fn encrypt(&self, plaintext: &str) -> Result<String>;
     fn decrypt(&self, ciphertext: &str) -> Result<String>;
+    /// True if `value` is not sealed with the store's current key and should be
+    /// rewritten by the re-encryption job.
+    fn needs_rotation(&self, _value: &str) -> bool {
+        false
+    }
     async fn store(&self, key: &str, secret: &str) -> Result<()>;
     async fn retrieve(&self, key: &str) -> Result<Option<String>>;
 }
@@ -338,6 +345,18 @@ impl AgentError {
         )
     }
 
+    /// Errors that another provider may not share: quota, credentials, and
+    /// provider-side outages that survived the retry budget.
+    pub fn warrants_failover(&self) -> bool {
+        matches!(
+            self,
+            Self::AuthError
+                | Self::InsufficientBalance
+                | Self::RateLimit { .. }
+                | Self::ServerError { .. }
+        )
+    }
+
     pub fn from_error_code(code: &str, message: &str) -> Self {
         match code {
             "rate_limit" => Self::RateLimit { retry_after: None },
@@ -51,8 +51,11 @@ pub enum PhaseType {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum IntegrationType {
-    /// Creates GitHub PRs and manages merge queue.
+    /// Opens PRs/MRs on the repo's forge and manages the merge queue.
     GitPr,
+    /// Rebases onto the local default branch, re-runs tests and fast-forwards
+    /// it — no forge needed.
+    GitLocal,
     /// Commits to a branch but no PR — branch preserved for versioned document history.
     GitBranch,
     /// No VCS integration.
@@ -69,7 +72,7 @@ pub enum SeedOutputType {
 // ── Pipeline Task ────────────────────────────────────────────────────────
 
 /// A pipeline task as stored in the database.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Task {
     pub id: i64,
     pub title: String,
@@ -187,7 +190,7 @@ pub struct PipelineStateSnapshot {
 // ── Config Types ─────────────────────────────────────────────────────────
 
 /// Per-repository pipeline configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RepoConfig {
     pub path: String,
     pub test_cmd: String,
@@ -204,6 +207,22 @@ pub struct RepoConfig {
     pub backend: String,
     /// GitHub repo slug (owner/repo). Used for `gh --repo` without a local checkout.
     pub repo_slug: String,
+    /// Pinned agent image for this repo (e.g. `ghcr.io/org/img@sha256:...`).
+    /// Takes precedence over `container_file`.
+    #[serde(default)]
+    pub container_image: String,
+    /// Container file to build the agent image from, relative to the repo.
+    /// Empty = `.borg/Containerfile` or `.borg/Dockerfile` if present.
+    #[serde(default)]
+    pub container_file: String,
+    /// Code host for the integration queue and issue seeding: "github"
+    /// (default), "gitlab", or "gitea"/"forgejo".
+    #[serde(default)]
+    pub forge: String,
+    /// Base URL of a self-hosted forge (e.g. `https://git.example.com`).
+    /// Empty = github.com / gitlab.com; required for Gitea.
+    #[serde(default)]
+    pub forge_url: String,
 }
 
 // ── Phase Config ─────────────────────────────────────────────────────────
@@ -295,6 +314,14 @@ pub struct PipelineMode {
     pub uses_test_cmd: bool,
     pub integration: IntegrationType,
     pub default_max_attempts: u8,
+    /// Domains agents in this mode may reach through the egress proxy.
+    /// Empty = no proxy (network policy as before).
+    #[serde(default)]
+    pub egress_allowlist: Vec<String>,
+    /// `key=value` labels a remote worker must carry to run this mode's
+    /// tasks (e.g. `gpu=true`). Non-empty pins tasks to matching workers.
+    #[serde(default)]
+    pub worker_labels: Vec<String>,
 }
 
 impl PipelineMode {
@@ -557,8 +584,9 @@ pub struct PhaseCompletionVerdict {
 
 // ── Phase Execution ──────────────────────────────────────────────────────
 
-/// Runtime context passed to a phase executor.
-#[derive(Debug, Clone)]
+/// Runtime context passed to a phase executor. Serializable so it can be
+/// shipped to a remote worker (the live stream sender stays behind).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PhaseContext {
     pub task: Task,
     pub repo_config: RepoConfig,
@@ -578,6 +606,7 @@ pub struct PhaseContext {
     /// If non-empty, append as Co-Authored-By trailer on git commits.
     pub user_coauthor: String,
     /// If set, each raw stdout line from the agent is sent here for live streaming.
+    #[serde(skip)]
     pub stream_tx: Option<tokio::sync::mpsc::UnboundedSender<String>>,
     /// Absolute path to a setup script to source at container start (mounted as /workspace/setup.sh).
     pub setup_script: String,
@@ -593,6 +622,9 @@ pub struct PhaseContext {
     pub knowledge_repo_paths: Vec<String>,
     /// Docker bridge network name for agent containers. None = use --network host.
     pub agent_network: Option<String>,
+    /// Repo-specific agent image. None = the backend's default image.
+    #[serde(default)]
+    pub container_image: Option<String>,
     /// Prior research chunks from the knowledge graph (injected for lawborg tasks).
     pub prior_research: Vec<String>,
     /// How many revision rounds this task has been through. 0 = first draft.
@@ -615,6 +647,16 @@ pub struct PhaseContext {
     pub clarification_resume_reuses_prior_review: bool,
     /// Clarification question carried from the prior blocked / guard-failed attempt, when available.
     pub clarification_resume_question: String,
+    /// Egress proxy grant when the task has a domain allowlist. None = no proxy.
+    pub egress_proxy: Option<EgressProxy>,
+}
+
+/// Credentials for the egress allowlist proxy, valid for one phase run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EgressProxy {
+    pub task_id: i64,
+    pub port: u16,
+    pub token: String,
 }
 
 /// A single in-container test/lint/compile result emitted by the entrypoint.
@@ -624,10 +666,23 @@ pub struct ContainerTestResult {
     pub passed: bool,
     pub exit_code: i32,
     pub output: String,
+    /// Structured failures; parsed from `output` when the entrypoint sends none.
+    #[serde(default)]
+    pub failures: Vec<crate::test_report::TestFailure>,
+}
+
+impl ContainerTestResult {
+    /// Fill `failures` from `output` for a failed run that arrived without them.
+    pub fn with_parsed_failures(mut self) -> Self {
+        if !self.passed && self.failures.is_empty() {
+            self.failures = crate::test_report::parse_failures(&self.output);
+        }
+        self
+    }
 }
 
 /// Output produced by a phase executor.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PhaseOutput {
     pub output: String,
     pub new_session_id: Option<String>,
@@ -639,6 +694,19 @@ pub struct PhaseOutput {
     pub ran_in_docker: bool,
     /// In-container test results emitted by the entrypoint (compile check / lint / test).
     pub container_test_results: Vec<ContainerTestResult>,
+    /// Fallback hops taken when the selected backend failed; empty if it ran.
+    pub failovers: Vec<BackendFailover>,
+    /// CPU/memory the backend measured for its sandbox; the pipeline fills
+    /// in wall time and worktree writes.
+    pub resources: Option<crate::resources::ResourceUsage>,
+}
+
+/// One step in a backend failover chain: `from` failed, `to` ran next.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendFailover {
+    pub from: String,
+    pub to: String,
+    pub reason: String,
 }
 
 impl PhaseOutput {
@@ -651,6 +719,8 @@ impl PhaseOutput {
             signal_json: None,
             ran_in_docker: false,
             container_test_results: Vec::new(),
+            failovers: Vec::new(),
+            resources: None,
         }
     }
 }
@@ -0,0 +1,860 @@
+//! Warm pool of idle agent containers.
+//!
+//! A cold container phase pays for `docker run`, image setup and cache
+//! mounting on every run. With a pool configured (`WARM_POOL_SIZE`), the
+//! sandbox layer keeps idle containers per key (image, repo, cache volumes,
+//! network, limits) with everything already mounted and the entrypoint
+//! replaced by `sleep`. A phase checks one out, runs the image's real
+//! entrypoint via `exec`, and releases it to be recycled or destroyed.
+//!
+//! Each warm container mounts its own host directories at `/home/bun` and
+//! `/workspace`. On checkout the task's session dir and worktree contents
+//! are moved in, and on release they are moved back, so a container never
+//! sees another task's session or files. The workspace directory sits next
+//! to the repo's worktrees (`<repo>/.worktrees/.warm-*`), so both moves are
+//! renames on one filesystem, and containers are keyed per repo, not per
+//! task.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::sandbox::Sandbox;
+
+const WARM_LABEL: &str = "borg-warm=1";
+const HOME_MOUNT: &str = "/home/bun";
+const WORKSPACE_MOUNT: &str = "/workspace";
+
+#[derive(Debug, Clone)]
+pub struct WarmPoolConfig {
+    /// Idle containers kept ready per key (0 disables the pool).
+    pub size: usize,
+    /// Phases one container serves before it is destroyed (1 = never reused).
+    pub max_uses: u32,
+    /// Idle containers older than this are destroyed.
+    pub idle_ttl: Duration,
+    /// Cap on memory held by idle containers; the oldest are evicted first.
+    pub max_idle_memory_bytes: u64,
+    /// Host directory holding the per-container home directories.
+    pub home_root: PathBuf,
+}
+
+/// What a warm container was started with. Only phases with an identical
+/// key can use it, since mounts, network and limits are fixed at `run`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WarmKey {
+    pub image: String,
+    /// Repo root; task worktrees live under `<repo>/.worktrees/`.
+    pub repo: String,
+    pub volumes: Vec<(String, String)>,
+    pub network: Option<String>,
+    pub memory_mb: u64,
+    pub cpus_milli: u64,
+}
+
+impl WarmKey {
+    pub fn new(
+        image: &str,
+        repo: &str,
+        volumes: &[(&str, &str)],
+        network: Option<&str>,
+        memory_mb: u64,
+        cpus: f64,
+    ) -> Self {
+        Self {
+            image: image.to_string(),
+            repo: repo.to_string(),
+            volumes: volumes
+                .iter()
+                .map(|(n, c)| (n.to_string(), c.to_string()))
+                .collect(),
+            network: network.map(str::to_string),
+            memory_mb,
+            cpus_milli: (cpus.max(0.0) * 1000.0).round() as u64,
+        }
+    }
+}
+
+/// Repo root of a task worktree at `<repo>/.worktrees/<name>`. Only such
+/// worktrees can be staged into a warm container.
+pub fn worktree_repo(worktree: &str) -> Option<&str> {
+    let (repo, name) = worktree.trim_end_matches('/').rsplit_once("/.worktrees/")?;
+    (!repo.is_empty() && !name.is_empty() && !name.contains('/')).then_some(repo)
+}
+
+struct WarmContainer {
+    id: String,
+    home: PathBuf,
+    workspace: PathBuf,
+    entrypoint: Vec<String>,
+    uses: u32,
+    idle_since: Instant,
+    memory_bytes: u64,
+}
+
+/// Pool counters plus a snapshot of current occupancy.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WarmPoolStats {
+    pub enabled: bool,
+    pub size_per_key: usize,
+    pub max_uses: u32,
+    pub max_idle_memory_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub created: u64,
+    pub create_failures: u64,
+    pub recycled: u64,
+    pub destroyed: u64,
+    pub evicted_ttl: u64,
+    pub evicted_memory: u64,
+    pub keys: usize,
+    pub idle: usize,
+    pub in_use: usize,
+    pub idle_memory_bytes: u64,
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: HashMap<WarmKey, Vec<WarmContainer>>,
+    starting: HashMap<WarmKey, usize>,
+    in_use: usize,
+}
+
+impl PoolState {
+    fn idle_memory(&self) -> u64 {
+        self.idle.values().flatten().map(|c| c.memory_bytes).sum()
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    created: AtomicU64,
+    create_failures: AtomicU64,
+    recycled: AtomicU64,
+    destroyed: AtomicU64,
+    evicted_ttl: AtomicU64,
+    evicted_memory: AtomicU64,
+}
+
+pub struct WarmPool {
+    config: WarmPoolConfig,
+    state: Mutex<PoolState>,
+    counters: Counters,
+    seq: AtomicU64,
+}
+
+impl WarmPool {
+    pub fn new(config: WarmPoolConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            state: Mutex::new(PoolState::default()),
+            counters: Counters::default(),
+            seq: AtomicU64::new(0),
+        })
+    }
+
+    pub fn config(&self) -> &WarmPoolConfig {
+        &self.config
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Check out an idle container for `key` and move `session_dir` into
+    /// its home and `worktree` into its workspace. Returns `None` on a miss;
+    /// the caller runs cold and the pool starts warming containers for `key`
+    /// in the background.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        key: &WarmKey,
+        session_dir: &str,
+        worktree: &str,
+    ) -> Option<WarmLease> {
+        loop {
+            let candidate = self.lock().idle.get_mut(key).and_then(Vec::pop);
+            let Some(container) = candidate else {
+                self.counters.misses.fetch_add(1, Ordering::Relaxed);
+                self.spawn_refill(key.clone());
+                return None;
+            };
+            if !container_running(&container.id).await {
+                debug!(id = %container.id, "warm pool: idle container gone, discarding");
+                self.destroy(container).await;
+                continue;
+            }
+            if let Err(e) = stage(&container, Path::new(session_dir), Path::new(worktree)) {
+                warn!(id = %container.id, "warm pool: could not stage task dirs: {e:#}");
+                let _ = unstage(&container, Path::new(session_dir), Path::new(worktree));
+                self.destroy(container).await;
+                self.counters.misses.fetch_add(1, Ordering::Relaxed);
+                self.spawn_refill(key.clone());
+                return None;
+            }
+            self.counters.hits.fetch_add(1, Ordering::Relaxed);
+            self.lock().in_use += 1;
+            self.spawn_refill(key.clone());
+            return Some(WarmLease {
+                pool: Arc::clone(self),
+                key: key.clone(),
+                container: Some(container),
+                session_dir: PathBuf::from(session_dir),
+                worktree: PathBuf::from(worktree),
+            });
+        }
+    }
+
+    fn spawn_refill(self: &Arc<Self>, key: WarmKey) {
+        let pool = Arc::clone(self);
+        tokio::spawn(async move { pool.refill(key).await });
+    }
+
+    /// Start containers until `key` has `size` idle or starting, unless idle
+    /// memory is already at the cap.
+    async fn refill(&self, key: WarmKey) {
+        loop {
+            {
+                let mut state = self.lock();
+                let have = state.idle.get(&key).map_or(0, Vec::len)
+                    + state.starting.get(&key).copied().unwrap_or(0);
+                if have >= self.config.size
+                    || state.idle_memory() >= self.config.max_idle_memory_bytes
+                {
+                    return;
+                }
+                *state.starting.entry(key.clone()).or_default() += 1;
+            }
+            let created = self.create(&key).await;
+            let mut state = self.lock();
+            if let Some(n) = state.starting.get_mut(&key) {
+                *n = n.saturating_sub(1);
+            }
+            match created {
+                Ok(container) => {
+                    self.counters.created.fetch_add(1, Ordering::Relaxed);
+                    state.idle.entry(key.clone()).or_default().push(container);
+                },
+                Err(e) => {
+                    self.counters
+                        .create_failures
+                        .fetch_add(1, Ordering::Relaxed);
+                    warn!(image = %key.image, "warm pool: failed to start container: {e:#}");
+                    return;
+                },
+            }
+        }
+    }
+
+    async fn create(&self, key: &WarmKey) -> Result<WarmContainer> {
+        let entrypoint = image_entrypoint(&key.image).await?;
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let home = self
+            .config
+            .home_root
+            .join(format!("warm-{}-{seq}", std::process::id()));
+        std::fs::create_dir_all(&home).with_context(|| format!("create {}", home.display()))?;
+        let workspace = Path::new(&key.repo)
+            .join(".worktrees")
+            .join(format!(".warm-{}-{seq}", std::process::id()));
+        if let Err(e) = std::fs::create_dir_all(&workspace) {
+            let _ = std::fs::remove_dir_all(&home);
+            return Err(e).with_context(|| format!("create {}", workspace.display()));
+        }
+        let home_str = home.to_string_lossy().to_string();
+        let workspace_str = workspace.to_string_lossy().to_string();
+        let binds = [
+            (workspace_str.as_str(), WORKSPACE_MOUNT, false),
+            (home_str.as_str(), HOME_MOUNT, false),
+        ];
+        let volumes: Vec<(&str, &str)> = key
+            .volumes
+            .iter()
+            .map(|(n, c)| (n.as_str(), c.as_str()))
+            .collect();
+        let run = Sandbox::docker_command(
+            &key.image,
+            &binds,
+            &volumes,
+            "",
+            &["infinity".to_string()],
+            &[],
+            key.memory_mb,
+            key.cpus_milli as f64 / 1000.0,
+            key.network.as_deref(),
+        );
+        let mut cmd = Sandbox::with_run_args(
+            &run,
+            &["-d", "--entrypoint", "sleep", "--label", WARM_LABEL],
+        );
+        let out = cmd
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .with_context(|| format!("spawn {}", Sandbox::cli()))?;
+        if !out.status.success() {
+            let _ = std::fs::remove_dir_all(&home);
+            let _ = std::fs::remove_dir(&workspace);
+            bail!("{}", String::from_utf8_lossy(&out.stderr).trim());
+        }
+        let id = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        info!(image = %key.image, id = %short_id(&id), "warm pool: started idle container");
+        Ok(WarmContainer {
+            id,
+            home,
+            workspace,
+            entrypoint,
+            uses: 0,
+            idle_since: Instant::now(),
+            memory_bytes: 0,
+        })
+    }
+
+    async fn release(&self, key: WarmKey, mut container: WarmContainer, healthy: bool) {
+        container.uses += 1;
+        let reusable = healthy && container.uses < self.config.max_uses;
+        let leftover = {
+            let mut state = self.lock();
+            state.in_use = state.in_use.saturating_sub(1);
+            let idle = state.idle.entry(key).or_default();
+            if reusable && idle.len() < self.config.size {
+                container.idle_since = Instant::now();
+                idle.push(container);
+                None
+            } else {
+                Some(container)
+            }
+        };
+        match leftover {
+            None => {
+                self.counters.recycled.fetch_add(1, Ordering::Relaxed);
+            },
+            Some(c) => self.destroy(c).await,
+        }
+    }
+
+    async fn destroy(&self, container: WarmContainer) {
+        self.counters.destroyed.fetch_add(1, Ordering::Relaxed);
+        remove_container(&container.id).await;
+        let _ = std::fs::remove_dir_all(&container.home);
+        remove_workspace(&container.workspace);
+    }
+
+    /// Destroy idle containers past their TTL, refresh idle memory figures,
+    /// and evict the longest-idle containers while over the memory cap.
+    pub async fn maintain(&self) {
+        let expired: Vec<WarmContainer> = {
+            let mut state = self.lock();
+            let ttl = self.config.idle_ttl;
+            let mut out = Vec::new();
+            for list in state.idle.values_mut() {
+                let (old, keep): (Vec<_>, Vec<_>) =
+                    list.drain(..).partition(|c| c.idle_since.elapsed() >= ttl);
+                *list = keep;
+                out.extend(old);
+            }
+            state.idle.retain(|_, v| !v.is_empty());
+            out
+        };
+        self.counters
+            .evicted_ttl
+            .fetch_add(expired.len() as u64, Ordering::Relaxed);
+        for c in expired {
+            self.destroy(c).await;
+        }
+
+        let ids: Vec<String> = self
+            .lock()
+            .idle
+            .values()
+            .flatten()
+            .map(|c| c.id.clone())
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+        let usage = container_memory(&ids).await;
+        let evicted: Vec<WarmContainer> = {
+            let mut state = self.lock();
+            for c in state.idle.values_mut().flatten() {
+                if let Some(bytes) = usage.get(short_id(&c.id)) {
+                    c.memory_bytes = *bytes;
+                }
+            }
+            let mut out = Vec::new();
+            while state.idle_memory() > self.config.max_idle_memory_bytes {
+                let oldest = state
+                    .idle
+                    .iter()
+                    .flat_map(|(k, v)| v.iter().enumerate().map(move |(i, c)| (k, i, c.idle_since)))
+                    .min_by_key(|(_, _, since)| *since)
+                    .map(|(k, i, _)| (k.clone(), i));
+                let Some((key, i)) = oldest else { break };
+                if let Some(list) = state.idle.get_mut(&key) {
+                    out.push(list.remove(i));
+                }
+            }
+            state.idle.retain(|_, v| !v.is_empty());
+            out
+        };
+        if !evicted.is_empty() {
+            info!(
+                "warm pool: evicting {} idle container(s) over memory cap",
+                evicted.len()
+            );
+        }
+        self.counters
+            .evicted_memory
+            .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+        for c in evicted {
+            self.destroy(c).await;
+        }
+    }
+
+    /// Run `maintain` every `every` until the process exits.
+    pub fn spawn_maintenance(self: &Arc<Self>, every: Duration) {
+        let pool = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(every).await;
+                pool.maintain().await;
+            }
+        });
+    }
+
+    /// Destroy every idle container (shutdown).
+    pub async fn drain(&self) {
+        let all: Vec<WarmContainer> = self.lock().idle.drain().flat_map(|(_, v)| v).collect();
+        for c in all {
+            self.destroy(c).await;
+        }
+    }
+
+    /// Remove warm containers left running by a previous process.
+    pub async fn remove_leftovers() {
+        let Ok(out) = Command::new(Sandbox::cli())
+            .args(["ps", "-aq", "--filter", &format!("label={WARM_LABEL}")])
+            .output()
+            .await
+        else {
+            return;
+        };
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let ids: Vec<&str> = stdout
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+        let _ = Command::new(Sandbox::cli())
+            .arg("rm")
+            .arg("-f")
+            .args(&ids)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+        info!("warm pool: removed {} leftover container(s)", ids.len());
+    }
+
+    pub fn stats(&self) -> WarmPoolStats {
+        let state = self.lock();
+        let c = &self.counters;
+        WarmPoolStats {
+            enabled: self.config.size > 0,
+            size_per_key: self.config.size,
+            max_uses: self.config.max_uses,
+            max_idle_memory_bytes: self.config.max_idle_memory_bytes,
+            hits: c.hits.load(Ordering::Relaxed),
+            misses: c.misses.load(Ordering::Relaxed),
+            created: c.created.load(Ordering::Relaxed),
+            create_failures: c.create_failures.load(Ordering::Relaxed),
+            recycled: c.recycled.load(Ordering::Relaxed),
+            destroyed: c.destroyed.load(Ordering::Relaxed),
+            evicted_ttl: c.evicted_ttl.load(Ordering::Relaxed),
+            evicted_memory: c.evicted_memory.load(Ordering::Relaxed),
+            keys: state.idle.len(),
+            idle: state.idle.values().map(Vec::len).sum(),
+            in_use: state.in_use,
+            idle_memory_bytes: state.idle_memory(),
+        }
+    }
+}
+
+/// A checked-out warm container. Call [`WarmLease::release`] when the phase
+/// ends; dropping the lease instead destroys the container.
+pub struct WarmLease {
+    pool: Arc<WarmPool>,
+    key: WarmKey,
+    container: Option<WarmContainer>,
+    session_dir: PathBuf,
+    worktree: PathBuf,
+}
+
+impl WarmLease {
+    pub fn container_id(&self) -> &str {
+        self.container.as_ref().map_or("", |c| c.id.as_str())
+    }
+
+    /// `exec` the image's entrypoint in the container with `env_vars`,
+    /// stdin attached.
+    pub fn exec_command(&self, env_vars: &[(&str, &str)]) -> Command {
+        let mut cmd = Command::new(Sandbox::cli());
+        cmd.arg("exec").arg("-i");
+        for (key, val) in env_vars {
+            cmd.arg("-e").arg(format!("{key}={val}"));
+        }
+        if let Some(c) = &self.container {
+            cmd.arg(&c.id).args(&c.entrypoint);
+        }
+        cmd
+    }
+
+    /// Write the container id where `ResourceMonitor::container` expects it.
+    pub fn write_cidfile(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.container_id())
+    }
+
+    /// Move the session and worktree back and return the container to the
+    /// pool.
+    /// Unhealthy containers (failed or timed-out phases, which may leave
+    /// an exec'd agent running) are destroyed.
+    pub async fn release(mut self, healthy: bool) {
+        let Some(container) = self.container.take() else {
+            return;
+        };
+        if !healthy {
+            // The exec'd agent may still be running; stop it before moving
+            // the task's files out from under it.
+            remove_container(&container.id).await;
+        }
+        let staged_back = unstage(&container, &self.session_dir, &self.worktree);
+        if let Err(e) = &staged_back {
+            warn!(id = %container.id, "warm pool: could not restore task dirs: {e:#}");
+        }
+        self.pool
+            .release(self.key.clone(), container, healthy && staged_back.is_ok())
+            .await;
+    }
+}
+
+impl Drop for WarmLease {
+    fn drop(&mut self) {
+        let Some(container) = self.container.take() else {
+            return;
+        };
+        let _ = std::process::Command::new(Sandbox::cli())
+            .args(["rm", "-f", &container.id])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        let _ = unstage(&container, &self.session_dir, &self.worktree);
+        self.pool.counters.destroyed.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut state = self.pool.lock();
+            state.in_use = state.in_use.saturating_sub(1);
+        }
+        let _ = std::fs::remove_dir_all(&container.home);
+        remove_workspace(&container.workspace);
+    }
+}
+
+/// Move a task's session dir and worktree into a checked-out container.
+fn stage(container: &WarmContainer, session_dir: &Path, worktree: &Path) -> Result<()> {
+    move_dir_contents(session_dir, &container.home)?;
+    move_dir_contents(worktree, &container.workspace)
+}
+
+/// Move a task's session dir and worktree back out of a container.
+fn unstage(container: &WarmContainer, session_dir: &Path, worktree: &Path) -> Result<()> {
+    let home = move_dir_contents(&container.home, session_dir);
+    move_dir_contents(&container.workspace, worktree)?;
+    home
+}
+
+/// Remove a container's workspace dir only if it is empty: anything left
+/// there is a task's work that failed to move back.
+fn remove_workspace(dir: &Path) {
+    if let Err(e) = std::fs::remove_dir(dir) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!(dir = %dir.display(), "warm pool: leaving non-empty workspace: {e}");
+        }
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(12)]
+}
+
+async fn container_running(id: &str) -> bool {
+    Command::new(Sandbox::cli())
+        .args(["inspect", "-f", "{{.State.Running}}", id])
+        .output()
+        .await
+        .map(|o| o.status.success() && String::from_utf8_lossy(&o.stdout).trim() == "true")
+        .unwrap_or(false)
+}
+
+async fn remove_container(id: &str) {
+    let _ = Command::new(Sandbox::cli())
+        .args(["rm", "-f", id])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await;
+}
+
+async fn image_entrypoint(image: &str) -> Result<Vec<String>> {
+    let out = Command::new(Sandbox::cli())
+        .args([
+            "image",
+            "inspect",
+            "-f",
+            "{{json .Config.Entrypoint}}",
+            image,
+        ])
+        .output()
+        .await
+        .context("inspect image")?;
+    if !out.status.success() {
+        bail!("image {image} not found");
+    }
+    let entrypoint: Option<Vec<String>> =
+        serde_json::from_slice(&out.stdout).context("parse entrypoint")?;
+    match entrypoint {
+        Some(e) if !e.is_empty() => Ok(e),
+        _ => bail!("image {image} has no entrypoint"),
+    }
+}
+
+/// Current memory use of `ids`, keyed by short container id.
+async fn container_memory(ids: &[String]) -> HashMap<String, u64> {
+    let Ok(out) = Command::new(Sandbox::cli())
+        .args(["stats", "--no-stream", "--format", "{{.ID}}\t{{.MemUsage}}"])
+        .args(ids)
+        .output()
+        .await
+    else {
+        return HashMap::new();
+    };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (id, usage) = line.split_once('\t')?;
+            Some((short_id(id.trim()).to_string(), parse_mem_usage(usage)?))
+        })
+        .collect()
+}
+
+/// Parse the used half of a `MemUsage` column, e.g. `12.5MiB / 2GiB`.
+fn parse_mem_usage(s: &str) -> Option<u64> {
+    let used = s.split('/').next()?.trim();
+    let split = used.find(|c: char| c.is_ascii_alphabetic())?;
+    let (num, unit) = used.split_at(split);
+    let num: f64 = num.trim().parse().ok()?;
+    let scale: u64 = match unit {
+        "B" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        "kB" | "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        _ => return None,
+    };
+    Some((num * scale as f64) as u64)
+}
+
+/// Move every entry of `from` into `to`, replacing entries already there.
+/// Renames when possible and copies across filesystems.
+fn move_dir_contents(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to).with_context(|| format!("create {}", to.display()))?;
+    let entries = match std::fs::read_dir(from) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("read {}", from.display())),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let src = entry.path();
+        let dst = to.join(entry.file_name());
+        if dst.symlink_metadata().is_ok() {
+            remove_path(&dst)?;
+        }
+        if std::fs::rename(&src, &dst).is_err() {
+            copy_path(&src, &dst).with_context(|| format!("copy {}", src.display()))?;
+            remove_path(&src)?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_path(p: &Path) -> std::io::Result<()> {
+    if p.symlink_metadata()?.is_dir() {
+        std::fs::remove_dir_all(p)
+    } else {
+        std::fs::remove_file(p)
+    }
+}
+
+fn copy_path(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let meta = src.symlink_metadata()?;
+    if meta.file_type().is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(src)?, dst)
+    } else if meta.is_dir() {
+        std::fs::create_dir_all(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        std::fs::copy(src, dst).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "borg-warm-{label}-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_mem_usage_handles_binary_and_decimal_units() {
+        assert_eq!(parse_mem_usage("12MiB / 1.944GiB"), Some(12 << 20));
+        assert_eq!(parse_mem_usage("1.5GiB / 2GiB"), Some(3 << 29));
+        assert_eq!(parse_mem_usage("  512kB / 1GB"), Some(512_000));
+        assert_eq!(parse_mem_usage("0B / 0B"), Some(0));
+        assert_eq!(parse_mem_usage("--"), None);
+    }
+
+    #[test]
+    fn warm_key_normalizes_cpus() {
+        let a = WarmKey::new("img", "/repo", &[("cargo", "/c")], None, 2048, 2.0);
+        let b = WarmKey::new("img", "/repo", &[("cargo", "/c")], None, 2048, 2.0004);
+        assert_eq!(a, b);
+        assert_eq!(a.cpus_milli, 2000);
+        let c = WarmKey::new("img", "/other", &[("cargo", "/c")], None, 2048, 2.0);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn worktrees_share_their_repo_key() {
+        assert_eq!(
+            worktree_repo("/src/borg/.worktrees/task-7"),
+            Some("/src/borg")
+        );
+        assert_eq!(
+            worktree_repo("/src/borg/.worktrees/task-8/"),
+            Some("/src/borg")
+        );
+        assert_eq!(worktree_repo("/src/borg"), None);
+        assert_eq!(worktree_repo("/src/borg/.worktrees/task-7/sub"), None);
+        assert_eq!(worktree_repo("/.worktrees/task-7"), None);
+    }
+
+    #[test]
+    fn move_dir_contents_round_trips_and_replaces() {
+        let session = temp_dir("session");
+        let home = temp_dir("home");
+        std::fs::create_dir_all(session.join(".claude/projects")).unwrap();
+        std::fs::write(session.join(".claude/projects/t.jsonl"), "{}").unwrap();
+        std::fs::write(session.join("note"), "a").unwrap();
+        std::fs::write(home.join("note"), "stale").unwrap();
+
+        move_dir_contents(&session, &home).unwrap();
+        assert_eq!(std::fs::read_dir(&session).unwrap().count(), 0);
+        assert_eq!(std::fs::read_to_string(home.join("note")).unwrap(), "a");
+
+        std::fs::write(home.join("new"), "b").unwrap();
+        move_dir_contents(&home, &session).unwrap();
+        assert_eq!(std::fs::read_dir(&home).unwrap().count(), 0);
+        assert!(session.join(".claude/projects/t.jsonl").exists());
+        assert_eq!(std::fs::read_to_string(session.join("new")).unwrap(), "b");
+
+        let _ = std::fs::remove_dir_all(&session);
+        let _ = std::fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn staging_moves_the_worktree_in_and_back() {
+        let repo = temp_dir("repo");
+        let session = temp_dir("session");
+        let worktree = repo.join(".worktrees/task-1");
+        std::fs::create_dir_all(worktree.join("src")).unwrap();
+        std::fs::write(worktree.join(".git"), "gitdir: /repo/.git/worktrees/task-1").unwrap();
+        std::fs::write(worktree.join("src/lib.rs"), "fn a() {}").unwrap();
+        let container = WarmContainer {
+            id: "c".into(),
+            home: temp_dir("home"),
+            workspace: repo.join(".worktrees/.warm-1"),
+            entrypoint: Vec::new(),
+            uses: 0,
+            idle_since: Instant::now(),
+            memory_bytes: 0,
+        };
+
+        stage(&container, &session, &worktree).unwrap();
+        assert!(container.workspace.join("src/lib.rs").exists());
+        assert_eq!(std::fs::read_dir(&worktree).unwrap().count(), 0);
+
+        std::fs::write(container.workspace.join("src/new.rs"), "").unwrap();
+        unstage(&container, &session, &worktree).unwrap();
+        assert!(worktree.join(".git").exists());
+        assert!(worktree.join("src/new.rs").exists());
+        remove_workspace(&container.workspace);
+        assert!(!container.workspace.exists());
+
+        for dir in [&repo, &session, &container.home] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn empty_pool_counts_a_miss() {
+        let home_root = temp_dir("root");
+        let pool = WarmPool::new(WarmPoolConfig {
+            size: 0,
+            max_uses: 1,
+            idle_ttl: Duration::from_secs(60),
+            max_idle_memory_bytes: 1 << 30,
+            home_root: home_root.clone(),
+        });
+        let key = WarmKey::new("img", "/repo", &[], None, 0, 0.0);
+        assert!(pool
+            .acquire(&key, "/nonexistent", "/repo/.worktrees/task-1")
+            .await
+            .is_none());
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses, stats.idle), (0, 1, 0));
+        assert!(!stats.enabled);
+        let _ = std::fs::remove_dir_all(&home_root);
+    }
+}
@@ -0,0 +1,399 @@
+//! Inbound forge webhooks.
+//!
+//! `/api/webhooks/{github,gitlab,gitea}` verify the delivery against
+//! `Config::webhook_secret`, normalise it with [`parse`] and push it into
+//! [`Webhooks`]. `Pipeline::tick` drains the queue: merged PRs update the
+//! integration queue at once, review activity triggers a review sync, pushes
+//! and CI results wake the merge queue, and labelled issues become tasks.
+//!
+//! A repo that has delivered a webhook recently is "live": the pipeline then
+//! skips its periodic PR review and issue polls and only runs the merge queue
+//! when an event arrives or [`FALLBACK_POLL`] has passed. Repos without
+//! webhooks keep polling as before.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::forge::{self, ForgeIssue, ForgeKind};
+
+/// A repo counts as webhook-driven for this long after its last delivery.
+pub const LIVE_FOR: Duration = Duration::from_secs(6 * 3600);
+/// Merge-queue poll interval for webhook-driven repos, in case a delivery
+/// is lost.
+pub const FALLBACK_POLL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookEvent {
+    /// Commits pushed to `branch`.
+    Push {
+        branch: String,
+    },
+    /// A PR was opened, reopened or got new commits.
+    PrUpdated {
+        branch: String,
+    },
+    PrClosed {
+        branch: String,
+        merged: bool,
+    },
+    /// A review, review comment or PR comment was posted.
+    Review,
+    /// A CI run finished or changed state.
+    Ci,
+    /// An issue was opened, reopened or relabelled.
+    Issue(ForgeIssue),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub kind: ForgeKind,
+    /// `owner/repo`, or the project path on GitLab; matched against
+    /// `RepoConfig::repo_slug`.
+    pub repo_slug: String,
+    pub event: WebhookEvent,
+}
+
+/// Check a GitHub (`X-Hub-Signature-256`, `sha256=` prefixed) or Gitea
+/// (`X-Gitea-Signature`, bare hex) HMAC-SHA256 signature of `body`.
+pub fn verify_hmac_sha256(secret: &str, signature: &str, body: &[u8]) -> bool {
+    let hex_sig = signature.trim();
+    let hex_sig = hex_sig.strip_prefix("sha256=").unwrap_or(hex_sig);
+    let Ok(expected) = hex::decode(hex_sig) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// Check GitLab's `X-Gitlab-Token`, which carries the secret itself.
+pub fn verify_token(secret: &str, token: &str) -> bool {
+    let (a, b) = (secret.as_bytes(), token.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn branch_of(git_ref: &str) -> Option<String> {
+    git_ref.strip_prefix("refs/heads/").map(str::to_string)
+}
+
+fn str_at<'a>(v: &'a Value, path: &[&str]) -> &'a str {
+    path.iter()
+        .fold(v, |v, key| &v[*key])
+        .as_str()
+        .unwrap_or_default()
+}
+
+/// Normalise a delivery. `event` is the `X-GitHub-Event`, `X-Gitlab-Event`
+/// or `X-Gitea-Event` header. Returns None for events borg does not act on.
+pub fn parse(kind: ForgeKind, event: &str, payload: &Value) -> Option<Webhook> {
+    let (repo_slug, event) = match kind {
+        ForgeKind::GitHub | ForgeKind::Gitea => (
+            str_at(payload, &["repository", "full_name"]),
+            parse_github_like(kind, event, payload)?,
+        ),
+        ForgeKind::GitLab => (
+            str_at(payload, &["project", "path_with_namespace"]),
+            parse_gitlab(event, payload)?,
+        ),
+    };
+    if repo_slug.is_empty() {
+        return None;
+    }
+    Some(Webhook {
+        kind,
+        repo_slug: repo_slug.to_string(),
+        event,
+    })
+}
+
+/// GitHub and Gitea share payload shapes; Gitea names review events
+/// `pull_request_review_{approved,rejected,comment}`.
+fn parse_github_like(kind: ForgeKind, event: &str, p: &Value) -> Option<WebhookEvent> {
+    let action = str_at(p, &["action"]);
+    let pr_branch = || str_at(p, &["pull_request", "head", "ref"]).to_string();
+    Some(match event {
+        "push" => WebhookEvent::Push {
+            branch: branch_of(str_at(p, &["ref"]))?,
+        },
+        "pull_request" => match action {
+            "closed" => WebhookEvent::PrClosed {
+                branch: pr_branch(),
+                merged: p["pull_request"]["merged"].as_bool() == Some(true),
+            },
+            "opened" | "reopened" | "synchronize" | "synchronized" => WebhookEvent::PrUpdated {
+                branch: pr_branch(),
+            },
+            _ => return None,
+        },
+        "pull_request_review" | "pull_request_review_comment" => WebhookEvent::Review,
+        e if e.starts_with("pull_request_review_") => WebhookEvent::Review,
+        "issue_comment" if !p["issue"]["pull_request"].is_null() || p["is_pull"] == true => {
+            WebhookEvent::Review
+        },
+        "check_suite" | "check_run" if action == "completed" => WebhookEvent::Ci,
+        "status" => WebhookEvent::Ci,
+        "issues" if matches!(action, "opened" | "reopened" | "labeled" | "label_updated") => {
+            let issue = match kind {
+                ForgeKind::Gitea => forge::gitea_issue(&p["issue"]),
+                _ => forge::github_issue(&p["issue"]),
+            };
+            WebhookEvent::Issue(issue?)
+        },
+        _ => return None,
+    })
+}
+
+fn parse_gitlab(event: &str, p: &Value) -> Option<WebhookEvent> {
+    let attrs = &p["object_attributes"];
+    Some(match event {
+        "Push Hook" => WebhookEvent::Push {
+            branch: branch_of(str_at(p, &["ref"]))?,
+        },
+        "Merge Request Hook" => {
+            let branch = str_at(attrs, &["source_branch"]).to_string();
+            match str_at(attrs, &["action"]) {
+                "merge" => WebhookEvent::PrClosed {
+                    branch,
+                    merged: true,
+                },
+                "close" => WebhookEvent::PrClosed {
+                    branch,
+                    merged: false,
+                },
+                "open" | "reopen" => WebhookEvent::PrUpdated { branch },
+                // Updates carry `oldrev` only when commits were pushed.
+                "update" if !attrs["oldrev"].is_null() => WebhookEvent::PrUpdated { branch },
+                _ => return None,
+            }
+        },
+        "Note Hook" if str_at(attrs, &["noteable_type"]) == "MergeRequest" => WebhookEvent::Review,
+        "Pipeline Hook"
+            if matches!(
+                str_at(attrs, &["status"]),
+                "success" | "failed" | "canceled"
+            ) =>
+        {
+            WebhookEvent::Ci
+        },
+        "Issue Hook" if matches!(str_at(attrs, &["action"]), "open" | "reopen" | "update") => {
+            WebhookEvent::Issue(ForgeIssue {
+                number: attrs["iid"].as_i64()?,
+                title: str_at(attrs, &["title"]).to_string(),
+                body: str_at(attrs, &["description"]).to_string(),
+                url: str_at(attrs, &["url"]).to_string(),
+                labels: p["labels"]
+                    .as_array()
+                    .map(|ls| {
+                        ls.iter()
+                            .map(|l| str_at(l, &["title"]).to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+        },
+        _ => return None,
+    })
+}
+
+/// Webhook deliveries waiting for the next tick, plus per-repo delivery
+/// state. Shared between the pipeline and the HTTP handlers.
+#[derive(Default)]
+pub struct Webhooks {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    queue: Vec<Webhook>,
+    /// Last delivery per repo path.
+    seen: HashMap<String, Instant>,
+    /// Repo paths whose merge queue an event has woken.
+    dirty: HashSet<String>,
+    /// Last merge-queue run per repo path.
+    polled: HashMap<String, Instant>,
+}
+
+impl Webhooks {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn push(&self, hook: Webhook) {
+        self.lock().queue.push(hook);
+    }
+
+    pub fn drain(&self) -> Vec<Webhook> {
+        std::mem::take(&mut self.lock().queue)
+    }
+
+    /// Record a delivery for the watched repo at `repo_path`.
+    pub fn mark_seen(&self, repo_path: &str) {
+        self.lock()
+            .seen
+            .insert(repo_path.to_string(), Instant::now());
+    }
+
+    /// True while `repo_path` is webhook-driven (see [`LIVE_FOR`]).
+    pub fn is_live(&self, repo_path: &str) -> bool {
+        self.lock()
+            .seen
+            .get(repo_path)
+            .is_some_and(|t| t.elapsed() < LIVE_FOR)
+    }
+
+    /// Ask for a merge-queue run for `repo_path` on the next tick.
+    pub fn wake(&self, repo_path: &str) {
+        self.lock().dirty.insert(repo_path.to_string());
+    }
+
+    /// Whether the merge queue for `repo_path` should run now: always for
+    /// polled repos, and for webhook-driven ones when woken or when
+    /// [`FALLBACK_POLL`] has passed. Clears the wake-up.
+    pub fn take_integration_turn(&self, repo_path: &str) -> bool {
+        let mut inner = self.lock();
+        let live = inner
+            .seen
+            .get(repo_path)
+            .is_some_and(|t| t.elapsed() < LIVE_FOR);
+        let woken = inner.dirty.remove(repo_path);
+        let stale = inner
+            .polled
+            .get(repo_path)
+            .is_none_or(|t| t.elapsed() >= FALLBACK_POLL);
+        if live && !woken && !stale {
+            return false;
+        }
+        inner.polled.insert(repo_path.to_string(), Instant::now());
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn verifies_signatures() {
+        let body = br#"{"zen":"Keep it logically awesome."}"#;
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+        mac.update(body);
+        let sig = hex::encode(mac.finalize().into_bytes());
+        assert!(verify_hmac_sha256("s3cret", &format!("sha256={sig}"), body));
+        assert!(verify_hmac_sha256("s3cret", &sig, body));
+        assert!(!verify_hmac_sha256("other", &sig, body));
+        assert!(!verify_hmac_sha256("s3cret", "sha256=zz", body));
+        assert!(verify_token("s3cret", "s3cret"));
+        assert!(!verify_token("s3cret", "s3cre"));
+    }
+
+    #[test]
+    fn parses_github_events() {
+        let repo = json!({"full_name": "acme/api"});
+        let merged = json!({"action": "closed", "repository": repo,
+                            "pull_request": {"merged": true, "head": {"ref": "task-4"}}});
+        assert_eq!(
+            parse(ForgeKind::GitHub, "pull_request", &merged).unwrap(),
+            Webhook {
+                kind: ForgeKind::GitHub,
+                repo_slug: "acme/api".into(),
+                event: WebhookEvent::PrClosed {
+                    branch: "task-4".into(),
+                    merged: true
+                },
+            }
+        );
+        let push = json!({"ref": "refs/heads/main", "repository": repo});
+        assert_eq!(
+            parse(ForgeKind::GitHub, "push", &push).unwrap().event,
+            WebhookEvent::Push {
+                branch: "main".into()
+            }
+        );
+        let tag = json!({"ref": "refs/tags/v1", "repository": repo});
+        assert_eq!(parse(ForgeKind::GitHub, "push", &tag), None);
+        let comment = json!({"action": "created", "repository": repo,
+                             "issue": {"number": 4, "pull_request": {}}});
+        assert_eq!(
+            parse(ForgeKind::GitHub, "issue_comment", &comment)
+                .unwrap()
+                .event,
+            WebhookEvent::Review
+        );
+        let issue = json!({"action": "labeled", "repository": repo,
+                           "issue": {"number": 9, "title": "Crash", "body": "",
+                                     "html_url": "u", "labels": [{"name": "borg"}]}});
+        assert_eq!(
+            parse(ForgeKind::GitHub, "issues", &issue).unwrap().event,
+            WebhookEvent::Issue(ForgeIssue {
+                number: 9,
+                title: "Crash".into(),
+                body: String::new(),
+                url: "u".into(),
+                labels: vec!["borg".into()],
+            })
+        );
+        let running = json!({"action": "requested", "repository": repo});
+        assert_eq!(parse(ForgeKind::GitHub, "check_suite", &running), None);
+    }
+
+    #[test]
+    fn parses_gitlab_and_gitea_events() {
+        let project = json!({"path_with_namespace": "grp/sub/api"});
+        let mr = json!({"project": project,
+                        "object_attributes": {"action": "merge", "source_branch": "task-2"}});
+        let hook = parse(ForgeKind::GitLab, "Merge Request Hook", &mr).unwrap();
+        assert_eq!(hook.repo_slug, "grp/sub/api");
+        assert_eq!(
+            hook.event,
+            WebhookEvent::PrClosed {
+                branch: "task-2".into(),
+                merged: true
+            }
+        );
+        let edit = json!({"project": project,
+                          "object_attributes": {"action": "update", "source_branch": "task-2"}});
+        assert_eq!(parse(ForgeKind::GitLab, "Merge Request Hook", &edit), None);
+        let issue = json!({"project": project, "labels": [{"title": "borg"}],
+                           "object_attributes": {"action": "open", "iid": 3, "title": "t",
+                                                 "description": "d", "url": "u"}});
+        assert!(matches!(
+            parse(ForgeKind::GitLab, "Issue Hook", &issue)
+                .unwrap()
+                .event,
+            WebhookEvent::Issue(ForgeIssue { number: 3, .. })
+        ));
+
+        let review = json!({"action": "reviewed", "repository": {"full_name": "o/r"}});
+        assert_eq!(
+            parse(ForgeKind::Gitea, "pull_request_review_rejected", &review)
+                .unwrap()
+                .event,
+            WebhookEvent::Review
+        );
+    }
+
+    #[test]
+    fn live_repos_run_the_merge_queue_only_when_woken() {
+        let hooks = Webhooks::default();
+        assert!(hooks.take_integration_turn("/r"));
+        assert!(hooks.take_integration_turn("/r"));
+
+        hooks.mark_seen("/r");
+        assert!(hooks.is_live("/r"));
+        assert!(!hooks.take_integration_turn("/r"));
+        hooks.wake("/r");
+        assert!(hooks.take_integration_turn("/r"));
+        assert!(!hooks.take_integration_turn("/r"));
+    }
+}
@@ -0,0 +1,637 @@
+//! Remote worker nodes.
+//!
+//! A `borg-worker` process registers with the server and advertises capacity,
+//! sandbox modes, backends and free-form `key=value` labels. `Pipeline::tick`
+//! reserves a worker for a task once local slots are full (or the task's mode
+//! pins it to labelled workers), and the task's phases then run through
+//! [`RemoteBackend`], which queues a [`WorkerJob`] here and waits for the
+//! worker to post the [`PhaseOutput`] back. Workers long-poll for jobs over
+//! HTTP, so they need no inbound connectivity.
+//!
+//! Workers operate on the same `repo_path`/`work_dir` paths as the server
+//! (shared storage, or a checkout at the same location), which is what the
+//! `repo=<name>` label advertises.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot, Notify};
+use tracing::{info, warn};
+
+use crate::{
+    agent::AgentBackend,
+    types::{PhaseConfig, PhaseContext, PhaseOutput, Task},
+};
+
+/// A worker that has not polled for this long is considered gone; its
+/// reservations are dropped and its running jobs fail.
+pub const WORKER_STALE_AFTER: Duration = Duration::from_secs(90);
+
+/// What a worker advertises when it registers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkerRegistration {
+    pub name: String,
+    /// Concurrent phases the worker will run.
+    pub capacity: u32,
+    #[serde(default)]
+    pub sandbox_modes: Vec<String>,
+    #[serde(default)]
+    pub backends: Vec<String>,
+    /// `key=value` labels, e.g. `gpu=false`, `repo=borg`. A worker with any
+    /// `repo=` labels only takes tasks for those repos.
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl WorkerRegistration {
+    fn has_label(&self, key: &str, value: &str) -> bool {
+        self.labels
+            .iter()
+            .filter_map(|l| l.split_once('='))
+            .any(|(k, v)| k.trim() == key && v.trim() == value)
+    }
+
+    fn has_label_key(&self, key: &str) -> bool {
+        self.labels
+            .iter()
+            .filter_map(|l| l.split_once('='))
+            .any(|(k, _)| k.trim() == key)
+    }
+
+    pub fn matches(&self, req: &WorkerRequirements) -> bool {
+        if !self.backends.iter().any(|b| b == &req.backend) {
+            return false;
+        }
+        if self.has_label_key("repo") && !self.has_label("repo", &req.repo) {
+            return false;
+        }
+        req.labels.iter().all(|(k, v)| self.has_label(k, v))
+    }
+}
+
+/// What a task needs from the worker that runs it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkerRequirements {
+    pub backend: String,
+    /// Repo directory name, matched against `repo=` labels.
+    pub repo: String,
+    /// Labels the worker must carry (from the mode's `worker_labels`).
+    pub labels: Vec<(String, String)>,
+}
+
+impl WorkerRequirements {
+    pub fn new(backend: &str, repo_path: &str, selectors: &[String]) -> Self {
+        let repo = std::path::Path::new(repo_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let labels = selectors
+            .iter()
+            .filter_map(|s| s.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+        Self {
+            backend: backend.to_string(),
+            repo,
+            labels,
+        }
+    }
+
+    /// True when the task may only run on a labelled worker.
+    pub fn pinned(&self) -> bool {
+        !self.labels.is_empty()
+    }
+}
+
+/// One phase run shipped to a worker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerJob {
+    pub job_id: String,
+    pub backend: String,
+    pub task: Task,
+    pub phase: PhaseConfig,
+    pub ctx: PhaseContext,
+}
+
+/// What a worker posts back when a job finishes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerJobResult {
+    #[serde(default)]
+    pub output: Option<PhaseOutput>,
+    #[serde(default)]
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerStatus {
+    pub id: String,
+    #[serde(flatten)]
+    pub registration: WorkerRegistration,
+    pub reserved_tasks: Vec<i64>,
+    pub running_jobs: usize,
+    pub queued_jobs: usize,
+    pub last_seen_secs: u64,
+}
+
+struct WorkerEntry {
+    registration: WorkerRegistration,
+    last_seen: Instant,
+    queue: VecDeque<WorkerJob>,
+    notify: Arc<Notify>,
+    reserved: HashSet<i64>,
+}
+
+struct PendingJob {
+    worker_id: String,
+    result_tx: oneshot::Sender<WorkerJobResult>,
+    stream_tx: Option<mpsc::UnboundedSender<String>>,
+}
+
+/// Registered workers, task reservations and jobs in flight.
+#[derive(Default)]
+pub struct WorkerPool {
+    workers: Mutex<HashMap<String, WorkerEntry>>,
+    pending: Mutex<HashMap<String, PendingJob>>,
+}
+
+impl WorkerPool {
+    /// Register (or re-register) a worker. The worker's name is its id; a
+    /// restarted worker replaces its old entry and its unfinished jobs fail.
+    pub fn register(&self, registration: WorkerRegistration) -> String {
+        let id = registration.name.clone();
+        let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        let reserved = workers
+            .remove(&id)
+            .map(|old| old.reserved)
+            .unwrap_or_default();
+        info!(
+            worker = %id,
+            capacity = registration.capacity,
+            backends = ?registration.backends,
+            labels = ?registration.labels,
+            "worker registered"
+        );
+        workers.insert(
+            id.clone(),
+            WorkerEntry {
+                registration,
+                last_seen: Instant::now(),
+                queue: VecDeque::new(),
+                notify: Arc::new(Notify::new()),
+                reserved,
+            },
+        );
+        drop(workers);
+        self.fail_jobs_of(&id, "worker re-registered");
+        id
+    }
+
+    /// Mark a worker alive. False if it is unknown (it should re-register).
+    pub fn heartbeat(&self, worker_id: &str) -> bool {
+        let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        match workers.get_mut(worker_id) {
+            Some(w) => {
+                w.last_seen = Instant::now();
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// True if some live worker has a free slot.
+    pub fn has_capacity(&self) -> bool {
+        let workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        workers
+            .values()
+            .any(|w| w.reserved.len() < w.registration.capacity as usize)
+    }
+
+    /// Number of tasks currently reserved on workers.
+    pub fn reserved_count(&self) -> usize {
+        let workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        workers.values().map(|w| w.reserved.len()).sum()
+    }
+
+    /// Reserve a slot for `task_id` on the least-loaded matching worker.
+    pub fn reserve(&self, task_id: i64, req: &WorkerRequirements) -> Option<String> {
+        let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        let (id, entry) = workers
+            .iter_mut()
+            .filter(|(_, w)| {
+                w.reserved.len() < w.registration.capacity as usize && w.registration.matches(req)
+            })
+            .min_by_key(|(id, w)| {
+                (
+                    w.reserved.len() * 1000 / (w.registration.capacity.max(1) as usize),
+                    (*id).clone(),
+                )
+            })?;
+        entry.reserved.insert(task_id);
+        Some(id.clone())
+    }
+
+    /// Worker reserved for `task_id`, if any.
+    pub fn assignment(&self, task_id: i64) -> Option<String> {
+        let workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        workers
+            .iter()
+            .find(|(_, w)| w.reserved.contains(&task_id))
+            .map(|(id, _)| id.clone())
+    }
+
+    pub fn release(&self, task_id: i64) {
+        let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        for w in workers.values_mut() {
+            w.reserved.remove(&task_id);
+        }
+    }
+
+    /// Queue a job for a worker and return the receiver for its result.
+    /// Streamed output lines are forwarded to `stream_tx`.
+    pub fn dispatch(
+        &self,
+        worker_id: &str,
+        job: WorkerJob,
+        stream_tx: Option<mpsc::UnboundedSender<String>>,
+    ) -> Result<oneshot::Receiver<WorkerJobResult>> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let job_id = job.job_id.clone();
+        {
+            let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+            let w = workers
+                .get_mut(worker_id)
+                .ok_or_else(|| anyhow!("worker {worker_id} is not registered"))?;
+            w.queue.push_back(job);
+            w.notify.notify_one();
+        }
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                job_id,
+                PendingJob {
+                    worker_id: worker_id.to_string(),
+                    result_tx,
+                    stream_tx,
+                },
+            );
+        Ok(result_rx)
+    }
+
+    /// Long-poll for the next job. `Err` if the worker is unknown.
+    pub async fn next_job(&self, worker_id: &str, wait: Duration) -> Result<Option<WorkerJob>> {
+        let notify = match self.pop_job(worker_id)? {
+            Ok(job) => return Ok(Some(job)),
+            Err(notify) => notify,
+        };
+        let _ = tokio::time::timeout(wait, notify.notified()).await;
+        Ok(self.pop_job(worker_id)?.ok())
+    }
+
+    fn pop_job(&self, worker_id: &str) -> Result<std::result::Result<WorkerJob, Arc<Notify>>> {
+        let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(w) = workers.get_mut(worker_id) else {
+            bail!("worker {worker_id} is not registered");
+        };
+        w.last_seen = Instant::now();
+        Ok(w.queue.pop_front().ok_or_else(|| Arc::clone(&w.notify)))
+    }
+
+    /// Forward streamed output lines for a running job.
+    pub fn push_stream(&self, worker_id: &str, job_id: &str, lines: Vec<String>) -> bool {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(job) = pending.get(job_id).filter(|j| j.worker_id == worker_id) else {
+            return false;
+        };
+        if let Some(tx) = &job.stream_tx {
+            for line in lines {
+                let _ = tx.send(line);
+            }
+        }
+        true
+    }
+
+    /// Deliver a job's result. False if the job is unknown or not this
+    /// worker's (e.g. it already failed as stale).
+    pub fn complete(&self, worker_id: &str, job_id: &str, result: WorkerJobResult) -> bool {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.get(job_id).is_none_or(|j| j.worker_id != worker_id) {
+            return false;
+        }
+        match pending.remove(job_id) {
+            Some(job) => job.result_tx.send(result).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Drop workers that stopped polling, failing their jobs and releasing
+    /// their reservations so the tasks can be dispatched again.
+    pub fn reap_stale(&self) {
+        let stale: Vec<String> = {
+            let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+            let stale: Vec<String> = workers
+                .iter()
+                .filter(|(_, w)| w.last_seen.elapsed() > WORKER_STALE_AFTER)
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in &stale {
+                workers.remove(id);
+            }
+            stale
+        };
+        for id in stale {
+            warn!(worker = %id, "worker stopped polling; dropping it");
+            self.fail_jobs_of(&id, "worker went offline");
+        }
+    }
+
+    fn fail_jobs_of(&self, worker_id: &str, reason: &str) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let ids: Vec<String> = pending
+            .iter()
+            .filter(|(_, j)| j.worker_id == worker_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ids {
+            if let Some(job) = pending.remove(&id) {
+                let _ = job.result_tx.send(WorkerJobResult {
+                    output: None,
+                    error: format!("{reason} ({worker_id})"),
+                });
+            }
+        }
+    }
+
+    pub fn list(&self) -> Vec<WorkerStatus> {
+        let running: HashMap<String, usize> = {
+            let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            let mut m = HashMap::new();
+            for j in pending.values() {
+                *m.entry(j.worker_id.clone()).or_insert(0) += 1;
+            }
+            m
+        };
+        let workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out: Vec<WorkerStatus> = workers
+            .iter()
+            .map(|(id, w)| {
+                let mut reserved: Vec<i64> = w.reserved.iter().copied().collect();
+                reserved.sort_unstable();
+                WorkerStatus {
+                    id: id.clone(),
+                    registration: w.registration.clone(),
+                    reserved_tasks: reserved,
+                    queued_jobs: w.queue.len(),
+                    running_jobs: running
+                        .get(id)
+                        .copied()
+                        .unwrap_or(0)
+                        .saturating_sub(w.queue.len()),
+                    last_seen_secs: w.last_seen.elapsed().as_secs(),
+                }
+            })
+            .collect();
+        out.sort_by(|a, b| a.id.cmp(&b.id));
+        out
+    }
+}
+
+/// Clear the server's credentials from a context bound for a worker. The
+/// worker token is shared by every worker, so it must not unlock the
+/// server's Claude, GitHub or borg API tokens; workers bring their own.
+fn redact_secrets(ctx: &mut PhaseContext) {
+    ctx.oauth_token.clear();
+    ctx.api_keys.clear();
+    ctx.borg_api_token.clear();
+    ctx.github_token.clear();
+    ctx.github_token_is_user = false;
+}
+
+/// Runs a phase on a remote worker through the [`WorkerPool`].
+pub struct RemoteBackend {
+    pool: Arc<WorkerPool>,
+    worker_id: String,
+    backend: String,
+}
+
+impl RemoteBackend {
+    pub fn new(pool: Arc<WorkerPool>, worker_id: String, backend: String) -> Self {
+        Self {
+            pool,
+            worker_id,
+            backend,
+        }
+    }
+}
+
+#[async_trait]
+impl AgentBackend for RemoteBackend {
+    async fn run_phase(
+        &self,
+        task: &Task,
+        phase: &PhaseConfig,
+        mut ctx: PhaseContext,
+    ) -> Result<PhaseOutput> {
+        let stream_tx = ctx.stream_tx.take();
+        if let Some(tx) = &stream_tx {
+            let evt = serde_json::json!({
+                "type": "status",
+                "status": format!("Dispatching to worker {}...", self.worker_id),
+            })
+            .to_string();
+            let _ = tx.send(evt);
+        }
+        redact_secrets(&mut ctx);
+        let job_id = format!(
+            "{}-{}-{}",
+            task.id,
+            phase.name,
+            chrono::Utc::now().timestamp_millis()
+        );
+        let job = WorkerJob {
+            job_id: job_id.clone(),
+            backend: self.backend.clone(),
+            task: task.clone(),
+            phase: phase.clone(),
+            ctx,
+        };
+        info!(
+            task_id = task.id,
+            phase = %phase.name,
+            worker = %self.worker_id,
+            job_id = %job_id,
+            "dispatching phase to worker"
+        );
+        let rx = self.pool.dispatch(&self.worker_id, job, stream_tx)?;
+        let result = rx
+            .await
+            .map_err(|_| anyhow!("worker {} dropped job {job_id}", self.worker_id))?;
+        match result.output {
+            Some(output) => Ok(output),
+            None => bail!("worker {}: {}", self.worker_id, result.error),
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.backend
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker(name: &str, capacity: u32, labels: &[&str]) -> WorkerRegistration {
+        WorkerRegistration {
+            name: name.into(),
+            capacity,
+            sandbox_modes: vec!["docker".into()],
+            backends: vec!["claude".into()],
+            labels: labels.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn matches_backend_repo_and_labels() {
+        let gpu = worker("gpu-1", 1, &["gpu=true", "repo=borg"]);
+        let any = worker("cpu-1", 1, &["gpu=false"]);
+        let req = WorkerRequirements::new("claude", "/src/borg", &[]);
+        assert!(gpu.matches(&req));
+        assert!(any.matches(&req));
+
+        let other_repo = WorkerRequirements::new("claude", "/src/site", &[]);
+        assert!(!gpu.matches(&other_repo));
+        assert!(any.matches(&other_repo));
+
+        let needs_gpu = WorkerRequirements::new("claude", "/src/borg", &["gpu=true".into()]);
+        assert!(gpu.matches(&needs_gpu));
+        assert!(!any.matches(&needs_gpu));
+
+        let codex = WorkerRequirements::new("codex", "/src/borg", &[]);
+        assert!(!gpu.matches(&codex));
+    }
+
+    #[test]
+    fn reserve_respects_capacity_and_balances() {
+        let pool = WorkerPool::default();
+        pool.register(worker("a", 2, &[]));
+        pool.register(worker("b", 1, &[]));
+        let req = WorkerRequirements::new("claude", "/src/borg", &[]);
+        let first = pool.reserve(1, &req).unwrap();
+        let second = pool.reserve(2, &req).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(pool.reserve(3, &req).as_deref(), Some("a"));
+        assert!(pool.reserve(4, &req).is_none());
+        assert!(!pool.has_capacity());
+        assert_eq!(pool.reserved_count(), 3);
+        pool.release(3);
+        assert_eq!(pool.assignment(3), None);
+        assert!(pool.has_capacity());
+    }
+
+    fn job(id: &str) -> WorkerJob {
+        WorkerJob {
+            job_id: id.into(),
+            backend: "claude".into(),
+            task: Task {
+                id: 7,
+                repo_path: "/src/borg".into(),
+                ..Default::default()
+            },
+            phase: PhaseConfig::default(),
+            ctx: PhaseContext::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn job_round_trip_streams_and_completes() {
+        let pool = Arc::new(WorkerPool::default());
+        pool.register(worker("a", 1, &[]));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let result_rx = pool.dispatch("a", job("j1"), Some(tx)).unwrap();
+
+        let got = pool
+            .next_job("a", Duration::from_millis(10))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(got.job_id, "j1");
+        assert!(pool.push_stream("a", "j1", vec!["line".into()]));
+        assert_eq!(rx.recv().await.as_deref(), Some("line"));
+        assert!(!pool.complete(
+            "b",
+            "j1",
+            WorkerJobResult {
+                output: None,
+                error: String::new(),
+            }
+        ));
+        assert!(pool.complete(
+            "a",
+            "j1",
+            WorkerJobResult {
+                output: Some(PhaseOutput::failed("done")),
+                error: String::new(),
+            }
+        ));
+        assert_eq!(result_rx.await.unwrap().output.unwrap().output, "done");
+    }
+
+    #[tokio::test]
+    async fn re_registering_fails_outstanding_jobs() {
+        let pool = WorkerPool::default();
+        pool.register(worker("a", 1, &[]));
+        let result_rx = pool.dispatch("a", job("j1"), None).unwrap();
+        pool.register(worker("a", 1, &[]));
+        let res = result_rx.await.unwrap();
+        assert!(res.output.is_none());
+        assert!(res.error.contains("re-registered"));
+        assert!(pool.next_job("zzz", Duration::ZERO).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn jobs_leave_server_credentials_behind() {
+        let pool = Arc::new(WorkerPool::default());
+        pool.register(worker("a", 1, &[]));
+        let backend = RemoteBackend::new(Arc::clone(&pool), "a".into(), "claude".into());
+        let ctx = PhaseContext {
+            oauth_token: "oauth".into(),
+            github_token: "ghp".into(),
+            borg_api_token: "api".into(),
+            api_keys: [("openai".to_string(), "sk".to_string())].into(),
+            work_dir: "/src/borg/.worktrees/task-7".into(),
+            ..Default::default()
+        };
+        let run = tokio::spawn(async move {
+            backend
+                .run_phase(&Task::default(), &PhaseConfig::default(), ctx)
+                .await
+        });
+
+        let job = pool
+            .next_job("a", Duration::from_secs(5))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(job.ctx.oauth_token.is_empty());
+        assert!(job.ctx.github_token.is_empty());
+        assert!(job.ctx.borg_api_token.is_empty());
+        assert!(job.ctx.api_keys.is_empty());
+        assert_eq!(job.ctx.work_dir, "/src/borg/.worktrees/task-7");
+        assert!(pool.complete(
+            "a",
+            &job.job_id,
+            WorkerJobResult {
+                output: Some(PhaseOutput::failed("done")),
+                error: String::new(),
+            }
+        ));
+        assert_eq!(run.await.unwrap().unwrap().output, "done");
+    }
+}
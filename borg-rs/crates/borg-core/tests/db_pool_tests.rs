@@ -0,0 +1,98 @@
+/// Tests for the async, pool-backed data layer: concurrent sessions and
+/// explicit transactions.
+use std::time::{Duration, Instant};
+
+use borg_core::pgcompat::{to_param, OptionalExtension};
+
+mod support;
+
+use support::open_db;
+
+fn unique_key(tag: &str) -> String {
+    format!(
+        "test-{tag}-{}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    )
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn async_sessions_run_concurrently() {
+    let db = open_db();
+    let a = db.client().await.expect("client a");
+    let b = db.client().await.expect("client b");
+
+    let started = Instant::now();
+    let (ra, rb) = tokio::join!(
+        a.execute_batch("SELECT pg_sleep(0.4)"),
+        b.execute_batch("SELECT pg_sleep(0.4)"),
+    );
+    ra.expect("sleep a");
+    rb.expect("sleep b");
+    assert!(
+        started.elapsed() < Duration::from_millis(750),
+        "two pooled sessions should not serialize: {:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn sync_queries_are_not_blocked_by_an_open_async_session() {
+    let db = open_db();
+    let key = unique_key("busy");
+    let mut holder = db.client().await.expect("client");
+    let tx = holder.transaction().await.expect("begin");
+    tx.execute(
+        "INSERT INTO config (key, value, updated_at) VALUES (?1, 'pending', '')",
+        vec![to_param(&key)],
+    )
+    .await
+    .expect("insert in tx");
+
+    // The open transaction owns its own pooled client; a sync read on another
+    // session must neither wait for it nor see its uncommitted row.
+    assert_eq!(db.get_config(&key).expect("get_config"), None);
+
+    tx.commit().await.expect("commit");
+    assert_eq!(
+        db.get_config(&key).expect("get_config"),
+        Some("pending".to_string())
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn dropped_async_transaction_rolls_back() {
+    let db = open_db();
+    let key = unique_key("rollback");
+    {
+        let mut session = db.client().await.expect("client");
+        let tx = session.transaction().await.expect("begin");
+        tx.execute(
+            "INSERT INTO config (key, value, updated_at) VALUES (?1, 'x', '')",
+            vec![to_param(&key)],
+        )
+        .await
+        .expect("insert in tx");
+        let seen: Option<String> = tx
+            .query_row(
+                "SELECT value FROM config WHERE key = ?1",
+                vec![to_param(&key)],
+                |row| row.get(0),
+            )
+            .await
+            .optional()
+            .expect("read in tx");
+        assert_eq!(seen.as_deref(), Some("x"));
+    }
+
+    let session = db.client().await.expect("client");
+    let after: Option<String> = session
+        .query_row(
+            "SELECT value FROM config WHERE key = ?1",
+            vec![to_param(&key)],
+            |row| row.get(0),
+        )
+        .await
+        .optional()
+        .expect("read after drop");
+    assert_eq!(after, None);
+}
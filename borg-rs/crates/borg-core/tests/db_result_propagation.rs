@@ -1,6 +1,7 @@
 /// Tests verifying db methods work correctly.
 use borg_core::{
     db::Db,
+    test_report::TestFailure,
     types::{Proposal, Task},
 };
 use chrono::Utc;
@@ -181,3 +182,95 @@ fn test_get_unknown_retries_reflects_increments() {
     db.increment_unknown_retries(entry_id).expect("increment");
     assert_eq!(db.get_unknown_retries(entry_id), 2);
 }
+
+// ── task stacking ────────────────────────────────────────────────────────────
+
+#[test]
+fn test_task_base_round_trips_and_lists_stack() {
+    let db = open_db();
+    let base = make_task(&db);
+    let stacked = make_task(&db);
+    assert_eq!(db.get_task_base(stacked).expect("get"), 0);
+
+    db.set_task_base(stacked, base).expect("set");
+    assert_eq!(db.get_task_base(stacked).expect("get"), base);
+    assert!(db
+        .list_stacked_tasks()
+        .expect("list")
+        .contains(&(stacked, base)));
+
+    db.update_task_status(stacked, "merged", None)
+        .expect("status");
+    assert!(!db
+        .list_stacked_tasks()
+        .expect("list")
+        .contains(&(stacked, base)));
+}
+
+// ── flaky tests ──────────────────────────────────────────────────────────────
+
+#[test]
+fn test_flaky_runs_quarantine_and_release() {
+    let db = open_db();
+    let repo = "/repos/flaky";
+    db.record_test_results(
+        repo,
+        &[
+            ("net::retry".to_string(), "passed"),
+            ("net::ok".to_string(), "passed"),
+        ],
+    )
+    .expect("record");
+    db.record_test_results(repo, &[("net::retry".to_string(), "failed")])
+        .expect("record");
+    let row = db
+        .get_test_flakiness(repo, "net::retry")
+        .expect("get")
+        .expect("row");
+    assert_eq!((row.passes, row.failures), (1, 1));
+    assert_eq!(row.last_status, "failed");
+
+    assert!(!db.record_flaky_run(repo, "net::retry", 2).expect("flaky"));
+    assert!(db.quarantined_tests(repo).expect("list").is_empty());
+    assert!(db.record_flaky_run(repo, "net::retry", 2).expect("flaky"));
+    assert_eq!(
+        db.quarantined_tests(repo).expect("list"),
+        vec!["net::retry".to_string()]
+    );
+    let flaky = db.list_flaky_tests(repo, 10).expect("report");
+    assert_eq!(flaky.len(), 1);
+    assert_eq!(flaky[0].flaky_runs, 2);
+
+    db.set_test_quarantined(repo, "net::retry", false)
+        .expect("release");
+    assert!(db.quarantined_tests(repo).expect("list").is_empty());
+}
+
+// ── test failures ────────────────────────────────────────────────────────────
+
+#[test]
+fn test_task_test_failures_keep_latest_attempt_per_phase() {
+    let db = open_db();
+    let task_id = make_task(&db);
+    let failure = |name: &str| TestFailure {
+        name: name.to_string(),
+        file: "src/cart.rs".to_string(),
+        line: 42,
+        message: "assertion failed".to_string(),
+        stack: String::new(),
+    };
+    db.insert_test_failures(task_id, "validate", 0, &[failure("a"), failure("b")])
+        .expect("insert");
+    db.insert_test_failures(task_id, "validate", 1, &[failure("b")])
+        .expect("insert");
+    db.insert_test_failures(task_id, "implement", 0, &[failure("c")])
+        .expect("insert");
+
+    let rows = db.get_task_test_failures(task_id).expect("get");
+    let got: Vec<(&str, i64, &str)> = rows
+        .iter()
+        .map(|r| (r.phase.as_str(), r.attempt, r.failure.name.as_str()))
+        .collect();
+    assert_eq!(got, vec![("validate", 1, "b"), ("implement", 0, "c")]);
+    assert_eq!(rows[0].failure.line, 42);
+}
@@ -0,0 +1,126 @@
+/// Tests for the git helpers behind local-merge integration.
+use std::process::Command;
+
+use borg_core::git::Git;
+use tempfile::TempDir;
+
+fn git(dir: &str, args: &[&str]) -> String {
+    let out = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "t")
+        .env("GIT_AUTHOR_EMAIL", "t@example.com")
+        .env("GIT_COMMITTER_NAME", "t")
+        .env("GIT_COMMITTER_EMAIL", "t@example.com")
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "git {args:?}: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8_lossy(&out.stdout).trim().to_string()
+}
+
+fn commit(dir: &str, file: &str, msg: &str) -> String {
+    std::fs::write(format!("{dir}/{file}"), msg).unwrap();
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-m", msg]);
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// Repo with `main` checked out and a `task-1` branch one commit ahead.
+fn repo() -> (TempDir, String) {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().to_string_lossy().to_string();
+    git(&path, &["init", "-q", "-b", "main"]);
+    commit(&path, "a.txt", "init");
+    git(&path, &["checkout", "-q", "-b", "task-1"]);
+    commit(&path, "b.txt", "feat");
+    git(&path, &["checkout", "-q", "main"]);
+    (dir, path)
+}
+
+#[test]
+fn default_branch_prefers_main() {
+    let (_dir, path) = repo();
+    assert_eq!(Git::new(&path).default_branch().as_deref(), Some("main"));
+}
+
+#[test]
+fn default_branch_follows_origin_head() {
+    let (_dir, path) = repo();
+    git(&path, &["branch", "develop", "main"]);
+    let clone = TempDir::new().unwrap();
+    let clone_path = clone.path().to_string_lossy().to_string();
+    git(&path, &["clone", "-q", &path, &clone_path]);
+    git(&clone_path, &["branch", "develop", "origin/develop"]);
+    git(&clone_path, &["remote", "set-head", "origin", "develop"]);
+    assert_eq!(
+        Git::new(&clone_path).default_branch().as_deref(),
+        Some("develop")
+    );
+}
+
+#[test]
+fn is_ancestor_follows_history() {
+    let (_dir, path) = repo();
+    let g = Git::new(&path);
+    assert!(g.is_ancestor("main", "task-1").unwrap());
+    assert!(!g.is_ancestor("task-1", "main").unwrap());
+}
+
+#[test]
+fn fast_forward_updates_checked_out_branch_and_files() {
+    let (_dir, path) = repo();
+    let g = Git::new(&path);
+    let old = g.rev_parse("main").unwrap();
+    let new = g.rev_parse("task-1").unwrap();
+    g.fast_forward("main", &new, &old).unwrap();
+    assert_eq!(g.rev_parse("main").unwrap(), new);
+    assert!(std::path::Path::new(&format!("{path}/b.txt")).exists());
+}
+
+#[test]
+fn fast_forward_refuses_moved_or_diverged_branch() {
+    let (_dir, path) = repo();
+    let g = Git::new(&path);
+    let old = g.rev_parse("main").unwrap();
+    let new = g.rev_parse("task-1").unwrap();
+    let moved = commit(&path, "c.txt", "concurrent");
+    assert!(g.fast_forward("main", &new, &old).is_err());
+    assert!(g.fast_forward("main", &new, &moved).is_err());
+    assert_eq!(g.rev_parse("main").unwrap(), moved);
+}
+
+#[test]
+fn changed_files_lists_both_sides_of_a_rename() {
+    let (_dir, path) = repo();
+    std::fs::create_dir(format!("{path}/migrations")).unwrap();
+    commit(&path, "migrations/0001.sql", "create table t (id int);");
+    git(&path, &["checkout", "-q", "-b", "task-2"]);
+    std::fs::create_dir(format!("{path}/tmp")).unwrap();
+    git(&path, &["mv", "migrations/0001.sql", "tmp/x.sql"]);
+    git(&path, &["commit", "-qm", "move"]);
+    let mut changed = Git::new(&path).changed_files("main", "task-2").unwrap();
+    changed.sort();
+    assert_eq!(changed, vec!["migrations/0001.sql", "tmp/x.sql"]);
+}
+
+#[test]
+fn local_origin_only_for_filesystem_remotes() {
+    let (_dir, path) = repo();
+    let bare = TempDir::new().unwrap();
+    let bare_path = bare.path().to_string_lossy().to_string();
+    git(&bare_path, &["init", "-q", "--bare"]);
+
+    let g = Git::new(&path);
+    assert_eq!(g.local_origin(), None);
+    git(
+        &path,
+        &["remote", "add", "origin", "https://example.com/x.git"],
+    );
+    assert_eq!(g.local_origin(), None);
+    git(&path, &["remote", "set-url", "origin", &bare_path]);
+    assert_eq!(g.local_origin(), Some(bare_path));
+}
@@ -0,0 +1,52 @@
+/// Tests for versioned schema migrations against a real database.
+use borg_core::migrations::{Direction, Migration, MigrationState, MIGRATIONS};
+
+mod support;
+
+use support::open_db;
+
+const PROBE: &[Migration] = &[Migration {
+    version: 900_001,
+    name: "migration_probe",
+    up: "CREATE TABLE migration_probe (id BIGINT PRIMARY KEY);",
+    down: Some("DROP TABLE migration_probe;"),
+}];
+
+fn probe_exists(db: &borg_core::db::Db) -> bool {
+    db.migration_status()
+        .expect("status")
+        .iter()
+        .any(|s| s.version == 900_001)
+}
+
+#[test]
+fn baseline_is_recorded_and_nothing_is_pending() {
+    let db = open_db();
+    let status = db.migration_status().expect("status");
+    assert_eq!(status.len(), MIGRATIONS.len());
+    assert!(status.iter().all(|s| s.state == MigrationState::Applied));
+    assert!(db.migrate_up(None, true).expect("dry run").is_empty());
+}
+
+#[test]
+fn custom_migration_applies_and_reverts() {
+    let db = open_db();
+    let _ = db.migrate_down_with(PROBE, 900_000, false);
+
+    let planned = db.migrate_up_with(PROBE, None, true).expect("dry run");
+    assert_eq!(planned.len(), 1);
+    assert!(!probe_exists(&db), "dry run must not apply");
+
+    let applied = db.migrate_up_with(PROBE, None, false).expect("up");
+    assert_eq!(applied[0].direction, Direction::Up);
+    assert!(probe_exists(&db));
+    assert!(db
+        .migrate_up_with(PROBE, None, false)
+        .expect("up again")
+        .is_empty());
+
+    let reverted = db.migrate_down_with(PROBE, 900_000, false).expect("down");
+    assert_eq!(reverted.len(), 1);
+    assert_eq!(reverted[0].direction, Direction::Down);
+    assert!(!probe_exists(&db));
+}
@@ -0,0 +1,102 @@
+/// Tests for per-phase resource usage persistence and aggregation.
+use borg_core::{db::Db, resources::ResourceUsage, types::Task};
+use chrono::Utc;
+
+mod support;
+
+use support::open_db;
+
+fn make_task(db: &Db) -> i64 {
+    let task = Task {
+        id: 0,
+        title: "Resource task".into(),
+        description: "desc".into(),
+        repo_path: "/repo".into(),
+        branch: "task-r".into(),
+        status: "implement".into(),
+        attempt: 1,
+        max_attempts: 5,
+        last_error: String::new(),
+        created_by: "test".into(),
+        notify_chat: String::new(),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        session_id: String::new(),
+        mode: "sweborg".into(),
+        backend: String::new(),
+        workspace_id: 0,
+        project_id: 0,
+        task_type: String::new(),
+        requires_exhaustive_corpus_review: false,
+        started_at: None,
+        completed_at: None,
+        duration_secs: None,
+        review_status: None,
+        revision_count: 0,
+        chat_thread: String::new(),
+    };
+    db.insert_task(&task).expect("insert_task")
+}
+
+fn usage(cpu_ms: i64, peak: i64) -> ResourceUsage {
+    ResourceUsage {
+        wall_ms: 60_000,
+        cpu_ms,
+        peak_memory_bytes: peak,
+        disk_written_bytes: 4096,
+        source: "cgroup".into(),
+    }
+}
+
+#[test]
+fn resource_usage_is_stored_per_output() {
+    let db = open_db();
+    let task_id = make_task(&db);
+    let first = db
+        .insert_task_output(task_id, "implement", "done", "", 0)
+        .expect("insert output");
+    let second = db
+        .insert_task_output(task_id, "validate", "ok", "", 0)
+        .expect("insert output");
+    db.insert_task_output_resources(first, task_id, "implement", &usage(90_000, 1 << 30))
+        .expect("insert resources");
+    db.insert_task_output_resources(second, task_id, "validate", &usage(5_000, 1 << 20))
+        .expect("insert resources");
+
+    let rows = db.get_task_resource_usage(task_id).expect("get resources");
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].task_output_id, first);
+    assert_eq!(rows[0].phase, "implement");
+    assert_eq!(rows[0].usage, usage(90_000, 1 << 30));
+    assert_eq!(rows[1].usage.cpu_ms, 5_000);
+}
+
+#[test]
+fn usage_summary_aggregates_resources_by_phase() {
+    let db = open_db();
+    let task_id = make_task(&db);
+    let phase = format!("bench-{}", Utc::now().timestamp_nanos_opt().unwrap_or(0));
+    for (cpu, peak) in [(1_000, 100), (3_000, 300)] {
+        let out = db
+            .insert_task_output(task_id, &phase, "", "", 0)
+            .expect("insert output");
+        db.insert_task_output_resources(out, task_id, &phase, &usage(cpu, peak))
+            .expect("insert resources");
+    }
+
+    let summary = db.get_usage_summary(None, None).expect("summary");
+    let totals = summary
+        .resources
+        .by_phase
+        .iter()
+        .find(|p| p.phase == phase)
+        .expect("phase totals");
+    assert_eq!(totals.runs, 2);
+    assert_eq!(totals.cpu_ms, 4_000);
+    assert_eq!(totals.wall_ms, 120_000);
+    assert_eq!(totals.max_peak_memory_bytes, 300);
+    assert_eq!(totals.avg_peak_memory_bytes, 200);
+    assert_eq!(totals.disk_written_bytes, 8192);
+    assert!(summary.resources.phase_runs >= 2);
+    assert!(summary.resources.top_runs.len() <= 10);
+}
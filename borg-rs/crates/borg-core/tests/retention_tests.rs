@@ -0,0 +1,224 @@
+/// Tests for workspace retention queries and legal holds.
+use borg_core::{
+    db::{Db, RetentionPolicy},
+    types::Task,
+};
+
+mod support;
+
+use support::open_db;
+
+fn unique(tag: &str) -> String {
+    format!(
+        "{tag}-{}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    )
+}
+
+/// A cutoff in the future, so everything written by the test counts as expired.
+fn future_cutoff() -> String {
+    (chrono::Utc::now() + chrono::Duration::days(1))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Returns (workspace_id, user_id).
+fn make_workspace(db: &Db) -> (i64, i64) {
+    let user_id = db
+        .create_user(&unique("owner"), "Owner", "", false)
+        .expect("create user");
+    let workspace_id = db
+        .create_workspace(&unique("retention"), "org", Some(user_id))
+        .expect("create workspace");
+    (workspace_id, user_id)
+}
+
+fn make_project(db: &Db, workspace_id: i64) -> i64 {
+    db.insert_project(workspace_id, "Matter", "lawborg", "", "", "", "", "")
+        .expect("insert project")
+}
+
+fn close_project(db: &Db, project_id: i64) {
+    db.update_project(
+        project_id,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some("closed"),
+        None,
+        None,
+    )
+    .expect("close project");
+}
+
+fn add_message(db: &Db, chat_jid: &str) {
+    db.insert_chat_message(&unique("msg"), chat_jid, None, None, "hi", false, false)
+        .expect("insert message");
+}
+
+#[test]
+fn policy_round_trips() {
+    let db = open_db();
+    let (ws, user_id) = make_workspace(&db);
+    assert!(db.get_retention_policy(ws).expect("get").is_none());
+    db.upsert_retention_policy(&RetentionPolicy {
+        workspace_id: ws,
+        chat_messages_days: Some(90),
+        task_outputs_days: Some(365),
+        project_files_days_after_close: None,
+        updated_by: Some(user_id),
+        updated_at: String::new(),
+    })
+    .expect("upsert");
+    let policy = db.get_retention_policy(ws).expect("get").expect("policy");
+    assert_eq!(policy.chat_messages_days, Some(90));
+    assert_eq!(policy.task_outputs_days, Some(365));
+    assert_eq!(policy.project_files_days_after_close, None);
+    assert!(db
+        .list_retention_policies()
+        .expect("list")
+        .iter()
+        .any(|p| p.workspace_id == ws));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn chat_purge_is_scoped_to_workspace_and_skips_held_projects() {
+    let db = open_db();
+    let (ws, user_id) = make_workspace(&db);
+    let (other_ws, _) = make_workspace(&db);
+    let open_project = make_project(&db, ws);
+    let held_project = make_project(&db, ws);
+    db.create_legal_hold(held_project, "litigation", Some(user_id))
+        .expect("hold");
+
+    let prefix = format!("web:workspace:{ws}:");
+    let thread = format!("{prefix}web:general");
+    let held_thread = format!("{prefix}web:project-{held_project}");
+    let open_chat = format!("project:{open_project}");
+    let held_chat = format!("project:{held_project}");
+    let other_thread = format!("web:workspace:{other_ws}:web:general");
+    for jid in [&thread, &held_thread, &open_chat, &held_chat, &other_thread] {
+        add_message(&db, jid);
+    }
+
+    let purged = db
+        .purge_workspace_chat_messages(ws, &prefix, &future_cutoff())
+        .expect("purge");
+    assert_eq!(purged, 2);
+    assert!(db
+        .get_chat_messages(&thread, 10)
+        .await
+        .expect("get")
+        .is_empty());
+    assert!(db
+        .get_chat_messages(&open_chat, 10)
+        .await
+        .expect("get")
+        .is_empty());
+    assert_eq!(
+        db.get_chat_messages(&held_thread, 10)
+            .await
+            .expect("get")
+            .len(),
+        1
+    );
+    assert_eq!(
+        db.get_chat_messages(&held_chat, 10)
+            .await
+            .expect("get")
+            .len(),
+        1
+    );
+    assert_eq!(
+        db.get_chat_messages(&other_thread, 10)
+            .await
+            .expect("get")
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn closed_projects_expire_unless_held() {
+    let db = open_db();
+    let (ws, user_id) = make_workspace(&db);
+    let closed = make_project(&db, ws);
+    let held = make_project(&db, ws);
+    let active = make_project(&db, ws);
+    for pid in [closed, held, active] {
+        db.insert_project_file(
+            pid,
+            "a.pdf",
+            "a.pdf",
+            "x/a.pdf",
+            "application/pdf",
+            1,
+            "h",
+            false,
+        )
+        .expect("insert file");
+    }
+    close_project(&db, closed);
+    close_project(&db, held);
+    let hold_id = db
+        .create_legal_hold(held, "audit", Some(user_id))
+        .expect("hold");
+    assert!(db.project_has_legal_hold(held).expect("has hold"));
+
+    let cutoff = future_cutoff();
+    let due = db
+        .list_closed_projects_for_retention(ws, &cutoff)
+        .expect("list");
+    assert_eq!(due, vec![closed]);
+
+    assert!(db
+        .release_legal_hold(held, hold_id, Some(user_id))
+        .expect("release"));
+    assert!(!db
+        .release_legal_hold(held, hold_id, Some(user_id))
+        .expect("release again"));
+    assert!(!db.project_has_legal_hold(held).expect("has hold"));
+    let due = db
+        .list_closed_projects_for_retention(ws, &cutoff)
+        .expect("list");
+    assert_eq!(due, vec![closed, held]);
+}
+
+#[test]
+fn purged_tasks_are_skipped_until_updated() {
+    let db = open_db();
+    let (ws, _) = make_workspace(&db);
+    let task_id = db
+        .insert_task(&Task {
+            title: "Finished".into(),
+            repo_path: "/repo".into(),
+            status: "done".into(),
+            mode: "lawborg".into(),
+            workspace_id: ws,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            ..Default::default()
+        })
+        .expect("insert task");
+    let due = |db: &Db| -> Vec<i64> {
+        db.list_tasks_for_retention(ws, &future_cutoff())
+            .expect("list")
+            .into_iter()
+            .map(|t| t.id)
+            .collect()
+    };
+    assert_eq!(due(&db), vec![task_id]);
+
+    db.mark_task_retention_purged(task_id).expect("mark");
+    assert!(due(&db).is_empty());
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    db.update_task_status(task_id, "done", None)
+        .expect("update");
+    assert_eq!(due(&db), vec![task_id]);
+}
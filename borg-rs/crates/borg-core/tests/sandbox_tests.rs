@@ -1,4 +1,4 @@
-use borg_core::sandbox::{Sandbox, SandboxMode};
+use borg_core::sandbox::{ContainerEngine, Sandbox, SandboxMode, ROOTLESS_NETWORK};
 use tempfile::TempDir;
 
 #[test]
@@ -33,6 +33,80 @@ fn none_parses_to_direct() {
     );
 }
 
+#[test]
+fn podman_parses_to_podman() {
+    assert_eq!(
+        SandboxMode::from_str_or_auto("Podman"),
+        Some(SandboxMode::Podman)
+    );
+}
+
+#[test]
+fn gvisor_suffix_keeps_container_backend() {
+    assert_eq!(
+        SandboxMode::from_str_or_auto("docker+gvisor"),
+        Some(SandboxMode::Docker)
+    );
+    assert_eq!(
+        SandboxMode::from_str_or_auto("podman+gvisor"),
+        Some(SandboxMode::Podman)
+    );
+    assert_eq!(SandboxMode::from_str_or_auto("gvisor"), None);
+}
+
+#[test]
+fn only_docker_and_podman_are_containers() {
+    assert!(SandboxMode::Docker.is_container());
+    assert!(SandboxMode::Podman.is_container());
+    assert!(!SandboxMode::Bwrap.is_container());
+    assert!(!SandboxMode::Direct.is_container());
+}
+
+#[test]
+fn rootless_podman_keeps_host_uid_and_skips_iptables() {
+    let engine = ContainerEngine::podman(true);
+    assert_eq!(engine.run_args(), vec!["--userns", "keep-id"]);
+    assert_eq!(engine.filter_chain(), None);
+    assert_eq!(
+        ContainerEngine::podman(false).filter_chain(),
+        Some("FORWARD")
+    );
+    assert_eq!(
+        ContainerEngine::docker().filter_chain(),
+        Some("DOCKER-USER")
+    );
+}
+
+#[test]
+fn rootless_podman_never_joins_the_agent_bridges() {
+    let rootless = ContainerEngine::podman(true);
+    assert_eq!(
+        rootless.network_args(Some(Sandbox::ISOLATED_NETWORK)),
+        vec!["--network", "none"]
+    );
+    for network in [Some(Sandbox::AGENT_NETWORK), None] {
+        let args = rootless.network_args(network);
+        assert_eq!(args[..2], ["--network", ROOTLESS_NETWORK]);
+    }
+    let docker = ContainerEngine::docker();
+    assert_eq!(
+        docker.network_args(Some(Sandbox::ISOLATED_NETWORK))[..2],
+        ["--network", Sandbox::ISOLATED_NETWORK]
+    );
+    assert_eq!(docker.network_args(None), vec!["--network", "host"]);
+}
+
+#[test]
+fn gvisor_runtime_is_passed_to_run() {
+    let engine = ContainerEngine::docker().with_runtime(Some("runsc".into()));
+    assert_eq!(engine.run_args(), vec!["--runtime", "runsc"]);
+    let engine = ContainerEngine::podman(true).with_runtime(Some("runsc".into()));
+    assert_eq!(
+        engine.run_args(),
+        vec!["--runtime", "runsc", "--userns", "keep-id"]
+    );
+}
+
 #[test]
 fn auto_returns_none() {
     assert_eq!(SandboxMode::from_str_or_auto("auto"), None);
@@ -40,7 +114,7 @@ fn auto_returns_none() {
 
 #[test]
 fn unknown_string_returns_none() {
-    assert_eq!(SandboxMode::from_str_or_auto("podman"), None);
+    assert_eq!(SandboxMode::from_str_or_auto("lxc"), None);
 }
 
 #[test]
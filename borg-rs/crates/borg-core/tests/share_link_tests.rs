@@ -0,0 +1,118 @@
+/// Tests for share link policies: scope, document allowlists and use caps.
+use borg_core::db::{Db, ShareLinkPolicy};
+
+mod support;
+
+use support::open_db;
+
+fn unique_token(tag: &str) -> String {
+    format!(
+        "{tag}-{}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    )
+}
+
+/// Returns (project_id, owner_user_id).
+fn make_project(db: &Db) -> (i64, i64) {
+    let user_id = db
+        .create_user(&unique_token("owner"), "Owner", "", false)
+        .expect("create user");
+    let workspace_id = db
+        .create_workspace(&unique_token("share-links"), "org", Some(user_id))
+        .expect("create workspace");
+    let project_id = db
+        .insert_project(workspace_id, "Matter", "lawborg", "", "", "", "", "")
+        .expect("insert project");
+    (project_id, user_id)
+}
+
+#[test]
+fn share_link_defaults_to_full_scope_and_unlimited_uses() {
+    let db = open_db();
+    let (project_id, user_id) = make_project(&db);
+    let token = unique_token("defaults");
+    db.create_project_share_link(
+        project_id,
+        &token,
+        "opposing counsel",
+        "2999-01-01 00:00:00",
+        user_id,
+        &ShareLinkPolicy::default(),
+    )
+    .expect("create link");
+
+    let link = db
+        .get_project_share_link_by_token(&token)
+        .expect("query")
+        .expect("link exists");
+    assert_eq!(link.scope, "full");
+    assert!(link.allows_tasks());
+    assert!(link.allows_document(42));
+    assert!(!link.password_protected);
+    for _ in 0..5 {
+        assert!(db.consume_share_link_use(link.id, false).expect("consume"));
+    }
+}
+
+#[test]
+fn share_link_use_cap_counts_views_and_downloads() {
+    let db = open_db();
+    let (project_id, user_id) = make_project(&db);
+    let token = unique_token("capped");
+    let policy = ShareLinkPolicy {
+        password_hash: "argon2-hash".into(),
+        scope: "documents".into(),
+        document_ids: vec![7, 9],
+        max_uses: 2,
+    };
+    let id = db
+        .create_project_share_link(
+            project_id,
+            &token,
+            "",
+            "2999-01-01 00:00:00",
+            user_id,
+            &policy,
+        )
+        .expect("create link");
+
+    assert!(db.consume_share_link_use(id, false).expect("view"));
+    assert!(db.consume_share_link_use(id, true).expect("download"));
+    assert!(!db.consume_share_link_use(id, true).expect("capped"));
+
+    let link = db.get_project_share_link(id).expect("query").expect("link");
+    assert_eq!(link.view_count, 1);
+    assert_eq!(link.download_count, 1);
+    assert!(link.password_protected);
+    assert!(!link.allows_tasks());
+    assert!(link.allows_document(9));
+    assert!(!link.allows_document(8));
+}
+
+#[test]
+fn share_link_access_log_is_newest_first() {
+    let db = open_db();
+    let (project_id, user_id) = make_project(&db);
+    let id = db
+        .create_project_share_link(
+            project_id,
+            &unique_token("log"),
+            "",
+            "2999-01-01 00:00:00",
+            user_id,
+            &ShareLinkPolicy::default(),
+        )
+        .expect("create link");
+
+    db.log_share_link_access(id, "view", None, 200, "10.0.0.1", "curl")
+        .expect("log view");
+    db.log_share_link_access(id, "download", Some(3), 401, "10.0.0.2", "")
+        .expect("log download");
+
+    let entries = db.list_share_link_access(id, 10).expect("list");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].action, "download");
+    assert_eq!(entries[0].file_id, Some(3));
+    assert_eq!(entries[0].status, 401);
+    assert_eq!(entries[1].ip, "10.0.0.1");
+}
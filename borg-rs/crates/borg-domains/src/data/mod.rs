@@ -12,6 +12,8 @@ pub fn data_mode() -> PipelineMode {
         uses_test_cmd: false,
         integration: IntegrationType::GitBranch,
         default_max_attempts: 3,
+        egress_allowlist: Vec::new(),
+        worker_labels: Vec::new(),
         phases: vec![
             setup_phase("implement"),
             PhaseConfig {
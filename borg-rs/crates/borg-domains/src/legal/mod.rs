@@ -25,6 +25,8 @@ pub fn legal_mode() -> PipelineMode {
         uses_test_cmd: false,
         integration: IntegrationType::GitBranch,
         default_max_attempts: 3,
+        egress_allowlist: Vec::new(),
+        worker_labels: Vec::new(),
         phases: vec![
             setup_phase("implement"),
             PhaseConfig {
@@ -12,6 +12,8 @@ pub fn swe_mode() -> PipelineMode {
         uses_test_cmd: true,
         integration: IntegrationType::GitPr,
         default_max_attempts: 5,
+        egress_allowlist: Vec::new(),
+        worker_labels: Vec::new(),
         phases: vec![
             setup_phase("implement"),
             PhaseConfig {
@@ -118,7 +120,8 @@ write {\"status\":\"abandon\",\"reason\":\"...\"} to .borg/signal.json.";
 
 pub(crate) const SWE_IMPLEMENT_RETRY: &str = "\n\n\
 Previous attempt failed. Test output:\n```\n{ERROR}\n```\n\
-Analyze the failures and fix them. If your previous approach is fundamentally \
+Analyze the failures and fix them, starting with any failing tests listed at the top \
+(name, location, assertion message). If your previous approach is fundamentally \
 wrong, try a different one rather than repeating the same mistake.";
 
 pub const SWE_REBASE_INSTRUCTION: &str = "\
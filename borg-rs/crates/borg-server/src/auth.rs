@@ -178,7 +178,7 @@ fn extract_email_header(headers: &HeaderMap, header_name: &str) -> Option<String
 }
 
 // Extract bearer token from Authorization header
-fn extract_bearer(headers: &axum::http::HeaderMap) -> Option<&str> {
+pub(crate) fn extract_bearer(headers: &axum::http::HeaderMap) -> Option<&str> {
     headers
         .get(axum::http::header::AUTHORIZATION)
         .and_then(|v| v.to_str().ok())
@@ -323,6 +323,8 @@ fn is_exempt(path: &str) -> bool {
         || path.starts_with("/api/auth/sso/")
         || path == "/api/email/inbound"
         || path.starts_with("/api/public/")
+        || path.starts_with("/api/worker/")
+        || path.starts_with("/api/webhooks/")
         || !path.starts_with("/api/")
 }
 
@@ -1097,6 +1099,19 @@ mod tests {
         assert!(is_exempt("/api/public/projects/abc123/documents"));
     }
 
+    #[test]
+    fn worker_protocol_is_exempt_but_listing_is_not() {
+        assert!(is_exempt("/api/worker/register"));
+        assert!(is_exempt("/api/worker/w1/poll"));
+        assert!(!is_exempt("/api/workers"));
+    }
+
+    #[test]
+    fn forge_webhooks_are_exempt() {
+        assert!(is_exempt("/api/webhooks/github"));
+        assert!(is_exempt("/api/webhooks/gitlab"));
+    }
+
     #[test]
     fn generate_token_is_64_hex_chars() {
         let token = generate_token();
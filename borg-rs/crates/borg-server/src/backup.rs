@@ -288,7 +288,7 @@ async fn backup_once(
 
     for task in tasks {
         let outputs = db.get_task_outputs(task.id).unwrap_or_default();
-        let messages = db.get_task_messages(task.id).unwrap_or_default();
+        let messages = db.get_task_messages(task.id).await.unwrap_or_default();
         let queue_entries = db.get_queue_entries_for_task(task.id).unwrap_or_default();
 
         let session_archive_key = maybe_archive_session_dir(config, target, task.id).await?;
@@ -436,7 +436,7 @@ async fn backup_project_uploads(
     project: &ProjectRow,
     include_uploads: bool,
 ) -> Result<Vec<ProjectUploadBackupRecord>> {
-    let files = db.list_project_files(project.id).unwrap_or_default();
+    let files = db.list_project_files(project.id).await.unwrap_or_default();
     let mut uploads = Vec::with_capacity(files.len());
     for file in files {
         let backup_key = if include_uploads {
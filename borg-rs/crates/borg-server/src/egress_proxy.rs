@@ -0,0 +1,480 @@
+//! Egress allowlist proxy for sandboxed agents.
+//!
+//! Agents with an egress allowlist run on the internal Docker network, whose
+//! only reachable peer is the host, and get `HTTP(S)_PROXY` pointing here.
+//! Each request authenticates with the per-run grant token issued by the
+//! pipeline (`Proxy-Authorization: Basic task-N:<token>`). CONNECT tunnels
+//! and plain-HTTP requests are forwarded only to allowlisted hosts, on 443
+//! and 80 respectively or on a port the allowlist names. A plain-HTTP
+//! request's `Host` header must name its target. Every attempt is recorded
+//! in `tool_calls` and blocked ones also in the audit log.
+
+use std::{sync::Arc, time::Instant};
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use borg_core::{
+    db::Db,
+    egress::{is_internal_addr, EgressGrant, EgressGrants},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{debug, info, warn};
+
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+const TOOL_NAME: &str = "egress";
+
+pub struct EgressProxyState {
+    pub db: Arc<Db>,
+    pub grants: Arc<EgressGrants>,
+}
+
+/// Bind `addr` and serve. The default address is the internal network's
+/// gateway, which only exists once sandbox setup has created that network,
+/// so binding is retried for a while before giving up.
+pub async fn bind_and_serve(addr: String, state: Arc<EgressProxyState>) {
+    const BIND_ATTEMPTS: u32 = 30;
+    for attempt in 1..=BIND_ATTEMPTS {
+        match TcpListener::bind(&addr).await {
+            Ok(listener) => {
+                info!("Egress proxy listening on {addr}");
+                serve(listener, state).await;
+                return;
+            },
+            Err(e) if attempt == BIND_ATTEMPTS => {
+                warn!(
+                    "egress proxy: cannot bind {addr}: {e}; tasks with an egress allowlist will have no outbound network"
+                );
+            },
+            Err(_) => tokio::time::sleep(std::time::Duration::from_secs(2)).await,
+        }
+    }
+}
+
+pub async fn serve(listener: TcpListener, state: Arc<EgressProxyState>) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("egress proxy accept: {e}");
+                continue;
+            },
+        };
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &state).await {
+                debug!(%peer, "egress proxy connection: {e}");
+            }
+        });
+    }
+}
+
+/// A parsed proxy request head.
+#[derive(Debug, PartialEq, Eq)]
+struct ProxyRequest {
+    method: String,
+    host: String,
+    port: u16,
+    /// Origin-form path for plain HTTP; empty for CONNECT.
+    path: String,
+    version: String,
+    headers: Vec<(String, String)>,
+}
+
+impl ProxyRequest {
+    fn parse(head: &str) -> Result<Self> {
+        let mut lines = head.split("\r\n");
+        let request_line = lines.next().unwrap_or_default();
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target), Some(version)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            bail!("malformed request line");
+        };
+        let headers: Vec<(String, String)> = lines
+            .take_while(|l| !l.is_empty())
+            .filter_map(|l| l.split_once(':'))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+
+        let (authority, path, default_port) = if method.eq_ignore_ascii_case("CONNECT") {
+            (target, String::new(), 443)
+        } else if let Some(rest) = target.strip_prefix("http://") {
+            match rest.find('/') {
+                Some(i) => (&rest[..i], rest[i..].to_string(), 80),
+                None => (rest, "/".to_string(), 80),
+            }
+        } else {
+            bail!("only CONNECT and absolute http:// requests are proxied");
+        };
+        let (host, port) = split_authority(authority, default_port)?;
+        if default_port == 80 {
+            // The upstream server routes on Host, so it must agree with the
+            // target the allowlist was checked against.
+            let host_header = headers.iter().find(|(k, _)| k.eq_ignore_ascii_case("Host"));
+            if let Some((_, value)) = host_header {
+                if split_authority(value, 80)? != (host.clone(), port) {
+                    bail!("Host header does not match the request target");
+                }
+            }
+        }
+        Ok(Self {
+            method: method.to_ascii_uppercase(),
+            host,
+            port,
+            path,
+            version: version.to_string(),
+            headers,
+        })
+    }
+
+    fn is_connect(&self) -> bool {
+        self.method == "CONNECT"
+    }
+
+    fn default_port(&self) -> u16 {
+        if self.is_connect() {
+            443
+        } else {
+            80
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Grant token from `Proxy-Authorization: Basic base64(user:token)`.
+    fn token(&self) -> Option<String> {
+        let value = self.header("Proxy-Authorization")?;
+        let (scheme, encoded) = value.split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .ok()?;
+        let creds = String::from_utf8(decoded).ok()?;
+        let (_, token) = creds.split_once(':')?;
+        Some(token.to_string())
+    }
+
+    /// Head to send upstream for a plain-HTTP request: origin-form target,
+    /// proxy headers stripped, one request per connection so a kept-alive
+    /// socket cannot be reused for a different host.
+    fn upstream_head(&self) -> String {
+        const HOP_HEADERS: &[&str] = &[
+            "proxy-authorization",
+            "proxy-connection",
+            "connection",
+            "keep-alive",
+        ];
+        let mut head = format!("{} {} {}\r\n", self.method, self.path, self.version);
+        for (k, v) in &self.headers {
+            if !HOP_HEADERS.contains(&k.to_ascii_lowercase().as_str()) {
+                head.push_str(&format!("{k}: {v}\r\n"));
+            }
+        }
+        if self.header("Host").is_none() {
+            let host = if self.host.contains(':') {
+                format!("[{}]", self.host)
+            } else {
+                self.host.clone()
+            };
+            match self.port {
+                80 => head.push_str(&format!("Host: {host}\r\n")),
+                port => head.push_str(&format!("Host: {host}:{port}\r\n")),
+            }
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        head
+    }
+
+    fn summary(&self) -> String {
+        format!("{} {}:{}", self.method, self.host, self.port)
+    }
+}
+
+fn split_authority(authority: &str, default_port: u16) -> Result<(String, u16)> {
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, after) = rest.split_once(']').context("unterminated IPv6 literal")?;
+        (host, after.strip_prefix(':'))
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    if host.is_empty() {
+        bail!("empty host");
+    }
+    let port = match port {
+        Some(p) => p.parse().context("invalid port")?,
+        None => default_port,
+    };
+    Ok((host.to_ascii_lowercase(), port))
+}
+
+/// Read until the end of the request head. Returns the head and any bytes
+/// the client already sent after it.
+async fn read_head(stream: &mut TcpStream) -> Result<(String, Vec<u8>)> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 2048];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            bail!("client closed before sending a request");
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            return Ok((String::from_utf8_lossy(&buf).into_owned(), rest));
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            bail!("request head too large");
+        }
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, extra: &str, body: &str) -> Result<()> {
+    let resp = format!(
+        "HTTP/1.1 {status}\r\n{extra}Content-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(resp.as_bytes()).await?;
+    Ok(())
+}
+
+/// Connect to `host:port`, refusing addresses inside the host or LAN even
+/// if an allowlisted name resolves there.
+async fn connect_upstream(host: &str, port: u16) -> Result<TcpStream> {
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .with_context(|| format!("resolve {host}"))?
+        .collect::<Vec<_>>();
+    let public: Vec<_> = addrs
+        .into_iter()
+        .filter(|a| !is_internal_addr(&a.ip()))
+        .collect();
+    if public.is_empty() {
+        bail!("{host} resolves only to internal addresses");
+    }
+    let mut last_err = None;
+    for addr in public {
+        match TcpStream::connect(addr).await {
+            Ok(s) => return Ok(s),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err
+        .map(anyhow::Error::from)
+        .unwrap_or_else(|| anyhow::anyhow!("connect {host}")))
+}
+
+async fn handle_connection(mut client: TcpStream, state: &EgressProxyState) -> Result<()> {
+    let (head, early) = read_head(&mut client).await?;
+    let req = match ProxyRequest::parse(&head) {
+        Ok(r) => r,
+        Err(e) => {
+            respond(&mut client, "400 Bad Request", "", &format!("{e}\n")).await?;
+            return Ok(());
+        },
+    };
+    let Some(grant) = req.token().and_then(|t| state.grants.lookup(&t)) else {
+        respond(
+            &mut client,
+            "407 Proxy Authentication Required",
+            "Proxy-Authenticate: Basic realm=\"borg-egress\"\r\n",
+            "missing or expired egress grant\n",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let started = Instant::now();
+    let call_id = state
+        .db
+        .insert_tool_call(
+            &grant.run_id,
+            TOOL_NAME,
+            Some(grant.task_id),
+            None,
+            Some(&req.summary()),
+        )
+        .ok();
+    let finish = |output: Option<&str>, success: bool, error: Option<&str>| {
+        if let Some(id) = call_id {
+            let elapsed = started.elapsed().as_millis() as i64;
+            let _ = state
+                .db
+                .complete_tool_call(id, output, elapsed, success, error);
+        }
+    };
+
+    if !grant
+        .policy
+        .allows_port(&req.host, req.port, req.default_port())
+    {
+        info!(task_id = grant.task_id, host = %req.host, port = req.port, "egress blocked");
+        log_blocked(state, &grant, &req);
+        finish(None, false, Some("blocked by egress allowlist"));
+        respond(
+            &mut client,
+            "403 Forbidden",
+            "",
+            &format!(
+                "{}:{} is not on this task's egress allowlist\n",
+                req.host, req.port
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut upstream = match connect_upstream(&req.host, req.port).await {
+        Ok(s) => s,
+        Err(e) => {
+            let msg = e.to_string();
+            finish(None, false, Some(&msg));
+            respond(&mut client, "502 Bad Gateway", "", &format!("{msg}\n")).await?;
+            return Ok(());
+        },
+    };
+    if req.is_connect() {
+        client
+            .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+            .await?;
+    } else {
+        upstream.write_all(req.upstream_head().as_bytes()).await?;
+    }
+    upstream.write_all(&early).await?;
+
+    match tokio::io::copy_bidirectional(&mut client, &mut upstream).await {
+        Ok((sent, received)) => {
+            let summary = format!("{sent} bytes sent, {received} bytes received");
+            finish(Some(&summary), true, None);
+        },
+        Err(e) => {
+            let msg = e.to_string();
+            finish(None, false, Some(&msg));
+        },
+    }
+    Ok(())
+}
+
+fn log_blocked(state: &EgressProxyState, grant: &EgressGrant, req: &ProxyRequest) {
+    let _ = state.db.log_event(
+        Some(grant.task_id),
+        None,
+        "egress.blocked",
+        &serde_json::json!({
+            "host": req.host,
+            "port": req.port,
+            "method": req.method,
+            "run_id": grant.run_id,
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use borg_core::egress::EgressPolicy;
+
+    use super::*;
+
+    fn basic(token: &str) -> String {
+        let creds = base64::engine::general_purpose::STANDARD.encode(format!("task-7:{token}"));
+        format!("Basic {creds}")
+    }
+
+    #[test]
+    fn parses_connect_with_credentials() {
+        let head = format!(
+            "CONNECT crates.io:443 HTTP/1.1\r\nHost: crates.io:443\r\nProxy-Authorization: {}\r\n\r\n",
+            basic("abc123")
+        );
+        let req = ProxyRequest::parse(&head).unwrap();
+        assert!(req.is_connect());
+        assert_eq!((req.host.as_str(), req.port), ("crates.io", 443));
+        assert_eq!(req.token().as_deref(), Some("abc123"));
+        assert_eq!(req.summary(), "CONNECT crates.io:443");
+    }
+
+    #[test]
+    fn plain_http_is_rewritten_to_origin_form() {
+        let head = format!(
+            "GET http://Example.com/a?b=1 HTTP/1.1\r\nHost: example.com\r\nProxy-Authorization: {}\r\nProxy-Connection: keep-alive\r\nAccept: */*\r\n\r\n",
+            basic("t")
+        );
+        let req = ProxyRequest::parse(&head).unwrap();
+        assert_eq!((req.host.as_str(), req.port), ("example.com", 80));
+        assert_eq!(
+            req.upstream_head(),
+            "GET /a?b=1 HTTP/1.1\r\nHost: example.com\r\nAccept: */*\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn plain_http_host_header_must_name_the_target() {
+        let req = |target: &str, host: &str| {
+            ProxyRequest::parse(&format!("GET {target} HTTP/1.1\r\n{host}\r\n"))
+        };
+        assert!(req("http://a.com/", "Host: A.com\r\n").is_ok());
+        assert!(req("http://a.com:8080/", "Host: a.com:8080\r\n").is_ok());
+        assert!(req("http://a.com/", "Host: evil.net\r\n").is_err());
+        assert!(req("http://a.com/", "Host: a.com:8080\r\n").is_err());
+
+        let bare = req("http://a.com:8080/x", "").unwrap();
+        assert_eq!(
+            bare.upstream_head(),
+            "GET /x HTTP/1.1\r\nHost: a.com:8080\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn non_default_ports_need_an_allowlist_entry() {
+        let policy = EgressPolicy::parse("github.com");
+        let connect = ProxyRequest::parse("CONNECT github.com:22 HTTP/1.1\r\n\r\n").unwrap();
+        assert!(!policy.allows_port(&connect.host, connect.port, connect.default_port()));
+        let connect = ProxyRequest::parse("CONNECT github.com:443 HTTP/1.1\r\n\r\n").unwrap();
+        assert!(policy.allows_port(&connect.host, connect.port, connect.default_port()));
+        let http = ProxyRequest::parse("GET http://github.com:443/ HTTP/1.1\r\n\r\n").unwrap();
+        assert!(!policy.allows_port(&http.host, http.port, http.default_port()));
+    }
+
+    #[test]
+    fn ipv6_and_explicit_ports() {
+        assert_eq!(
+            split_authority("[2606:4700::1111]:8443", 443).unwrap(),
+            ("2606:4700::1111".to_string(), 8443)
+        );
+        assert_eq!(
+            split_authority("github.com", 80).unwrap(),
+            ("github.com".to_string(), 80)
+        );
+        assert!(split_authority(":443", 443).is_err());
+    }
+
+    #[test]
+    fn rejects_origin_form_and_https_absolute_targets() {
+        assert!(ProxyRequest::parse("GET /index.html HTTP/1.1\r\n\r\n").is_err());
+        assert!(ProxyRequest::parse("GET https://x.com/ HTTP/1.1\r\n\r\n").is_err());
+        assert!(ProxyRequest::parse("garbage\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn missing_or_non_basic_auth_has_no_token() {
+        let req = ProxyRequest::parse("CONNECT a.com:443 HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(req.token(), None);
+        let req = ProxyRequest::parse(
+            "CONNECT a.com:443 HTTP/1.1\r\nProxy-Authorization: Bearer xyz\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(req.token(), None);
+    }
+}
@@ -1,10 +1,12 @@
 mod auth;
 mod backup;
+mod egress_proxy;
 mod ingestion;
 mod instrumentation;
 mod logging;
 mod messaging_progress;
 mod proxy;
+mod retention;
 mod routes;
 mod routes_modes;
 mod search;
@@ -37,7 +39,10 @@ use borg_core::{
     pipeline::{Pipeline, PipelineEvent},
     registry::PluginRegistry,
     sandbox::Sandbox,
-    secrets::{EncryptedStore, PlaintextStore, SecretKeyManager},
+    secrets::{
+        AwsKmsWrapper, EncryptedStore, KeyWrapper, PlaintextStore, SecretKeyManager,
+        VaultTransitWrapper,
+    },
     sidecar::{Sidecar, SidecarEvent, Source},
     stream::{ChatStreamManager, TaskStreamManager},
     traits::SecretStore,
@@ -143,6 +148,8 @@ pub struct AppState {
     pub active_chat_agents: Arc<std::sync::atomic::AtomicUsize>,
     pub secret_store: Arc<dyn SecretStore>,
     pub document_parser: Arc<DocumentParserRouter>,
+    pub workers: Arc<borg_core::worker::WorkerPool>,
+    pub webhooks: Arc<borg_core::webhook::Webhooks>,
 }
 
 impl AppState {
@@ -264,6 +271,7 @@ fn spawn_telegram_poller(
     chat_event_tx: broadcast::Sender<String>,
     chat_stream_manager: Arc<ChatStreamManager>,
     ai_request_count: Arc<AtomicU64>,
+    registry: Arc<PluginRegistry>,
 ) {
     let tg_sessions: Arc<TokioMutex<HashMap<String, String>>> =
         Arc::new(TokioMutex::new(HashMap::new()));
@@ -361,6 +369,7 @@ fn spawn_telegram_poller(
                             let chat_tx2 = chat_event_tx.clone();
                             let csm2 = Arc::clone(&chat_stream_manager);
                             let ai_request_count2 = Arc::clone(&ai_request_count);
+                            let registry2 = Arc::clone(&registry);
                             let sender_name = msg.sender_name.clone();
                             let chat_id = msg.chat_id;
                             let message_id = msg.message_id;
@@ -391,6 +400,7 @@ fn spawn_telegram_poller(
                                     &ai_request_count2,
                                     None,
                                     None,
+                                    Some(&registry2),
                                 )
                                 .await
                                 {
@@ -435,6 +445,7 @@ fn spawn_user_bot_manager(
     chat_stream_manager: Arc<ChatStreamManager>,
     ai_request_count: Arc<AtomicU64>,
     sidecar_slot: Arc<TokioMutex<Option<Arc<Sidecar>>>>,
+    registry: Arc<PluginRegistry>,
 ) {
     let mgr = Arc::new(user_bots::UserBotManager::new(
         db,
@@ -445,6 +456,7 @@ fn spawn_user_bot_manager(
         chat_stream_manager,
         ai_request_count,
         sidecar_slot,
+        registry,
     ));
     tokio::spawn(async move { mgr.run().await });
 }
@@ -511,6 +523,7 @@ async fn spawn_sidecar_manager(
     sidecar_slot: Arc<TokioMutex<Option<Arc<Sidecar>>>>,
     wa_status: Arc<std::sync::Mutex<WaStatus>>,
     slack_status: Arc<std::sync::Mutex<SlackStatus>>,
+    registry: Arc<PluginRegistry>,
 ) {
     match Sidecar::spawn(
         &config.assistant_name,
@@ -547,6 +560,7 @@ async fn spawn_sidecar_manager(
                 let chat_tx_flush = chat_event_tx.clone();
                 let csm_flush = Arc::clone(&chat_stream_manager);
                 let flush_ai_request_count = Arc::clone(&ai_request_count);
+                let registry_flush = Arc::clone(&registry);
                 tokio::spawn(async move {
                     loop {
                         tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
@@ -560,6 +574,7 @@ async fn spawn_sidecar_manager(
                             let chat_tx2 = chat_tx_flush.clone();
                             let csm2 = Arc::clone(&csm_flush);
                             let ai_request_count2 = Arc::clone(&flush_ai_request_count);
+                            let registry2 = Arc::clone(&registry_flush);
                             let collector2 = Arc::clone(&collector_flush);
                             let chat_source = sidecar_source_prefix(&batch.chat_key);
                             let (user_id, chat_id) = parse_user_chat_id(&batch.chat_key);
@@ -593,6 +608,7 @@ async fn spawn_sidecar_manager(
                                     &ai_request_count2,
                                     None,
                                     None,
+                                    Some(&registry2),
                                 )
                                 .await
                                 {
@@ -638,6 +654,7 @@ async fn spawn_sidecar_manager(
                 let chat_tx_events = chat_event_tx.clone();
                 let csm_events = Arc::clone(&chat_stream_manager);
                 let events_ai_request_count = Arc::clone(&ai_request_count);
+                let registry_events = Arc::clone(&registry);
                 let wa_status_events = Arc::clone(&wa_status);
                 let slack_status_events = Arc::clone(&slack_status);
                 tokio::spawn(async move {
@@ -729,6 +746,7 @@ async fn spawn_sidecar_manager(
                             let chat_tx2 = chat_tx_events.clone();
                             let csm2 = Arc::clone(&csm_events);
                             let ai_request_count2 = Arc::clone(&events_ai_request_count);
+                            let registry2 = Arc::clone(&registry_events);
                             let collector2 = Arc::clone(&collector);
                             let chat_source = source_prefix.to_string();
                             let chat_id = msg.chat_id.clone();
@@ -772,6 +790,7 @@ async fn spawn_sidecar_manager(
                                     &ai_request_count2,
                                     None,
                                     None,
+                                    Some(&registry2),
                                 )
                                 .await
                                 {
@@ -904,6 +923,7 @@ fn spawn_imap_poller(
     chat_event_tx: broadcast::Sender<String>,
     chat_stream_manager: Arc<ChatStreamManager>,
     ai_request_count: Arc<AtomicU64>,
+    registry: Arc<PluginRegistry>,
 ) {
     let imap_sessions = Arc::new(TokioMutex::new(HashMap::new()));
     tokio::spawn(async move {
@@ -916,6 +936,7 @@ fn spawn_imap_poller(
             let chat_tx = chat_event_tx.clone();
             let csm = Arc::clone(&chat_stream_manager);
             let ai_count = Arc::clone(&ai_request_count);
+            let registry = Arc::clone(&registry);
             async move {
                 for email in emails {
                     let user = db.get_user_by_email(&email.from).ok().flatten();
@@ -972,6 +993,7 @@ fn spawn_imap_poller(
                         &ai_count,
                         None,
                         None,
+                        Some(&registry),
                     )
                     .await
                     {
@@ -1012,8 +1034,122 @@ fn spawn_knowledge_repo_sync(db: Arc<Db>, data_dir: String) {
     });
 }
 
+/// Re-seal secrets that are not on the active key. Runs once per startup so a
+/// `rotate-secret-key` followed by a restart migrates every row in the background.
+fn spawn_secret_reencryption(db: Arc<Db>) {
+    tokio::spawn(async move {
+        let result = tokio::task::spawn_blocking(move || db.reencrypt_secrets(200)).await;
+        match result {
+            Ok(Ok(report)) => {
+                let total = report.api_keys + report.linked_credentials + report.cloud_connections;
+                if total > 0 || report.skipped > 0 {
+                    info!(
+                        "secret re-encryption: api_keys={} linked_credentials={} cloud_connections={} skipped={}",
+                        report.api_keys,
+                        report.linked_credentials,
+                        report.cloud_connections,
+                        report.skipped
+                    );
+                }
+            },
+            Ok(Err(e)) => tracing::error!("secret re-encryption failed: {e}"),
+            Err(e) => tracing::error!("secret re-encryption task panicked: {e}"),
+        }
+    });
+}
+
 // ── Setup helpers ─────────────────────────────────────────────────────────
 
+async fn build_secret_key_wrapper(config: &Config) -> anyhow::Result<Option<Box<dyn KeyWrapper>>> {
+    match config.secret_kms.as_str() {
+        "" | "none" => Ok(None),
+        "aws" => Ok(Some(Box::new(
+            AwsKmsWrapper::new(&config.secret_kms_key_id, Some(&config.secret_kms_region)).await,
+        ))),
+        "vault" => {
+            if config.vault_token.is_empty() {
+                anyhow::bail!("BORG_SECRET_KMS=vault requires VAULT_TOKEN");
+            }
+            Ok(Some(Box::new(VaultTransitWrapper::new(
+                &config.vault_addr,
+                &config.vault_token,
+                &config.secret_kms_key_id,
+            ))))
+        },
+        other => anyhow::bail!("unknown BORG_SECRET_KMS: {other} (expected aws or vault)"),
+    }
+}
+
+/// `borg-server rotate-secret-key`: add a new active data key. The running
+/// server picks it up on restart and re-encrypts stored secrets in the background.
+async fn rotate_secret_key_command(config: &Config) -> anyhow::Result<()> {
+    let wrapper = build_secret_key_wrapper(config).await?;
+    let id = SecretKeyManager::rotate(&config.data_dir, wrapper.as_deref()).await?;
+    println!("active secret key is now {id}; restart borg-server to re-encrypt stored secrets");
+    Ok(())
+}
+
+/// `borg-server migrate [status|up|down] [--to <version>] [--dry-run]`.
+/// `up` applies pending migrations (optionally only through `--to`), `down`
+/// reverts everything newer than `--to`, and `--dry-run` prints the SQL
+/// that would run without changing the schema.
+fn migrate_command(config: &Config, args: &[String]) -> anyhow::Result<()> {
+    let mut action = "status";
+    let mut target: Option<i64> = None;
+    let mut dry_run = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "status" | "up" | "down" => action = arg.as_str(),
+            "--dry-run" => dry_run = true,
+            "--to" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("--to requires a version"))?;
+                target = Some(value.parse()?);
+            },
+            other => anyhow::bail!(
+                "unknown migrate argument {other:?}; usage: borg-server migrate [status|up|down] [--to <version>] [--dry-run]"
+            ),
+        }
+    }
+
+    let db = Db::open(&config.database_url)?;
+    let steps = match action {
+        "up" => db.migrate_up(target, dry_run)?,
+        "down" => {
+            let target =
+                target.ok_or_else(|| anyhow::anyhow!("migrate down requires --to <version>"))?;
+            db.migrate_down(target, dry_run)?
+        },
+        _ => {
+            for s in db.migration_status()? {
+                println!(
+                    "{:04}  {:<32} {:<8} {}",
+                    s.version,
+                    s.name,
+                    format!("{:?}", s.state).to_lowercase(),
+                    s.applied_at.unwrap_or_default()
+                );
+            }
+            return Ok(());
+        },
+    };
+
+    if steps.is_empty() {
+        println!("schema is up to date");
+    }
+    for step in steps {
+        let label = format!("{:04}_{} ({:?})", step.version, step.name, step.direction);
+        if dry_run {
+            println!("-- {label}\n{}\n", step.sql.trim_end());
+        } else {
+            println!("applied {label}");
+        }
+    }
+    Ok(())
+}
+
 fn init_tracing(
     log_tx: broadcast::Sender<String>,
     log_ring: Arc<std::sync::Mutex<VecDeque<String>>>,
@@ -1046,6 +1182,15 @@ fn write_api_token(data_dir: &str, token: &str) -> anyhow::Result<()> {
 
 fn init_db(env_config: &Config) -> anyhow::Result<(Db, Config)> {
     let mut db = Db::open(&env_config.database_url)?;
+    if !env_config.auto_migrate {
+        let pending = db.migrate_up(None, true)?;
+        if !pending.is_empty() {
+            anyhow::bail!(
+                "{} pending schema migration(s); run `borg-server migrate up` first",
+                pending.len()
+            );
+        }
+    }
     db.migrate()?;
     env_config.seed_db(&db)?;
     let config = env_config
@@ -1187,11 +1332,33 @@ fn build_registry(
         let model = std::env::var("LOCAL_MODEL").unwrap_or_else(|_| "llama3.2".into());
         backends.insert(
             "local".into(),
-            Arc::new(OllamaBackend::new(url, model)?.with_timeout(300)?),
+            Arc::new(
+                OllamaBackend::new(url, model)?
+                    .with_timeout(300)?
+                    .with_sandbox(sandbox_mode.clone())
+                    .with_docker_image(&config.container_image)
+                    .with_resource_limits(config.container_memory_mb, config.container_cpus),
+            ),
         );
         info!("local backend registered (Ollama)");
     }
 
+    if let Ok(url) = std::env::var("OPENAI_COMPAT_URL") {
+        let model = std::env::var("OPENAI_COMPAT_MODEL").unwrap_or_else(|_| "default".into());
+        let api_key = std::env::var("OPENAI_COMPAT_API_KEY").unwrap_or_default();
+        backends.insert(
+            "openai-compatible".into(),
+            Arc::new(
+                OllamaBackend::openai_compatible(url, model, api_key)?
+                    .with_timeout(300)?
+                    .with_sandbox(sandbox_mode.clone())
+                    .with_docker_image(&config.container_image)
+                    .with_resource_limits(config.container_memory_mb, config.container_cpus),
+            ),
+        );
+        info!("openai-compatible backend registered");
+    }
+
     // Agent SDK backend (primary Claude path when agent-bridge is available)
     {
         let provider = borg_core::traits::ProviderConfig::from_env();
@@ -1212,15 +1379,65 @@ fn build_registry(
         info!("container backend registered");
     }
 
-    // Wrap all backends with ReliableBackend for retry/backoff
+    // Replay a recorded fixture instead of calling a model (offline testing).
+    if let Ok(path) = std::env::var("BORG_REPLAY_FIXTURE") {
+        if !path.is_empty() {
+            backends.insert(
+                "replay".into(),
+                Arc::new(borg_agent::ReplayBackend::from_file(&path)?),
+            );
+            info!(fixture = %path, "replay backend registered");
+        }
+    }
+
+    // Record every agent run as a replay fixture.
+    if let Ok(dir) = std::env::var("BORG_RECORD_DIR") {
+        if !dir.is_empty() {
+            backends = backends
+                .into_iter()
+                .map(|(name, backend)| {
+                    let recorded: Arc<dyn borg_core::agent::AgentBackend> =
+                        Arc::new(borg_agent::RecordingBackend::new(backend, &dir));
+                    (name, recorded)
+                })
+                .collect();
+            info!(dir = %dir, "recording agent runs as replay fixtures");
+        }
+    }
+
+    // Failover chain: configured order, restricted to registered backends.
+    let chain: Vec<(String, Arc<dyn borg_core::agent::AgentBackend>)> = config
+        .backend_fallbacks
+        .iter()
+        .filter_map(|name| match backends.get(name) {
+            Some(b) => Some((name.clone(), Arc::clone(b))),
+            None => {
+                warn!(backend = %name, "fallback backend not registered, skipping");
+                None
+            },
+        })
+        .collect();
+    let local_backends = backends
+        .keys()
+        .filter(|name| is_local_backend(name))
+        .cloned()
+        .collect::<Vec<_>>();
+    let failover = Arc::new(
+        borg_agent::FailoverPolicy::new(chain)
+            .with_local_only_modes(config.local_only_fallback_modes.clone(), local_backends),
+    );
+
+    // Wrap all backends with ReliableBackend for retry/backoff and failover
     let reliable_backends: std::collections::HashMap<
         String,
         Arc<dyn borg_core::agent::AgentBackend>,
     > = backends
         .into_iter()
         .map(|(name, backend)| {
-            let wrapped: Arc<dyn borg_core::agent::AgentBackend> =
-                Arc::new(borg_agent::ReliableBackend::wrap(backend));
+            let wrapped: Arc<dyn borg_core::agent::AgentBackend> = Arc::new(
+                borg_agent::ReliableBackend::wrap(backend)
+                    .with_failover(name.clone(), Arc::clone(&failover)),
+            );
             (name, wrapped)
         })
         .collect();
@@ -1232,6 +1449,20 @@ fn build_registry(
     Ok(Arc::new(registry))
 }
 
+/// Backends whose model runs on this host: Ollama, and an OpenAI-compatible
+/// server when it listens on loopback.
+fn is_local_backend(name: &str) -> bool {
+    match name {
+        "local" => true,
+        "openai-compatible" => std::env::var("OPENAI_COMPAT_URL")
+            .ok()
+            .and_then(|u| reqwest::Url::parse(&u).ok())
+            .and_then(|u| u.host_str().map(str::to_string))
+            .is_some_and(|h| h == "localhost" || h == "127.0.0.1" || h == "[::1]"),
+        _ => false,
+    }
+}
+
 fn spawn_post_state_tasks(state: &Arc<AppState>, config: &Arc<Config>, db: &Arc<Db>) {
     routes::spawn_linked_credential_maintenance(Arc::clone(state));
 
@@ -1254,6 +1485,11 @@ fn spawn_post_state_tasks(state: &Arc<AppState>, config: &Arc<Config>, db: &Arc<
         Arc::clone(&state.file_storage),
     );
 
+    let retention_state = Arc::clone(state);
+    tokio::spawn(async move {
+        retention::run_retention_loop(retention_state).await;
+    });
+
     if !config.imap_host.is_empty() {
         let imap_cfg = borg_core::email::ImapConfig {
             host: config.imap_host.clone(),
@@ -1271,6 +1507,7 @@ fn spawn_post_state_tasks(state: &Arc<AppState>, config: &Arc<Config>, db: &Arc<
             state.chat_event_tx.clone(),
             Arc::clone(&state.chat_stream_manager),
             Arc::clone(&state.ai_request_count),
+            Arc::clone(&state.registry),
         );
     }
 
@@ -1321,6 +1558,23 @@ fn build_app_router(state: Arc<AppState>, dashboard_dir: &str) -> Router {
         )
         // Health (unauthenticated)
         .route("/api/health", get(routes::health))
+        // Remote worker protocol (authenticated by BORG_WORKER_TOKEN)
+        .route("/api/worker/register", post(routes::worker_register))
+        .route("/api/worker/:id/poll", post(routes::worker_poll))
+        .route("/api/worker/:id/heartbeat", post(routes::worker_heartbeat))
+        .route(
+            "/api/worker/:id/jobs/:job_id/stream",
+            post(routes::worker_stream),
+        )
+        .route(
+            "/api/worker/:id/jobs/:job_id/complete",
+            post(routes::worker_complete).layer(DefaultBodyLimit::max(64 * 1024 * 1024)),
+        )
+        .route("/api/workers", get(routes::list_workers))
+        // Forge webhooks (authenticated by FORGE_WEBHOOK_SECRET)
+        .route("/api/webhooks/github", post(routes::github_webhook))
+        .route("/api/webhooks/gitlab", post(routes::gitlab_webhook))
+        .route("/api/webhooks/gitea", post(routes::gitea_webhook))
         // Auth endpoints (unauthenticated)
         .route("/api/auth/token", get(auth::get_token))
         .route("/api/auth/status", get(auth::auth_status))
@@ -1336,6 +1590,18 @@ fn build_app_router(state: Arc<AppState>, dashboard_dir: &str) -> Router {
             "/api/workspaces/:id/members",
             post(routes::add_workspace_member),
         )
+        .route(
+            "/api/workspaces/:id/retention",
+            get(routes::get_retention_policy).put(routes::put_retention_policy),
+        )
+        .route(
+            "/api/workspaces/:id/retention/run",
+            post(routes::run_retention_policy),
+        )
+        .route(
+            "/api/workspaces/:id/chat-backend",
+            get(routes::get_workspace_chat_backend).put(routes::put_workspace_chat_backend),
+        )
         // User management (admin-only, enforced in handlers)
         .route("/api/users", get(routes::list_users))
         .route("/api/users", post(routes::create_user))
@@ -1498,6 +1764,14 @@ fn build_app_router(state: Arc<AppState>, dashboard_dir: &str) -> Router {
             get(routes::summarize_project_themes),
         )
         .route("/api/projects/:id/audit", get(routes::list_project_audit))
+        .route(
+            "/api/projects/:id/legal-holds",
+            get(routes::list_legal_holds).post(routes::create_legal_hold),
+        )
+        .route(
+            "/api/projects/:id/legal-holds/:hold_id",
+            delete(routes::release_legal_hold),
+        )
         .route(
             "/api/projects/:id/documents",
             get(routes::list_project_documents),
@@ -1543,6 +1817,10 @@ fn build_app_router(state: Arc<AppState>, dashboard_dir: &str) -> Router {
             "/api/projects/:id/share-links/:link_id",
             delete(routes::revoke_project_share_link),
         )
+        .route(
+            "/api/projects/:id/share-links/:link_id/access",
+            get(routes::list_share_link_access),
+        )
         .route("/api/shared-projects", get(routes::list_shared_projects))
         // Public share link views (no auth required)
         .route(
@@ -1557,6 +1835,11 @@ fn build_app_router(state: Arc<AppState>, dashboard_dir: &str) -> Router {
             "/api/public/projects/:token/documents",
             get(routes::get_public_project_documents),
         )
+        .route(
+            "/api/public/projects/:token/documents/:file_id/download",
+            get(routes::download_public_project_document)
+                .post(routes::post_download_public_project_document),
+        )
         // Modes
         .route("/api/modes", get(routes::get_modes))
         .route("/api/modes/full", get(routes::get_full_modes))
@@ -1574,6 +1857,9 @@ fn build_app_router(state: Arc<AppState>, dashboard_dir: &str) -> Router {
         .route("/api/logs", get(routes::sse_logs))
         // Events (queryable log)
         .route("/api/events", get(routes::get_events))
+        // Flaky test report / quarantine
+        .route("/api/tests/flaky", get(routes::list_flaky_tests))
+        .route("/api/tests/quarantine", put(routes::put_test_quarantine))
         // Chat
         .route("/api/chat/events", get(routes::sse_chat_events))
         .route(
@@ -1588,14 +1874,25 @@ fn build_app_router(state: Arc<AppState>, dashboard_dir: &str) -> Router {
         .route("/api/release", post(routes::post_release))
         // Backend overrides
         .route("/api/tasks/:id/backend", put(routes::put_task_backend))
+        .route(
+            "/api/tasks/:id/egress",
+            get(routes::get_task_egress).put(routes::put_task_egress),
+        )
+        .route(
+            "/api/tasks/:id/base",
+            get(routes::get_task_base).put(routes::put_task_base),
+        )
         .route("/api/repos", get(routes::list_repos_handler))
         .route("/api/repos/:id/backend", put(routes::put_repo_backend))
+        .route("/api/repos/:id/container", put(routes::put_repo_container))
+        .route("/api/repos/:id/forge", put(routes::put_repo_forge))
         // API keys (BYOK)
         .route("/api/keys", get(routes::list_api_keys))
         .route("/api/keys", post(routes::store_api_key))
         .route("/api/keys/:id", delete(routes::delete_api_key))
         // Cache volumes
         .route("/api/cache", get(routes::list_cache_volumes))
+        .route("/api/cache/warm-pool", get(routes::warm_pool_stats))
         .route("/api/cache/:name", delete(routes::delete_cache_volume))
         // Cloud storage OAuth
         .route("/api/cloud/:provider/auth", get(routes::cloud_auth_init))
@@ -1743,6 +2040,13 @@ async fn main() -> anyhow::Result<()> {
     let env_config = Config::from_env()?;
     std::fs::create_dir_all(&env_config.data_dir)?;
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("rotate-secret-key") => return rotate_secret_key_command(&env_config).await,
+        Some("migrate") => return migrate_command(&env_config, &args[1..]),
+        _ => {},
+    }
+
     let api_token = auth::generate_token();
     write_api_token(&env_config.data_dir, &api_token)?;
 
@@ -1798,8 +2102,15 @@ async fn main() -> anyhow::Result<()> {
         let sm_slot = Arc::clone(&sandbox_mode_slot);
         let an_slot = Arc::clone(&agent_network_slot);
         let mode_clone = mode.clone();
+        let warm_config = borg_core::warm_pool::WarmPoolConfig {
+            size: config.warm_pool_size as usize,
+            max_uses: config.warm_pool_max_uses,
+            idle_ttl: std::time::Duration::from_secs(config.warm_pool_idle_ttl_s),
+            max_idle_memory_bytes: config.warm_pool_max_idle_memory_mb * 1024 * 1024,
+            home_root: std::path::Path::new(&config.data_dir).join("warm-homes"),
+        };
         tokio::spawn(async move {
-            let net_ok = if mode_clone == borg_core::sandbox::SandboxMode::Docker {
+            let net_ok = if mode_clone.is_container() {
                 borg_core::sandbox::Sandbox::prune_orphan_containers().await;
                 let net_ok = borg_core::sandbox::Sandbox::ensure_agent_network().await;
                 let _ = borg_core::sandbox::Sandbox::ensure_isolated_network().await;
@@ -1808,6 +2119,12 @@ async fn main() -> anyhow::Result<()> {
                         "sandbox: iptables rules not installed — agent containers have unrestricted network access"
                     );
                 }
+                if warm_config.size > 0 {
+                    borg_core::warm_pool::WarmPool::remove_leftovers().await;
+                    let pool = borg_core::warm_pool::WarmPool::new(warm_config);
+                    pool.spawn_maintenance(std::time::Duration::from_secs(60));
+                    borg_core::sandbox::Sandbox::set_warm_pool(pool);
+                }
                 net_ok
             } else {
                 false
@@ -1861,6 +2178,7 @@ async fn main() -> anyhow::Result<()> {
             chat_event_tx.clone(),
             Arc::clone(&chat_stream_manager),
             Arc::clone(&ai_request_count),
+            Arc::clone(&registry),
         );
     }
 
@@ -1880,6 +2198,7 @@ async fn main() -> anyhow::Result<()> {
         Arc::clone(&chat_stream_manager),
         Arc::clone(&ai_request_count),
         Arc::clone(&sidecar_slot),
+        Arc::clone(&registry),
     );
 
     if let Some(self_repo) = config.watched_repos.iter().find(|r| r.is_self).cloned() {
@@ -1903,6 +2222,7 @@ async fn main() -> anyhow::Result<()> {
         let sidecar_slot = Arc::clone(&sidecar_slot);
         let wa_status = Arc::clone(&wa_status);
         let slack_status = Arc::clone(&slack_status);
+        let registry = Arc::clone(&registry);
         tokio::spawn(async move {
             spawn_sidecar_manager(
                 config,
@@ -1915,6 +2235,7 @@ async fn main() -> anyhow::Result<()> {
                 sidecar_slot,
                 wa_status,
                 slack_status,
+                registry,
             )
             .await;
         });
@@ -1951,11 +2272,21 @@ async fn main() -> anyhow::Result<()> {
 
     // Initialize secret store based on config
     let secret_store: Arc<dyn SecretStore> = if config.secret_encryption {
-        let key = SecretKeyManager::load_or_generate(&config.data_dir)
-            .expect("failed to load or generate secret encryption key");
-        let store = EncryptedStore::new(&key);
-        info!("secret encryption enabled (ChaCha20-Poly1305)");
-        Arc::new(store)
+        let wrapper = build_secret_key_wrapper(&config)
+            .await
+            .map_err(|e| e.context("invalid secret KMS configuration"))?;
+        let ring = SecretKeyManager::load_keyring(&config.data_dir, wrapper.as_deref())
+            .await
+            .map_err(|e| e.context("failed to load or generate secret encryption keyring"))?;
+        let store: Arc<dyn SecretStore> = Arc::new(EncryptedStore::from_keyring(&ring));
+        info!(
+            "secret encryption enabled (ChaCha20-Poly1305, active key {}, kms={})",
+            ring.active_id(),
+            wrapper.as_ref().map(|w| w.name()).unwrap_or("none")
+        );
+        Db::install_secret_store(Arc::clone(&store));
+        spawn_secret_reencryption(Arc::clone(&db));
+        store
     } else {
         Arc::new(PlaintextStore)
     };
@@ -2007,6 +2338,8 @@ async fn main() -> anyhow::Result<()> {
         active_chat_agents: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
         secret_store,
         document_parser,
+        workers: Arc::clone(&pipeline.workers),
+        webhooks: Arc::clone(&pipeline.webhooks),
     });
 
     spawn_post_state_tasks(&state, &config, &db);
@@ -2048,6 +2381,15 @@ async fn main() -> anyhow::Result<()> {
     };
     info!("Proxy listening on {proxy_addr}");
 
+    if config.egress_proxy_port > 0 {
+        let egress_addr = format!("{}:{}", config.egress_proxy_bind, config.egress_proxy_port);
+        let egress_state = Arc::new(egress_proxy::EgressProxyState {
+            db: Arc::clone(&db),
+            grants: Arc::clone(&pipeline.egress),
+        });
+        tokio::spawn(egress_proxy::bind_and_serve(egress_addr, egress_state));
+    }
+
     let server = axum::serve(
         listener,
         app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
@@ -2101,6 +2443,10 @@ async fn main() -> anyhow::Result<()> {
         tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
     }
 
+    if let Some(pool) = Sandbox::warm_pool() {
+        pool.drain().await;
+    }
+
     if agent_network_available {
         Sandbox::remove_network_rules().await;
         Sandbox::remove_agent_network().await;
@@ -0,0 +1,150 @@
+//! Workspace retention enforcement. Expires chat messages, finished task
+//! outputs and closed-project files according to `retention_policies`, skips
+//! projects under an active legal hold, and writes a purge receipt to the
+//! audit trail for every run that deletes something.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use borg_core::{
+    db::RetentionPolicy,
+    retention::{purge_task_artifacts, retention_cutoff},
+};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{routes::workspace_chat_prefix, AppState};
+
+const RETENTION_INTERVAL_S: u64 = 3600;
+
+#[derive(Debug, Default, Serialize)]
+pub struct PurgeReceipt {
+    pub workspace_id: i64,
+    pub chat_messages: usize,
+    pub tasks_purged: usize,
+    pub task_outputs: usize,
+    pub projects: Vec<i64>,
+    pub project_files: i64,
+}
+
+impl PurgeReceipt {
+    fn is_empty(&self) -> bool {
+        self.chat_messages == 0
+            && self.tasks_purged == 0
+            && self.task_outputs == 0
+            && self.project_files == 0
+    }
+}
+
+pub async fn run_retention_loop(state: Arc<AppState>) {
+    loop {
+        match state.db.list_retention_policies() {
+            Ok(policies) => {
+                for policy in policies {
+                    if let Err(e) = enforce_policy(&state, &policy).await {
+                        tracing::error!(
+                            workspace_id = policy.workspace_id,
+                            "retention enforcement failed: {e}"
+                        );
+                    }
+                }
+            },
+            Err(e) => tracing::error!("list_retention_policies failed: {e}"),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(RETENTION_INTERVAL_S)).await;
+    }
+}
+
+pub async fn enforce_policy(state: &AppState, policy: &RetentionPolicy) -> Result<PurgeReceipt> {
+    let now = Utc::now();
+    let ws = policy.workspace_id;
+    let mut receipt = PurgeReceipt {
+        workspace_id: ws,
+        ..Default::default()
+    };
+
+    let chat_cutoff = retention_cutoff(now, policy.chat_messages_days);
+    if let Some(cutoff) = &chat_cutoff {
+        receipt.chat_messages =
+            state
+                .db
+                .purge_workspace_chat_messages(ws, &workspace_chat_prefix(ws), cutoff)?;
+    }
+
+    let outputs_cutoff = retention_cutoff(now, policy.task_outputs_days);
+    if let Some(cutoff) = &outputs_cutoff {
+        for task in state.db.list_tasks_for_retention(ws, cutoff)? {
+            let deleted = state.db.delete_task_outputs_before(task.id, cutoff)?;
+            purge_task_artifacts(&state.db, &state.config.data_dir, &task)?;
+            state.db.mark_task_retention_purged(task.id)?;
+            if deleted > 0 {
+                receipt.tasks_purged += 1;
+                receipt.task_outputs += deleted;
+            }
+        }
+    }
+
+    let files_cutoff = retention_cutoff(now, policy.project_files_days_after_close);
+    if let Some(cutoff) = &files_cutoff {
+        for project_id in state.db.list_closed_projects_for_retention(ws, cutoff)? {
+            let deleted = purge_project_files(state, project_id).await?;
+            let _ = state.db.log_event_full(
+                None,
+                None,
+                Some(project_id),
+                "retention",
+                "retention_purge",
+                &json!({
+                    "workspace_id": ws,
+                    "project_files": deleted,
+                    "closed_before": cutoff,
+                }),
+            );
+            receipt.projects.push(project_id);
+            receipt.project_files += deleted;
+        }
+    }
+
+    if !receipt.is_empty() {
+        tracing::info!(
+            workspace_id = ws,
+            chat_messages = receipt.chat_messages,
+            task_outputs = receipt.task_outputs,
+            project_files = receipt.project_files,
+            "retention purge"
+        );
+        state.db.log_event_full(
+            None,
+            None,
+            None,
+            "retention",
+            "retention_purge",
+            &json!({
+                "receipt": &receipt,
+                "cutoffs": {
+                    "chat_messages": chat_cutoff,
+                    "task_outputs": outputs_cutoff,
+                    "project_files": files_cutoff,
+                },
+            }),
+        )?;
+    }
+    Ok(receipt)
+}
+
+async fn purge_project_files(state: &AppState, project_id: i64) -> Result<i64> {
+    for file in state.db.list_project_files(project_id).await? {
+        if let Err(err) = state.file_storage.delete(&file.stored_path).await {
+            tracing::warn!(
+                project_id,
+                file_id = file.id,
+                "retention: failed to delete stored file: {err}"
+            );
+        }
+    }
+    if let Some(search) = &state.search {
+        let _ = search.delete_project_chunks(project_id).await;
+    }
+    state.db.delete_all_project_files(project_id)
+}
@@ -13,6 +13,7 @@ use axum::{
     },
 };
 use borg_core::{
+    egress::EgressPolicy,
     linked_credentials::{PROVIDER_CLAUDE, PROVIDER_OPENAI},
     types::{PhaseConfig, PhaseContext, RepoConfig, Task},
 };
@@ -333,6 +334,7 @@ pub(crate) async fn health(State(state): State<Arc<AppState>>) -> Json<Value> {
         },
         "search": search_info,
         "backup": backup,
+        "db_pool": state.db.pool_status(),
     }))
 }
 
@@ -710,7 +712,7 @@ pub(crate) async fn get_status(
 pub(crate) async fn list_queue(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<Value>, StatusCode> {
-    let entries = state.db.list_queue().map_err(internal)?;
+    let entries = state.db.list_queue().await.map_err(internal)?;
     Ok(Json(json!(entries)))
 }
 
@@ -900,6 +902,10 @@ pub(crate) async fn triage_proposals(State(state): State<Arc<AppState>>) -> Json
                     lint_cmd: String::new(),
                     backend: String::new(),
                     repo_slug: String::new(),
+                    container_image: String::new(),
+                    container_file: String::new(),
+                    forge: String::new(),
+                    forge_url: String::new(),
                 },
                 data_dir: state.config.data_dir.clone(),
                 session_dir: format!("{}/sessions/triage-{}", state.config.data_dir, proposal.id),
@@ -925,6 +931,7 @@ pub(crate) async fn triage_proposals(State(state): State<Arc<AppState>>) -> Json
                 knowledge_dir: String::new(),
                 knowledge_repo_paths: Vec::new(),
                 agent_network: None,
+                container_image: None,
                 prior_research: Vec::new(),
                 revision_count: 0,
                 experimental_domains: state.config.experimental_domains,
@@ -936,6 +943,7 @@ pub(crate) async fn triage_proposals(State(state): State<Arc<AppState>>) -> Json
                 github_token_is_user: false,
                 clarification_resume_reuses_prior_review: false,
                 clarification_resume_question: String::new(),
+                egress_proxy: None,
             };
 
             tokio::fs::create_dir_all(&ctx.session_dir).await.ok();
@@ -1272,6 +1280,165 @@ pub(crate) async fn add_workspace_member(
     })))
 }
 
+#[derive(Deserialize)]
+pub(crate) struct RetentionPolicyBody {
+    chat_messages_days: Option<i64>,
+    task_outputs_days: Option<i64>,
+    project_files_days_after_close: Option<i64>,
+}
+
+fn require_workspace_manager(
+    state: &AppState,
+    user: &crate::auth::AuthUser,
+    workspace_id: i64,
+) -> Result<(), StatusCode> {
+    let membership = state
+        .db
+        .get_user_workspace_membership(user.id, workspace_id)
+        .map_err(internal)?
+        .ok_or(StatusCode::FORBIDDEN)?;
+    if !user.is_admin && !workspace_role_can_manage(&membership.role) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(())
+}
+
+pub(crate) async fn get_retention_policy(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, StatusCode> {
+    state
+        .db
+        .get_user_workspace_membership(user.id, id)
+        .map_err(internal)?
+        .ok_or(StatusCode::FORBIDDEN)?;
+    let policy = state
+        .db
+        .get_retention_policy(id)
+        .map_err(internal)?
+        .unwrap_or(borg_core::db::RetentionPolicy {
+            workspace_id: id,
+            ..Default::default()
+        });
+    Ok(Json(json!(policy)))
+}
+
+pub(crate) async fn put_retention_policy(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    Path(id): Path<i64>,
+    Json(body): Json<RetentionPolicyBody>,
+) -> Result<Json<Value>, StatusCode> {
+    require_workspace_manager(&state, &user, id)?;
+    let windows = [
+        body.chat_messages_days,
+        body.task_outputs_days,
+        body.project_files_days_after_close,
+    ];
+    if windows.iter().flatten().any(|days| *days < 0) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let policy = borg_core::db::RetentionPolicy {
+        workspace_id: id,
+        chat_messages_days: body.chat_messages_days.filter(|d| *d > 0),
+        task_outputs_days: body.task_outputs_days.filter(|d| *d > 0),
+        project_files_days_after_close: body.project_files_days_after_close.filter(|d| *d > 0),
+        updated_by: Some(user.id),
+        updated_at: String::new(),
+    };
+    state
+        .db
+        .upsert_retention_policy(&policy)
+        .map_err(internal)?;
+    let _ = state.db.log_event_full(
+        None,
+        None,
+        None,
+        &user.username,
+        "retention_policy.updated",
+        &json!({
+            "workspace_id": id,
+            "chat_messages_days": policy.chat_messages_days,
+            "task_outputs_days": policy.task_outputs_days,
+            "project_files_days_after_close": policy.project_files_days_after_close,
+        }),
+    );
+    Ok(Json(json!({ "ok": true })))
+}
+
+/// Enforces the workspace's policy immediately instead of waiting for the
+/// hourly retention job.
+pub(crate) async fn run_retention_policy(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, StatusCode> {
+    require_workspace_manager(&state, &user, id)?;
+    let policy = state
+        .db
+        .get_retention_policy(id)
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let receipt = crate::retention::enforce_policy(&state, &policy)
+        .await
+        .map_err(internal)?;
+    Ok(Json(json!(receipt)))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChatBackendBody {
+    chat_backend: String,
+}
+
+fn chat_backend_response(state: &AppState, chat_backend: String) -> Json<Value> {
+    let mut available = state.registry.backend_names();
+    available.sort_unstable();
+    Json(json!({ "chat_backend": chat_backend, "available": available }))
+}
+
+pub(crate) async fn get_workspace_chat_backend(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, StatusCode> {
+    state
+        .db
+        .get_user_workspace_membership(user.id, id)
+        .map_err(internal)?
+        .ok_or(StatusCode::FORBIDDEN)?;
+    let backend = state.db.get_workspace_chat_backend(id).map_err(internal)?;
+    Ok(chat_backend_response(&state, backend))
+}
+
+/// Selects the backend that answers this workspace's chat. An empty name
+/// restores the default Claude chat agent.
+pub(crate) async fn put_workspace_chat_backend(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    Path(id): Path<i64>,
+    Json(body): Json<ChatBackendBody>,
+) -> Result<Json<Value>, StatusCode> {
+    require_workspace_manager(&state, &user, id)?;
+    let backend = body.chat_backend.trim().to_string();
+    if !backend.is_empty() && state.registry.get_backend(&backend).is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state
+        .db
+        .set_workspace_chat_backend(id, &backend)
+        .map_err(internal)?;
+    let _ = state.db.log_event_full(
+        None,
+        None,
+        None,
+        &user.username,
+        "workspace.chat_backend_updated",
+        &json!({ "workspace_id": id, "chat_backend": backend }),
+    );
+    Ok(chat_backend_response(&state, backend))
+}
+
 const USER_SETTINGS_KEYS: &[&str] = &[
     "model",
     "backend",
@@ -1801,6 +1968,73 @@ pub(crate) async fn put_task_backend(
     Ok(Json(json!({ "ok": true })))
 }
 
+fn task_egress_json(state: &AppState, task: &Task) -> Result<Value, StatusCode> {
+    let mode_list = borg_core::modes::get_mode(&task.mode)
+        .or_else(|| {
+            crate::routes_modes::get_custom_modes(&state.db)
+                .into_iter()
+                .find(|m| m.name == task.mode)
+        })
+        .map(|m| EgressPolicy::new(&m.egress_allowlist))
+        .unwrap_or_default();
+    let task_list = EgressPolicy::parse(
+        &state
+            .db
+            .get_task_egress_allowlist(task.id)
+            .map_err(internal)?,
+    );
+    Ok(json!({
+        "task_id": task.id,
+        "mode": mode_list.domains(),
+        "task": task_list.domains(),
+        "effective": mode_list.merge(&task_list).domains(),
+    }))
+}
+
+pub(crate) async fn get_task_egress(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let task = state
+        .db
+        .get_task(id)
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(task_egress_json(&state, &task)?))
+}
+
+/// Replace the task's own egress allowlist. Body: `{"domains": [...]}`;
+/// an empty list leaves only the mode's allowlist in force.
+pub(crate) async fn put_task_egress(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let task = state
+        .db
+        .get_task(id)
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let domains = body["domains"]
+        .as_array()
+        .ok_or(StatusCode::BAD_REQUEST)?
+        .iter()
+        .filter_map(|d| d.as_str())
+        .collect::<Vec<_>>();
+    let policy = EgressPolicy::new(domains);
+    state
+        .db
+        .set_task_egress_allowlist(id, policy.domains())
+        .map_err(internal)?;
+    let _ = state.db.log_event(
+        Some(id),
+        None,
+        "egress.allowlist_updated",
+        &json!({ "domains": policy.domains() }),
+    );
+    Ok(Json(task_egress_json(&state, &task)?))
+}
+
 pub(crate) async fn list_repos_handler(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<Value>, StatusCode> {
@@ -1817,6 +2051,10 @@ pub(crate) async fn list_repos_handler(
                 "test_cmd": r.test_cmd,
                 "auto_merge": r.auto_merge,
                 "repo_slug": r.repo_slug,
+                "container_image": r.container_image,
+                "container_file": r.container_file,
+                "forge": r.forge,
+                "forge_url": r.forge_url,
             })
         })
         .collect();
@@ -1836,6 +2074,51 @@ pub(crate) async fn put_repo_backend(
     Ok(Json(json!({ "ok": true })))
 }
 
+/// Set a repo's pinned image and container file (relative to the repo).
+/// Like the repo backend, read into the config at server start.
+pub(crate) async fn put_repo_container(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let image = body["container_image"].as_str().unwrap_or("").trim();
+    let file = body["container_file"].as_str().unwrap_or("").trim();
+    if file.starts_with('/') || file.split('/').any(|c| c == "..") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state
+        .db
+        .update_repo_container(id, image, file)
+        .map_err(internal)?;
+    Ok(Json(json!({ "ok": true })))
+}
+
+pub(crate) async fn put_repo_forge(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let forge = body["forge"]
+        .as_str()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    let forge_url = body["forge_url"].as_str().unwrap_or("").trim();
+    if borg_core::forge::ForgeKind::parse(&forge).is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !forge_url.is_empty()
+        && !(forge_url.starts_with("https://") || forge_url.starts_with("http://"))
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state
+        .db
+        .update_repo_forge(id, &forge, forge_url.trim_end_matches('/'))
+        .map_err(internal)?;
+    Ok(Json(json!({ "ok": true })))
+}
+
 pub(crate) async fn list_api_keys(
     State(state): State<Arc<AppState>>,
     axum::Extension(workspace): axum::Extension<crate::auth::WorkspaceContext>,
@@ -1885,6 +2168,15 @@ pub(crate) async fn list_cache_volumes(
     Ok(Json(json!({ "volumes": arr })))
 }
 
+pub(crate) async fn warm_pool_stats(
+    State(_state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let stats = borg_core::sandbox::Sandbox::warm_pool()
+        .map(|pool| pool.stats())
+        .unwrap_or_default();
+    Ok(Json(json!(stats)))
+}
+
 pub(crate) async fn delete_cache_volume(
     State(_state): State<Arc<AppState>>,
     Path(name): Path<String>,
@@ -1927,7 +2219,7 @@ pub(crate) async fn get_task_container(
     let container_id = container_id_from_stream(&state, task_id).await;
     match container_id {
         Some(id) => {
-            let status = tokio::process::Command::new("docker")
+            let status = tokio::process::Command::new(borg_core::sandbox::Sandbox::cli())
                 .args(["inspect", "--format", "{{.State.Status}}", &id])
                 .output()
                 .await
@@ -1951,6 +2243,7 @@ pub(crate) async fn admin_conversation_dump(
     let msgs = state
         .db
         .get_chat_messages(&query.thread, query.limit)
+        .await
         .map_err(internal)?;
 
     let result: Vec<Value> = msgs
@@ -2155,6 +2448,7 @@ pub(crate) async fn email_inbound(
     let chat_tx = state.chat_event_tx.clone();
     let csm = Arc::clone(&state.chat_stream_manager);
     let ai_count = Arc::clone(&state.ai_request_count);
+    let registry = Arc::clone(&state.registry);
     let from_email = email.from.clone();
     let reply_subject = format!("Re: {}", email.subject);
 
@@ -2175,6 +2469,7 @@ pub(crate) async fn email_inbound(
             &ai_count,
             None,
             None,
+            Some(&registry),
         )
         .await
         {
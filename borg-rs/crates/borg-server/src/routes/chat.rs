@@ -15,9 +15,12 @@ use axum::{
     },
 };
 use borg_core::{
+    agent::AgentBackend,
     config::{refresh_oauth_token, Config},
     db::Db,
     linked_credentials::{claude_oauth_token_from_home, restore_bundle, PROVIDER_CLAUDE},
+    registry::PluginRegistry,
+    traits::{ChatContext, ChatRequest},
 };
 use chrono::Utc;
 use serde::Deserialize;
@@ -134,6 +137,7 @@ pub(crate) async fn run_chat_agent(
     ai_request_count: &Arc<AtomicU64>,
     user_id: Option<i64>,
     model_override: Option<String>,
+    registry: Option<&PluginRegistry>,
 ) -> anyhow::Result<String> {
     let session_dir = format!(
         "{}/sessions/chat-{}",
@@ -285,7 +289,9 @@ pub(crate) async fn run_chat_agent(
         }
     }
 
-    let effective_model = model_override.unwrap_or_else(|| config.model.clone());
+    let effective_model = model_override
+        .clone()
+        .unwrap_or_else(|| config.model.clone());
     let mut args = vec![
         "--model".to_string(),
         effective_model,
@@ -296,7 +302,7 @@ pub(crate) async fn run_chat_agent(
         "--max-turns".to_string(),
         "64".to_string(),
         "--append-system-prompt".to_string(),
-        system_prompt,
+        system_prompt.clone(),
     ];
 
     if let Ok(Some(disallowed)) = db.get_config("chat_disallowed_tools") {
@@ -363,7 +369,7 @@ pub(crate) async fn run_chat_agent(
     }
 
     args.push("--print".to_string());
-    args.push(prompt);
+    args.push(prompt.clone());
 
     let mut token = refresh_oauth_token(&config.credentials_path, &config.oauth_token);
     if token.is_empty() {
@@ -391,7 +397,9 @@ pub(crate) async fn run_chat_agent(
         }
     }
 
-    if !api_token.is_empty() {
+    let agent_guide = if api_token.is_empty() {
+        None
+    } else {
         let project_id_hint = project_for_chat
             .as_ref()
             .map(|p| {
@@ -452,6 +460,145 @@ pub(crate) async fn run_chat_agent(
         );
         let claude_md_path = format!("{session_dir}/CLAUDE.md");
         let _ = std::fs::write(&claude_md_path, &agent_claude_md);
+        Some(agent_claude_md)
+    };
+
+    let chat_workspace_id = workspace_id_for_chat(db, chat_key, project_for_chat.as_ref(), user_id);
+    if let Some((backend_name, backend)) =
+        registry.and_then(|r| workspace_chat_backend(db, r, chat_workspace_id))
+    {
+        let disallowed_tools = db
+            .get_config("chat_disallowed_tools")
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+        let request = ChatRequest {
+            message: prompt,
+            conversation_history: Vec::new(),
+            system_prompt: match &agent_guide {
+                Some(guide) => format!("{system_prompt}\n\n{guide}"),
+                None => system_prompt,
+            },
+            // The configured default model is a Claude model; other backends
+            // use their own default unless the user picked one explicitly.
+            model: model_override.unwrap_or_default(),
+            allowed_tools: Vec::new(),
+            disallowed_tools,
+            mcp_servers_json: json!(mcp_servers),
+            max_turns: 64,
+            max_budget_usd: None,
+        };
+        let session_folder = backend_session_folder(&backend_name, chat_key);
+        let (stream_tx, mut stream_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+        let ctx = ChatContext {
+            session_dir: session_dir.clone(),
+            session_id: db.get_session(&session_folder).ok().flatten(),
+            oauth_token: String::new(),
+            provider_env: HashMap::new(),
+            stream_tx: Some(stream_tx),
+            borg_api_url: api_url,
+            borg_api_token: api_token,
+            project_id,
+            workspace_id: chat_workspace_id,
+            mode: project_mode.to_string(),
+            chat_thread: Some(chat_key.to_string()),
+            api_keys: legal_linked_creds.into_iter().collect(),
+            knowledge_dir: String::new(),
+        };
+
+        let csm = Arc::clone(chat_stream_manager);
+        let events = chat_event_tx.clone();
+        let (forward_key, forward_chat, forward_run) =
+            (thread_key.clone(), chat_key.to_string(), run_id.to_string());
+        let forwarder = tokio::spawn(async move {
+            while let Some(line) = stream_rx.recv().await {
+                csm.push_line(&forward_key, line.clone()).await;
+                let stream_event = json!({
+                    "type": "chat_stream",
+                    "thread": forward_chat,
+                    "run_id": forward_run,
+                    "data": line,
+                })
+                .to_string();
+                let _ = events.send(stream_event);
+            }
+        });
+
+        ai_request_count.fetch_add(1, Ordering::Relaxed);
+        tracing::info!(chat_key, backend = %backend_name, "running chat on workspace backend");
+        let result = backend.run_chat(&request, &ctx).await;
+        drop(ctx);
+        let _ = forwarder.await;
+        let response = match result {
+            Ok(r) => r,
+            Err(e) => {
+                chat_stream_manager.end_stream(&thread_key).await;
+                return Err(e.context(format!("{backend_name} chat failed")));
+            },
+        };
+        tracing::info!(
+            chat_key,
+            backend = %backend_name,
+            input_tokens = response.input_tokens,
+            output_tokens = response.output_tokens,
+            cost_usd = response.cost_usd,
+            tool_calls = response.tool_calls.len(),
+            "chat backend reply"
+        );
+        for hop in &response.failovers {
+            tracing::info!(
+                chat_key,
+                from = hop.from.as_str(),
+                to = hop.to.as_str(),
+                "chat ran on fallback backend"
+            );
+            let _ = db.log_event_full(
+                None,
+                None,
+                (project_id > 0).then_some(project_id),
+                "chat",
+                "backend_failover",
+                &json!({
+                    "chat_key": chat_key,
+                    "from": hop.from,
+                    "to": hop.to,
+                    "reason": hop.reason,
+                }),
+            );
+        }
+        if let Some(sid) = &response.session_id {
+            if let Err(e) = db.set_session(&session_folder, sid) {
+                tracing::warn!(chat_key, "failed to persist session mapping: {e}");
+            }
+        }
+        if let Err(e) = borg_agent::bridge::persist_tool_calls(
+            db,
+            &response.tool_calls,
+            None,
+            Some(chat_key),
+            run_id,
+        )
+        .await
+        {
+            tracing::warn!(chat_key, "failed to persist chat tool calls: {e}");
+        }
+        return Ok(finish_chat_reply(
+            db,
+            chat_key,
+            run_id,
+            &response.raw_stream,
+            response.text,
+            &ChatReplyStream {
+                chat_event_tx,
+                chat_stream_manager,
+                thread_key: &thread_key,
+            },
+        )
+        .await);
     }
 
     let timeout = std::time::Duration::from_secs(config.agent_timeout_s.max(300) as u64);
@@ -542,14 +689,41 @@ pub(crate) async fn run_chat_agent(
         }
     }
 
+    Ok(finish_chat_reply(
+        db,
+        chat_key,
+        run_id,
+        &raw,
+        text,
+        &ChatReplyStream {
+            chat_event_tx,
+            chat_stream_manager,
+            thread_key: &thread_key,
+        },
+    )
+    .await)
+}
+
+/// Where a chat reply is broadcast to and whose live stream it closes.
+struct ChatReplyStream<'a> {
+    chat_event_tx: &'a broadcast::Sender<String>,
+    chat_stream_manager: &'a borg_core::stream::ChatStreamManager,
+    thread_key: &'a str,
+}
+
+/// Persist and broadcast the agent's reply, then close the live stream.
+async fn finish_chat_reply(
+    db: &Db,
+    chat_key: &str,
+    run_id: &str,
+    raw: &str,
+    text: String,
+    stream: &ChatReplyStream<'_>,
+) -> String {
     if !text.is_empty() {
         let reply_ts = Utc::now().timestamp();
         let reply_id = format!("{}-bot-{}", chat_key, reply_ts);
-        let stream_data = if raw.is_empty() {
-            None
-        } else {
-            Some(raw.as_str())
-        };
+        let stream_data = if raw.is_empty() { None } else { Some(raw) };
         if let Err(e) = db.insert_chat_message_with_stream(
             &reply_id,
             chat_key,
@@ -571,13 +745,90 @@ pub(crate) async fn run_chat_agent(
             "run_id": run_id,
         })
         .to_string();
-        let receivers = chat_event_tx.send(event).unwrap_or(0);
+        let receivers = stream.chat_event_tx.send(event).unwrap_or(0);
         tracing::info!(chat_key, receivers, "broadcast chat_reply to SSE clients");
     }
 
-    chat_stream_manager.end_stream(&thread_key).await;
+    stream
+        .chat_stream_manager
+        .end_stream(&stream.thread_key.to_string())
+        .await;
+    text
+}
+
+/// The chat's workspace: the owning project's, the one encoded in a
+/// workspace-scoped web thread key, the default workspace of the user a
+/// per-user bot or email chat belongs to, or the system workspace for the
+/// instance-wide bots.
+fn workspace_id_for_chat(
+    db: &Db,
+    chat_key: &str,
+    project: Option<&borg_core::db::ProjectRow>,
+    user_id: Option<i64>,
+) -> i64 {
+    if let Some(p) = project {
+        return p.workspace_id;
+    }
+    if let Some(id) = chat_key_workspace_id(chat_key) {
+        return id;
+    }
+    let owner = user_id.or_else(|| chat_key_user_id(chat_key)).or_else(|| {
+        let email = chat_key.strip_prefix("email:")?;
+        db.get_user_by_email(email).ok().flatten().map(|u| u.0)
+    });
+    if let Some(Ok(Some(id))) = owner.map(|uid| db.get_user_default_workspace_id(uid)) {
+        return id;
+    }
+    db.get_system_workspace()
+        .ok()
+        .flatten()
+        .map(|w| w.id)
+        .unwrap_or(0)
+}
+
+/// Workspace id from a workspace-scoped web thread key
+/// (`web:workspace:<id>:...`).
+fn chat_key_workspace_id(chat_key: &str) -> Option<i64> {
+    chat_key
+        .strip_prefix("web:workspace:")?
+        .split(':')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Owning user of a per-user bot chat key (`<source>:u<user_id>:<chat>`).
+fn chat_key_user_id(chat_key: &str) -> Option<i64> {
+    let (_, rest) = chat_key.split_once(':')?;
+    let (user, _) = rest.strip_prefix('u')?.split_once(':')?;
+    user.parse().ok()
+}
 
-    Ok(text)
+/// The non-default chat backend configured for a workspace, if any.
+fn workspace_chat_backend(
+    db: &Db,
+    registry: &PluginRegistry,
+    workspace_id: i64,
+) -> Option<(String, Arc<dyn AgentBackend>)> {
+    if workspace_id <= 0 {
+        return None;
+    }
+    let name = db.get_workspace_chat_backend(workspace_id).ok()?;
+    if name.is_empty() || name == "claude" {
+        return None;
+    }
+    match registry.get_backend(&name) {
+        Some(backend) => Some((name, Arc::clone(backend))),
+        None => {
+            tracing::warn!(workspace_id, backend = %name, "chat backend not registered, using claude");
+            None
+        },
+    }
+}
+
+/// Session ids are backend-specific, so each backend keeps its own mapping.
+fn backend_session_folder(backend: &str, chat_key: &str) -> String {
+    format!("chat-{backend}-{}", sanitize_chat_key(chat_key))
 }
 
 pub(crate) async fn sse_chat_events(
@@ -678,7 +929,7 @@ pub(crate) async fn sse_chat_thread_stream(
         let Some(mut live_rx) = live_rx else {
             if history.is_empty() {
                 // No active stream — try DB fallback for completed conversations
-                if let Ok(messages) = db.get_chat_messages(&internal_thread, 100) {
+                if let Ok(messages) = db.get_chat_messages(&internal_thread, 100).await {
                     for msg in &messages {
                         if let Some(ref raw) = msg.raw_stream {
                             for line in raw.lines() {
@@ -773,6 +1024,7 @@ pub(crate) async fn get_chat_messages(
     let msgs = match state
         .db
         .get_chat_messages(&actual_thread, q.limit.unwrap_or(100))
+        .await
     {
         Ok(m) => m,
         Err(e) => {
@@ -830,6 +1082,7 @@ pub(crate) async fn get_project_chat_messages(
     let msgs = state
         .db
         .get_chat_messages(&thread, q.limit.unwrap_or(200))
+        .await
         .map_err(internal)?;
     let v: Vec<Value> = msgs
         .iter()
@@ -910,6 +1163,7 @@ pub(crate) async fn post_project_chat(
             &state2.ai_request_count,
             Some(uid),
             model2,
+            Some(&state2.registry),
         )
         .await
         {
@@ -1013,6 +1267,7 @@ pub(crate) async fn post_chat(
             &state2.ai_request_count,
             Some(uid),
             model2,
+            Some(&state2.registry),
         )
         .await
         {
@@ -1032,3 +1287,35 @@ pub(crate) async fn post_chat(
 
     Ok(Json(json!({ "ok": true })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspace_is_parsed_from_web_thread_keys() {
+        assert_eq!(
+            chat_key_workspace_id("web:workspace:7:web:general"),
+            Some(7)
+        );
+        assert_eq!(chat_key_workspace_id("telegram:123"), None);
+        assert_eq!(chat_key_workspace_id("web:workspace:x:web:general"), None);
+    }
+
+    #[test]
+    fn owner_is_parsed_from_per_user_bot_keys() {
+        assert_eq!(chat_key_user_id("tg:u12:345"), Some(12));
+        assert_eq!(chat_key_user_id("discord:u3:chan:thread"), Some(3));
+        assert_eq!(chat_key_user_id("telegram:123"), None);
+        assert_eq!(chat_key_user_id("email:user@example.com"), None);
+        assert_eq!(chat_key_user_id("slack:uabc:chan"), None);
+    }
+
+    #[test]
+    fn backend_sessions_do_not_collide_with_claude_sessions() {
+        let key = "web:workspace:7:web:general";
+        let codex = backend_session_folder("codex", key);
+        assert_ne!(codex, format!("chat-{}", sanitize_chat_key(key)));
+        assert_ne!(codex, backend_session_folder("gemini", key));
+    }
+}
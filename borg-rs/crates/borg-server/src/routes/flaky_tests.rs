@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::internal;
+use crate::AppState;
+
+#[derive(Deserialize)]
+pub(crate) struct FlakyTestsQuery {
+    pub repo: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Flakiness report: tests that passed on re-run or are quarantined.
+pub(crate) async fn list_flaky_tests(
+    State(state): State<Arc<AppState>>,
+    Query(q): Query<FlakyTestsQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let tests = state
+        .db
+        .list_flaky_tests(q.repo.as_deref().unwrap_or(""), q.limit.unwrap_or(100))
+        .map_err(internal)?;
+    Ok(Json(json!(tests)))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct QuarantineBody {
+    pub repo: String,
+    pub test: String,
+    #[serde(default = "default_quarantined")]
+    pub quarantined: bool,
+}
+
+fn default_quarantined() -> bool {
+    true
+}
+
+/// Quarantine a test by hand, or release it with `"quarantined": false`.
+pub(crate) async fn put_test_quarantine(
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<QuarantineBody>,
+) -> Result<Json<Value>, StatusCode> {
+    if body.repo.trim().is_empty() || body.test.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state
+        .db
+        .set_test_quarantined(&body.repo, &body.test, body.quarantined)
+        .map_err(internal)?;
+    let kind = if body.quarantined {
+        "test.quarantined"
+    } else {
+        "test.released"
+    };
+    let _ = state.db.log_event(
+        None,
+        None,
+        kind,
+        &json!({ "repo": body.repo, "test": body.test, "manual": true }),
+    );
+    let row = state
+        .db
+        .get_test_flakiness(&body.repo, &body.test)
+        .map_err(internal)?;
+    Ok(Json(json!(row)))
+}
@@ -10,6 +10,9 @@ pub(crate) use chat::*;
 pub(crate) mod cloud;
 pub(crate) use cloud::*;
 
+pub(crate) mod flaky_tests;
+pub(crate) use flaky_tests::*;
+
 pub(crate) mod knowledge;
 pub(crate) use knowledge::*;
 
@@ -33,6 +36,12 @@ pub(crate) use tool_calls::*;
 
 pub(crate) mod utils;
 
+pub(crate) mod webhooks;
+pub(crate) use webhooks::*;
+
+pub(crate) mod workers;
+pub(crate) use workers::*;
+
 pub(crate) use crate::routes_modes::{
     delete_custom_mode, get_full_modes, get_modes, list_custom_modes, upsert_custom_mode,
 };
@@ -6,7 +6,7 @@ use std::{
 
 use axum::{
     body::Bytes,
-    extract::{Multipart, Path, Query, State},
+    extract::{ConnectInfo, Multipart, Path, Query, State},
     http::StatusCode,
     response::Json,
 };
@@ -766,7 +766,7 @@ pub(crate) async fn colocate_project_workspace(
     let repos_dir = format!("{session_dir}/repos");
     let repo_link = format!("{session_dir}/repo");
 
-    let all_files = db.list_project_files(project.id).unwrap_or_default();
+    let all_files = db.list_project_files(project.id).await.unwrap_or_default();
     let total_files = all_files.len();
     let mut linked = 0usize;
     let mut written = 0usize;
@@ -1702,6 +1702,7 @@ pub(crate) async fn list_projects(
     let mut projects = state
         .db
         .list_projects_in_workspace(workspace.id)
+        .await
         .map_err(internal)?;
 
     // Include projects shared directly with this user from other workspaces
@@ -1720,19 +1721,17 @@ pub(crate) async fn list_projects(
         }
     }
 
-    let out: Vec<Value> = projects
-        .into_iter()
-        .map(|p| {
-            let pid = p.id;
-            let counts = state.db.project_task_status_counts(pid).ok();
-            let mut j = serde_json::to_value(ProjectJson::from_row(p, counts))
-                .unwrap_or_else(|_| json!({"id": pid, "error": "serialization failed"}));
-            if let Some(role) = shared_roles.get(&pid) {
-                j["shared_role"] = json!(role);
-            }
-            j
-        })
-        .collect();
+    let mut out: Vec<Value> = Vec::with_capacity(projects.len());
+    for p in projects {
+        let pid = p.id;
+        let counts = state.db.project_task_status_counts(pid).await.ok();
+        let mut j = serde_json::to_value(ProjectJson::from_row(p, counts))
+            .unwrap_or_else(|_| json!({"id": pid, "error": "serialization failed"}));
+        if let Some(role) = shared_roles.get(&pid) {
+            j["shared_role"] = json!(role);
+        }
+        out.push(j);
+    }
     Ok(Json(json!(out)))
 }
 
@@ -1755,13 +1754,11 @@ pub(crate) async fn search_projects(
         .db
         .search_projects_in_workspace(workspace.id, &q)
         .map_err(internal)?;
-    let out: Vec<ProjectJson> = projects
-        .into_iter()
-        .map(|p| {
-            let counts = state.db.project_task_status_counts(p.id).ok();
-            ProjectJson::from_row(p, counts)
-        })
-        .collect();
+    let mut out: Vec<ProjectJson> = Vec::with_capacity(projects.len());
+    for p in projects {
+        let counts = state.db.project_task_status_counts(p.id).await.ok();
+        out.push(ProjectJson::from_row(p, counts));
+    }
     Ok(Json(json!(out)))
 }
 
@@ -1858,7 +1855,7 @@ pub(crate) async fn get_project(
 ) -> Result<Json<Value>, StatusCode> {
     let (project, _role) =
         super::require_project_access_with_shares(state.as_ref(), &user, &workspace, id)?;
-    let counts = state.db.project_task_status_counts(id).ok();
+    let counts = state.db.project_task_status_counts(id).await.ok();
     Ok(Json(json!(ProjectJson::from_row(project, counts))))
 }
 
@@ -1910,6 +1907,7 @@ pub(crate) async fn delete_project(
     Path(id): Path<i64>,
 ) -> Result<StatusCode, StatusCode> {
     let project = require_project_access(state.as_ref(), &workspace, id)?;
+    reject_if_legal_hold(state.as_ref(), id)?;
     if !project.repo_path.is_empty() {
         let _ = tokio::fs::remove_dir_all(&project.repo_path).await;
     }
@@ -1945,7 +1943,7 @@ pub(crate) async fn list_project_tasks(
 ) -> Result<Json<Value>, StatusCode> {
     let (_project, _role) =
         super::require_project_access_with_shares(state.as_ref(), &user, &workspace, id)?;
-    let tasks = state.db.list_project_tasks(id).map_err(internal)?;
+    let tasks = state.db.list_project_tasks(id).await.map_err(internal)?;
     Ok(Json(json!(tasks)))
 }
 
@@ -1982,6 +1980,7 @@ pub(crate) async fn search_documents(
     let allowed_project_ids: HashSet<i64> = state
         .db
         .list_projects_in_workspace(workspace.id)
+        .await
         .map_err(internal)?
         .into_iter()
         .map(|p| p.id)
@@ -2030,10 +2029,10 @@ pub(crate) async fn search_documents(
             .embed_query(&query.q)
             .await
         {
-            if let Ok(sem_results) =
-                state
-                    .db
-                    .search_embeddings(&query_emb, query.limit as usize, query.project_id)
+            if let Ok(sem_results) = state
+                .db
+                .search_embeddings(&query_emb, query.limit as usize, query.project_id)
+                .await
             {
                 for r in sem_results.iter().filter(|r| r.score > 0.5) {
                     let Some(project_id) = r.project_id else {
@@ -2108,7 +2107,7 @@ pub(crate) async fn list_project_documents(
 ) -> Result<Json<Value>, StatusCode> {
     let (project, _role) =
         super::require_project_access_with_shares(state.as_ref(), &user, &workspace, id)?;
-    let tasks = state.db.list_project_tasks(id).map_err(internal)?;
+    let tasks = state.db.list_project_tasks(id).await.map_err(internal)?;
     let mut documents: Vec<Value> = Vec::new();
 
     for task in &tasks {
@@ -2711,7 +2710,7 @@ pub(crate) async fn export_all_project_documents(
     Query(q): Query<ExportAllQuery>,
 ) -> Result<axum::response::Response, StatusCode> {
     let project = require_project_access(state.as_ref(), &workspace, id)?;
-    let tasks = state.db.list_project_tasks(id).map_err(internal)?;
+    let tasks = state.db.list_project_tasks(id).await.map_err(internal)?;
     let format = q.format.as_deref().unwrap_or("docx");
     if format != "pdf" && format != "docx" {
         return Err(StatusCode::BAD_REQUEST);
@@ -2985,6 +2984,7 @@ pub(crate) async fn delete_project_file(
     Path((project_id, file_id)): Path<(i64, i64)>,
 ) -> Result<Json<Value>, StatusCode> {
     let _project = require_project_access(state.as_ref(), &workspace, project_id)?;
+    reject_if_legal_hold(state.as_ref(), project_id)?;
     let file = state
         .db
         .get_project_file(project_id, file_id)
@@ -3010,8 +3010,9 @@ pub(crate) async fn delete_all_project_files(
     Path(id): Path<i64>,
 ) -> Result<Json<Value>, StatusCode> {
     let _project = require_project_access(state.as_ref(), &workspace, id)?;
+    reject_if_legal_hold(state.as_ref(), id)?;
 
-    let files = state.db.list_project_files(id).map_err(internal)?;
+    let files = state.db.list_project_files(id).await.map_err(internal)?;
     for file in &files {
         if let Err(err) = state.file_storage.delete(&file.stored_path).await {
             tracing::warn!(
@@ -3645,6 +3646,23 @@ pub(crate) struct AddProjectShareBody {
 pub(crate) struct CreateShareLinkBody {
     pub label: Option<String>,
     pub expires_in_hours: Option<i64>,
+    pub password: Option<String>,
+    /// "full" (tasks and documents) or "documents".
+    pub scope: Option<String>,
+    pub document_ids: Option<Vec<i64>>,
+    pub max_uses: Option<i64>,
+}
+
+/// Form body for downloading from a password-protected link without
+/// JavaScript; other clients send the `x-share-password` header.
+#[derive(Deserialize)]
+pub(crate) struct PublicShareForm {
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ShareLinkAccessQuery {
+    pub limit: Option<i64>,
 }
 
 pub(crate) async fn list_project_shares(
@@ -3738,9 +3756,35 @@ pub(crate) async fn create_project_share_link(
         .to_string();
     let label = body.label.as_deref().unwrap_or("");
 
+    let scope = body.scope.as_deref().unwrap_or("full");
+    if !["full", "documents"].contains(&scope) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let document_ids = body.document_ids.unwrap_or_default();
+    if !document_ids.is_empty() {
+        let files = state.db.list_project_files(id).await.map_err(internal)?;
+        let known: HashSet<i64> = files.iter().map(|f| f.id).collect();
+        if document_ids.iter().any(|d| !known.contains(d)) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+    let password_hash = match body.password.as_deref().filter(|p| !p.is_empty()) {
+        Some(password) => crate::auth::hash_password(password).map_err(|e| {
+            tracing::error!("hash share link password: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        None => String::new(),
+    };
+    let policy = borg_core::db::ShareLinkPolicy {
+        password_hash,
+        scope: scope.to_string(),
+        document_ids,
+        max_uses: body.max_uses.unwrap_or(0).max(0),
+    };
+
     let link_id = state
         .db
-        .create_project_share_link(id, &token, label, &expires_at, user.id)
+        .create_project_share_link(id, &token, label, &expires_at, user.id, &policy)
         .map_err(internal)?;
 
     Ok((
@@ -3749,10 +3793,40 @@ pub(crate) async fn create_project_share_link(
             "id": link_id,
             "token": token,
             "expires_at": expires_at,
+            "scope": policy.scope,
+            "max_uses": policy.max_uses,
+            "password_protected": !policy.password_hash.is_empty(),
         })),
     ))
 }
 
+pub(crate) async fn list_share_link_access(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    axum::Extension(workspace): axum::Extension<crate::auth::WorkspaceContext>,
+    Path((id, link_id)): Path<(i64, i64)>,
+    Query(q): Query<ShareLinkAccessQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let (_project, role) =
+        super::require_project_access_with_shares(state.as_ref(), &user, &workspace, id)?;
+    super::require_min_role(&role, "owner")?;
+    let link = state
+        .db
+        .get_project_share_link(link_id)
+        .map_err(internal)?
+        .filter(|l| l.project_id == id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let limit = q.limit.unwrap_or(200).clamp(1, 1000);
+    let entries = state
+        .db
+        .list_share_link_access(link.id, limit)
+        .map_err(internal)?;
+    Ok(Json(json!({
+        "link": link,
+        "entries": entries,
+    })))
+}
+
 pub(crate) async fn revoke_project_share_link(
     State(state): State<Arc<AppState>>,
     axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
@@ -3770,65 +3844,503 @@ pub(crate) async fn revoke_project_share_link(
     Ok(Json(json!({ "revoked": revoked })))
 }
 
-fn resolve_public_share(
+/// What a public share request is trying to do; decides the counter that is
+/// charged and the scope check applied.
+#[derive(Clone, Copy)]
+enum PublicShareAction {
+    View,
+    Tasks,
+    Documents,
+    Download(i64),
+}
+
+impl PublicShareAction {
+    fn name(self) -> &'static str {
+        match self {
+            Self::View => "view",
+            Self::Tasks => "tasks",
+            Self::Documents => "documents",
+            Self::Download(_) => "download",
+        }
+    }
+
+    fn file_id(self) -> Option<i64> {
+        match self {
+            Self::Download(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// The client address for the access log. Forwarding headers are only
+/// believed when the connection comes from a configured `TRUSTED_PROXIES`
+/// peer; anyone else could put any address in them.
+fn share_client_ip(
     state: &AppState,
-) -> impl Fn(&str) -> Result<(borg_core::db::ProjectShareLinkRow, ProjectRow), StatusCode> + '_ {
-    move |token: &str| {
-        let link = state
-            .db
-            .get_project_share_link_by_token(token)
-            .map_err(internal)?
-            .ok_or(StatusCode::NOT_FOUND)?;
-        if link.revoked {
-            return Err(StatusCode::NOT_FOUND);
+    peer: std::net::SocketAddr,
+    headers: &axum::http::HeaderMap,
+) -> String {
+    let peer_ip = peer.ip().to_string();
+    if !state.config.trusted_proxies.contains(&peer_ip) {
+        return peer_ip;
+    }
+    ["cf-connecting-ip", "x-forwarded-for", "x-real-ip"]
+        .iter()
+        .find_map(|h| headers.get(*h).and_then(|v| v.to_str().ok()))
+        .and_then(|v| v.split(',').next())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or(peer_ip)
+}
+
+/// Who is asking, for the share access log and password check.
+struct PublicShareRequest<'a> {
+    peer: std::net::SocketAddr,
+    headers: &'a axum::http::HeaderMap,
+    password: Option<&'a str>,
+}
+
+impl PublicShareRequest<'_> {
+    fn log(&self, state: &AppState, link_id: i64, action: PublicShareAction, status: StatusCode) {
+        let user_agent = self
+            .headers
+            .get("user-agent")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        if let Err(e) = state.db.log_share_link_access(
+            link_id,
+            action.name(),
+            action.file_id(),
+            i64::from(status.as_u16()),
+            &share_client_ip(state, self.peer, self.headers),
+            user_agent,
+        ) {
+            tracing::warn!("share link access log failed: {e}");
         }
-        let expires = chrono::NaiveDateTime::parse_from_str(&link.expires_at, "%Y-%m-%d %H:%M:%S")
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        if Utc::now().naive_utc() > expires {
-            return Err(StatusCode::GONE);
+    }
+}
+
+/// Resolve a public share token and enforce expiry, password, scope and the
+/// use cap. Every attempt on an existing link is written to its access log.
+/// Nothing is charged against the cap here; see `charge_public_share`.
+fn authorize_public_share(
+    state: &AppState,
+    token: &str,
+    req: &PublicShareRequest<'_>,
+    action: PublicShareAction,
+) -> Result<(borg_core::db::ProjectShareLinkRow, ProjectRow), StatusCode> {
+    let link = state
+        .db
+        .get_project_share_link_by_token(token)
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let result = check_public_share(state, &link, req, action);
+    let status = match &result {
+        Ok(_) => StatusCode::OK,
+        Err(code) => *code,
+    };
+    req.log(state, link.id, action, status);
+    result.map(|project| (link, project))
+}
+
+fn check_public_share(
+    state: &AppState,
+    link: &borg_core::db::ProjectShareLinkRow,
+    req: &PublicShareRequest<'_>,
+    action: PublicShareAction,
+) -> Result<ProjectRow, StatusCode> {
+    if link.revoked {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let expires = chrono::NaiveDateTime::parse_from_str(&link.expires_at, "%Y-%m-%d %H:%M:%S")
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if Utc::now().naive_utc() > expires {
+        return Err(StatusCode::GONE);
+    }
+    if link.password_protected {
+        let supplied = req
+            .headers
+            .get("x-share-password")
+            .and_then(|v| v.to_str().ok())
+            .or(req.password)
+            .unwrap_or("");
+        if supplied.is_empty() || !crate::auth::verify_password(supplied, &link.password_hash) {
+            return Err(StatusCode::UNAUTHORIZED);
         }
-        let project = state
-            .db
-            .get_project(link.project_id)
-            .map_err(internal)?
-            .ok_or(StatusCode::NOT_FOUND)?;
-        Ok((link, project))
     }
+    match action {
+        PublicShareAction::Tasks if !link.allows_tasks() => return Err(StatusCode::FORBIDDEN),
+        PublicShareAction::Download(file_id) if !link.allows_document(file_id) => {
+            return Err(StatusCode::NOT_FOUND)
+        },
+        _ => {},
+    }
+    if link.max_uses > 0 && link.view_count + link.download_count >= link.max_uses {
+        return Err(StatusCode::GONE);
+    }
+    state
+        .db
+        .get_project(link.project_id)
+        .map_err(internal)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Count one use against the link once a view or download has succeeded.
+/// The task and document listings a page view loads are not charged.
+fn charge_public_share(
+    state: &AppState,
+    link: &borg_core::db::ProjectShareLinkRow,
+    req: &PublicShareRequest<'_>,
+    action: PublicShareAction,
+) -> Result<(), StatusCode> {
+    let download = matches!(action, PublicShareAction::Download(_));
+    if state
+        .db
+        .consume_share_link_use(link.id, download)
+        .map_err(internal)?
+    {
+        return Ok(());
+    }
+    // Another request used up the cap between the check and now.
+    req.log(state, link.id, action, StatusCode::GONE);
+    Err(StatusCode::GONE)
 }
 
 pub(crate) async fn get_public_project(
     State(state): State<Arc<AppState>>,
     Path(token): Path<String>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    headers: axum::http::HeaderMap,
 ) -> Result<Json<Value>, StatusCode> {
-    let (_link, project) = resolve_public_share(state.as_ref())(&token)?;
-    let counts = state.db.project_task_status_counts(project.id).ok();
-    Ok(Json(json!(ProjectJson::from_row(project, counts))))
+    let req = PublicShareRequest {
+        peer,
+        headers: &headers,
+        password: None,
+    };
+    let (link, project) =
+        authorize_public_share(state.as_ref(), &token, &req, PublicShareAction::View)?;
+    charge_public_share(state.as_ref(), &link, &req, PublicShareAction::View)?;
+    let counts = state.db.project_task_status_counts(project.id).await.ok();
+    let mut body = json!(ProjectJson::from_row(project, counts));
+    body["share"] = json!({
+        "label": link.label,
+        "scope": link.scope,
+        "expires_at": link.expires_at,
+    });
+    Ok(Json(body))
 }
 
 pub(crate) async fn get_public_project_tasks(
     State(state): State<Arc<AppState>>,
     Path(token): Path<String>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    headers: axum::http::HeaderMap,
 ) -> Result<Json<Value>, StatusCode> {
-    let (_link, project) = resolve_public_share(state.as_ref())(&token)?;
-    let tasks = state.db.list_project_tasks(project.id).map_err(internal)?;
+    let req = PublicShareRequest {
+        peer,
+        headers: &headers,
+        password: None,
+    };
+    let (_link, project) =
+        authorize_public_share(state.as_ref(), &token, &req, PublicShareAction::Tasks)?;
+    let tasks = state
+        .db
+        .list_project_tasks(project.id)
+        .await
+        .map_err(internal)?;
     Ok(Json(json!(tasks)))
 }
 
 pub(crate) async fn get_public_project_documents(
     State(state): State<Arc<AppState>>,
     Path(token): Path<String>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    headers: axum::http::HeaderMap,
 ) -> Result<Json<Value>, StatusCode> {
-    let (_link, project) = resolve_public_share(state.as_ref())(&token)?;
-    let files = state.db.list_project_files(project.id).map_err(internal)?;
-    let public_files: Vec<_> = files.into_iter().filter(|f| !f.privileged).collect();
+    let req = PublicShareRequest {
+        peer,
+        headers: &headers,
+        password: None,
+    };
+    let (link, project) =
+        authorize_public_share(state.as_ref(), &token, &req, PublicShareAction::Documents)?;
+    let files = state
+        .db
+        .list_project_files(project.id)
+        .await
+        .map_err(internal)?;
+    let public_files: Vec<_> = files
+        .into_iter()
+        .filter(|f| !f.privileged && link.allows_document(f.id))
+        .collect();
     Ok(Json(json!(public_files)))
 }
 
+pub(crate) async fn download_public_project_document(
+    State(state): State<Arc<AppState>>,
+    Path((token, file_id)): Path<(String, i64)>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    headers: axum::http::HeaderMap,
+) -> Result<axum::response::Response, StatusCode> {
+    let req = PublicShareRequest {
+        peer,
+        headers: &headers,
+        password: None,
+    };
+    serve_public_document(&state, &token, file_id, &req).await
+}
+
+/// Download with the share password in a form body instead of a header.
+pub(crate) async fn post_download_public_project_document(
+    State(state): State<Arc<AppState>>,
+    Path((token, file_id)): Path<(String, i64)>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    headers: axum::http::HeaderMap,
+    axum::Form(form): axum::Form<PublicShareForm>,
+) -> Result<axum::response::Response, StatusCode> {
+    let req = PublicShareRequest {
+        peer,
+        headers: &headers,
+        password: form.password.as_deref(),
+    };
+    serve_public_document(&state, &token, file_id, &req).await
+}
+
+async fn serve_public_document(
+    state: &AppState,
+    token: &str,
+    file_id: i64,
+    req: &PublicShareRequest<'_>,
+) -> Result<axum::response::Response, StatusCode> {
+    let action = PublicShareAction::Download(file_id);
+    let (link, project) = authorize_public_share(state, token, req, action)?;
+    let row = state
+        .db
+        .get_project_file(project.id, file_id)
+        .map_err(internal)?
+        .filter(|f| !f.privileged)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let bytes = state
+        .file_storage
+        .read_all(&row.stored_path)
+        .await
+        .map_err(internal)?;
+
+    let is_pdf = row.mime_type.starts_with("application/pdf")
+        || row.file_name.to_lowercase().ends_with(".pdf");
+    let label = link.label.trim();
+    let (bytes, content_type) = if is_pdf && !label.is_empty() {
+        let stamp = format!("Shared via {} - {label}", project.name);
+        match watermark_pdf(&bytes, &stamp).await {
+            Ok(stamped) => (stamped, "application/pdf"),
+            Err(e) => {
+                tracing::warn!("share link watermark failed for file {file_id}: {e}");
+                return axum::response::Response::builder()
+                    .status(StatusCode::NOT_IMPLEMENTED)
+                    .header("content-type", "text/plain")
+                    .body(axum::body::Body::from(
+                        "this link requires watermarked PDFs but watermarking is unavailable (install qpdf)",
+                    ))
+                    .map_err(internal);
+            },
+        }
+    } else {
+        (bytes.to_vec(), "application/octet-stream")
+    };
+
+    charge_public_share(state, &link, req, action)?;
+    let safe_name = row.file_name.replace('"', "_");
+    axum::response::Response::builder()
+        .header("content-type", content_type)
+        .header(
+            "content-disposition",
+            format!("attachment; filename=\"{safe_name}\""),
+        )
+        .body(axum::body::Body::from(bytes))
+        .map_err(internal)
+}
+
+/// Overlay a diagonal text stamp on every page of `pdf` using `qpdf`.
+async fn watermark_pdf(pdf: &[u8], text: &str) -> anyhow::Result<Vec<u8>> {
+    let tmp_dir = tempfile::tempdir()?;
+    let input = tmp_dir.path().join("input.pdf");
+    let stamp = tmp_dir.path().join("stamp.pdf");
+    let output = tmp_dir.path().join("output.pdf");
+    tokio::fs::write(&input, pdf).await?;
+    tokio::fs::write(&stamp, watermark_stamp_pdf(text)).await?;
+
+    let out = tokio::time::timeout(
+        std::time::Duration::from_secs(60),
+        tokio::process::Command::new("qpdf")
+            .arg(&input)
+            .arg("--overlay")
+            .arg(&stamp)
+            .arg("--repeat=1")
+            .arg("--")
+            .arg(&output)
+            .stderr(std::process::Stdio::piped())
+            .output(),
+    )
+    .await??;
+    // qpdf exits 3 for warnings but still writes the output.
+    if !matches!(out.status.code(), Some(0) | Some(3)) {
+        anyhow::bail!("qpdf failed: {}", String::from_utf8_lossy(&out.stderr));
+    }
+    Ok(tokio::fs::read(&output).await?)
+}
+
+/// Build a single-page, letter-sized PDF containing a translucent diagonal
+/// `text` stamp and a small footer, for use as a `qpdf --overlay` page.
+fn watermark_stamp_pdf(text: &str) -> Vec<u8> {
+    let escaped: String = text
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() {
+                c
+            } else {
+                '?'
+            }
+        })
+        .collect::<String>()
+        .replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)");
+    let content = format!(
+        "q /GS1 gs 0.5 g\n\
+         BT /F1 26 Tf 0.7071 0.7071 -0.7071 0.7071 140 220 Tm ({escaped}) Tj ET\n\
+         BT /F1 8 Tf 36 20 Td ({escaped}) Tj ET\n\
+         Q\n"
+    );
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+         /Resources << /Font << /F1 5 0 R >> /ExtGState << /GS1 6 0 R >> >> \
+         /Contents 4 0 R >>"
+            .to_string(),
+        format!(
+            "<< /Length {} >>\nstream\n{content}endstream",
+            content.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        "<< /Type /ExtGState /ca 0.2 /CA 0.2 >>".to_string(),
+    ];
+
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{body}\nendobj\n", i + 1).as_bytes());
+    }
+    let xref_at = out.len();
+    out.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_at}\n%%EOF\n",
+            objects.len() + 1
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+// ── Legal holds ──────────────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+pub(crate) struct CreateLegalHoldBody {
+    #[serde(default)]
+    reason: String,
+}
+
+pub(crate) async fn list_legal_holds(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    axum::Extension(workspace): axum::Extension<crate::auth::WorkspaceContext>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, StatusCode> {
+    let (_project, _role) =
+        super::require_project_access_with_shares(state.as_ref(), &user, &workspace, id)?;
+    let holds = state.db.list_legal_holds(id).map_err(internal)?;
+    Ok(Json(json!(holds)))
+}
+
+pub(crate) async fn create_legal_hold(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    axum::Extension(workspace): axum::Extension<crate::auth::WorkspaceContext>,
+    Path(id): Path<i64>,
+    Json(body): Json<CreateLegalHoldBody>,
+) -> Result<(StatusCode, Json<Value>), StatusCode> {
+    let (_project, role) =
+        super::require_project_access_with_shares(state.as_ref(), &user, &workspace, id)?;
+    super::require_min_role(&role, "owner")?;
+    let reason = body.reason.trim();
+    let hold_id = state
+        .db
+        .create_legal_hold(id, reason, Some(user.id))
+        .map_err(internal)?;
+    let _ = state.db.log_event_full(
+        None,
+        None,
+        Some(id),
+        &user.username,
+        "legal_hold.created",
+        &json!({ "hold_id": hold_id, "reason": reason }),
+    );
+    Ok((StatusCode::CREATED, Json(json!({ "id": hold_id }))))
+}
+
+pub(crate) async fn release_legal_hold(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+    axum::Extension(workspace): axum::Extension<crate::auth::WorkspaceContext>,
+    Path((id, hold_id)): Path<(i64, i64)>,
+) -> Result<Json<Value>, StatusCode> {
+    let (_project, role) =
+        super::require_project_access_with_shares(state.as_ref(), &user, &workspace, id)?;
+    super::require_min_role(&role, "owner")?;
+    let released = state
+        .db
+        .release_legal_hold(id, hold_id, Some(user.id))
+        .map_err(internal)?;
+    if !released {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let _ = state.db.log_event_full(
+        None,
+        None,
+        Some(id),
+        &user.username,
+        "legal_hold.released",
+        &json!({ "hold_id": hold_id }),
+    );
+    Ok(Json(json!({ "ok": true })))
+}
+
+/// Held projects keep their files and chat history until every hold is released.
+fn reject_if_legal_hold(state: &AppState, project_id: i64) -> Result<(), StatusCode> {
+    if state
+        .db
+        .project_has_legal_hold(project_id)
+        .map_err(internal)?
+    {
+        return Err(StatusCode::CONFLICT);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::{fs, path::Path, process::Command};
 
-    use super::{is_safe_repo_relative_path, list_worktree_files, read_worktree_file};
+    use super::{
+        is_safe_repo_relative_path, list_worktree_files, read_worktree_file, watermark_stamp_pdf,
+    };
 
     fn run_git(repo_path: &Path, args: &[&str]) {
         let output = Command::new("git")
@@ -3894,4 +4406,24 @@ mod tests {
         assert!(!is_safe_repo_relative_path("/tmp/secret.txt"));
         assert!(is_safe_repo_relative_path("nested/file.md"));
     }
+
+    #[test]
+    fn watermark_stamp_is_well_formed_pdf() {
+        let pdf = watermark_stamp_pdf("Client (draft) \\ review – ünicode");
+        let text = String::from_utf8(pdf).expect("stamp is ascii");
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("(Client \\(draft\\) \\\\ review ? ?nicode)"));
+
+        let startxref: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|tail| tail.lines().next())
+            .and_then(|n| n.parse().ok())
+            .expect("startxref offset");
+        assert!(text[startxref..].starts_with("xref\n0 7\n"));
+
+        let first_obj = text.find("1 0 obj").expect("object 1");
+        assert!(text.contains(&format!("{first_obj:010} 00000 n ")));
+    }
 }
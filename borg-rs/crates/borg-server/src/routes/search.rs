@@ -111,7 +111,7 @@ pub(crate) async fn borgsearch_reindex(
                 .map(|p| p.mode)
                 .unwrap_or_default();
             let embed = embed_reg.client_for_mode(&project_mode);
-            let files = match db.list_project_files(*pid) {
+            let files = match db.list_project_files(*pid).await {
                 Ok(f) => f,
                 Err(e) => {
                     tracing::warn!("reindex: failed to list files for project {pid}: {e}");
@@ -311,10 +311,10 @@ pub(crate) async fn agent_search(
             None => state.embed_registry.default_client(),
         };
         if let Ok(query_emb) = fallback_ec.embed_query(&query.q).await {
-            if let Ok(sem) =
-                state
-                    .db
-                    .search_embeddings(&query_emb, limit as usize, query.project_id)
+            if let Ok(sem) = state
+                .db
+                .search_embeddings(&query_emb, limit as usize, query.project_id)
+                .await
             {
                 for r in sem.iter().filter(|r| r.score > 0.5) {
                     let already = results.iter().any(|(p, _, _, _)| *p == r.file_path);
@@ -512,6 +512,7 @@ pub(crate) async fn agent_coverage(
             if let Ok(sem) = state
                 .db
                 .search_embeddings(&emb, 500, Some(query.project_id))
+                .await
             {
                 for r in sem.iter().filter(|r| r.score > 0.4) {
                     if let Some(f) = all_files.iter().find(|f| f.source_path == r.file_path) {
@@ -60,6 +60,8 @@ pub(crate) struct CreateTaskBody {
     pub requires_exhaustive_corpus_review: Option<bool>,
     pub notify_chat: Option<String>,
     pub chat_thread: Option<String>,
+    /// Stack the task on this task's unmerged branch.
+    pub base_task_id: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -92,6 +94,7 @@ pub(crate) async fn list_tasks(
     let tasks = state
         .db
         .list_all_tasks_in_workspace(workspace.id, q.repo.as_deref())
+        .await
         .map_err(internal)?;
     Ok(Json(json!(tasks)))
 }
@@ -169,6 +172,8 @@ pub(crate) async fn get_task_diagnostics(
     let outputs = state.db.get_task_outputs(id).map_err(internal)?;
     let queue_entries = state.db.get_queue_entries_for_task(id).map_err(internal)?;
     let events = state.db.list_task_events(id, limit).map_err(internal)?;
+    let resource_usage = state.db.get_task_resource_usage(id).map_err(internal)?;
+    let test_failures = state.db.get_task_test_failures(id).map_err(internal)?;
 
     let mut same_failure_streak = 0u32;
     if outputs.len() >= 3 {
@@ -188,6 +193,28 @@ pub(crate) async fn get_task_diagnostics(
         }
     }
 
+    // Failing tests per phase, from the latest attempt that recorded any.
+    let mut failing_tests: std::collections::BTreeMap<&str, (i64, Vec<&str>)> =
+        std::collections::BTreeMap::new();
+    for f in &test_failures {
+        let entry = failing_tests.entry(f.phase.as_str()).or_default();
+        if f.attempt > entry.0 {
+            *entry = (f.attempt, Vec::new());
+        }
+        if f.attempt == entry.0 && !entry.1.contains(&f.failure.name.as_str()) {
+            entry.1.push(f.failure.name.as_str());
+        }
+    }
+    let failing_tests: serde_json::Map<String, Value> = failing_tests
+        .into_iter()
+        .map(|(phase, (attempt, names))| {
+            (
+                phase.to_string(),
+                json!({ "attempt": attempt, "count": names.len(), "tests": names }),
+            )
+        })
+        .collect();
+
     let recent_outputs: Vec<TaskOutputJson> = outputs
         .into_iter()
         .rev()
@@ -208,9 +235,15 @@ pub(crate) async fn get_task_diagnostics(
             "stuck_suspected": same_failure_streak >= 3,
             "same_failure_streak": same_failure_streak,
             "has_queue_entry": !queue_entries.is_empty(),
+            "cpu_ms": resource_usage.iter().map(|r| r.usage.cpu_ms).sum::<i64>(),
+            "peak_memory_bytes": resource_usage.iter().map(|r| r.usage.peak_memory_bytes).max().unwrap_or(0),
+            "disk_written_bytes": resource_usage.iter().map(|r| r.usage.disk_written_bytes).sum::<i64>(),
+            "failing_tests": failing_tests,
         },
         "queue_entries": queue_entries,
         "recent_outputs": recent_outputs,
+        "resource_usage": resource_usage,
+        "test_failures": test_failures,
         "recent_events": events,
     })))
 }
@@ -260,6 +293,7 @@ pub(crate) async fn create_task(
             })
             .unwrap_or_else(|| "sweborg".into())
     });
+    let base_task_id = body.base_task_id.unwrap_or(0);
     let task = Task {
         id: 0,
         title: body.title,
@@ -288,7 +322,11 @@ pub(crate) async fn create_task(
         revision_count: 0,
         chat_thread: body.chat_thread.unwrap_or_default(),
     };
-    let id = state.db.insert_task(&task).map_err(internal)?;
+    check_task_base(state.as_ref(), &workspace, &task, base_task_id)?;
+    let id = state
+        .db
+        .insert_stacked_task(&task, base_task_id)
+        .map_err(internal)?;
     let pid = (project_id > 0).then_some(project_id);
     let _ = state.db.log_event_full(
         Some(id),
@@ -330,6 +368,74 @@ pub(crate) async fn patch_task(
     Ok(StatusCode::OK)
 }
 
+fn repo_root(path: &str) -> &str {
+    path.split_once("/.worktrees/")
+        .map_or(path, |(root, _)| root)
+}
+
+/// A base task must be in the same repo, not yet merged, and must not
+/// (transitively) be stacked on `task` itself.
+fn check_task_base(
+    state: &AppState,
+    workspace: &crate::auth::WorkspaceContext,
+    task: &Task,
+    base_task_id: i64,
+) -> Result<(), StatusCode> {
+    if base_task_id == 0 {
+        return Ok(());
+    }
+    let base = require_task_access(state, workspace, base_task_id)?;
+    if repo_root(&base.repo_path) != repo_root(&task.repo_path)
+        || matches!(base.status.as_str(), "merged" | "failed" | "purged")
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut next = base_task_id;
+    while next != 0 {
+        if next == task.id {
+            return Err(StatusCode::CONFLICT);
+        }
+        next = state.db.get_task_base(next).map_err(internal)?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn get_task_base(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(workspace): axum::Extension<crate::auth::WorkspaceContext>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, StatusCode> {
+    require_task_access(state.as_ref(), &workspace, id)?;
+    let base_task_id = state.db.get_task_base(id).map_err(internal)?;
+    Ok(Json(json!({ "task_id": id, "base_task_id": base_task_id })))
+}
+
+/// Stack a task on another task's branch. Body: `{"base_task_id": N}`;
+/// 0 unstacks it. Only possible before the task has created its branch.
+pub(crate) async fn put_task_base(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(workspace): axum::Extension<crate::auth::WorkspaceContext>,
+    Path(id): Path<i64>,
+    Json(body): Json<Value>,
+) -> Result<Json<Value>, StatusCode> {
+    let task = require_task_access(state.as_ref(), &workspace, id)?;
+    let base_task_id = body["base_task_id"]
+        .as_i64()
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    if task.status != "backlog" {
+        return Err(StatusCode::CONFLICT);
+    }
+    check_task_base(state.as_ref(), &workspace, &task, base_task_id)?;
+    state.db.set_task_base(id, base_task_id).map_err(internal)?;
+    let _ = state.db.log_event(
+        Some(id),
+        None,
+        "task.base_updated",
+        &json!({ "base_task_id": base_task_id }),
+    );
+    Ok(Json(json!({ "task_id": id, "base_task_id": base_task_id })))
+}
+
 #[derive(Deserialize)]
 pub(crate) struct ReviewAction {
     #[serde(default)]
@@ -479,7 +585,7 @@ pub(crate) async fn get_revision_history(
     Path(id): Path<i64>,
 ) -> Result<Json<Value>, StatusCode> {
     let task = require_task_access(state.as_ref(), &workspace, id)?;
-    let messages = state.db.get_task_messages(id).map_err(internal)?;
+    let messages = state.db.get_task_messages(id).await.map_err(internal)?;
     let outputs = state.db.get_task_outputs(id).map_err(internal)?;
 
     let mut rounds: Vec<Value> = Vec::new();
@@ -665,6 +771,7 @@ pub(crate) async fn retry_all_failed(
     let tasks = state
         .db
         .list_all_tasks_in_workspace(workspace.id, None)
+        .await
         .map_err(internal)?;
     let mut count = 0;
     for task in &tasks {
@@ -738,7 +845,7 @@ pub(crate) async fn get_task_messages(
     {
         None => Err(StatusCode::NOT_FOUND),
         Some(_) => {
-            let messages = state.db.get_task_messages(id).map_err(internal)?;
+            let messages = state.db.get_task_messages(id).await.map_err(internal)?;
             let messages_json: Vec<TaskMessageJson> =
                 messages.into_iter().map(TaskMessageJson::from).collect();
             Ok(Json(json!({ "messages": messages_json })))
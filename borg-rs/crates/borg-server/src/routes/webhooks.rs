@@ -0,0 +1,94 @@
+//! Inbound forge webhooks (`/api/webhooks/*`).
+//!
+//! These bypass user auth and verify the delivery against
+//! `FORGE_WEBHOOK_SECRET` instead; they 404 when no secret is configured.
+//! Accepted events are queued for the pipeline's next tick.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+};
+use borg_core::{forge::ForgeKind, webhook};
+
+use crate::AppState;
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+fn verify(state: &AppState, kind: ForgeKind, headers: &HeaderMap, body: &[u8]) -> bool {
+    let secret = &state.config.webhook_secret;
+    match kind {
+        ForgeKind::GitHub => {
+            webhook::verify_hmac_sha256(secret, header(headers, "x-hub-signature-256"), body)
+        },
+        ForgeKind::GitLab => webhook::verify_token(secret, header(headers, "x-gitlab-token")),
+        ForgeKind::Gitea => ["x-gitea-signature", "x-forgejo-signature"]
+            .iter()
+            .map(|name| header(headers, name))
+            .any(|sig| !sig.is_empty() && webhook::verify_hmac_sha256(secret, sig, body)),
+    }
+}
+
+fn receive(state: &AppState, kind: ForgeKind, headers: &HeaderMap, body: &[u8]) -> StatusCode {
+    if state.config.webhook_secret.is_empty() {
+        return StatusCode::NOT_FOUND;
+    }
+    if !verify(state, kind, headers, body) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    let event = match kind {
+        ForgeKind::GitHub => header(headers, "x-github-event"),
+        ForgeKind::GitLab => header(headers, "x-gitlab-event"),
+        ForgeKind::Gitea => {
+            let gitea = header(headers, "x-gitea-event");
+            if gitea.is_empty() {
+                header(headers, "x-forgejo-event")
+            } else {
+                gitea
+            }
+        },
+    };
+    let Ok(payload) = serde_json::from_slice(body) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    match webhook::parse(kind, event, &payload) {
+        Some(hook) => {
+            tracing::debug!(?hook.event, repo = %hook.repo_slug, "webhook queued");
+            state.webhooks.push(hook);
+            StatusCode::ACCEPTED
+        },
+        // Pings and events borg does not act on.
+        None => StatusCode::NO_CONTENT,
+    }
+}
+
+pub(crate) async fn github_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    receive(&state, ForgeKind::GitHub, &headers, &body)
+}
+
+pub(crate) async fn gitlab_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    receive(&state, ForgeKind::GitLab, &headers, &body)
+}
+
+pub(crate) async fn gitea_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    receive(&state, ForgeKind::Gitea, &headers, &body)
+}
@@ -0,0 +1,128 @@
+//! Remote worker protocol (`/api/worker/*`) and the admin worker listing.
+//!
+//! Worker endpoints bypass user auth and check `BORG_WORKER_TOKEN` instead;
+//! they 404 when no token is configured.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use borg_core::{
+    webhook,
+    worker::{WorkerJob, WorkerJobResult, WorkerRegistration},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::AppState;
+
+/// How long a poll waits for a job before returning `null`.
+const POLL_WAIT: Duration = Duration::from_secs(25);
+
+fn require_worker_token(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    if state.config.worker_token.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    match crate::auth::extract_bearer(headers) {
+        Some(token) if webhook::verify_token(&state.config.worker_token, token) => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+pub(crate) async fn worker_register(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<WorkerRegistration>,
+) -> Result<Json<Value>, StatusCode> {
+    require_worker_token(&state, &headers)?;
+    if body.name.trim().is_empty() || body.capacity == 0 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let worker_id = state.workers.register(body);
+    Ok(Json(json!({
+        "worker_id": worker_id,
+        "poll_wait_s": POLL_WAIT.as_secs(),
+    })))
+}
+
+/// Long-poll for the next job; `null` when none arrived in time. 404 tells
+/// the worker to register again (e.g. after a server restart).
+pub(crate) async fn worker_poll(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(worker_id): Path<String>,
+) -> Result<Json<Option<WorkerJob>>, StatusCode> {
+    require_worker_token(&state, &headers)?;
+    let job = state
+        .workers
+        .next_job(&worker_id, POLL_WAIT)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok(Json(job))
+}
+
+/// Keep a busy worker from being reaped while its agents print nothing.
+/// 404 tells the worker to register again.
+pub(crate) async fn worker_heartbeat(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(worker_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    require_worker_token(&state, &headers)?;
+    if state.workers.heartbeat(&worker_id) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WorkerStreamBody {
+    lines: Vec<String>,
+}
+
+pub(crate) async fn worker_stream(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((worker_id, job_id)): Path<(String, String)>,
+    Json(body): Json<WorkerStreamBody>,
+) -> Result<StatusCode, StatusCode> {
+    require_worker_token(&state, &headers)?;
+    state.workers.heartbeat(&worker_id);
+    if state.workers.push_stream(&worker_id, &job_id, body.lines) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::GONE)
+    }
+}
+
+pub(crate) async fn worker_complete(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((worker_id, job_id)): Path<(String, String)>,
+    Json(body): Json<WorkerJobResult>,
+) -> Result<StatusCode, StatusCode> {
+    require_worker_token(&state, &headers)?;
+    state.workers.heartbeat(&worker_id);
+    if state.workers.complete(&worker_id, &job_id, body) {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::GONE)
+    }
+}
+
+pub(crate) async fn list_workers(
+    State(state): State<Arc<AppState>>,
+    axum::Extension(user): axum::Extension<crate::auth::AuthUser>,
+) -> Result<Json<Value>, StatusCode> {
+    if !user.is_admin {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(Json(json!({
+        "enabled": !state.config.worker_token.is_empty(),
+        "workers": state.workers.list(),
+    })))
+}
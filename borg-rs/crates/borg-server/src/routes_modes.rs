@@ -10,7 +10,7 @@ use serde_json::{json, Value};
 
 use crate::{routes::internal, AppState};
 
-fn get_custom_modes(db: &borg_core::db::Db) -> Vec<PipelineMode> {
+pub(crate) fn get_custom_modes(db: &borg_core::db::Db) -> Vec<PipelineMode> {
     let raw = match db.get_config("custom_modes") {
         Ok(Some(v)) => v,
         _ => return Vec::new(),
@@ -3,7 +3,9 @@ use std::{
     sync::{atomic::AtomicU64, Arc},
 };
 
-use borg_core::{config::Config, db::Db, sidecar::Sidecar, telegram::Telegram};
+use borg_core::{
+    config::Config, db::Db, registry::PluginRegistry, sidecar::Sidecar, telegram::Telegram,
+};
 use tokio::{
     sync::{broadcast, Mutex as TokioMutex},
     task::JoinHandle,
@@ -43,6 +45,7 @@ pub struct UserBotManager {
     chat_stream_manager: Arc<borg_core::stream::ChatStreamManager>,
     ai_request_count: Arc<AtomicU64>,
     sidecar_slot: Arc<TokioMutex<Option<Arc<Sidecar>>>>,
+    registry: Arc<PluginRegistry>,
 }
 
 fn hash_token(token: &str) -> u64 {
@@ -62,6 +65,7 @@ impl UserBotManager {
         chat_stream_manager: Arc<borg_core::stream::ChatStreamManager>,
         ai_request_count: Arc<AtomicU64>,
         sidecar_slot: Arc<TokioMutex<Option<Arc<Sidecar>>>>,
+        registry: Arc<PluginRegistry>,
     ) -> Self {
         Self {
             bots: TokioMutex::new(HashMap::new()),
@@ -75,6 +79,7 @@ impl UserBotManager {
             chat_stream_manager,
             ai_request_count,
             sidecar_slot,
+            registry,
         }
     }
 
@@ -251,6 +256,7 @@ impl UserBotManager {
         let chat_event_tx = self.chat_event_tx.clone();
         let chat_stream_manager = Arc::clone(&self.chat_stream_manager);
         let ai_request_count = Arc::clone(&self.ai_request_count);
+        let registry = Arc::clone(&self.registry);
         let sessions: Arc<TokioMutex<HashMap<String, String>>> =
             Arc::new(TokioMutex::new(HashMap::new()));
 
@@ -266,6 +272,7 @@ impl UserBotManager {
                 chat_stream_manager,
                 ai_request_count,
                 sessions,
+                registry,
             )
             .await;
         });
@@ -295,6 +302,7 @@ async fn poll_loop(
     chat_stream_manager: Arc<borg_core::stream::ChatStreamManager>,
     ai_request_count: Arc<AtomicU64>,
     sessions: Arc<TokioMutex<HashMap<String, String>>>,
+    registry: Arc<PluginRegistry>,
 ) {
     let repos = config.watched_repos.clone();
     loop {
@@ -326,6 +334,7 @@ async fn poll_loop(
                         let chat_tx2 = chat_event_tx.clone();
                         let csm2 = Arc::clone(&chat_stream_manager);
                         let ai_count2 = Arc::clone(&ai_request_count);
+                        let registry2 = Arc::clone(&registry);
                         let sender_name = msg.sender_name.clone();
                         let chat_id = msg.chat_id;
                         let message_id = msg.message_id;
@@ -395,6 +404,7 @@ async fn poll_loop(
                                 &ai_count2,
                                 None,
                                 None,
+                                Some(&registry2),
                             )
                             .await
                             {
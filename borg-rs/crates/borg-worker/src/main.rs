@@ -0,0 +1,447 @@
+//! `borg-worker`: runs pipeline phases for a remote borg-server.
+//!
+//! Registers with `BORG_SERVER_URL` using `BORG_WORKER_TOKEN`, then keeps
+//! `WORKER_CAPACITY` long-poll loops open. Each job is run on the local
+//! backend it names; output lines are streamed back in batches and the
+//! `PhaseOutput` is posted when the phase ends.
+//!
+//! The worker must see task repos and worktrees at the same paths as the
+//! server (shared storage or an identical checkout layout). Jobs arrive
+//! without the server's credentials; the worker supplies its own Claude,
+//! GitHub and borg API tokens from its environment.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use borg_agent::{
+    claude::ClaudeBackend, codex::CodexBackend, container::ContainerBackend, gemini::GeminiBackend,
+    ollama::OllamaBackend, FailoverPolicy, ReliableBackend,
+};
+use borg_core::{
+    agent::AgentBackend,
+    repo_image::RepoImages,
+    sandbox::{Sandbox, SandboxMode},
+    worker::{WorkerJob, WorkerJobResult, WorkerRegistration, WORKER_STALE_AFTER},
+};
+use serde_json::Value;
+use tokio::sync::{mpsc, RwLock};
+use tracing::{error, info, warn};
+
+const STREAM_BATCH_LINES: usize = 200;
+const STREAM_FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Keeps the server from reaping a worker whose agents run quietly.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(WORKER_STALE_AFTER.as_secs() / 3);
+
+struct WorkerConfig {
+    server_url: String,
+    token: String,
+    name: String,
+    capacity: u32,
+    labels: Vec<String>,
+    sandbox_backend: String,
+    container_image: String,
+    container_memory_mb: u64,
+    container_cpus: f64,
+    agent_timeout_s: u64,
+    oauth_token: String,
+    github_token: String,
+    borg_api_token: String,
+    backend_fallbacks: Vec<String>,
+    local_only_fallback_modes: Vec<String>,
+}
+
+fn env_or(key: &str, default: &str) -> String {
+    std::env::var(key)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| default.to_string())
+}
+
+fn csv(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl WorkerConfig {
+    fn from_env() -> Result<Self> {
+        let server_url = env_or("BORG_SERVER_URL", "");
+        let token = env_or("BORG_WORKER_TOKEN", "");
+        if server_url.is_empty() || token.is_empty() {
+            bail!("BORG_SERVER_URL and BORG_WORKER_TOKEN are required");
+        }
+        let hostname = std::fs::read_to_string("/etc/hostname")
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|_| "worker".to_string());
+        Ok(Self {
+            server_url: server_url.trim_end_matches('/').to_string(),
+            token,
+            name: env_or("WORKER_NAME", &hostname),
+            capacity: env_or("WORKER_CAPACITY", "2").parse().unwrap_or(2),
+            labels: env_or("WORKER_LABELS", "")
+                .split(',')
+                .map(str::trim)
+                .filter(|l| l.contains('='))
+                .map(str::to_string)
+                .collect(),
+            sandbox_backend: env_or("SANDBOX_BACKEND", "auto"),
+            container_image: env_or("CONTAINER_IMAGE", "borg-agent"),
+            container_memory_mb: env_or("CONTAINER_MEMORY_MB", "2048")
+                .parse()
+                .unwrap_or(2048),
+            container_cpus: env_or("CONTAINER_CPUS", "2").parse().unwrap_or(2.0),
+            agent_timeout_s: env_or("AGENT_TIMEOUT_S", "1000").parse().unwrap_or(1000),
+            oauth_token: env_or("CLAUDE_CODE_OAUTH_TOKEN", ""),
+            github_token: env_or("GH_TOKEN", &env_or("GITHUB_TOKEN", "")),
+            borg_api_token: env_or("BORG_API_TOKEN", ""),
+            backend_fallbacks: csv(&env_or("BACKEND_FALLBACKS", "")),
+            local_only_fallback_modes: csv(&env_or("LOCAL_ONLY_FALLBACK_MODES", "lawborg,legal")),
+        })
+    }
+}
+
+fn build_backends(
+    config: &WorkerConfig,
+    sandbox_mode: &SandboxMode,
+) -> Result<HashMap<String, Arc<dyn AgentBackend>>> {
+    let mut backends: HashMap<String, Arc<dyn AgentBackend>> = HashMap::new();
+    backends.insert(
+        "claude".into(),
+        Arc::new(
+            ClaudeBackend::new("claude", sandbox_mode.clone(), &config.container_image)
+                .with_timeout(config.agent_timeout_s)
+                .with_resource_limits(config.container_memory_mb, config.container_cpus),
+        ),
+    );
+    let codex_key = env_or("CODEX_API_KEY", "");
+    if !codex_key.is_empty() {
+        let model = env_or("CODEX_MODEL", "gpt-5.3-codex");
+        backends.insert(
+            "codex".into(),
+            Arc::new(
+                CodexBackend::new(codex_key, model)
+                    .with_reasoning_effort(env_or("CODEX_REASONING_EFFORT", "medium"))
+                    .with_timeout(config.agent_timeout_s),
+            ),
+        );
+    }
+    let gemini_key = env_or("GEMINI_API_KEY", "");
+    if !gemini_key.is_empty() {
+        backends.insert(
+            "gemini".into(),
+            Arc::new(GeminiBackend::new(gemini_key).with_timeout(config.agent_timeout_s)),
+        );
+    }
+    if std::env::var("OLLAMA_URL").is_ok() || std::env::var("LOCAL_MODEL").is_ok() {
+        let url = env_or("OLLAMA_URL", "http://localhost:11434");
+        let model = env_or("LOCAL_MODEL", "llama3.2");
+        backends.insert(
+            "local".into(),
+            Arc::new(
+                OllamaBackend::new(url, model)?
+                    .with_timeout(300)?
+                    .with_sandbox(sandbox_mode.clone())
+                    .with_resource_limits(config.container_memory_mb, config.container_cpus),
+            ),
+        );
+    }
+    if sandbox_mode.is_container() && !config.container_image.is_empty() {
+        backends.insert(
+            "container".into(),
+            Arc::new(
+                ContainerBackend::new(&config.container_image)
+                    .with_timeout(config.agent_timeout_s)
+                    .with_resource_limits(config.container_memory_mb, config.container_cpus),
+            ),
+        );
+    }
+
+    // Retry and fail over on this host, as the server does for local runs.
+    let chain = config
+        .backend_fallbacks
+        .iter()
+        .filter_map(|name| Some((name.clone(), Arc::clone(backends.get(name)?))))
+        .collect();
+    let local_backends = backends.keys().filter(|name| *name == "local").cloned();
+    let failover = Arc::new(
+        FailoverPolicy::new(chain)
+            .with_local_only_modes(config.local_only_fallback_modes.clone(), local_backends),
+    );
+    Ok(backends
+        .into_iter()
+        .map(|(name, backend)| {
+            let wrapped: Arc<dyn AgentBackend> = Arc::new(
+                ReliableBackend::wrap(backend).with_failover(name.clone(), Arc::clone(&failover)),
+            );
+            (name, wrapped)
+        })
+        .collect())
+}
+
+struct Worker {
+    config: WorkerConfig,
+    http: reqwest::Client,
+    registration: WorkerRegistration,
+    backends: HashMap<String, Arc<dyn AgentBackend>>,
+    repo_images: RepoImages,
+    worker_id: RwLock<String>,
+}
+
+impl Worker {
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/worker/{path}", self.config.server_url)
+    }
+
+    async fn register(&self) -> Result<()> {
+        let resp = self
+            .http
+            .post(self.url("register"))
+            .bearer_auth(&self.config.token)
+            .json(&self.registration)
+            .send()
+            .await
+            .context("register request")?
+            .error_for_status()
+            .context("register")?;
+        let body: Value = resp.json().await?;
+        let id = body["worker_id"].as_str().unwrap_or_default().to_string();
+        if id.is_empty() {
+            bail!("server returned no worker_id");
+        }
+        info!(worker_id = %id, "registered with {}", self.config.server_url);
+        *self.worker_id.write().await = id;
+        Ok(())
+    }
+
+    async fn register_until_ok(&self) {
+        while let Err(e) = self.register().await {
+            warn!("registration failed: {e:#}; retrying");
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+    }
+
+    async fn poll(&self) -> Result<Option<WorkerJob>> {
+        let id = self.worker_id.read().await.clone();
+        let resp = self
+            .http
+            .post(self.url(&format!("{id}/poll")))
+            .bearer_auth(&self.config.token)
+            .send()
+            .await
+            .context("poll request")?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            // Server restarted or dropped us as stale.
+            self.register_until_ok().await;
+            return Ok(None);
+        }
+        Ok(resp.error_for_status()?.json().await?)
+    }
+
+    async fn slot_loop(self: Arc<Self>, slot: u32) {
+        loop {
+            match self.poll().await {
+                Ok(Some(job)) => self.run_job(job).await,
+                Ok(None) => {},
+                Err(e) => {
+                    warn!(slot, "poll failed: {e:#}");
+                    tokio::time::sleep(RETRY_DELAY).await;
+                },
+            }
+        }
+    }
+
+    async fn run_job(&self, job: WorkerJob) {
+        let WorkerJob {
+            job_id,
+            backend,
+            task,
+            phase,
+            mut ctx,
+        } = job;
+        info!(task_id = task.id, phase = %phase.name, %job_id, %backend, "running job");
+        let worker_id = self.worker_id.read().await.clone();
+        let stream_url = self.url(&format!("{worker_id}/jobs/{job_id}/stream"));
+        let heartbeat = tokio::spawn(heartbeat_loop(
+            self.http.clone(),
+            self.url(&format!("{worker_id}/heartbeat")),
+            self.config.token.clone(),
+        ));
+        let (tx, rx) = mpsc::unbounded_channel::<String>();
+        let streamer = tokio::spawn(stream_lines(
+            self.http.clone(),
+            stream_url,
+            self.config.token.clone(),
+            rx,
+        ));
+
+        // Agents reach borg's API on the server, not on this host.
+        ctx.borg_api_url = self.config.server_url.clone();
+        ctx.borg_api_token = self.config.borg_api_token.clone();
+        ctx.oauth_token = self.config.oauth_token.clone();
+        ctx.github_token = self.config.github_token.clone();
+        ctx.stream_tx = Some(tx);
+        // Repo images are built on each host. Tags come from the committed
+        // context tree, so a host at the same commit yields the server's tag.
+        if ctx.container_image.is_some() {
+            ctx.container_image = match self.repo_images.resolve(&ctx.repo_config).await {
+                Ok(image) => image,
+                Err(e) => {
+                    warn!(
+                        task_id = task.id,
+                        "repo image unavailable, using default: {e:#}"
+                    );
+                    None
+                },
+            };
+        }
+
+        let result = match self.backends.get(&backend) {
+            Some(b) => match b.run_phase(&task, &phase, ctx).await {
+                Ok(output) => WorkerJobResult {
+                    output: Some(output),
+                    error: String::new(),
+                },
+                Err(e) => {
+                    error!(task_id = task.id, "run_phase: {e:#}");
+                    WorkerJobResult {
+                        output: None,
+                        error: format!("{e:#}"),
+                    }
+                },
+            },
+            None => WorkerJobResult {
+                output: None,
+                error: format!("backend {backend} is not available on this worker"),
+            },
+        };
+        // run_phase dropped its sender; wait for the last batch to go out.
+        let _ = streamer.await;
+        heartbeat.abort();
+
+        let complete_url = self.url(&format!("{worker_id}/jobs/{job_id}/complete"));
+        for attempt in 0..5 {
+            let sent = self
+                .http
+                .post(&complete_url)
+                .bearer_auth(&self.config.token)
+                .json(&result)
+                .send()
+                .await;
+            match sent {
+                Ok(r) if r.status().is_success() => return,
+                Ok(r) if r.status() == reqwest::StatusCode::GONE => {
+                    warn!(%job_id, "server no longer waiting for job");
+                    return;
+                },
+                Ok(r) => warn!(%job_id, attempt, "complete returned {}", r.status()),
+                Err(e) => warn!(%job_id, attempt, "complete failed: {e}"),
+            }
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+        error!(%job_id, "giving up delivering job result");
+    }
+}
+
+/// Tell the server this worker is alive until aborted. Polls do this while
+/// a slot is idle; a running agent may print nothing for minutes.
+async fn heartbeat_loop(http: reqwest::Client, url: String, token: String) {
+    let mut ticks = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        ticks.tick().await;
+        match http.post(&url).bearer_auth(&token).send().await {
+            Ok(r) if !r.status().is_success() => warn!("heartbeat returned {}", r.status()),
+            Ok(_) => {},
+            Err(e) => warn!("heartbeat failed: {e}"),
+        }
+    }
+}
+
+/// Forward agent output to the server in batches until the sender drops.
+async fn stream_lines(
+    http: reqwest::Client,
+    url: String,
+    token: String,
+    mut rx: mpsc::UnboundedReceiver<String>,
+) {
+    let mut buf: Vec<String> = Vec::new();
+    let mut open = true;
+    while open {
+        tokio::select! {
+            line = rx.recv() => match line {
+                Some(l) => {
+                    buf.push(l);
+                    if buf.len() < STREAM_BATCH_LINES {
+                        continue;
+                    }
+                },
+                None => open = false,
+            },
+            _ = tokio::time::sleep(STREAM_FLUSH_INTERVAL), if !buf.is_empty() => {},
+        }
+        if buf.is_empty() {
+            continue;
+        }
+        let lines = std::mem::take(&mut buf);
+        if let Err(e) = http
+            .post(&url)
+            .bearer_auth(&token)
+            .json(&serde_json::json!({ "lines": lines }))
+            .send()
+            .await
+        {
+            warn!("stream upload failed: {e}");
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
+    let config = WorkerConfig::from_env()?;
+    let sandbox_mode = Sandbox::detect(&config.sandbox_backend).await;
+    if sandbox_mode.is_container() {
+        if !Sandbox::ensure_agent_network().await {
+            warn!("agent network unavailable; containers fall back to host networking");
+        }
+        let _ = Sandbox::ensure_isolated_network().await;
+    }
+    let backends = build_backends(&config, &sandbox_mode)?;
+    let mut backend_names: Vec<String> = backends.keys().cloned().collect();
+    backend_names.sort();
+    let registration = WorkerRegistration {
+        name: config.name.clone(),
+        capacity: config.capacity.max(1),
+        sandbox_modes: vec![format!("{sandbox_mode:?}").to_lowercase()],
+        backends: backend_names,
+        labels: config.labels.clone(),
+    };
+    let worker = Arc::new(Worker {
+        http: reqwest::Client::builder()
+            .timeout(Duration::from_secs(120))
+            .build()?,
+        registration,
+        backends,
+        repo_images: RepoImages::default(),
+        worker_id: RwLock::new(String::new()),
+        config,
+    });
+    worker.register_until_ok().await;
+
+    let mut slots = Vec::new();
+    for slot in 0..worker.registration.capacity {
+        slots.push(tokio::spawn(Arc::clone(&worker).slot_loop(slot)));
+    }
+    for s in slots {
+        let _ = s.await;
+    }
+    Ok(())
+}
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS legal_holds;
+DROP TABLE IF EXISTS retention_policies;
+ALTER TABLE projects DROP COLUMN IF EXISTS closed_at;
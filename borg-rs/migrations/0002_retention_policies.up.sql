@@ -0,0 +1,30 @@
+-- Workspace retention policies and per-project legal holds.
+
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS closed_at TEXT;
+
+-- Start the post-closure clock for projects that were already closed.
+UPDATE projects
+SET closed_at = to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS')
+WHERE status = 'closed' AND closed_at IS NULL;
+
+-- NULL or 0 means "keep forever" for each category.
+CREATE TABLE IF NOT EXISTS retention_policies (
+  workspace_id BIGINT PRIMARY KEY REFERENCES workspaces(id) ON DELETE CASCADE,
+  chat_messages_days BIGINT,
+  task_outputs_days BIGINT,
+  project_files_days_after_close BIGINT,
+  updated_by BIGINT REFERENCES users(id),
+  updated_at TEXT NOT NULL DEFAULT (to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS'))
+);
+
+-- A hold is active until released_at is set; held projects are skipped by retention.
+CREATE TABLE IF NOT EXISTS legal_holds (
+  id BIGSERIAL PRIMARY KEY,
+  project_id BIGINT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+  reason TEXT NOT NULL DEFAULT '',
+  created_by BIGINT REFERENCES users(id),
+  created_at TEXT NOT NULL DEFAULT (to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS')),
+  released_by BIGINT REFERENCES users(id),
+  released_at TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_legal_holds_active ON legal_holds(project_id) WHERE released_at IS NULL;
@@ -0,0 +1,3 @@
+-- Per-workspace chat backend. Empty means the default Claude chat agent.
+
+ALTER TABLE workspaces ADD COLUMN IF NOT EXISTS chat_backend TEXT NOT NULL DEFAULT '';
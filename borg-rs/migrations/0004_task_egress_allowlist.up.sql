@@ -0,0 +1,3 @@
+-- Per-task egress allowlist (comma-separated domains), merged with the mode's list.
+
+ALTER TABLE pipeline_tasks ADD COLUMN IF NOT EXISTS egress_allowlist TEXT NOT NULL DEFAULT '';
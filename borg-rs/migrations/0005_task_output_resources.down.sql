@@ -0,0 +1 @@
+DROP TABLE IF EXISTS task_output_resources;
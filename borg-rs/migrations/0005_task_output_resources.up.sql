@@ -0,0 +1,16 @@
+-- Per-phase resource usage (CPU, peak memory, worktree writes, wall time)
+-- measured around each agent run, one row per task_outputs row.
+
+CREATE TABLE IF NOT EXISTS task_output_resources (
+  task_output_id BIGINT PRIMARY KEY REFERENCES task_outputs(id) ON DELETE CASCADE,
+  task_id BIGINT NOT NULL,
+  phase TEXT NOT NULL,
+  wall_ms BIGINT NOT NULL DEFAULT 0,
+  cpu_ms BIGINT NOT NULL DEFAULT 0,
+  peak_memory_bytes BIGINT NOT NULL DEFAULT 0,
+  disk_written_bytes BIGINT NOT NULL DEFAULT 0,
+  source TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT (to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS'))
+);
+CREATE INDEX IF NOT EXISTS idx_task_output_resources_task ON task_output_resources(task_id);
+CREATE INDEX IF NOT EXISTS idx_task_output_resources_created ON task_output_resources(created_at);
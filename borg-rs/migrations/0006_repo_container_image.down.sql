@@ -0,0 +1,2 @@
+ALTER TABLE repos DROP COLUMN IF EXISTS container_file;
+ALTER TABLE repos DROP COLUMN IF EXISTS container_image;
@@ -0,0 +1,5 @@
+-- Per-repo agent image: a pinned image reference, or a container file in the
+-- repo that borg builds. Both empty means the global CONTAINER_IMAGE.
+
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS container_image TEXT NOT NULL DEFAULT '';
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS container_file TEXT NOT NULL DEFAULT '';
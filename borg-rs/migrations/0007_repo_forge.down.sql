@@ -0,0 +1,2 @@
+ALTER TABLE repos DROP COLUMN IF EXISTS forge_url;
+ALTER TABLE repos DROP COLUMN IF EXISTS forge;
@@ -0,0 +1,5 @@
+-- Per-repo code host for the integration queue: github (default), gitlab or
+-- gitea/forgejo, plus the base URL of a self-hosted instance.
+
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS forge TEXT NOT NULL DEFAULT '';
+ALTER TABLE repos ADD COLUMN IF NOT EXISTS forge_url TEXT NOT NULL DEFAULT '';
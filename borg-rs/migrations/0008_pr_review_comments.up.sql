@@ -0,0 +1,13 @@
+-- Review comments pulled from a task's PR/MR. One row per forge comment, so
+-- each is turned into revision feedback once; replied_at is set when borg
+-- has answered on the PR after pushing the revision.
+
+CREATE TABLE IF NOT EXISTS pr_review_comments (
+  task_id BIGINT NOT NULL,
+  comment_id TEXT NOT NULL,
+  author TEXT NOT NULL DEFAULT '',
+  revision BIGINT NOT NULL DEFAULT 0,
+  replied_at TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT (to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS')),
+  PRIMARY KEY (task_id, comment_id)
+);
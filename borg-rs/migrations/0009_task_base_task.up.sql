@@ -0,0 +1,3 @@
+-- Stacked tasks: a task may build on another task's unmerged branch. 0 = none.
+
+ALTER TABLE pipeline_tasks ADD COLUMN IF NOT EXISTS base_task_id BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,15 @@
+-- Per-repo test outcomes parsed from validate runs. flaky_runs counts
+-- failures that passed on an immediate re-run of the same code;
+-- quarantined_at is set while a test is quarantined ('' = not quarantined).
+
+CREATE TABLE IF NOT EXISTS test_flakiness (
+  repo_path TEXT NOT NULL,
+  test_name TEXT NOT NULL,
+  passes BIGINT NOT NULL DEFAULT 0,
+  failures BIGINT NOT NULL DEFAULT 0,
+  flaky_runs BIGINT NOT NULL DEFAULT 0,
+  last_status TEXT NOT NULL DEFAULT '',
+  quarantined_at TEXT NOT NULL DEFAULT '',
+  updated_at TEXT NOT NULL DEFAULT (to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS')),
+  PRIMARY KEY (repo_path, test_name)
+);
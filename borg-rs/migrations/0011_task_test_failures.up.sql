@@ -0,0 +1,17 @@
+-- Structured failures parsed from a task's failed test runs (JUnit XML,
+-- libtest, TAP), one row per failing test per phase attempt. file is ''
+-- and line is 0 when the runner did not report a location.
+
+CREATE TABLE IF NOT EXISTS task_test_failures (
+  id BIGSERIAL PRIMARY KEY,
+  task_id BIGINT NOT NULL,
+  phase TEXT NOT NULL,
+  attempt BIGINT NOT NULL DEFAULT 0,
+  test_name TEXT NOT NULL,
+  file TEXT NOT NULL DEFAULT '',
+  line BIGINT NOT NULL DEFAULT 0,
+  message TEXT NOT NULL DEFAULT '',
+  stack TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT (to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS'))
+);
+CREATE INDEX IF NOT EXISTS idx_task_test_failures_task ON task_test_failures(task_id);
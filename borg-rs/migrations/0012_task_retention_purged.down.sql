@@ -0,0 +1 @@
+ALTER TABLE pipeline_tasks DROP COLUMN IF EXISTS retention_purged_at;
@@ -0,0 +1,4 @@
+-- When workspace retention last purged a finished task's outputs and
+-- artifacts, so the hourly job skips it until the task changes again.
+
+ALTER TABLE pipeline_tasks ADD COLUMN IF NOT EXISTS retention_purged_at TEXT;
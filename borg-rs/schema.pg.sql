@@ -1,5 +1,6 @@
 -- Borg-rs complete Postgres schema.
 -- Clean-break control-plane schema; SQLite is no longer supported.
+-- Frozen as migration 0001_baseline: new changes go in migrations/.
 
 -- ── Repos ─────────────────────────────────────────────────────────────────
 
@@ -578,6 +579,48 @@ CREATE TABLE IF NOT EXISTS project_share_links (
 CREATE INDEX IF NOT EXISTS idx_project_share_links_token ON project_share_links(token);
 CREATE INDEX IF NOT EXISTS idx_project_share_links_project ON project_share_links(project_id);
 
+DO $$ BEGIN
+  ALTER TABLE project_share_links ADD COLUMN password_hash TEXT NOT NULL DEFAULT '';
+EXCEPTION WHEN duplicate_column THEN NULL;
+END $$;
+
+DO $$ BEGIN
+  ALTER TABLE project_share_links ADD COLUMN scope TEXT NOT NULL DEFAULT 'full'; -- full | documents
+EXCEPTION WHEN duplicate_column THEN NULL;
+END $$;
+
+DO $$ BEGIN
+  ALTER TABLE project_share_links ADD COLUMN document_ids TEXT NOT NULL DEFAULT '[]'; -- JSON ids; [] = all
+EXCEPTION WHEN duplicate_column THEN NULL;
+END $$;
+
+DO $$ BEGIN
+  ALTER TABLE project_share_links ADD COLUMN max_uses BIGINT NOT NULL DEFAULT 0; -- 0 = unlimited
+EXCEPTION WHEN duplicate_column THEN NULL;
+END $$;
+
+DO $$ BEGIN
+  ALTER TABLE project_share_links ADD COLUMN view_count BIGINT NOT NULL DEFAULT 0;
+EXCEPTION WHEN duplicate_column THEN NULL;
+END $$;
+
+DO $$ BEGIN
+  ALTER TABLE project_share_links ADD COLUMN download_count BIGINT NOT NULL DEFAULT 0;
+EXCEPTION WHEN duplicate_column THEN NULL;
+END $$;
+
+CREATE TABLE IF NOT EXISTS project_share_link_access (
+  id BIGSERIAL PRIMARY KEY,
+  link_id BIGINT NOT NULL REFERENCES project_share_links(id) ON DELETE CASCADE,
+  action TEXT NOT NULL,          -- view | tasks | documents | download
+  file_id BIGINT,
+  status BIGINT NOT NULL DEFAULT 200,
+  ip TEXT NOT NULL DEFAULT '',
+  user_agent TEXT NOT NULL DEFAULT '',
+  created_at TEXT NOT NULL DEFAULT (to_char(timezone('UTC', now()), 'YYYY-MM-DD HH24:MI:SS'))
+);
+CREATE INDEX IF NOT EXISTS idx_project_share_link_access_link ON project_share_link_access(link_id, id DESC);
+
 -- ── Cron scheduling ─────────────────────────────────────────────────────
 
 CREATE TABLE IF NOT EXISTS cron_jobs (
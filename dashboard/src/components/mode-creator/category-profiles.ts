@@ -26,6 +26,7 @@ const CODE_PROFILE: CategoryProfile = {
   ],
   integrations: [
     { value: "git_pr", label: "Git PR" },
+    { value: "git_local", label: "Local merge" },
     { value: "none", label: "None" },
   ],
   tools: ALL_TOOLS,
@@ -57,6 +58,7 @@ const ALL_PROFILE: CategoryProfile = {
   ],
   integrations: [
     { value: "git_pr", label: "Git PR" },
+    { value: "git_local", label: "Local merge" },
     { value: "git_branch", label: "Git Branch" },
     { value: "none", label: "None" },
   ],
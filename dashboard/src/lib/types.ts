@@ -136,7 +136,7 @@ export interface PipelineMode {
 }
 
 export type PhaseType = "setup" | "agent" | "rebase" | "lint_fix" | "human_review" | "validate" | "compliance_check";
-export type IntegrationType = "git_pr" | "git_branch" | "none";
+export type IntegrationType = "git_pr" | "git_local" | "git_branch" | "none";
 export type SeedOutputType = "task" | "proposal";
 
 export interface PhaseConfigFull {